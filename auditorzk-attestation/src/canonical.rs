@@ -0,0 +1,19 @@
+//! Canonical JSON form: compact, with object keys sorted, so byte-equal
+//! attestations always serialize to byte-equal strings.
+
+use alloc::string::{String, ToString};
+
+use crate::{Attestation, Error};
+
+/// Serialize an attestation to canonical JSON
+pub fn to_canonical_json(attestation: &Attestation) -> Result<String, Error> {
+    // `serde_json::Map` is a BTreeMap (no `preserve_order`), so going through
+    // `Value` sorts keys at every level
+    let value = serde_json::to_value(attestation).map_err(|e| Error::Json(e.to_string()))?;
+    serde_json::to_string(&value).map_err(|e| Error::Json(e.to_string()))
+}
+
+/// Parse an attestation from JSON (canonical or not)
+pub fn from_json(json: &str) -> Result<Attestation, Error> {
+    serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))
+}
@@ -0,0 +1,393 @@
+//! Claim sets: signed messages that declare which fields they carry.
+//!
+//! The legacy layout (see [`crate::message`]) appends every new signed field to
+//! one fixed sequence, so each field costs a schema bump and every consumer pays
+//! for fields it doesn't read. From schema 22 the verifier operator picks a
+//! *claim set* instead (`AUDITORZK_CLAIM_SET`, `full` when unset), and the
+//! signed message (layout 2.0.0) is:
+//!
+//! | Bytes | Field                                                   |
+//! |-------|---------------------------------------------------------|
+//! | 0..3  | layout version `02 00 00`, [`CLAIMS_LAYOUT_VERSION`]    |
+//! | 3     | claim-set tag `0x00`, [`TAG_CLAIM_SET`]                 |
+//! | 4..36 | the set's id, see [`ClaimSet::id`]                      |
+//!
+//! followed by one claim per field the attestation carries, in tag order: the
+//! tag byte, then the value, as wide as in the legacy layout. Optional fields
+//! the attestation leaves empty are omitted, and fields outside the set can't
+//! be signed at all. Legacy messages start with the server name, never with
+//! the layout version, so a consumer can tell the layouts apart from the first
+//! bytes alone.
+//!
+//! # Tag registry
+//!
+//! | Tag    | Claim                         | Width | Value                                              |
+//! |--------|-------------------------------|-------|----------------------------------------------------|
+//! | `0x00` | claim set                     | 32    | [`ClaimSet::id`]                                   |
+//! | `0x01` | server name                   | 32    | UTF-8, right-padded with zeros                     |
+//! | `0x02` | timestamp                     | 32    | u64 little-endian, right-padded with zeros         |
+//! | `0x03` | balance commitment            | 32    | SHA-256                                            |
+//! | `0x04` | environment                   | 1     | `ENV_*` byte, see [`crate::message`]               |
+//! | `0x05` | account count                 | 32    | u32 little-endian, right-padded with zeros         |
+//! | `0x06` | accounts commitment           | 32    | see [`crate::inventory`]                           |
+//! | `0x07` | formula                       | 1     | `FORMULA_*` byte                                   |
+//! | `0x08` | claim type                    | 1     | `CLAIM_*` byte                                     |
+//! | `0x09` | account filter                | 32    | [`crate::message::account_filter_hash`]            |
+//! | `0x0a` | verifier version              | 32    | [`crate::message::verifier_version_hash`]          |
+//! | `0x0b` | recipient                     | 32    | as given by the prover                             |
+//! | `0x0c` | transcript commitment count   | 32    | u32 little-endian, right-padded with zeros         |
+//! | `0x0d` | transcript commitments digest | 32    | see [`crate::commitments`]                         |
+//! | `0x0e` | label                         | 32    | SHA-256 of the label                               |
+//! | `0x0f` | signer set                    | 32    | set id, see [`crate::multisig`]                    |
+//! | `0x10` | request template              | 32    | [`crate::request::RequestTemplateMatch::signed_hash`] |
+//! | `0x11` | API version                   | 32    | SHA-256 of the version string                      |
+//! | `0x12` | prover timestamp              | 32    | u64 little-endian, right-padded with zeros         |
+//! | `0x13` | item count                    | 32    | u32 little-endian, right-padded with zeros         |
+//! | `0x14` | over-revealed bytes           | 32    | u64 little-endian, right-padded with zeros         |
+//! | `0x15` | inactive accounts             | 32    | u32 little-endian, right-padded with zeros         |
+//!
+//! Tags are never reused or renumbered: a new field takes the next free tag,
+//! without a new layout. A set's id commits to its tags, so a registered set
+//! never gains one; the new field joins a new set, or `full`, which no issued
+//! message declares before the field exists.
+//!
+//! # Registered sets
+//!
+//! | Set        | Claims                                                                     |
+//! |------------|----------------------------------------------------------------------------|
+//! | `minimal`  | server name, timestamp, commitment                                         |
+//! | `standard` | `minimal`, environment, account inventory, formula, claim type, verifier version, recipient |
+//! | `extended` | every tag up to `0x11`, the claims of schema 22                            |
+//! | `full`     | every tag above                                                            |
+//!
+//! A contract checks the layout version, compares the set id with the ids of the
+//! sets it accepts, and then reads claims by tag.
+//!
+//! ```
+//! use auditorzk_attestation::claims::{verify_claims, verify_legacy_claims, STANDARD};
+//! use auditorzk_attestation::message::SignedField::{self, *};
+//! use auditorzk_attestation::{Attestation, Error};
+//!
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! let attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v22.json")).unwrap();
+//! let claims = verify_claims(&attestation, &pubkey).unwrap();
+//! assert_eq!(claims.set, Some(&STANDARD));
+//! assert_eq!(claims.server_name(), Some("sandbox.plaid.com"));
+//! assert_eq!(claims.get(SignedField::Recipient), attestation.recipient.as_deref());
+//! assert_eq!(claims.get(SignedField::Label), None);
+//!
+//! // Legacy attestations don't say which fields they sign, so the consumer pins them
+//! let legacy: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v21.json")).unwrap();
+//! assert_eq!(verify_claims(&legacy, &pubkey), Err(Error::LegacyLayout(21)));
+//! let fields = [
+//!     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+//!     AccountFilter, VerifierVersion, Recipient, Label, RequestTemplate, ApiVersion,
+//! ];
+//! let claims = verify_legacy_claims(&legacy, &pubkey, &fields).unwrap();
+//! assert_eq!(claims.set, None);
+//! assert_eq!(claims.timestamp(), Some(legacy.timestamp));
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use k256::schnorr::VerifyingKey;
+use sha2::{Digest, Sha256};
+
+use crate::message::{encode_claims, message_digest, SignedField, FIELD_LEN};
+use crate::signature::{decode_signature, verify_digest, verify_legacy_attestation};
+use crate::{verify_attestation, Attestation, Error};
+
+/// First bytes of every claim-set message
+pub const CLAIMS_LAYOUT_VERSION: [u8; 3] = [0x02, 0x00, 0x00];
+
+/// Tag of the claim carrying the set id, always the first claim
+pub const TAG_CLAIM_SET: u8 = 0x00;
+
+/// Domain separator hashed into every set id
+const CLAIM_SET_ID_DOMAIN: &[u8] = b"auditorzk/claim-set/v1";
+
+/// A named, ordered set of claims an attestation may sign
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClaimSet {
+    pub name: &'static str,
+    /// Fields the set allows, in message order, always starting with the base three
+    pub fields: &'static [SignedField],
+}
+
+/// Just the server, time and commitment, for consumers that check nothing else
+pub const MINIMAL: ClaimSet = ClaimSet {
+    name: "minimal",
+    fields: &[SignedField::ServerName, SignedField::Timestamp, SignedField::Commitment],
+};
+
+/// What balance and income consumers usually check, plus a recipient binding
+pub const STANDARD: ClaimSet = ClaimSet {
+    name: "standard",
+    fields: &[
+        SignedField::ServerName,
+        SignedField::Timestamp,
+        SignedField::Commitment,
+        SignedField::Environment,
+        SignedField::AccountCount,
+        SignedField::AccountsCommitment,
+        SignedField::Formula,
+        SignedField::ClaimType,
+        SignedField::VerifierVersion,
+        SignedField::Recipient,
+    ],
+};
+
+/// Every claim registered with schema 22, the verifier's default until schema 24
+pub const EXTENDED: ClaimSet = ClaimSet {
+    name: "extended",
+    fields: &[
+        SignedField::ServerName,
+        SignedField::Timestamp,
+        SignedField::Commitment,
+        SignedField::Environment,
+        SignedField::AccountCount,
+        SignedField::AccountsCommitment,
+        SignedField::Formula,
+        SignedField::ClaimType,
+        SignedField::AccountFilter,
+        SignedField::VerifierVersion,
+        SignedField::Recipient,
+        SignedField::CommitmentCount,
+        SignedField::CommitmentsDigest,
+        SignedField::Label,
+        SignedField::SignerSet,
+        SignedField::RequestTemplate,
+        SignedField::ApiVersion,
+    ],
+};
+
+/// Every registered claim
+pub const FULL: ClaimSet = ClaimSet { name: "full", fields: &SignedField::ALL };
+
+/// Sets a message may declare
+pub const CLAIM_SETS: [&ClaimSet; 4] = [&MINIMAL, &STANDARD, &EXTENDED, &FULL];
+
+/// Longest message any registered set produces
+pub const MAX_CLAIMS_MESSAGE_LEN: usize = FULL.max_message_len();
+
+impl ClaimSet {
+    /// Look a registered set up by name
+    pub fn find(name: &str) -> Option<&'static ClaimSet> {
+        CLAIM_SETS.into_iter().find(|set| set.name == name)
+    }
+
+    /// Look a registered set up by the id its messages carry
+    pub fn by_id(id: &[u8]) -> Option<&'static ClaimSet> {
+        CLAIM_SETS.into_iter().find(|set| set.id()[..] == *id)
+    }
+
+    /// `SHA-256("auditorzk/claim-set/v1" || tags)`, committing to exactly
+    /// which claims the set allows
+    pub fn id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(CLAIM_SET_ID_DOMAIN);
+        for field in self.fields {
+            hasher.update([field.tag()]);
+        }
+        hasher.finalize().into()
+    }
+
+    pub fn contains(&self, field: SignedField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    /// Length of a message carrying every claim of the set
+    pub const fn max_message_len(&self) -> usize {
+        let mut len = CLAIMS_LAYOUT_VERSION.len() + 1 + FIELD_LEN;
+        let mut i = 0;
+        while i < self.fields.len() {
+            len += 1 + self.fields[i].width();
+            i += 1;
+        }
+        len
+    }
+
+    /// Clear the attestation's signed fields that fall outside the set. The
+    /// environment name stays, unsigned, when the environment claim goes.
+    ///
+    /// ```
+    /// use auditorzk_attestation::claims::MINIMAL;
+    /// use auditorzk_attestation::Attestation;
+    ///
+    /// let mut attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v22.json")).unwrap();
+    /// attestation.claim_set = Some(MINIMAL.name.into());
+    /// assert!(attestation.signed_message().is_err());
+    ///
+    /// MINIMAL.strip(&mut attestation);
+    /// assert_eq!(attestation.recipient, None);
+    /// assert_eq!(attestation.signed_message().unwrap().len(), MINIMAL.max_message_len());
+    /// ```
+    pub fn strip(&self, attestation: &mut Attestation) {
+        if !self.contains(SignedField::Environment) {
+            attestation.environment_signed = false;
+        }
+        if !self.contains(SignedField::AccountCount) || !self.contains(SignedField::AccountsCommitment) {
+            attestation.account_count = None;
+            attestation.accounts_commitment = None;
+        }
+        if !self.contains(SignedField::Formula) {
+            attestation.balance_formula = None;
+        }
+        if !self.contains(SignedField::ClaimType) {
+            attestation.claim_type = None;
+        }
+        if !self.contains(SignedField::AccountFilter) {
+            attestation.account_filter = None;
+        }
+        if !self.contains(SignedField::VerifierVersion) {
+            attestation.verifier_version = None;
+        }
+        if !self.contains(SignedField::Recipient) {
+            attestation.recipient = None;
+        }
+        if !self.contains(SignedField::CommitmentCount) || !self.contains(SignedField::CommitmentsDigest) {
+            attestation.transcript_commitments.clear();
+        }
+        if !self.contains(SignedField::Label) {
+            attestation.label = None;
+        }
+        if !self.contains(SignedField::SignerSet) {
+            attestation.signer_set_id = None;
+        }
+        if !self.contains(SignedField::RequestTemplate) {
+            attestation.request_template = None;
+        }
+        if !self.contains(SignedField::ApiVersion) {
+            attestation.api_version = None;
+        }
+        if !self.contains(SignedField::ProverTimestamp) {
+            attestation.prover_timestamp = None;
+        }
+        if !self.contains(SignedField::ItemCount) {
+            attestation.item_count = None;
+        }
+        if !self.contains(SignedField::OverRevealedBytes) {
+            attestation.over_revealed_bytes = None;
+        }
+        if !self.contains(SignedField::InactiveAccounts) {
+            attestation.inactive_accounts = None;
+        }
+    }
+}
+
+/// The claims a verified message carries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claims {
+    /// Set the message declares, `None` for a legacy (1.0.0) message
+    pub set: Option<&'static ClaimSet>,
+    /// Each claim's value exactly as signed, e.g. hashes for hashed fields
+    pub values: BTreeMap<SignedField, Vec<u8>>,
+}
+
+impl Claims {
+    /// Parse a claim-set message, checking its tags against the declared set.
+    ///
+    /// ```
+    /// use auditorzk_attestation::claims::Claims;
+    /// use auditorzk_attestation::{Attestation, Error};
+    ///
+    /// let attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v22.json")).unwrap();
+    /// let mut message = attestation.signed_message().unwrap();
+    /// assert!(Claims::decode(&message).is_ok());
+    ///
+    /// // Swap the declared set for one that doesn't allow the recipient
+    /// message[4..36].copy_from_slice(&auditorzk_attestation::claims::MINIMAL.id());
+    /// assert!(matches!(Claims::decode(&message), Err(Error::ClaimNotInSet { set: "minimal", .. })));
+    /// ```
+    pub fn decode(message: &[u8]) -> Result<Self, Error> {
+        let rest = message
+            .strip_prefix(&CLAIMS_LAYOUT_VERSION[..])
+            .ok_or(Error::InvalidClaimEncoding("message does not start with the claims layout version"))?;
+        let (&tag, rest) = rest.split_first().ok_or(Error::InvalidClaimEncoding("missing claim set"))?;
+        if tag != TAG_CLAIM_SET || rest.len() < FIELD_LEN {
+            return Err(Error::InvalidClaimEncoding("missing claim set"));
+        }
+        let (id, mut rest) = rest.split_at(FIELD_LEN);
+        let set = ClaimSet::by_id(id).ok_or_else(|| Error::UnknownClaimSet(hex::encode(id)))?;
+
+        let mut values = BTreeMap::new();
+        let mut last: Option<SignedField> = None;
+        while let Some((&tag, tail)) = rest.split_first() {
+            let field = SignedField::from_tag(tag).ok_or(Error::InvalidClaimEncoding("unknown claim tag"))?;
+            if !set.contains(field) {
+                return Err(Error::ClaimNotInSet { field: field.name(), set: set.name });
+            }
+            if let Some(last) = last.filter(|&last| last >= field) {
+                return Err(Error::FieldOutOfOrder { field: field.name(), after: last.name() });
+            }
+            if tail.len() < field.width() {
+                return Err(Error::InvalidClaimEncoding("truncated claim"));
+            }
+            let (value, tail) = tail.split_at(field.width());
+            values.insert(field, value.to_vec());
+            last = Some(field);
+            rest = tail;
+        }
+
+        let claims = Self { set: Some(set), values };
+        if claims.server_name().is_none() || claims.timestamp().is_none() || claims.commitment().is_none() {
+            return Err(Error::InvalidClaimEncoding("missing server name, timestamp or commitment"));
+        }
+        Ok(claims)
+    }
+
+    pub fn get(&self, field: SignedField) -> Option<&[u8]> {
+        self.values.get(&field).map(Vec::as_slice)
+    }
+
+    /// The server name, without its padding
+    pub fn server_name(&self) -> Option<&str> {
+        let padded = self.get(SignedField::ServerName)?;
+        let name = &padded[..padded.iter().position(|&b| b == 0).unwrap_or(padded.len())];
+        core::str::from_utf8(name).ok()
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        self.get(SignedField::Timestamp).map(|padded| u64::from_le_bytes(padded[..8].try_into().expect("8-byte slice")))
+    }
+
+    pub fn commitment(&self) -> Option<&[u8]> {
+        self.get(SignedField::Commitment)
+    }
+}
+
+/// Verify a claim-set attestation and return the claims it signs. Legacy
+/// attestations fail with [`Error::LegacyLayout`], see [`verify_legacy_claims`].
+pub fn verify_claims(attestation: &Attestation, trusted_pubkey: &[u8]) -> Result<Claims, Error> {
+    verify_attestation(attestation, trusted_pubkey)?;
+    Claims::decode(&attestation.signed_message()?)
+}
+
+/// Verify an attestation against the fields the consumer expects it to sign
+/// (see [`verify_legacy_attestation`]) and return its claims.
+///
+/// Legacy attestations come back with no declared set, so consumers read both
+/// layouts the same way.
+pub fn verify_legacy_claims(
+    attestation: &Attestation,
+    trusted_pubkey: &[u8],
+    expected: &[SignedField],
+) -> Result<Claims, Error> {
+    verify_legacy_attestation(attestation, trusted_pubkey, expected)?;
+    if attestation.declared_claim_set()?.is_some() {
+        return Claims::decode(&attestation.signed_message()?);
+    }
+
+    let mut claims = Claims::decode(&encode_claims(&FULL, &attestation.signed_fields()?)?)?;
+    claims.set = None;
+    Ok(claims)
+}
+
+/// Verify a bare claim-set message and its hex-encoded versioned signature, for
+/// consumers that receive the message rather than the attestation JSON
+pub fn verify_message(message: &[u8], signature: &str, trusted_pubkey: &[u8]) -> Result<Claims, Error> {
+    let verifying_key = VerifyingKey::from_bytes(trusted_pubkey).map_err(|_| Error::InvalidPublicKey)?;
+    let signature = decode_signature(signature)?;
+    verify_digest(&verifying_key, &message_digest(message), &signature)?;
+    Claims::decode(message)
+}
@@ -0,0 +1,123 @@
+//! Transcript hash commitments listed in an attestation.
+//!
+//! A prover can commit to several parts of the notarized response separately,
+//! e.g. the balances of two institutions. Each hash commitment is listed with
+//! its direction, transcript byte ranges and hash algorithm, and the list is
+//! signed as its length plus a digest over the entries (schema 13+). Each entry
+//! is encoded as:
+//!
+//! | Bytes        | Field                                                  |
+//! |--------------|--------------------------------------------------------|
+//! | 0            | direction byte (`0x01` sent, `0x02` received)          |
+//! | 1            | hash algorithm byte (tlsn ids: `0x01` sha256, `0x02` blake3, `0x03` keccak256) |
+//! | 2..6         | range count, u32 little-endian                         |
+//! | 16 per range | start and end offsets, u64 little-endian each          |
+//! | +0..32       | commitment hash                                        |
+//!
+//! The digest is SHA-256 of the encodings concatenated in listed order. Once the
+//! signature checks out, a consumer verifies each commitment against its own
+//! opening, independently of the others:
+//!
+//! ```
+//! use auditorzk_attestation::message::SignedField::*;
+//! use auditorzk_attestation::signature::verify_legacy_attestation;
+//! use auditorzk_attestation::{Attestation, Error};
+//! use sha2::{Digest, Sha256};
+//!
+//! let json = include_str!("../fixtures/attestation_v13.json");
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! let fields = [
+//!     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+//!     AccountFilter, VerifierVersion, Recipient, CommitmentCount, CommitmentsDigest,
+//! ];
+//! let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+//! verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+//!
+//! // Two received-direction commitments, one per institution
+//! let [first, second] = &attestation.transcript_commitments[..] else { panic!() };
+//! assert_eq!(first.hash, Sha256::digest(br#""current": 20000.00"#).to_vec());
+//! assert_eq!(second.hash, Sha256::digest(br#""current": 912.75"#).to_vec());
+//!
+//! // Every entry, including its ranges, is covered by the signature
+//! attestation.transcript_commitments[1].ranges.pop();
+//! assert_eq!(verify_legacy_attestation(&attestation, &pubkey, &fields), Err(Error::InvalidSignature));
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::message::FIELD_LEN;
+use crate::Error;
+
+/// Direction bytes
+pub const DIRECTION_SENT: u8 = 0x01;
+pub const DIRECTION_RECEIVED: u8 = 0x02;
+
+/// Hash algorithm bytes, matching tlsn's `HashAlgId`
+pub const HASH_SHA256: u8 = 0x01;
+pub const HASH_BLAKE3: u8 = 0x02;
+pub const HASH_KECCAK256: u8 = 0x03;
+
+/// A prover's hash commitment over part of the transcript
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct TranscriptHash {
+    /// `sent` or `received`
+    pub direction: String,
+    /// Committed transcript byte ranges, as `[start, end)` pairs
+    pub ranges: Vec<[u64; 2]>,
+    /// `sha256`, `blake3` or `keccak256`
+    pub algorithm: String,
+    /// The commitment hash
+    pub hash: Vec<u8>,
+}
+
+impl TranscriptHash {
+    /// Canonical encoding of this entry, see the module docs
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        if self.hash.len() != FIELD_LEN {
+            return Err(Error::InvalidCommitmentLength(self.hash.len()));
+        }
+
+        let mut encoded = Vec::with_capacity(6 + 16 * self.ranges.len() + FIELD_LEN);
+        encoded.push(direction_byte(&self.direction)?);
+        encoded.push(hash_algorithm_byte(&self.algorithm)?);
+        encoded.extend_from_slice(&(self.ranges.len() as u32).to_le_bytes());
+        for [start, end] in &self.ranges {
+            encoded.extend_from_slice(&start.to_le_bytes());
+            encoded.extend_from_slice(&end.to_le_bytes());
+        }
+        encoded.extend_from_slice(&self.hash);
+        Ok(encoded)
+    }
+}
+
+/// Digest over a list of commitments, as it appears in the signed message
+pub fn commitments_digest(commitments: &[TranscriptHash]) -> Result<[u8; 32], Error> {
+    let mut hasher = Sha256::new();
+    for commitment in commitments {
+        hasher.update(commitment.encode()?);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Byte for a transcript direction name
+pub fn direction_byte(name: &str) -> Result<u8, Error> {
+    match name {
+        "sent" => Ok(DIRECTION_SENT),
+        "received" => Ok(DIRECTION_RECEIVED),
+        other => Err(Error::UnknownCommitmentDirection(other.into())),
+    }
+}
+
+/// Byte for a hash algorithm name
+pub fn hash_algorithm_byte(name: &str) -> Result<u8, Error> {
+    match name {
+        "sha256" => Ok(HASH_SHA256),
+        "blake3" => Ok(HASH_BLAKE3),
+        "keccak256" => Ok(HASH_KECCAK256),
+        other => Err(Error::UnknownHashAlgorithm(other.into())),
+    }
+}
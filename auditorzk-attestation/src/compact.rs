@@ -0,0 +1,362 @@
+//! Compact binary form for size-constrained hand-offs (QR codes, calldata).
+//!
+//! A compact attestation is a fixed-layout *core*, everything a consumer needs to
+//! check the signature, optionally followed by a zstd-compressed *metadata*
+//! section that restores the full [`Attestation`]:
+//!
+//! | Bytes    | Field                                                 |
+//! |----------|-------------------------------------------------------|
+//! | 0..4     | magic `AZKC`                                          |
+//! | 4        | format version, [`COMPACT_VERSION`]                   |
+//! | 5..7     | schema version, u16 little-endian                     |
+//! | 7..39    | verifier public key (x-only)                          |
+//! | 39..106  | versioned signature (3-byte version + 64 bytes)       |
+//! | 106..108 | signed message length, u16 little-endian             |
+//! | 108..    | the signed message (see [`crate::message`] and [`crate::claims`]) |
+//!
+//! then, when present, the metadata: a zstd frame holding the attestation's
+//! canonical JSON with the fields the core already carries blanked out.
+//!
+//! Optional fields only grow the signed message by fixed-size hashes, so the core
+//! never exceeds [`MAX_CORE_LEN`]; unsigned fields only grow the metadata. A
+//! consumer short on space drops the metadata and still verifies the core with
+//! [`CompactAttestation::verify`].
+
+#[cfg(feature = "compact")]
+use alloc::string::String;
+use alloc::vec::Vec;
+use k256::schnorr::VerifyingKey;
+
+#[cfg(feature = "compact")]
+use crate::claims::{Claims, CLAIMS_LAYOUT_VERSION};
+use crate::message::{message_digest, FIELD_LEN};
+use crate::signature::{decode_signature, verify_digest};
+use crate::{Attestation, Error};
+
+/// First bytes of every compact attestation
+pub const COMPACT_MAGIC: &[u8; 4] = b"AZKC";
+
+/// Version of the layout above
+pub const COMPACT_VERSION: u8 = 1;
+
+/// Bytes before the signed message
+pub const CORE_HEADER_LEN: usize = 4 + 1 + 2 + FIELD_LEN + SIGNATURE_LEN + 2;
+
+/// Longest legacy-layout message: the three base fields, the environment,
+/// formula and claim-type bytes, the account inventory and transcript
+/// commitments (two fields each), and the account filter, verifier version,
+/// recipient, label, signer-set, request-template, API version, prover
+/// timestamp, item count, over-revealed bytes and inactive accounts fields
+const MAX_LEGACY_MESSAGE_LEN: usize = 3 * FIELD_LEN + 3 + 2 * 2 * FIELD_LEN + 11 * FIELD_LEN;
+
+/// Longest signed message any schema produces: the `full` claim set, which
+/// tags every legacy field and declares its set id on top
+pub const MAX_MESSAGE_LEN: usize = crate::claims::MAX_CLAIMS_MESSAGE_LEN;
+
+const _: () = assert!(MAX_LEGACY_MESSAGE_LEN <= MAX_MESSAGE_LEN);
+
+/// Most bytes the core may take, whatever optional fields are set. Leaves room
+/// for a few more signed fields in later schemas without changing the budget
+pub const MAX_CORE_LEN: usize = 768;
+
+const _: () = assert!(CORE_HEADER_LEN + MAX_MESSAGE_LEN <= MAX_CORE_LEN);
+
+/// Largest metadata section accepted once decompressed
+pub const MAX_METADATA_LEN: usize = 64 * 1024;
+
+/// Versioned signature length: 3-byte version + 64-byte BIP-340 signature
+const SIGNATURE_LEN: usize = 67;
+
+/// Length of the schema 1 message (server name, timestamp, commitment), the only
+/// part of the message the core can be expanded from without metadata
+#[cfg(feature = "compact")]
+const BASE_MESSAGE_LEN: usize = 3 * FIELD_LEN;
+
+/// A parsed compact attestation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactAttestation {
+    pub schema_version: u16,
+    pub verifier_pubkey: [u8; FIELD_LEN],
+    pub signature: [u8; SIGNATURE_LEN],
+    /// The exact message the verifier signed
+    pub message: Vec<u8>,
+    /// zstd-compressed metadata, when present
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl CompactAttestation {
+    /// The core of `attestation`, without metadata
+    pub fn core(attestation: &Attestation) -> Result<Self, Error> {
+        let schema_version = u16::try_from(attestation.schema_version)
+            .map_err(|_| Error::UnsupportedSchemaVersion(u64::from(attestation.schema_version)))?;
+        let verifier_pubkey = attestation.verifier_pubkey.as_slice().try_into().map_err(|_| Error::InvalidPublicKey)?;
+        let signature = hex::decode(&attestation.signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(Error::InvalidSignatureEncoding)?;
+
+        Ok(Self { schema_version, verifier_pubkey, signature, message: attestation.signed_message()?, metadata: None })
+    }
+
+    /// Bytes taken by the core, at most [`MAX_CORE_LEN`]
+    pub fn core_len(&self) -> usize {
+        CORE_HEADER_LEN + self.message.len()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let metadata = self.metadata.as_deref().unwrap_or_default();
+        let mut bytes = Vec::with_capacity(self.core_len() + metadata.len());
+        bytes.extend_from_slice(COMPACT_MAGIC);
+        bytes.push(COMPACT_VERSION);
+        bytes.extend_from_slice(&self.schema_version.to_le_bytes());
+        bytes.extend_from_slice(&self.verifier_pubkey);
+        bytes.extend_from_slice(&self.signature);
+        bytes.extend_from_slice(&(self.message.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.message);
+        bytes.extend_from_slice(metadata);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < CORE_HEADER_LEN {
+            return Err(Error::InvalidCompactEncoding("shorter than the core header"));
+        }
+        if &bytes[..4] != COMPACT_MAGIC {
+            return Err(Error::InvalidCompactEncoding("bad magic"));
+        }
+        if bytes[4] != COMPACT_VERSION {
+            return Err(Error::InvalidCompactEncoding("unknown format version"));
+        }
+
+        let schema_version = u16::from_le_bytes([bytes[5], bytes[6]]);
+        let verifier_pubkey = bytes[7..39].try_into().expect("32-byte slice");
+        let signature = bytes[39..106].try_into().expect("67-byte slice");
+        let message_len = usize::from(u16::from_le_bytes([bytes[106], bytes[107]]));
+        if message_len > MAX_MESSAGE_LEN {
+            return Err(Error::InvalidCompactEncoding("signed message longer than any schema's"));
+        }
+        let message = bytes
+            .get(CORE_HEADER_LEN..CORE_HEADER_LEN + message_len)
+            .ok_or(Error::InvalidCompactEncoding("truncated signed message"))?
+            .to_vec();
+        let metadata = Some(&bytes[CORE_HEADER_LEN + message_len..]).filter(|rest| !rest.is_empty()).map(<[u8]>::to_vec);
+
+        Ok(Self { schema_version, verifier_pubkey, signature, message, metadata })
+    }
+
+    /// Check the core's signature against the trusted verifier key, without
+    /// expanding the metadata
+    pub fn verify(&self, trusted_pubkey: &[u8]) -> Result<(), Error> {
+        if self.verifier_pubkey[..] != *trusted_pubkey {
+            return Err(Error::PublicKeyMismatch);
+        }
+        let verifying_key = VerifyingKey::from_bytes(trusted_pubkey).map_err(|_| Error::InvalidPublicKey)?;
+        let signature = decode_signature(&hex::encode(self.signature))?;
+        verify_digest(&verifying_key, &message_digest(&self.message), &signature)
+    }
+
+    /// Whether the core alone describes the whole signed message: a bare
+    /// schema 1 style message, or a claim-set message with only the base claims
+    #[cfg(feature = "compact")]
+    fn is_base_only(&self) -> bool {
+        match self.claims() {
+            Some(Ok(claims)) => claims.values.len() == 3,
+            Some(Err(_)) => false,
+            None => self.message.len() == BASE_MESSAGE_LEN,
+        }
+    }
+
+    /// The claims of a claim-set message, `None` for a legacy one
+    #[cfg(feature = "compact")]
+    fn claims(&self) -> Option<Result<Claims, Error>> {
+        self.message.starts_with(&CLAIMS_LAYOUT_VERSION).then(|| Claims::decode(&self.message))
+    }
+
+    /// Put the fields carried by the core back into an attestation expanded
+    /// from the metadata, or into an empty one
+    #[cfg(feature = "compact")]
+    fn restore(&self, attestation: &mut Attestation) -> Result<(), Error> {
+        attestation.schema_version = u32::from(self.schema_version);
+        attestation.signature = hex::encode(self.signature);
+        attestation.verifier_pubkey = self.verifier_pubkey.to_vec();
+
+        if let Some(claims) = self.claims() {
+            let claims = claims?;
+            let server_name = claims.server_name().ok_or(Error::InvalidCompactEncoding("server name is not UTF-8"))?;
+            attestation.server_name = String::from(server_name);
+            attestation.timestamp = claims.timestamp().expect("decoded claims carry a timestamp");
+            attestation.balance_commitment = claims.commitment().expect("decoded claims carry a commitment").to_vec();
+            attestation.claim_set = claims.set.map(|set| String::from(set.name));
+            return Ok(());
+        }
+
+        if self.message.len() < BASE_MESSAGE_LEN {
+            return Err(Error::InvalidCompactEncoding("signed message shorter than its base fields"));
+        }
+        let server_name = &self.message[..FIELD_LEN];
+        let server_name = &server_name[..server_name.iter().position(|&b| b == 0).unwrap_or(FIELD_LEN)];
+
+        attestation.server_name = String::from(
+            core::str::from_utf8(server_name).map_err(|_| Error::InvalidCompactEncoding("server name is not UTF-8"))?,
+        );
+        attestation.timestamp = u64::from_le_bytes(self.message[FIELD_LEN..FIELD_LEN + 8].try_into().expect("8-byte slice"));
+        attestation.balance_commitment = self.message[2 * FIELD_LEN..BASE_MESSAGE_LEN].to_vec();
+        Ok(())
+    }
+}
+
+/// An attestation with every field empty, for the core to fill in
+#[cfg(feature = "compact")]
+fn blank() -> Attestation {
+    Attestation {
+        schema_version: 0,
+        server_name: String::new(),
+        timestamp: 0,
+        balance_commitment: Vec::new(),
+        signature: String::new(),
+        verifier_pubkey: Vec::new(),
+        environment: None,
+        environment_signed: false,
+        prover_timestamp: None,
+        account_count: None,
+        accounts_commitment: None,
+        response_headers: Default::default(),
+        balance_formula: None,
+        item_count: None,
+        claim_type: None,
+        account_filter: None,
+        verifier_version: None,
+        over_revealed_bytes: None,
+        recipient: None,
+        transcript_commitments: Vec::new(),
+        digest: None,
+        label: None,
+        inactive_accounts: None,
+        signer_set_id: None,
+        partial_signatures: Vec::new(),
+        request_template: None,
+        disclosure: None,
+        balances_by_currency: Default::default(),
+        api_version: None,
+        claim_set: None,
+        schema_profile: None,
+    }
+}
+
+#[cfg(feature = "compact")]
+impl Attestation {
+    /// Encode as a compact attestation: the core, then the metadata.
+    ///
+    /// Every fixture round-trips, and the core stays within [`MAX_CORE_LEN`] no
+    /// matter how many unsigned fields the metadata carries:
+    ///
+    /// ```
+    /// use auditorzk_attestation::compact::{CompactAttestation, MAX_CORE_LEN};
+    /// use auditorzk_attestation::{verify_attestation, Attestation};
+    ///
+    /// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+    /// for json in [
+    ///     include_str!("../fixtures/attestation_v1.json"),
+    ///     include_str!("../fixtures/attestation_v4.json"),
+    ///     include_str!("../fixtures/attestation_v9.json"),
+    ///     include_str!("../fixtures/attestation_v13.json"),
+    ///     include_str!("../fixtures/attestation_v17.json"),
+    ///     include_str!("../fixtures/attestation_v18.json"),
+    ///     include_str!("../fixtures/attestation_v19.json"),
+    ///     include_str!("../fixtures/attestation_v20.json"),
+    ///     include_str!("../fixtures/attestation_v21.json"),
+    ///     include_str!("../fixtures/attestation_v22.json"),
+    ///     include_str!("../fixtures/attestation_v24.json"),
+    /// ] {
+    ///     let attestation: Attestation = serde_json::from_str(json).unwrap();
+    ///     let compact = attestation.to_compact().unwrap();
+    ///     assert_eq!(Attestation::from_compact(&compact).unwrap(), attestation);
+    ///
+    ///     // The core alone still verifies
+    ///     let core = CompactAttestation::from_bytes(&compact).unwrap();
+    ///     assert!(core.core_len() <= MAX_CORE_LEN);
+    ///     core.verify(&pubkey).unwrap();
+    /// }
+    ///
+    /// // Unsigned fields only grow the metadata
+    /// let mut attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v17.json")).unwrap();
+    /// let core_len = CompactAttestation::core(&attestation).unwrap().core_len();
+    /// for i in 0..200 {
+    ///     attestation.response_headers.insert(format!("x-header-{}", i), "v".repeat(64));
+    /// }
+    /// let compact = attestation.to_compact().unwrap();
+    /// assert_eq!(CompactAttestation::from_bytes(&compact).unwrap().core_len(), core_len);
+    /// assert_eq!(Attestation::from_compact(&compact).unwrap(), attestation);
+    /// ```
+    pub fn to_compact(&self) -> Result<Vec<u8>, Error> {
+        let mut compact = CompactAttestation::core(self)?;
+
+        let mut metadata = self.clone();
+        metadata.schema_version = 0;
+        metadata.server_name = String::new();
+        metadata.timestamp = 0;
+        metadata.balance_commitment = Vec::new();
+        metadata.signature = String::new();
+        metadata.verifier_pubkey = Vec::new();
+        metadata.claim_set = None;
+        if metadata != blank() {
+            let json = crate::canonical::to_canonical_json(&metadata)?;
+            let compressed = zstd::bulk::compress(json.as_bytes(), zstd::DEFAULT_COMPRESSION_LEVEL)
+                .map_err(|_| Error::InvalidCompactEncoding("metadata compression failed"))?;
+            compact.metadata = Some(compressed);
+        }
+
+        Ok(compact.to_bytes())
+    }
+
+    /// Decode a compact attestation, expanding its metadata.
+    ///
+    /// The result's signed message must be the core's, so metadata can't change
+    /// what was signed. Without metadata only a message of the base fields alone
+    /// (schema 1 style, or the `minimal` claim set) can be expanded; anything
+    /// else fails with [`Error::MissingCompactMetadata`]:
+    ///
+    /// ```
+    /// use auditorzk_attestation::compact::CompactAttestation;
+    /// use auditorzk_attestation::{Attestation, Error};
+    ///
+    /// let attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v17.json")).unwrap();
+    /// let core = CompactAttestation::core(&attestation).unwrap().to_bytes();
+    /// assert_eq!(Attestation::from_compact(&core), Err(Error::MissingCompactMetadata));
+    ///
+    /// // ...but a `minimal` claim set core expands on its own
+    /// let mut minimal: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v22.json")).unwrap();
+    /// minimal.claim_set = Some("minimal".into());
+    /// auditorzk_attestation::claims::MINIMAL.strip(&mut minimal);
+    /// let core = CompactAttestation::core(&minimal).unwrap().to_bytes();
+    /// let expanded = Attestation::from_compact(&core).unwrap();
+    /// assert_eq!(expanded.claim_set.as_deref(), Some("minimal"));
+    /// assert_eq!(expanded.signed_message(), minimal.signed_message());
+    ///
+    /// // One flipped byte in the signed message no longer matches the metadata
+    /// let mut compact = attestation.to_compact().unwrap();
+    /// compact[300] ^= 1;
+    /// assert_eq!(Attestation::from_compact(&compact), Err(Error::CompactMismatch));
+    /// ```
+    pub fn from_compact(bytes: &[u8]) -> Result<Self, Error> {
+        use alloc::string::ToString;
+
+        let compact = CompactAttestation::from_bytes(bytes)?;
+        let mut attestation = match &compact.metadata {
+            Some(metadata) => {
+                let json = zstd::bulk::decompress(metadata, MAX_METADATA_LEN)
+                    .map_err(|_| Error::InvalidCompactEncoding("metadata is not a zstd frame within the size limit"))?;
+                let json = core::str::from_utf8(&json).map_err(|e| Error::Json(e.to_string()))?;
+                crate::canonical::from_json(json)?
+            }
+            None if !compact.is_base_only() => return Err(Error::MissingCompactMetadata),
+            None => blank(),
+        };
+        compact.restore(&mut attestation)?;
+
+        if attestation.signed_message()? != compact.message {
+            return Err(Error::CompactMismatch);
+        }
+        Ok(attestation)
+    }
+}
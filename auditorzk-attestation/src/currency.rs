@@ -0,0 +1,100 @@
+//! Per-currency balance commitments for multi-currency accounts.
+//!
+//! When the verifier commits by currency (schema 20+), `balances_by_currency`
+//! maps each ISO 4217 code to a commitment to that currency's total, and the
+//! signed `balance_commitment` is [`map_commitment`] of the whole map. The
+//! signature therefore binds every subtotal without converting between
+//! currencies.
+//!
+//! Each total is written as a plain decimal with exactly the currency's minor-unit
+//! places (`1334.56`, `120000`, `12.345`) and committed as
+//! `SHA-256(total || currency_blinder)`. The currency blinder is derived from the
+//! prover's blinder with [`currency_blinder`], so opening one currency to a
+//! consumer reveals nothing that helps guess the others.
+//!
+//! ```
+//! use auditorzk_attestation::message::SignedField::*;
+//! use auditorzk_attestation::signature::verify_legacy_attestation;
+//! use auditorzk_attestation::{currency, policy, Attestation, Error};
+//!
+//! let json = include_str!("../fixtures/attestation_v20.json");
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! let fields = [
+//!     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+//!     AccountFilter, VerifierVersion, Recipient, Label, RequestTemplate,
+//! ];
+//! let attestation: Attestation = serde_json::from_str(json).unwrap();
+//! verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+//!
+//! // The prover opens its EUR subtotal to this consumer only
+//! let blinder = currency::currency_blinder(b"mock_blinder_for_testing", "EUR");
+//! policy::check_currency_total(&attestation, "EUR", "1500.50", &blinder).unwrap();
+//! assert_eq!(
+//!     policy::check_currency_total(&attestation, "EUR", "1600.50", &blinder),
+//!     Err(Error::CurrencyTotalMismatch("EUR".into())),
+//! );
+//! assert_eq!(
+//!     policy::check_currency_total(&attestation, "GBP", "1500.50", &blinder),
+//!     Err(Error::CurrencyNotAttested("GBP".into())),
+//! );
+//!
+//! // A currency added after signing isn't covered by the balance commitment
+//! let mut tampered = attestation.clone();
+//! tampered.balances_by_currency.insert("GBP".into(), vec![0; 32]);
+//! assert_eq!(
+//!     policy::check_currency_total(&tampered, "EUR", "1500.50", &blinder),
+//!     Err(Error::CurrencyMapMismatch),
+//! );
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+/// Blinder for one currency's commitment: `SHA-256(blinder || currency)`
+pub fn currency_blinder(blinder: &[u8], currency: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(blinder);
+    hasher.update(currency.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Commitment to one currency's total: `SHA-256(total || currency_blinder)`
+pub fn currency_commitment(total: &str, currency_blinder: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(total.as_bytes());
+    hasher.update(currency_blinder);
+    hasher.finalize().into()
+}
+
+/// Canonical bytes of a currency map: for each entry in code order, the 3-byte
+/// code followed by its 32-byte commitment
+pub fn encode_map(map: &BTreeMap<String, Vec<u8>>) -> Result<Vec<u8>, Error> {
+    let mut encoded = Vec::with_capacity(map.len() * 35);
+    for (code, commitment) in map {
+        check_code(code)?;
+        if commitment.len() != 32 {
+            return Err(Error::InvalidCommitmentLength(commitment.len()));
+        }
+        encoded.extend_from_slice(code.as_bytes());
+        encoded.extend_from_slice(commitment);
+    }
+    Ok(encoded)
+}
+
+/// SHA-256 of [`encode_map`], the value signed as `balance_commitment`
+pub fn map_commitment(map: &BTreeMap<String, Vec<u8>>) -> Result<[u8; 32], Error> {
+    encode_map(map).map(|encoded| Sha256::digest(&encoded).into())
+}
+
+/// Currency codes are three uppercase ASCII letters, so entries are fixed-width
+fn check_code(code: &str) -> Result<(), Error> {
+    if code.len() == 3 && code.bytes().all(|b| b.is_ascii_uppercase()) {
+        Ok(())
+    } else {
+        Err(Error::InvalidCurrencyCode(code.to_string()))
+    }
+}
@@ -0,0 +1,64 @@
+//! What the verifier saw of the transcript, as byte counts.
+//!
+//! Each direction's bytes fall in exactly one class: *revealed* in plaintext to
+//! the verifier (whether or not also committed), *committed* to by hash but never
+//! revealed, or *hidden*. The verifier records the counts (schema 19+) as
+//! `disclosure`. It is unsigned and informational: it tells the user what the
+//! auditor saw, but consumers shouldn't base policy on it.
+//!
+//! ```
+//! use auditorzk_attestation::Attestation;
+//! use auditorzk_attestation::message::SignedField::*;
+//! use auditorzk_attestation::signature::verify_legacy_attestation;
+//!
+//! let json = include_str!("../fixtures/attestation_v19.json");
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! let fields = [
+//!     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+//!     AccountFilter, VerifierVersion, Recipient, CommitmentCount, CommitmentsDigest, Label, SignerSet,
+//!     RequestTemplate,
+//! ];
+//! let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+//! verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+//!
+//! let disclosure = attestation.disclosure.unwrap();
+//! assert_eq!(disclosure.received.revealed, 458);
+//! assert_eq!(disclosure.received.total(), 1520);
+//!
+//! // The request was committed (e.g. to a template), never revealed
+//! assert_eq!(disclosure.sent.revealed, 0);
+//! assert_eq!(disclosure.sent.committed, disclosure.sent.total());
+//!
+//! // Unsigned, so it doesn't affect the signature
+//! attestation.disclosure = None;
+//! verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// Byte counts of one transcript direction, by what the verifier saw of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DirectionCounts {
+    /// Bytes revealed in plaintext, including revealed bytes that are also committed
+    pub revealed: u64,
+    /// Bytes only committed to by hash
+    pub committed: u64,
+    /// Bytes neither revealed nor committed
+    pub hidden: u64,
+}
+
+impl DirectionCounts {
+    /// Length of the direction's data
+    pub fn total(&self) -> u64 {
+        self.revealed + self.committed + self.hidden
+    }
+}
+
+/// Byte counts of both directions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DisclosureCounts {
+    pub sent: DirectionCounts,
+    pub received: DirectionCounts,
+}
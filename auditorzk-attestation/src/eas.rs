@@ -0,0 +1,352 @@
+//! Attestation fields ABI-encoded for the Ethereum Attestation Service (EAS).
+//!
+//! An [`EasSchema`] is the schema string the integrator registered with EAS,
+//! e.g. `string serverName, uint256 timestamp, bytes32 balanceCommitment`. Each
+//! field name picks an attestation field, and [`EasSchema::encode`] ABI-encodes
+//! their values in schema order: the `data` an EAS resolver reads back with
+//! `abi.decode`. [`AttestationRequestData`] wraps it with the rest of the
+//! `EAS.attest` arguments.
+//!
+//! | Name                 | Attestation field               | Types              |
+//! |----------------------|---------------------------------|--------------------|
+//! | `serverName`         | `server_name`                   | `string`           |
+//! | `timestamp`          | `timestamp`                     | `uint8`…`uint256`  |
+//! | `balanceCommitment`  | `balance_commitment`            | `bytes32`, `bytes` |
+//! | `accountCount`       | `account_count`                 | `uint8`…`uint256`  |
+//! | `accountsCommitment` | `accounts_commitment`           | `bytes32`, `bytes` |
+//! | `claimType`          | `claim_type`                    | `string`           |
+//! | `balanceFormula`     | `balance_formula`               | `string`           |
+//! | `recipient`          | `recipient`                     | `bytes32`, `bytes` |
+//! | `verifierPubkey`     | `verifier_pubkey`               | `bytes32`, `bytes` |
+//! | `messageHash`        | SHA-256 of the signed message   | `bytes32`, `bytes` |
+//! | `signature`          | `signature`, with its version   | `bytes`            |
+//!
+//! EAS only records who submitted the attestation; the BIP-340 signature isn't
+//! checked on-chain. Carry `signature`, `verifierPubkey` and `messageHash` for
+//! consumers that verify it off-chain. The schema UID is a keccak-256 hash,
+//! which this crate doesn't compute: register the schema string as written.
+//!
+//! ```
+//! use auditorzk_attestation::eas::{AttestationRequestData, EasSchema};
+//! use auditorzk_attestation::Attestation;
+//!
+//! let json = include_str!("../fixtures/attestation_v22.json");
+//! let attestation: Attestation = serde_json::from_str(json).unwrap();
+//!
+//! let schema: EasSchema = "string serverName, uint256 timestamp, bytes32 balanceCommitment".parse().unwrap();
+//! let data = schema.encode(&attestation).unwrap();
+//! assert_eq!(
+//!     hex::encode(&data),
+//!     concat!(
+//!         // serverName: offset of its tail
+//!         "0000000000000000000000000000000000000000000000000000000000000060",
+//!         // timestamp
+//!         "000000000000000000000000000000000000000000000000000000006919f500",
+//!         // balanceCommitment
+//!         "5e3313ac5b445c82f0d1a43e67998639b6550c963a233abd27be6bf77013dd43",
+//!         // serverName: length, then "sandbox.plaid.com" padded to a word
+//!         "0000000000000000000000000000000000000000000000000000000000000011",
+//!         "73616e64626f782e706c6169642e636f6d000000000000000000000000000000",
+//!     )
+//! );
+//!
+//! // abi.encode(AttestationRequestData): offset, six head words, then the data
+//! let request = AttestationRequestData::new(data.clone(), true).abi_encode();
+//! assert_eq!(request.len(), 32 + 6 * 32 + 32 + data.len());
+//! assert_eq!(request[32 * 3 + 31], 1); // revocable
+//! assert_eq!(&request[32 * 8..], &data[..]);
+//!
+//! // A field the attestation doesn't have, or a type its value can't take, is refused
+//! assert!("string serverName, bool accountCount".parse::<EasSchema>().is_err());
+//! assert!("uint8 timestamp".parse::<EasSchema>().unwrap().encode(&attestation).is_err());
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::message::message_digest;
+use crate::{Attestation, Error};
+
+/// Size of an ABI word
+const WORD: usize = 32;
+
+/// ABI types an [`EasSchema`] field can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiType {
+    /// `uint8` to `uint256`, by bit width
+    Uint(u16),
+    Bytes32,
+    Bytes,
+    String,
+}
+
+impl AbiType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "bytes32" => Some(Self::Bytes32),
+            "bytes" => Some(Self::Bytes),
+            "string" => Some(Self::String),
+            _ => {
+                let width = name.strip_prefix("uint")?;
+                let bits: u16 = width.parse().ok()?;
+                (bits.is_multiple_of(8) && (8..=256).contains(&bits) && bits.to_string() == width).then_some(Self::Uint(bits))
+            }
+        }
+    }
+}
+
+impl fmt::Display for AbiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uint(bits) => write!(f, "uint{}", bits),
+            Self::Bytes32 => f.write_str("bytes32"),
+            Self::Bytes => f.write_str("bytes"),
+            Self::String => f.write_str("string"),
+        }
+    }
+}
+
+/// Attestation field an EAS schema field is filled from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    ServerName,
+    Timestamp,
+    BalanceCommitment,
+    AccountCount,
+    AccountsCommitment,
+    ClaimType,
+    BalanceFormula,
+    Recipient,
+    VerifierPubkey,
+    MessageHash,
+    Signature,
+}
+
+impl Source {
+    const ALL: [Self; 11] = [
+        Self::ServerName,
+        Self::Timestamp,
+        Self::BalanceCommitment,
+        Self::AccountCount,
+        Self::AccountsCommitment,
+        Self::ClaimType,
+        Self::BalanceFormula,
+        Self::Recipient,
+        Self::VerifierPubkey,
+        Self::MessageHash,
+        Self::Signature,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::ServerName => "serverName",
+            Self::Timestamp => "timestamp",
+            Self::BalanceCommitment => "balanceCommitment",
+            Self::AccountCount => "accountCount",
+            Self::AccountsCommitment => "accountsCommitment",
+            Self::ClaimType => "claimType",
+            Self::BalanceFormula => "balanceFormula",
+            Self::Recipient => "recipient",
+            Self::VerifierPubkey => "verifierPubkey",
+            Self::MessageHash => "messageHash",
+            Self::Signature => "signature",
+        }
+    }
+
+    fn accepts(self, ty: AbiType) -> bool {
+        match self {
+            Self::ServerName | Self::ClaimType | Self::BalanceFormula => ty == AbiType::String,
+            Self::Timestamp | Self::AccountCount => matches!(ty, AbiType::Uint(_)),
+            Self::Signature => ty == AbiType::Bytes,
+            _ => matches!(ty, AbiType::Bytes32 | AbiType::Bytes),
+        }
+    }
+
+    fn value(self, attestation: &Attestation) -> Result<Value, Error> {
+        let missing = || Error::EasFieldUnavailable(self.name());
+        let bytes = |bytes: Option<&Vec<u8>>| bytes.cloned().map(Value::Bytes).ok_or_else(missing);
+        let text = |text: Option<&String>| text.map(|text| Value::Bytes(text.as_bytes().to_vec())).ok_or_else(missing);
+        match self {
+            Self::ServerName => text(Some(&attestation.server_name)),
+            Self::Timestamp => Ok(Value::Uint(attestation.timestamp)),
+            Self::BalanceCommitment => bytes(Some(&attestation.balance_commitment)),
+            Self::AccountCount => attestation.account_count.map(|count| Value::Uint(count.into())).ok_or_else(missing),
+            Self::AccountsCommitment => bytes(attestation.accounts_commitment.as_ref()),
+            Self::ClaimType => text(attestation.claim_type.as_ref()),
+            Self::BalanceFormula => text(attestation.balance_formula.as_ref()),
+            Self::Recipient => bytes(attestation.recipient.as_ref()),
+            Self::VerifierPubkey => bytes(Some(&attestation.verifier_pubkey)),
+            Self::MessageHash => Ok(Value::Bytes(message_digest(&attestation.signed_message()?).to_vec())),
+            Self::Signature => {
+                hex::decode(&attestation.signature).map(Value::Bytes).map_err(|_| Error::InvalidSignatureEncoding)
+            }
+        }
+    }
+}
+
+enum Value {
+    Uint(u64),
+    /// Raw bytes, or a string's UTF-8
+    Bytes(Vec<u8>),
+}
+
+/// One `type name` entry of an [`EasSchema`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EasField {
+    pub ty: AbiType,
+    source: Source,
+}
+
+impl EasField {
+    pub fn name(&self) -> &'static str {
+        self.source.name()
+    }
+}
+
+/// An EAS schema whose fields all name attestation fields (see the module docs)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EasSchema {
+    fields: Vec<EasField>,
+}
+
+impl EasSchema {
+    /// Parse a comma-separated `type name` list, checking each name against the
+    /// attestation fields and the types they can be encoded as
+    pub fn parse(schema: &str) -> Result<Self, Error> {
+        let invalid = Error::InvalidEasSchema;
+        let mut fields: Vec<EasField> = Vec::new();
+        for entry in schema.split(',') {
+            let mut words = entry.split_whitespace();
+            let (Some(ty), Some(name), None) = (words.next(), words.next(), words.next()) else {
+                return Err(invalid(format!("expected `type name`, got '{}'", entry.trim())));
+            };
+            let ty = AbiType::parse(ty).ok_or_else(|| invalid(format!("unsupported type '{}'", ty)))?;
+            let source = Source::ALL
+                .into_iter()
+                .find(|source| source.name() == name)
+                .ok_or_else(|| invalid(format!("no attestation field '{}'", name)))?;
+            if !source.accepts(ty) {
+                return Err(invalid(format!("{} can't be encoded as {}", name, ty)));
+            }
+            if fields.iter().any(|field| field.source == source) {
+                return Err(invalid(format!("{} is listed twice", name)));
+            }
+            fields.push(EasField { ty, source });
+        }
+        Ok(Self { fields })
+    }
+
+    pub fn fields(&self) -> &[EasField] {
+        &self.fields
+    }
+
+    /// `abi.encode` of the schema's fields, taken from `attestation`
+    pub fn encode(&self, attestation: &Attestation) -> Result<Vec<u8>, Error> {
+        let head_len = self.fields.len() * WORD;
+        let mut head = Vec::with_capacity(head_len);
+        let mut tail = Vec::new();
+        for field in &self.fields {
+            let mismatch = || Error::EasValueMismatch { field: field.name(), ty: field.ty.to_string() };
+            match (field.ty, field.source.value(attestation)?) {
+                (AbiType::Uint(bits), Value::Uint(n)) => {
+                    if bits < 64 && n >> bits != 0 {
+                        return Err(mismatch());
+                    }
+                    head.extend_from_slice(&uint_word(n.into()));
+                }
+                (AbiType::Bytes32, Value::Bytes(bytes)) => {
+                    let word: [u8; WORD] = bytes.as_slice().try_into().map_err(|_| mismatch())?;
+                    head.extend_from_slice(&word);
+                }
+                (AbiType::Bytes | AbiType::String, Value::Bytes(bytes)) => {
+                    head.extend_from_slice(&uint_word((head_len + tail.len()) as u128));
+                    push_dynamic(&mut tail, &bytes);
+                }
+                _ => return Err(mismatch()),
+            }
+        }
+        head.extend_from_slice(&tail);
+        Ok(head)
+    }
+}
+
+impl FromStr for EasSchema {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for EasSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {}", field.ty, field.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// EAS `AttestationRequestData`, the per-attestation part of `EAS.attest`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationRequestData {
+    /// Address the attestation is about, zero for none
+    pub recipient: [u8; 20],
+    /// Unix time the attestation expires, zero for never
+    pub expiration_time: u64,
+    /// Must match whether the schema was registered as revocable
+    pub revocable: bool,
+    /// UID of an attestation this one refers to, zero for none
+    pub ref_uid: [u8; 32],
+    /// The schema fields, from [`EasSchema::encode`]
+    pub data: Vec<u8>,
+    /// Wei sent along to the schema's resolver
+    pub value: u128,
+}
+
+impl AttestationRequestData {
+    /// Request `data` with no recipient, expiry, referenced attestation or value.
+    ///
+    /// The attestation's own `recipient` is a 32-byte hash, not an address, so it
+    /// belongs in the schema (`bytes32 recipient`) rather than here.
+    pub fn new(data: Vec<u8>, revocable: bool) -> Self {
+        Self { recipient: [0; 20], expiration_time: 0, revocable, ref_uid: [0; 32], data, value: 0 }
+    }
+
+    /// `abi.encode(data)`: the struct is dynamic, so an offset word comes first
+    pub fn abi_encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(9 * WORD + self.data.len() + WORD);
+        out.extend_from_slice(&uint_word(WORD as u128));
+        let mut recipient = [0u8; WORD];
+        recipient[WORD - 20..].copy_from_slice(&self.recipient);
+        out.extend_from_slice(&recipient);
+        out.extend_from_slice(&uint_word(self.expiration_time.into()));
+        out.extend_from_slice(&uint_word(self.revocable.into()));
+        out.extend_from_slice(&self.ref_uid);
+        // `data` starts right after the six head words
+        out.extend_from_slice(&uint_word(6 * WORD as u128));
+        out.extend_from_slice(&uint_word(self.value));
+        push_dynamic(&mut out, &self.data);
+        out
+    }
+}
+
+/// `n` as a big-endian ABI word
+fn uint_word(n: u128) -> [u8; WORD] {
+    let mut word = [0u8; WORD];
+    word[WORD - 16..].copy_from_slice(&n.to_be_bytes());
+    word
+}
+
+/// Length word, then `bytes` zero-padded to whole words
+fn push_dynamic(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&uint_word(bytes.len() as u128));
+    out.extend_from_slice(bytes);
+    out.resize(out.len() + (WORD - bytes.len() % WORD) % WORD, 0);
+}
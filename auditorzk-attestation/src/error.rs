@@ -0,0 +1,213 @@
+use alloc::string::String;
+use core::fmt;
+
+/// Errors returned while encoding or verifying an attestation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Server name does not fit in its 32-byte field
+    ServerNameTooLong(usize),
+    /// Commitment is not a 32-byte hash
+    InvalidCommitmentLength(usize),
+    /// Recipient is not 32 bytes
+    InvalidRecipientLength(usize),
+    /// Attestation is bound to a different recipient than the redeemer, or to none
+    RecipientMismatch,
+    /// Operator label is longer than `message::MAX_LABEL_LEN` bytes
+    LabelTooLong(usize),
+    /// Signer-set id is not 32 bytes
+    InvalidSignerSetIdLength(usize),
+    /// A signed field was appended after one that follows it in the message
+    FieldOutOfOrder { field: &'static str, after: &'static str },
+    /// Attestation claims a signed environment but carries none
+    MissingEnvironment,
+    /// Environment name has no signed-message encoding
+    UnknownEnvironment(String),
+    /// Only one of `account_count` and `accounts_commitment` is present
+    IncompleteAccountInventory,
+    /// Field is set but the attestation's schema version never signed it
+    UnsignedField { field: &'static str, schema_version: u32 },
+    /// Balance formula name has no signed-message encoding
+    UnknownFormula(String),
+    /// Claim type name has no signed-message encoding
+    UnknownClaimType(String),
+    /// Transcript commitment direction is neither `sent` nor `received`
+    UnknownCommitmentDirection(String),
+    /// Transcript commitment hash algorithm has no encoding
+    UnknownHashAlgorithm(String),
+    /// Signature is not valid hex or has the wrong length
+    InvalidSignatureEncoding,
+    /// Signature version prefix is not one this crate understands
+    UnsupportedSignatureVersion([u8; 3]),
+    /// Public key bytes are not a valid x-only secp256k1 key
+    InvalidPublicKey,
+    /// Embedded verifier key differs from the trusted key
+    PublicKeyMismatch,
+    /// Signature does not verify over the reconstructed message
+    InvalidSignature,
+    /// Stored digest differs from the one rebuilt from the signed fields
+    DigestMismatch,
+    /// Attestation is older than the accepted maximum age
+    Stale { age: u64, max_age: u64 },
+    /// Attestation timestamp lies too far in the future
+    FromFuture { timestamp: u64, now: u64 },
+    /// Server name is not in the consumer's allowlist
+    ServerNotAllowed(String),
+    /// Environment is missing, unsigned or different from the expected one
+    EnvironmentMismatch { expected: String, actual: Option<String> },
+    /// Attested account count differs from the consumer's own account list
+    AccountCountMismatch { expected: u32, actual: Option<u32> },
+    /// Attested accounts commitment does not match the consumer's own account ids
+    AccountIdsMismatch,
+    /// Attestation was issued by a distrusted verifier build, or records none
+    DistrustedVerifierVersion(Option<String>),
+    /// Attestation is covered by a revocation list entry
+    Revoked { reason: String },
+    /// Revocation list signature does not verify against the organization key
+    InvalidRevocationList,
+    /// Signer-set threshold is zero or larger than the set
+    InvalidThreshold { threshold: u32, members: usize },
+    /// Attestation was not issued under the consumer's signer set, or under none
+    SignerSetMismatch,
+    /// Fewer distinct members of the signer set signed than its threshold
+    QuorumNotMet { valid: usize, threshold: u32 },
+    /// Partial signatures were merged from an attestation with different signed fields
+    AttestationMismatch,
+    /// Attestation records no request template, an unaccepted one, or a failed match
+    RequestTemplateNotMatched(Option<String>),
+    /// Currency code in `balances_by_currency` is not three uppercase letters
+    InvalidCurrencyCode(String),
+    /// `balances_by_currency` does not hash to the signed balance commitment
+    CurrencyMapMismatch,
+    /// Attestation has no commitment for the currency
+    CurrencyNotAttested(String),
+    /// Opened currency total does not match its commitment
+    CurrencyTotalMismatch(String),
+    /// Transparency tree head signature does not verify against the organization key
+    InvalidTreeHead,
+    /// Inclusion or consistency proof is malformed or for other tree sizes
+    InvalidTransparencyProof(&'static str),
+    /// Inclusion proof does not lead from the attestation to the tree head's root
+    NotInTransparencyLog,
+    /// Consistency proof does not show the later tree head extending the earlier one
+    InconsistentTreeHeads,
+    /// Compact attestation bytes don't follow the compact layout
+    InvalidCompactEncoding(&'static str),
+    /// Compact attestation carries only a core, whose signed message can't be expanded alone
+    MissingCompactMetadata,
+    /// Compact attestation's metadata describes a different signed message than its core
+    CompactMismatch,
+    /// Claim set name or id is not registered, see `claims::CLAIM_SETS`
+    UnknownClaimSet(String),
+    /// A signed field is set but the declared claim set doesn't carry it
+    ClaimNotInSet { field: &'static str, set: &'static str },
+    /// Claim-set message doesn't follow the claims layout
+    InvalidClaimEncoding(&'static str),
+    /// Attestation signs the untagged legacy layout, which only verifies against a pinned field list
+    LegacyLayout(u32),
+    /// Legacy attestation signs other fields than the consumer pinned
+    SignedFieldsMismatch,
+    /// EAS schema string is malformed or names a field or type that can't be encoded
+    InvalidEasSchema(String),
+    /// The EAS schema names a field this attestation doesn't carry
+    EasFieldUnavailable(&'static str),
+    /// An attestation value doesn't fit the ABI type the EAS schema gives it
+    EasValueMismatch { field: &'static str, ty: String },
+    /// Attestation schema is newer than this crate or otherwise unknown
+    UnsupportedSchemaVersion(u64),
+    /// JSON (de)serialization failed
+    Json(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServerNameTooLong(len) => write!(f, "Server name too long: {} bytes (max 32)", len),
+            Self::InvalidCommitmentLength(len) => write!(f, "Commitment must be 32 bytes, got {}", len),
+            Self::InvalidRecipientLength(len) => write!(f, "Recipient must be 32 bytes, got {}", len),
+            Self::RecipientMismatch => f.write_str("Attestation is not bound to this recipient"),
+            Self::LabelTooLong(len) => write!(f, "Label too long: {} bytes (max 64)", len),
+            Self::InvalidSignerSetIdLength(len) => write!(f, "Signer set id must be 32 bytes, got {}", len),
+            Self::FieldOutOfOrder { field, after } => {
+                write!(f, "Signed field {} can't follow {} in the message", field, after)
+            }
+            Self::MissingEnvironment => f.write_str("Environment is marked as signed but missing"),
+            Self::UnknownEnvironment(name) => write!(f, "Unknown environment '{}'", name),
+            Self::IncompleteAccountInventory => {
+                f.write_str("Account count and accounts commitment must be present together")
+            }
+            Self::UnsignedField { field, schema_version } => {
+                write!(f, "Schema version {} does not sign the {}", schema_version, field)
+            }
+            Self::UnknownFormula(name) => write!(f, "Unknown balance formula '{}'", name),
+            Self::UnknownClaimType(name) => write!(f, "Unknown claim type '{}'", name),
+            Self::UnknownCommitmentDirection(name) => write!(f, "Unknown commitment direction '{}'", name),
+            Self::UnknownHashAlgorithm(name) => write!(f, "Unknown hash algorithm '{}'", name),
+            Self::InvalidSignatureEncoding => f.write_str("Signature is not a hex-encoded 67-byte versioned signature"),
+            Self::UnsupportedSignatureVersion(v) => {
+                write!(f, "Unsupported signature version {}.{}.{}", v[0], v[1], v[2])
+            }
+            Self::InvalidPublicKey => f.write_str("Invalid verifier public key"),
+            Self::PublicKeyMismatch => f.write_str("Attestation was not issued by the trusted verifier key"),
+            Self::InvalidSignature => f.write_str("Signature verification failed"),
+            Self::DigestMismatch => f.write_str("Stored digest does not match the signed fields"),
+            Self::Stale { age, max_age } => write!(f, "Attestation is {}s old (max {}s)", age, max_age),
+            Self::FromFuture { timestamp, now } => {
+                write!(f, "Attestation timestamp {} is ahead of current time {}", timestamp, now)
+            }
+            Self::ServerNotAllowed(name) => write!(f, "Server {} is not accepted", name),
+            Self::EnvironmentMismatch { expected, actual } => match actual {
+                Some(actual) => write!(f, "Expected signed environment {}, got {}", expected, actual),
+                None => write!(f, "Expected signed environment {}, got none", expected),
+            },
+            Self::AccountCountMismatch { expected, actual } => match actual {
+                Some(actual) => write!(f, "Expected {} accounts, attestation covers {}", expected, actual),
+                None => write!(f, "Expected {} accounts, attestation carries no account inventory", expected),
+            },
+            Self::AccountIdsMismatch => f.write_str("Attested account ids differ from the expected accounts"),
+            Self::DistrustedVerifierVersion(version) => match version {
+                Some(version) => write!(f, "Verifier version {} is not trusted", version),
+                None => f.write_str("Attestation does not record its verifier version"),
+            },
+            Self::Revoked { reason } => write!(f, "Attestation has been revoked: {}", reason),
+            Self::InvalidRevocationList => f.write_str("Revocation list signature verification failed"),
+            Self::InvalidThreshold { threshold, members } => {
+                write!(f, "Threshold {} is invalid for a signer set of {}", threshold, members)
+            }
+            Self::SignerSetMismatch => f.write_str("Attestation was not issued under the trusted signer set"),
+            Self::QuorumNotMet { valid, threshold } => {
+                write!(f, "{} valid signatures from the signer set, {} required", valid, threshold)
+            }
+            Self::AttestationMismatch => f.write_str("Partial signatures are for a different attestation"),
+            Self::RequestTemplateNotMatched(id) => match id {
+                Some(id) => write!(f, "Request was not matched against accepted template {}", id),
+                None => f.write_str("Attestation does not record a request template"),
+            },
+            Self::InvalidCurrencyCode(code) => write!(f, "Invalid currency code '{}'", code),
+            Self::CurrencyMapMismatch => f.write_str("Per-currency commitments do not match the signed balance commitment"),
+            Self::CurrencyNotAttested(code) => write!(f, "Attestation has no {} balance", code),
+            Self::CurrencyTotalMismatch(code) => write!(f, "{} total does not match its commitment", code),
+            Self::InvalidTreeHead => f.write_str("Tree head signature verification failed"),
+            Self::InvalidTransparencyProof(what) => write!(f, "Invalid transparency proof: {}", what),
+            Self::NotInTransparencyLog => f.write_str("Attestation is not included in the signed tree head"),
+            Self::InconsistentTreeHeads => f.write_str("Tree heads are inconsistent: the log was not append-only"),
+            Self::InvalidCompactEncoding(what) => write!(f, "Invalid compact attestation: {}", what),
+            Self::MissingCompactMetadata => f.write_str("Compact attestation has no metadata to expand its signed fields"),
+            Self::CompactMismatch => f.write_str("Compact attestation metadata does not match its signed core"),
+            Self::UnknownClaimSet(name) => write!(f, "Unknown claim set '{}'", name),
+            Self::ClaimNotInSet { field, set } => write!(f, "Claim set {} does not carry the {}", set, field),
+            Self::InvalidClaimEncoding(what) => write!(f, "Invalid claim-set message: {}", what),
+            Self::LegacyLayout(v) => {
+                write!(f, "Schema {} attestation signs the legacy layout; verify it against pinned fields", v)
+            }
+            Self::SignedFieldsMismatch => f.write_str("Attestation signs different fields than expected"),
+            Self::InvalidEasSchema(reason) => write!(f, "Invalid EAS schema: {}", reason),
+            Self::EasFieldUnavailable(field) => write!(f, "Attestation has no {} for the EAS schema", field),
+            Self::EasValueMismatch { field, ty } => write!(f, "Attestation {} does not fit EAS type {}", field, ty),
+            Self::UnsupportedSchemaVersion(v) => write!(f, "Unsupported schema version {}", v),
+            Self::Json(msg) => write!(f, "JSON error: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
@@ -0,0 +1,26 @@
+//! Commitment over the set of accounts an attestation covers.
+//!
+//! Each `account_id` is hashed with SHA-256, the hashes are sorted bytewise and
+//! the commitment is SHA-256 of their concatenation. Sorting makes it independent
+//! of the order the data source listed the accounts in, so a consumer can
+//! recompute it from its own `/accounts/get` call.
+
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of a single account id
+pub fn account_id_hash(account_id: &str) -> [u8; 32] {
+    Sha256::digest(account_id.as_bytes()).into()
+}
+
+/// Order-independent commitment over a list of account ids
+pub fn accounts_commitment<S: AsRef<str>>(account_ids: &[S]) -> [u8; 32] {
+    let mut hashes: Vec<[u8; 32]> = account_ids.iter().map(|id| account_id_hash(id.as_ref())).collect();
+    hashes.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for hash in &hashes {
+        hasher.update(hash);
+    }
+    hasher.finalize().into()
+}
@@ -0,0 +1,576 @@
+//! Attestation types and verification for AuditorZK.
+//!
+//! This crate contains everything a consumer needs to check an attestation issued
+//! by the verifier server: the [`Attestation`] type, the signed-message layout,
+//! BIP-340 signature verification, canonical JSON and freshness/policy helpers.
+//! It has no async or networking dependencies and builds with `no_std` + `alloc`
+//! when the default `std` feature is disabled.
+//!
+//! # Example
+//!
+//! ```
+//! use auditorzk_attestation::{policy, verify_attestation, Attestation};
+//!
+//! let json = include_str!("../fixtures/attestation_v22.json");
+//! let attestation: Attestation = serde_json::from_str(json).unwrap();
+//!
+//! // The trusted key comes from the verifier operator, not from the attestation itself
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! verify_attestation(&attestation, &pubkey).unwrap();
+//!
+//! policy::check_server_name(&attestation, &["sandbox.plaid.com"]).unwrap();
+//! ```
+//!
+//! The signed preimage depends on the attestation's `schema_version`. The frozen
+//! fixtures double as test vectors for every schema, checked here the way a
+//! contract checks them: rebuild the binary message, verify BIP-340 over its
+//! digest against the published key, then enforce freshness and the server-name
+//! hash. Any change to the contract-facing encoding fails this loop:
+//!
+//! ```
+//! use auditorzk_attestation::message::message_digest;
+//! use auditorzk_attestation::signature::{decode_signature, VersionedSignature};
+//! use auditorzk_attestation::Attestation;
+//! use k256::schnorr::VerifyingKey;
+//! use sha2::{Digest, Sha256};
+//!
+//! const PUBKEY: &str = "42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db";
+//! const MAX_AGE: u64 = 3 * 86_400;
+//!
+//! fn contract_verify(attestation: &Attestation, now: u64) -> bool {
+//!     let digest = message_digest(&attestation.signed_message().unwrap());
+//!     let key = VerifyingKey::from_bytes(&hex::decode(PUBKEY).unwrap()).unwrap();
+//!     // 2.0.0 signatures are plain BIP-340 over the digest
+//!     let VersionedSignature::Digest(signature) = decode_signature(&attestation.signature).unwrap() else {
+//!         return false;
+//!     };
+//!     key.verify_raw(&digest, &signature).is_ok()
+//!         && attestation.digest.as_deref().is_none_or(|stored| stored == hex::encode(digest))
+//!         && now.saturating_sub(attestation.timestamp) <= MAX_AGE
+//!         && Sha256::digest(attestation.server_name.as_bytes()) == Sha256::digest(b"sandbox.plaid.com")
+//! }
+//!
+//! let now = 1_763_395_200;
+//! for json in [
+//!     include_str!("../fixtures/attestation_v1.json"),
+//!     include_str!("../fixtures/attestation_v2.json"),
+//!     include_str!("../fixtures/attestation_v3.json"),
+//!     include_str!("../fixtures/attestation_v4.json"),
+//!     include_str!("../fixtures/attestation_v5.json"),
+//!     include_str!("../fixtures/attestation_v6.json"),
+//!     include_str!("../fixtures/attestation_v7.json"),
+//!     include_str!("../fixtures/attestation_v8.json"),
+//!     include_str!("../fixtures/attestation_v9.json"),
+//!     include_str!("../fixtures/attestation_v10.json"),
+//!     include_str!("../fixtures/attestation_v11.json"),
+//!     include_str!("../fixtures/attestation_v12.json"),
+//!     include_str!("../fixtures/attestation_v13.json"),
+//!     include_str!("../fixtures/attestation_v14.json"),
+//!     include_str!("../fixtures/attestation_v15.json"),
+//!     include_str!("../fixtures/attestation_v16.json"),
+//!     include_str!("../fixtures/attestation_v17.json"),
+//!     include_str!("../fixtures/attestation_v18.json"),
+//!     include_str!("../fixtures/attestation_v19.json"),
+//!     include_str!("../fixtures/attestation_v20.json"),
+//!     include_str!("../fixtures/attestation_v21.json"),
+//!     include_str!("../fixtures/attestation_v22.json"),
+//!     include_str!("../fixtures/attestation_v23.json"),
+//!     include_str!("../fixtures/attestation_v24.json"),
+//! ] {
+//!     let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+//!     assert!(contract_verify(&attestation, now), "schema {}", attestation.schema_version);
+//!     assert!(!contract_verify(&attestation, now + MAX_AGE));
+//!
+//!     attestation.timestamp += 1;
+//!     assert!(!contract_verify(&attestation, now));
+//! }
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+pub mod canonical;
+pub mod claims;
+pub mod commitments;
+pub mod compact;
+pub mod currency;
+pub mod disclosure;
+pub mod eas;
+mod error;
+pub mod inventory;
+pub mod message;
+pub mod migrations;
+pub mod multisig;
+pub mod policy;
+pub mod profile;
+pub mod request;
+pub mod revocation;
+pub mod signature;
+pub mod transparency;
+pub mod vectors;
+
+pub use error::Error;
+pub use signature::verify_attestation;
+
+/// Schema version written by this crate
+pub const CURRENT_SCHEMA_VERSION: u32 = 24;
+
+/// Attestations written before `schema_version` existed are schema 1
+const fn legacy_schema_version() -> u32 {
+    1
+}
+
+/// Attestation structure that will be signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Attestation {
+    /// Version of this JSON schema, see [`migrations`] for the history
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    /// The server that was connected to
+    pub server_name: String,
+    /// Timestamp of the session
+    pub timestamp: u64,
+    /// Commitment to the claimed figure (the balance, or income for income claims)
+    pub balance_commitment: Vec<u8>,
+    /// BIP-340 signature (hex-encoded with 3-byte version prefix)
+    pub signature: String,
+    /// The verifier's public key (for signature verification)
+    pub verifier_pubkey: Vec<u8>,
+    /// Plaid environment the verifier is configured for, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// Whether the environment byte is part of the signed message
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub environment_signed: bool,
+    /// Prover's own clock at handshake time (signed when present from schema 24,
+    /// unsigned before)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prover_timestamp: Option<u64>,
+    /// Number of accounts in the attested response (signed when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_count: Option<u32>,
+    /// Commitment over the sorted `account_id` hashes, see [`inventory`] (signed when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accounts_commitment: Option<Vec<u8>>,
+    /// Operator-required response headers and their values (unsigned, informational)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub response_headers: BTreeMap<String, String>,
+    /// How the committed figure was computed, e.g. `current`,
+    /// `available_minus_holds` or `latest_pay_annualized` (signed when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_formula: Option<String>,
+    /// Number of Plaid Items (linked institutions) summed (signed when present from
+    /// schema 24, unsigned before)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_count: Option<u32>,
+    /// What the commitment is a claim about, `balance` or `income` (signed when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim_type: Option<String>,
+    /// Account types summed, e.g. `include=depository;exclude=` (signed as its hash when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_filter: Option<String>,
+    /// Verifier build that issued the attestation, e.g. `auditor-zk-verifier/0.1.0+1a2b3c4`
+    /// (signed as its hash when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verifier_version: Option<String>,
+    /// Received bytes the prover revealed beyond its committed region (signed when
+    /// present from schema 24, unsigned before)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub over_revealed_bytes: Option<u64>,
+    /// 32-byte value the prover bound the attestation to, e.g. a hash of the
+    /// redeeming wallet address (signed when present, under its own claim tag
+    /// from schema 22, see [`policy::check_recipient`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<Vec<u8>>,
+    /// The prover's transcript hash commitments, see [`commitments`] (signed when non-empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transcript_commitments: Vec<commitments::TranscriptHash>,
+    /// SHA-256 of the signed message (hex), for consumers that verify the signature
+    /// without rebuilding the preimage; checked against the fields by [`verify_attestation`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Free-form operator tag such as `staging` or a campaign id, at most 64 bytes
+    /// (signed as its hash when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Accounts left out of the sum for a closed or errored status, when the
+    /// verifier excludes them (signed when present from schema 24, unsigned before)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive_accounts: Option<u32>,
+    /// Id of the signer set co-signing the attestation, see [`multisig`] (signed when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer_set_id: Option<Vec<u8>>,
+    /// Co-signers' signatures over the same digest, collected towards the signer
+    /// set's threshold (unsigned; each is checked by [`multisig::verify_quorum`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partial_signatures: Vec<multisig::PartialSignature>,
+    /// Request template the prover's committed request head was checked against,
+    /// and whether it matched, see [`request`] (signed as its hash when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_template: Option<request::RequestTemplateMatch>,
+    /// How many transcript bytes the verifier saw revealed, only committed or
+    /// hidden, see [`disclosure`] (unsigned, informational)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disclosure: Option<disclosure::DisclosureCounts>,
+    /// Commitment to each currency's total when the verifier commits by currency;
+    /// `balance_commitment` is then the hash of this map, see [`currency`]
+    /// (bound through the signed commitment)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub balances_by_currency: BTreeMap<String, Vec<u8>>,
+    /// Provider API version the prover's request asked for, such as Plaid's
+    /// `Plaid-Version` header, when the verifier checked it (signed as its hash
+    /// when present)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// Claim set the signed message declares, e.g. `standard`, see [`claims`]
+    /// (signed as the set id, schema 22+)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim_set: Option<String>,
+    /// Response schema profile the claim was extracted with, and why newer ones
+    /// were passed over, see [`profile`] (unsigned, informational)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_profile: Option<profile::ProfileSelection>,
+}
+
+impl Attestation {
+    /// Fingerprint of the issuing build, the hash of `verifier_version` that the
+    /// signature covers (schema 10+), see [`policy::check_build_fingerprint`]
+    pub fn build_fingerprint(&self) -> Option<[u8; 32]> {
+        self.verifier_version.as_deref().map(message::verifier_version_hash)
+    }
+
+    /// Reconstruct the exact message the verifier signed.
+    ///
+    /// The preimage depends on `schema_version`: each schema only signs the fields
+    /// it knew about, so a field set on an attestation whose schema could not have
+    /// signed it is rejected rather than silently ignored. A schema 22 attestation
+    /// that declares a [`claims::ClaimSet`] signs its claims instead of the legacy
+    /// layout.
+    pub fn signed_message(&self) -> Result<Vec<u8>, Error> {
+        let fields = self.signed_fields()?;
+        match self.declared_claim_set()? {
+            Some(set) => message::encode_claims(set, &fields),
+            None => message::encode_message(&fields),
+        }
+    }
+
+    /// The fields the attestation signs, in message order.
+    ///
+    /// For a legacy attestation this is only what its JSON says: the same
+    /// signature covers other field sets too, so consumers pin their own list in
+    /// [`signature::verify_legacy_attestation`] rather than pass this one.
+    pub fn signed_field_list(&self) -> Result<Vec<message::SignedField>, Error> {
+        Ok(self.signed_fields()?.fields())
+    }
+
+    /// The fields the attestation's schema signs
+    pub(crate) fn signed_fields(&self) -> Result<message::SignedFields<'_>, Error> {
+        match self.schema_version {
+            1 => self.signed_fields_v1(),
+            2 | 3 => self.signed_fields_v2(),
+            4 | 5 => self.signed_fields_v4(),
+            6 | 7 => self.signed_fields_v6(),
+            8 => self.signed_fields_v8(),
+            9 => self.signed_fields_v9(),
+            10 | 11 => self.signed_fields_v10(),
+            12 => self.signed_fields_v12(),
+            13 | 14 => self.signed_fields_v13(),
+            15 | 16 => self.signed_fields_v15(),
+            17 => self.signed_fields_v17(),
+            18..=20 => self.signed_fields_v18(),
+            // Schema 22 signs the same fields, restricted to its claim set when encoded
+            21..=23 => self.signed_fields_v21(),
+            24 => self.signed_fields_v24(),
+            version => Err(Error::UnsupportedSchemaVersion(u64::from(version))),
+        }
+    }
+
+    /// The registered claim set the attestation declares (schema 22+), or `None`
+    /// for the legacy layout
+    pub(crate) fn declared_claim_set(&self) -> Result<Option<&'static claims::ClaimSet>, Error> {
+        let Some(name) = self.claim_set.as_deref() else {
+            return Ok(None);
+        };
+        if self.schema_version < 22 {
+            return Err(Error::UnsignedField { field: "claim set", schema_version: self.schema_version });
+        }
+        claims::ClaimSet::find(name).map(Some).ok_or_else(|| Error::UnknownClaimSet(name.into()))
+    }
+
+    /// Schema 1: server name, timestamp and balance commitment
+    fn signed_fields_v1(&self) -> Result<message::SignedFields<'_>, Error> {
+        if self.environment_signed {
+            return Err(Error::UnsignedField { field: "environment", schema_version: self.schema_version });
+        }
+        self.reject_inventory()?;
+        self.reject_formula()?;
+        self.reject_claim_type()?;
+        self.reject_account_filter()?;
+        self.reject_verifier_version()?;
+        self.reject_recipient()?;
+        self.reject_transcript_commitments()?;
+        self.reject_label()?;
+        self.reject_signer_set()?;
+        self.reject_request_template()?;
+        self.reject_api_version()?;
+
+        Ok(self.signed_fields_base())
+    }
+
+    /// Schemas 2 and 3: schema 1 plus the optional environment byte
+    fn signed_fields_v2(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_inventory()?;
+        self.reject_formula()?;
+        self.reject_claim_type()?;
+        self.reject_account_filter()?;
+        self.reject_verifier_version()?;
+        self.reject_recipient()?;
+        self.reject_transcript_commitments()?;
+        self.reject_label()?;
+        self.reject_signer_set()?;
+        self.reject_request_template()?;
+        self.reject_api_version()?;
+
+        Ok(message::SignedFields {
+            environment: self.signed_environment()?,
+            ..self.signed_fields_base()
+        })
+    }
+
+    /// Schemas 4 and 5: schema 2 plus the optional account inventory
+    fn signed_fields_v4(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_formula()?;
+
+        // Without a formula the schema 6 preimage is exactly the schema 4 one
+        self.signed_fields_v6()
+    }
+
+    /// Schemas 6 and 7: schema 4 plus the optional formula byte
+    fn signed_fields_v6(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_claim_type()?;
+
+        // Without a claim type the schema 8 preimage is exactly the schema 6 one
+        self.signed_fields_v8()
+    }
+
+    /// Schema 8: schema 6 plus the optional claim-type byte
+    fn signed_fields_v8(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_account_filter()?;
+
+        // Without an account filter the schema 9 preimage is exactly the schema 8 one
+        self.signed_fields_v9()
+    }
+
+    /// Schema 9: schema 8 plus the optional account filter hash
+    fn signed_fields_v9(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_verifier_version()?;
+
+        // Without a verifier version the schema 10 preimage is exactly the schema 9 one
+        self.signed_fields_v10()
+    }
+
+    /// Schemas 10 and 11: schema 9 plus the optional verifier version hash
+    fn signed_fields_v10(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_recipient()?;
+
+        // Without a recipient the schema 12 preimage is exactly the schema 10 one
+        self.signed_fields_v12()
+    }
+
+    /// Schema 12: schema 10 plus the optional recipient
+    fn signed_fields_v12(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_transcript_commitments()?;
+
+        // Without transcript commitments the schema 13 preimage is exactly the schema 12 one
+        self.signed_fields_v13()
+    }
+
+    /// Schemas 13 and 14: schema 12 plus the optional transcript commitment list
+    fn signed_fields_v13(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_label()?;
+
+        // Without a label the schema 15 preimage is exactly the schema 13 one
+        self.signed_fields_v15()
+    }
+
+    /// Schemas 15 and 16: schema 13 plus the optional label hash
+    fn signed_fields_v15(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_signer_set()?;
+
+        // Without a signer set the schema 17 preimage is exactly the schema 15 one
+        self.signed_fields_v17()
+    }
+
+    /// Schema 17: schema 15 plus the optional signer-set id
+    fn signed_fields_v17(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_request_template()?;
+
+        // Without a request template the schema 18 preimage is exactly the schema 17 one
+        self.signed_fields_v18()
+    }
+
+    /// Schemas 18 to 20: schema 17 plus the optional request template match
+    fn signed_fields_v18(&self) -> Result<message::SignedFields<'_>, Error> {
+        self.reject_api_version()?;
+
+        // Without an API version the schema 21 preimage is exactly the schema 18 one
+        self.signed_fields_v21()
+    }
+
+    /// Schemas 21 to 23: schema 18 plus the optional API version hash
+    fn signed_fields_v21(&self) -> Result<message::SignedFields<'_>, Error> {
+        let inventory = match (self.account_count, &self.accounts_commitment) {
+            (Some(account_count), Some(accounts_commitment)) => Some(message::SignedInventory {
+                account_count,
+                accounts_commitment,
+            }),
+            (None, None) => None,
+            _ => return Err(Error::IncompleteAccountInventory),
+        };
+
+        Ok(message::SignedFields {
+            environment: self.signed_environment()?,
+            inventory,
+            formula: self.balance_formula.as_deref().map(message::formula_byte).transpose()?,
+            claim_type: self.claim_type.as_deref().map(message::claim_type_byte).transpose()?,
+            account_filter: self.account_filter.as_deref(),
+            verifier_version: self.verifier_version.as_deref(),
+            recipient: self.recipient.as_deref(),
+            transcript_commitments: Some(&self.transcript_commitments[..]).filter(|list| !list.is_empty()),
+            label: self.label.as_deref(),
+            signer_set: self.signer_set_id.as_deref(),
+            request_template: self.request_template.as_ref(),
+            api_version: self.api_version.as_deref(),
+            ..self.signed_fields_base()
+        })
+    }
+
+    /// Schema 24: schema 21 plus the optional prover timestamp, item count,
+    /// over-revealed bytes and inactive accounts, which older schemas record
+    /// unsigned and so can't reject
+    fn signed_fields_v24(&self) -> Result<message::SignedFields<'_>, Error> {
+        Ok(message::SignedFields {
+            prover_timestamp: self.prover_timestamp,
+            item_count: self.item_count,
+            over_revealed_bytes: self.over_revealed_bytes,
+            inactive_accounts: self.inactive_accounts,
+            ..self.signed_fields_v21()?
+        })
+    }
+
+    fn signed_fields_base(&self) -> message::SignedFields<'_> {
+        message::SignedFields {
+            server_name: &self.server_name,
+            timestamp: self.timestamp,
+            commitment: &self.balance_commitment,
+            environment: None,
+            inventory: None,
+            formula: None,
+            claim_type: None,
+            account_filter: None,
+            verifier_version: None,
+            recipient: None,
+            transcript_commitments: None,
+            label: None,
+            signer_set: None,
+            request_template: None,
+            api_version: None,
+            prover_timestamp: None,
+            item_count: None,
+            over_revealed_bytes: None,
+            inactive_accounts: None,
+        }
+    }
+
+    fn signed_environment(&self) -> Result<Option<u8>, Error> {
+        if !self.environment_signed {
+            return Ok(None);
+        }
+        let name = self.environment.as_deref().ok_or(Error::MissingEnvironment)?;
+        message::environment_byte(name).map(Some)
+    }
+
+    fn reject_api_version(&self) -> Result<(), Error> {
+        if self.api_version.is_some() {
+            return Err(Error::UnsignedField { field: "API version", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_request_template(&self) -> Result<(), Error> {
+        if self.request_template.is_some() {
+            return Err(Error::UnsignedField { field: "request template", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_signer_set(&self) -> Result<(), Error> {
+        if self.signer_set_id.is_some() {
+            return Err(Error::UnsignedField { field: "signer set", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_label(&self) -> Result<(), Error> {
+        if self.label.is_some() {
+            return Err(Error::UnsignedField { field: "label", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_transcript_commitments(&self) -> Result<(), Error> {
+        if !self.transcript_commitments.is_empty() {
+            return Err(Error::UnsignedField { field: "transcript commitments", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_recipient(&self) -> Result<(), Error> {
+        if self.recipient.is_some() {
+            return Err(Error::UnsignedField { field: "recipient", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_verifier_version(&self) -> Result<(), Error> {
+        if self.verifier_version.is_some() {
+            return Err(Error::UnsignedField { field: "verifier version", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_account_filter(&self) -> Result<(), Error> {
+        if self.account_filter.is_some() {
+            return Err(Error::UnsignedField { field: "account filter", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_claim_type(&self) -> Result<(), Error> {
+        if self.claim_type.is_some() {
+            return Err(Error::UnsignedField { field: "claim type", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_formula(&self) -> Result<(), Error> {
+        if self.balance_formula.is_some() {
+            return Err(Error::UnsignedField { field: "balance formula", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+
+    fn reject_inventory(&self) -> Result<(), Error> {
+        if self.account_count.is_some() || self.accounts_commitment.is_some() {
+            return Err(Error::UnsignedField { field: "account inventory", schema_version: self.schema_version });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,661 @@
+//! Layout of the message covered by the verifier's signature.
+//!
+//! Schemas 1 to 21 sign the legacy layout, version 1.0.0, described below.
+//! Schema 22 signs a declared claim set instead (layout 2.0.0, see
+//! [`crate::claims`]), whose messages start with the layout version; legacy
+//! messages start with the server name, so the two can't be confused.
+//!
+//! A legacy message doesn't say which fields it carries: optional fields are
+//! appended without tags and `schema_version` isn't signed, so values of the
+//! same width can be relabelled, e.g. a schema 12 recipient as a schema 17
+//! signer-set id. The verifier therefore only issues claim-set messages, and
+//! [`crate::verify_attestation`] rejects legacy ones; consumers still holding
+//! them verify against the fields they expect with
+//! [`crate::signature::verify_legacy_attestation`].
+//!
+//! Version 1.0.0 is the concatenation of:
+//!
+//! | Bytes  | Field                                              |
+//! |--------|----------------------------------------------------|
+//! | 0..32  | server name, UTF-8, right-padded with zeros        |
+//! | 32..64 | timestamp, u64 little-endian, right-padded with zeros |
+//! | 64..96 | balance commitment (SHA-256)                       |
+//! | 96     | environment byte, only when the environment is signed |
+//!
+//! followed, only when the attestation carries an account inventory, by:
+//!
+//! | Bytes  | Field                                              |
+//! |--------|----------------------------------------------------|
+//! | +0..32 | account count, u32 little-endian, right-padded with zeros |
+//! | +32..64 | accounts commitment (see [`crate::inventory`])    |
+//!
+//! then, only when the formula is recorded (schema 6+), one formula byte, then,
+//! only when the claim type is recorded (schema 8+), one claim-type byte, then,
+//! only when an account filter was applied (schema 9+), the SHA-256 of its
+//! canonical description (32 bytes, see [`account_filter_hash`]), then, only
+//! when the verifier build is recorded (schema 10+), the SHA-256 of its version
+//! string (32 bytes), then, only when the prover bound a recipient (schema 12+),
+//! the 32-byte recipient as given, then, only when the attestation lists
+//! transcript hash commitments (schema 13+), their count (u32 little-endian,
+//! right-padded to 32 bytes) and digest (32 bytes, see [`crate::commitments`]),
+//! then, only when the operator set a label (schema 15+), the SHA-256 of the
+//! label (32 bytes), then, only when the attestation is co-signed under a signer
+//! set (schema 17+), the 32-byte set id (see [`crate::multisig`]), then, only
+//! when the prover's request was checked against a template (schema 18+), the
+//! hash of the template id and match result (32 bytes, see [`crate::request`]),
+//! then, only when the verifier checked the provider API version the request
+//! asked for (schema 21+), the SHA-256 of that version string (32 bytes), and
+//! finally, from schema 24, the prover's timestamp when it sent its clock in the
+//! `hello` (u64 little-endian), the number of Plaid Items summed (u32
+//! little-endian), the received bytes revealed beyond the committed region (u64
+//! little-endian) and the number of inactive accounts left out of the sum (u32
+//! little-endian), each only when present and right-padded to 32 bytes.
+//!
+//! Every field has a fixed width: one byte, exactly 32 bytes, or at most 32
+//! bytes right-padded with zeros (see [`Framing`]). [`PreimageBuilder`] appends
+//! them in the order above and rejects any other, and both the verifier (when
+//! signing) and [`crate::Attestation::signed_message`] (when verifying) build the
+//! message through it.
+//!
+//! The signature is BIP-340 Schnorr over `SHA-256(message)`.
+
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::claims::{ClaimSet, CLAIMS_LAYOUT_VERSION, TAG_CLAIM_SET};
+use crate::commitments::{commitments_digest, TranscriptHash};
+use crate::request::RequestTemplateMatch;
+use crate::Error;
+
+/// Width of every fixed-size field in the signed message
+pub const FIELD_LEN: usize = 32;
+
+/// Longest operator label, in bytes.
+///
+/// The label is signed (as its hash), so it can't be retagged after issuance:
+///
+/// ```
+/// use auditorzk_attestation::message::SignedField::*;
+/// use auditorzk_attestation::signature::verify_legacy_attestation;
+/// use auditorzk_attestation::{Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v15.json");
+/// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+/// let fields = [
+///     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+///     AccountFilter, VerifierVersion, Recipient, CommitmentCount, CommitmentsDigest, Label,
+/// ];
+/// let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+/// verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+/// assert_eq!(attestation.label.as_deref(), Some("staging"));
+///
+/// attestation.label = Some("production".into());
+/// attestation.digest = None;
+/// assert_eq!(verify_legacy_attestation(&attestation, &pubkey, &fields), Err(Error::InvalidSignature));
+///
+/// attestation.label = Some("x".repeat(65));
+/// assert_eq!(verify_legacy_attestation(&attestation, &pubkey, &fields), Err(Error::LabelTooLong(65)));
+/// ```
+pub const MAX_LABEL_LEN: usize = 64;
+
+/// Environment bytes appended when the environment is signed
+pub const ENV_SANDBOX: u8 = 0x01;
+pub const ENV_DEVELOPMENT: u8 = 0x02;
+pub const ENV_PRODUCTION: u8 = 0x03;
+
+/// Balance formula bytes appended when the formula is recorded
+pub const FORMULA_CURRENT: u8 = 0x01;
+pub const FORMULA_AVAILABLE_MINUS_HOLDS: u8 = 0x02;
+pub const FORMULA_LATEST_PAY_ANNUALIZED: u8 = 0x03;
+pub const FORMULA_TRAILING_AVERAGE_ANNUALIZED: u8 = 0x04;
+
+/// Claim-type bytes appended when the claim type is recorded
+pub const CLAIM_BALANCE: u8 = 0x01;
+pub const CLAIM_INCOME: u8 = 0x02;
+
+/// Values covered by the signature
+#[derive(Debug, Clone, Copy)]
+pub struct SignedFields<'a> {
+    pub server_name: &'a str,
+    pub timestamp: u64,
+    pub commitment: &'a [u8],
+    pub environment: Option<u8>,
+    pub inventory: Option<SignedInventory<'a>>,
+    pub formula: Option<u8>,
+    pub claim_type: Option<u8>,
+    /// Canonical account filter description, signed as its hash
+    pub account_filter: Option<&'a str>,
+    /// Verifier software version, signed as its hash
+    pub verifier_version: Option<&'a str>,
+    /// Prover-chosen recipient (e.g. a wallet address hash), exactly 32 bytes
+    pub recipient: Option<&'a [u8]>,
+    /// Prover's transcript hash commitments, signed as count and digest
+    pub transcript_commitments: Option<&'a [TranscriptHash]>,
+    /// Operator label, at most [`MAX_LABEL_LEN`] bytes, signed as its hash
+    pub label: Option<&'a str>,
+    /// Id of the signer set co-signing the attestation, exactly 32 bytes
+    pub signer_set: Option<&'a [u8]>,
+    /// Request template match, signed as its hash
+    pub request_template: Option<&'a RequestTemplateMatch>,
+    /// Provider API version the request asked for, signed as its hash
+    pub api_version: Option<&'a str>,
+    /// Prover's clock at handshake time
+    pub prover_timestamp: Option<u64>,
+    /// Number of Plaid Items (linked institutions) summed
+    pub item_count: Option<u32>,
+    /// Received bytes the prover revealed beyond its committed region
+    pub over_revealed_bytes: Option<u64>,
+    /// Accounts left out of the sum for a closed or errored status
+    pub inactive_accounts: Option<u32>,
+}
+
+impl SignedFields<'_> {
+    /// The fields present, in message order
+    pub fn fields(&self) -> Vec<SignedField> {
+        let optional = [
+            (self.environment.is_some(), &[SignedField::Environment][..]),
+            (self.inventory.is_some(), &[SignedField::AccountCount, SignedField::AccountsCommitment]),
+            (self.formula.is_some(), &[SignedField::Formula]),
+            (self.claim_type.is_some(), &[SignedField::ClaimType]),
+            (self.account_filter.is_some(), &[SignedField::AccountFilter]),
+            (self.verifier_version.is_some(), &[SignedField::VerifierVersion]),
+            (self.recipient.is_some(), &[SignedField::Recipient]),
+            (self.transcript_commitments.is_some(), &[SignedField::CommitmentCount, SignedField::CommitmentsDigest]),
+            (self.label.is_some(), &[SignedField::Label]),
+            (self.signer_set.is_some(), &[SignedField::SignerSet]),
+            (self.request_template.is_some(), &[SignedField::RequestTemplate]),
+            (self.api_version.is_some(), &[SignedField::ApiVersion]),
+            (self.prover_timestamp.is_some(), &[SignedField::ProverTimestamp]),
+            (self.item_count.is_some(), &[SignedField::ItemCount]),
+            (self.over_revealed_bytes.is_some(), &[SignedField::OverRevealedBytes]),
+            (self.inactive_accounts.is_some(), &[SignedField::InactiveAccounts]),
+        ];
+        let mut fields = Vec::from([SignedField::ServerName, SignedField::Timestamp, SignedField::Commitment]);
+        for (present, group) in optional {
+            if present {
+                fields.extend_from_slice(group);
+            }
+        }
+        fields
+    }
+}
+
+/// Account inventory appended after the environment byte
+#[derive(Debug, Clone, Copy)]
+pub struct SignedInventory<'a> {
+    pub account_count: u32,
+    pub accounts_commitment: &'a [u8],
+}
+
+/// Encode the signed fields into the legacy (1.0.0) message layout
+pub fn encode_message(fields: &SignedFields<'_>) -> Result<Vec<u8>, Error> {
+    let preimage = PreimageBuilder::new(fields.server_name, fields.timestamp, fields.commitment)?;
+    encode_fields(preimage, fields)
+}
+
+/// Encode the signed fields as the claims of `set` (layout 2.0.0, see [`crate::claims`]).
+///
+/// Fields the attestation leaves empty are omitted; a set field outside `set` is
+/// rejected with [`Error::ClaimNotInSet`].
+pub fn encode_claims(set: &'static ClaimSet, fields: &SignedFields<'_>) -> Result<Vec<u8>, Error> {
+    let preimage = PreimageBuilder::for_claim_set(set, fields.server_name, fields.timestamp, fields.commitment)?;
+    encode_fields(preimage, fields)
+}
+
+/// Append the optional fields after the base ones
+fn encode_fields(mut preimage: PreimageBuilder, fields: &SignedFields<'_>) -> Result<Vec<u8>, Error> {
+    if let Some(env) = fields.environment {
+        preimage.environment(env)?;
+    }
+    if let Some(inventory) = fields.inventory {
+        preimage.inventory(inventory)?;
+    }
+    if let Some(formula) = fields.formula {
+        preimage.formula(formula)?;
+    }
+    if let Some(claim_type) = fields.claim_type {
+        preimage.claim_type(claim_type)?;
+    }
+    if let Some(filter) = fields.account_filter {
+        preimage.account_filter(filter)?;
+    }
+    if let Some(version) = fields.verifier_version {
+        preimage.verifier_version(version)?;
+    }
+    if let Some(recipient) = fields.recipient {
+        preimage.recipient(recipient)?;
+    }
+    if let Some(commitments) = fields.transcript_commitments {
+        preimage.transcript_commitments(commitments)?;
+    }
+    if let Some(label) = fields.label {
+        preimage.label(label)?;
+    }
+    if let Some(signer_set) = fields.signer_set {
+        preimage.signer_set(signer_set)?;
+    }
+    if let Some(template) = fields.request_template {
+        preimage.request_template(template)?;
+    }
+    if let Some(version) = fields.api_version {
+        preimage.api_version(version)?;
+    }
+    if let Some(timestamp) = fields.prover_timestamp {
+        preimage.prover_timestamp(timestamp)?;
+    }
+    if let Some(count) = fields.item_count {
+        preimage.item_count(count)?;
+    }
+    if let Some(bytes) = fields.over_revealed_bytes {
+        preimage.over_revealed_bytes(bytes)?;
+    }
+    if let Some(count) = fields.inactive_accounts {
+        preimage.inactive_accounts(count)?;
+    }
+
+    Ok(preimage.finish())
+}
+
+/// Width of a signed field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// A single byte
+    Byte,
+    /// Exactly [`FIELD_LEN`] bytes, e.g. a hash
+    Fixed,
+    /// At most [`FIELD_LEN`] bytes, right-padded with zeros
+    Padded,
+}
+
+/// Fields of the signed message, in message order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SignedField {
+    ServerName,
+    Timestamp,
+    Commitment,
+    Environment,
+    AccountCount,
+    AccountsCommitment,
+    Formula,
+    ClaimType,
+    AccountFilter,
+    VerifierVersion,
+    Recipient,
+    CommitmentCount,
+    CommitmentsDigest,
+    Label,
+    SignerSet,
+    RequestTemplate,
+    ApiVersion,
+    ProverTimestamp,
+    ItemCount,
+    OverRevealedBytes,
+    InactiveAccounts,
+}
+
+impl SignedField {
+    /// Every field, in message order
+    pub const ALL: [Self; 21] = [
+        Self::ServerName,
+        Self::Timestamp,
+        Self::Commitment,
+        Self::Environment,
+        Self::AccountCount,
+        Self::AccountsCommitment,
+        Self::Formula,
+        Self::ClaimType,
+        Self::AccountFilter,
+        Self::VerifierVersion,
+        Self::Recipient,
+        Self::CommitmentCount,
+        Self::CommitmentsDigest,
+        Self::Label,
+        Self::SignerSet,
+        Self::RequestTemplate,
+        Self::ApiVersion,
+        Self::ProverTimestamp,
+        Self::ItemCount,
+        Self::OverRevealedBytes,
+        Self::InactiveAccounts,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ServerName => "server name",
+            Self::Timestamp => "timestamp",
+            Self::Commitment => "commitment",
+            Self::Environment => "environment",
+            Self::AccountCount => "account count",
+            Self::AccountsCommitment => "accounts commitment",
+            Self::Formula => "formula",
+            Self::ClaimType => "claim type",
+            Self::AccountFilter => "account filter",
+            Self::VerifierVersion => "verifier version",
+            Self::Recipient => "recipient",
+            Self::CommitmentCount => "transcript commitment count",
+            Self::CommitmentsDigest => "transcript commitments digest",
+            Self::Label => "label",
+            Self::SignerSet => "signer set",
+            Self::RequestTemplate => "request template",
+            Self::ApiVersion => "API version",
+            Self::ProverTimestamp => "prover timestamp",
+            Self::ItemCount => "item count",
+            Self::OverRevealedBytes => "over-revealed bytes",
+            Self::InactiveAccounts => "inactive accounts",
+        }
+    }
+
+    pub const fn framing(&self) -> Framing {
+        match self {
+            Self::Environment | Self::Formula | Self::ClaimType => Framing::Byte,
+            Self::ServerName
+            | Self::Timestamp
+            | Self::AccountCount
+            | Self::CommitmentCount
+            | Self::ProverTimestamp
+            | Self::ItemCount
+            | Self::OverRevealedBytes
+            | Self::InactiveAccounts => Framing::Padded,
+            Self::Commitment
+            | Self::AccountsCommitment
+            | Self::AccountFilter
+            | Self::VerifierVersion
+            | Self::Recipient
+            | Self::CommitmentsDigest
+            | Self::Label
+            | Self::SignerSet
+            | Self::RequestTemplate
+            | Self::ApiVersion => Framing::Fixed,
+        }
+    }
+
+    /// Tag byte identifying the field in a claim-set message, see [`crate::claims`]
+    pub const fn tag(&self) -> u8 {
+        match self {
+            Self::ServerName => 0x01,
+            Self::Timestamp => 0x02,
+            Self::Commitment => 0x03,
+            Self::Environment => 0x04,
+            Self::AccountCount => 0x05,
+            Self::AccountsCommitment => 0x06,
+            Self::Formula => 0x07,
+            Self::ClaimType => 0x08,
+            Self::AccountFilter => 0x09,
+            Self::VerifierVersion => 0x0a,
+            Self::Recipient => 0x0b,
+            Self::CommitmentCount => 0x0c,
+            Self::CommitmentsDigest => 0x0d,
+            Self::Label => 0x0e,
+            Self::SignerSet => 0x0f,
+            Self::RequestTemplate => 0x10,
+            Self::ApiVersion => 0x11,
+            Self::ProverTimestamp => 0x12,
+            Self::ItemCount => 0x13,
+            Self::OverRevealedBytes => 0x14,
+            Self::InactiveAccounts => 0x15,
+        }
+    }
+
+    /// The field a claim tag stands for
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|field| field.tag() == tag)
+    }
+
+    /// Bytes the field's value takes in the message
+    pub const fn width(&self) -> usize {
+        match self.framing() {
+            Framing::Byte => 1,
+            Framing::Fixed | Framing::Padded => FIELD_LEN,
+        }
+    }
+
+    /// Error for a value of the wrong length
+    fn length_error(&self, len: usize) -> Error {
+        match self {
+            Self::ServerName => Error::ServerNameTooLong(len),
+            Self::Recipient => Error::InvalidRecipientLength(len),
+            Self::SignerSet => Error::InvalidSignerSetIdLength(len),
+            _ => Error::InvalidCommitmentLength(len),
+        }
+    }
+}
+
+/// Builds the signed message one typed field at a time, in [`SignedField`] order.
+///
+/// The schema 1 field set is the legacy layout, byte for byte:
+///
+/// ```
+/// use auditorzk_attestation::message::{PreimageBuilder, SignedField::*};
+/// use auditorzk_attestation::signature::verify_legacy_attestation;
+/// use auditorzk_attestation::Attestation;
+///
+/// let attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v1.json")).unwrap();
+/// let preimage = PreimageBuilder::new(
+///     &attestation.server_name,
+///     attestation.timestamp,
+///     &attestation.balance_commitment,
+/// )
+/// .unwrap()
+/// .finish();
+///
+/// // Padded server name, padded little-endian timestamp, commitment
+/// let mut legacy = [0u8; 96];
+/// legacy[..17].copy_from_slice(b"sandbox.plaid.com");
+/// legacy[32..40].copy_from_slice(&attestation.timestamp.to_le_bytes());
+/// legacy[64..].copy_from_slice(&attestation.balance_commitment);
+/// assert_eq!(preimage, legacy);
+///
+/// // ...which is what the v1 fixture's signature covers
+/// assert_eq!(attestation.signed_message().unwrap(), preimage);
+/// verify_legacy_attestation(&attestation, &attestation.verifier_pubkey, &[ServerName, Timestamp, Commitment]).unwrap();
+/// ```
+///
+/// Fields appended out of order are rejected, so a new field can't slip in
+/// between existing ones:
+///
+/// ```
+/// use auditorzk_attestation::message::PreimageBuilder;
+/// use auditorzk_attestation::Error;
+///
+/// let mut preimage = PreimageBuilder::new("sandbox.plaid.com", 1763136000, &[7; 32]).unwrap();
+/// preimage.label("staging").unwrap();
+/// assert_eq!(
+///     preimage.environment(0x01).unwrap_err(),
+///     Error::FieldOutOfOrder { field: "environment", after: "label" }
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct PreimageBuilder {
+    message: Vec<u8>,
+    last: Option<SignedField>,
+    /// Claim set of a layout 2.0.0 message, `None` for the legacy layout
+    claim_set: Option<&'static ClaimSet>,
+}
+
+impl PreimageBuilder {
+    /// Start a legacy (1.0.0) message with the fields every schema signs
+    pub fn new(server_name: &str, timestamp: u64, commitment: &[u8]) -> Result<Self, Error> {
+        let preimage = Self { message: Vec::with_capacity(18 * FIELD_LEN + 3), last: None, claim_set: None };
+        preimage.base(server_name, timestamp, commitment)
+    }
+
+    /// Start a claim-set (2.0.0) message: the layout version, the set id claim,
+    /// then the fields every set signs
+    pub fn for_claim_set(
+        set: &'static ClaimSet,
+        server_name: &str,
+        timestamp: u64,
+        commitment: &[u8],
+    ) -> Result<Self, Error> {
+        let mut message = Vec::with_capacity(set.max_message_len());
+        message.extend_from_slice(&CLAIMS_LAYOUT_VERSION);
+        message.push(TAG_CLAIM_SET);
+        message.extend_from_slice(&set.id());
+        let preimage = Self { message, last: None, claim_set: Some(set) };
+        preimage.base(server_name, timestamp, commitment)
+    }
+
+    fn base(mut self, server_name: &str, timestamp: u64, commitment: &[u8]) -> Result<Self, Error> {
+        self.put(SignedField::ServerName, server_name.as_bytes())?;
+        self.put(SignedField::Timestamp, &timestamp.to_le_bytes())?;
+        self.put(SignedField::Commitment, commitment)?;
+        Ok(self)
+    }
+
+    pub fn environment(&mut self, env: u8) -> Result<&mut Self, Error> {
+        self.put(SignedField::Environment, &[env])
+    }
+
+    pub fn inventory(&mut self, inventory: SignedInventory<'_>) -> Result<&mut Self, Error> {
+        self.put(SignedField::AccountCount, &inventory.account_count.to_le_bytes())?;
+        self.put(SignedField::AccountsCommitment, inventory.accounts_commitment)
+    }
+
+    pub fn formula(&mut self, formula: u8) -> Result<&mut Self, Error> {
+        self.put(SignedField::Formula, &[formula])
+    }
+
+    pub fn claim_type(&mut self, claim_type: u8) -> Result<&mut Self, Error> {
+        self.put(SignedField::ClaimType, &[claim_type])
+    }
+
+    pub fn account_filter(&mut self, filter: &str) -> Result<&mut Self, Error> {
+        self.put(SignedField::AccountFilter, &account_filter_hash(filter))
+    }
+
+    pub fn verifier_version(&mut self, version: &str) -> Result<&mut Self, Error> {
+        self.put(SignedField::VerifierVersion, &verifier_version_hash(version))
+    }
+
+    pub fn recipient(&mut self, recipient: &[u8]) -> Result<&mut Self, Error> {
+        self.put(SignedField::Recipient, recipient)
+    }
+
+    pub fn transcript_commitments(&mut self, commitments: &[TranscriptHash]) -> Result<&mut Self, Error> {
+        self.put(SignedField::CommitmentCount, &(commitments.len() as u32).to_le_bytes())?;
+        self.put(SignedField::CommitmentsDigest, &commitments_digest(commitments)?)
+    }
+
+    pub fn label(&mut self, label: &str) -> Result<&mut Self, Error> {
+        if label.len() > MAX_LABEL_LEN {
+            return Err(Error::LabelTooLong(label.len()));
+        }
+        self.put(SignedField::Label, &Sha256::digest(label.as_bytes()))
+    }
+
+    pub fn signer_set(&mut self, signer_set: &[u8]) -> Result<&mut Self, Error> {
+        self.put(SignedField::SignerSet, signer_set)
+    }
+
+    pub fn request_template(&mut self, template: &RequestTemplateMatch) -> Result<&mut Self, Error> {
+        self.put(SignedField::RequestTemplate, &template.signed_hash())
+    }
+
+    pub fn api_version(&mut self, version: &str) -> Result<&mut Self, Error> {
+        self.put(SignedField::ApiVersion, &Sha256::digest(version.as_bytes()))
+    }
+
+    pub fn prover_timestamp(&mut self, timestamp: u64) -> Result<&mut Self, Error> {
+        self.put(SignedField::ProverTimestamp, &timestamp.to_le_bytes())
+    }
+
+    pub fn item_count(&mut self, count: u32) -> Result<&mut Self, Error> {
+        self.put(SignedField::ItemCount, &count.to_le_bytes())
+    }
+
+    pub fn over_revealed_bytes(&mut self, bytes: u64) -> Result<&mut Self, Error> {
+        self.put(SignedField::OverRevealedBytes, &bytes.to_le_bytes())
+    }
+
+    pub fn inactive_accounts(&mut self, count: u32) -> Result<&mut Self, Error> {
+        self.put(SignedField::InactiveAccounts, &count.to_le_bytes())
+    }
+
+    /// The message to sign
+    pub fn finish(self) -> Vec<u8> {
+        self.message
+    }
+
+    /// Append one field after checking its order and width
+    fn put(&mut self, field: SignedField, bytes: &[u8]) -> Result<&mut Self, Error> {
+        if let Some(last) = self.last.filter(|&last| last >= field) {
+            return Err(Error::FieldOutOfOrder { field: field.name(), after: last.name() });
+        }
+        let fits = match field.framing() {
+            Framing::Byte => bytes.len() == 1,
+            Framing::Fixed => bytes.len() == FIELD_LEN,
+            Framing::Padded => bytes.len() <= FIELD_LEN,
+        };
+        if !fits {
+            return Err(field.length_error(bytes.len()));
+        }
+        // Claim-set messages tag each value and only carry the set's fields
+        if let Some(set) = self.claim_set {
+            if !set.contains(field) {
+                return Err(Error::ClaimNotInSet { field: field.name(), set: set.name });
+            }
+            self.message.push(field.tag());
+        }
+        match field.framing() {
+            Framing::Padded => self.message.extend_from_slice(&pad(bytes)),
+            Framing::Byte | Framing::Fixed => self.message.extend_from_slice(bytes),
+        }
+        self.last = Some(field);
+        Ok(self)
+    }
+}
+
+/// Digest that is actually signed
+pub fn message_digest(message: &[u8]) -> [u8; 32] {
+    Sha256::digest(message).into()
+}
+
+/// Hash of a verifier version such as `auditor-zk-verifier/0.1.0+1a2b3c4`, as it
+/// appears in the signed message. Consumers key build denylists on it as the
+/// build fingerprint
+pub fn verifier_version_hash(version: &str) -> [u8; 32] {
+    Sha256::digest(version.as_bytes()).into()
+}
+
+/// Hash of an account filter description such as `include=depository;exclude=`,
+/// as it appears in the signed message
+pub fn account_filter_hash(filter: &str) -> [u8; 32] {
+    Sha256::digest(filter.as_bytes()).into()
+}
+
+/// Signed-message byte for an environment name
+pub fn environment_byte(name: &str) -> Result<u8, Error> {
+    match name {
+        "sandbox" => Ok(ENV_SANDBOX),
+        "development" => Ok(ENV_DEVELOPMENT),
+        "production" => Ok(ENV_PRODUCTION),
+        other => Err(Error::UnknownEnvironment(other.into())),
+    }
+}
+
+/// Signed-message byte for a balance formula name
+pub fn formula_byte(name: &str) -> Result<u8, Error> {
+    match name {
+        "current" => Ok(FORMULA_CURRENT),
+        "available_minus_holds" => Ok(FORMULA_AVAILABLE_MINUS_HOLDS),
+        "latest_pay_annualized" => Ok(FORMULA_LATEST_PAY_ANNUALIZED),
+        "trailing_average_annualized" => Ok(FORMULA_TRAILING_AVERAGE_ANNUALIZED),
+        other => Err(Error::UnknownFormula(other.into())),
+    }
+}
+
+/// Signed-message byte for a claim type name
+pub fn claim_type_byte(name: &str) -> Result<u8, Error> {
+    match name {
+        "balance" => Ok(CLAIM_BALANCE),
+        "income" => Ok(CLAIM_INCOME),
+        other => Err(Error::UnknownClaimType(other.into())),
+    }
+}
+
+/// Right-pad a field to 32 bytes with zeros
+fn pad(bytes: &[u8]) -> [u8; FIELD_LEN] {
+    let mut padded = [0u8; FIELD_LEN];
+    padded[..bytes.len()].copy_from_slice(bytes);
+    padded
+}
@@ -0,0 +1,224 @@
+//! Upgrades stored attestation JSON from older schemas to the current [`Attestation`].
+//!
+//! Schema history:
+//!
+//! | Version | Changes                                                          |
+//! |---------|------------------------------------------------------------------|
+//! | 1       | `server_name`, `timestamp`, `balance_commitment`, `signature`, `verifier_pubkey` (no `schema_version` field) |
+//! | 2       | adds `schema_version`, optional `environment` and `environment_signed` |
+//! | 3       | adds optional `prover_timestamp`                                 |
+//! | 4       | adds optional, signed `account_count` and `accounts_commitment`  |
+//! | 5       | adds `response_headers`                                          |
+//! | 6       | adds optional, signed `balance_formula`                          |
+//! | 7       | adds optional `item_count`                                       |
+//! | 8       | adds optional, signed `claim_type`                               |
+//! | 9       | adds optional, signed `account_filter`                           |
+//! | 10      | adds optional, signed `verifier_version`                         |
+//! | 11      | adds optional `over_revealed_bytes`                              |
+//! | 12      | adds optional, signed `recipient`                                |
+//! | 13      | adds signed `transcript_commitments` (empty when absent)         |
+//! | 14      | adds optional `digest`                                           |
+//! | 15      | adds optional, signed `label`                                    |
+//! | 16      | adds optional `inactive_accounts`                                |
+//! | 17      | adds optional, signed `signer_set_id` and `partial_signatures`   |
+//! | 18      | adds optional, signed `request_template`                         |
+//! | 19      | adds optional `disclosure`                                       |
+//! | 20      | adds optional `balances_by_currency`                             |
+//! | 21      | adds optional, signed `api_version`                              |
+//! | 22      | adds optional `claim_set`, signed instead of the legacy layout   |
+//! | 23      | only adds optional, unsigned `schema_profile`                    |
+//! | 24      | signs `prover_timestamp`, `item_count`, `over_revealed_bytes` and `inactive_accounts` when present |
+//!
+//! Each step only fills defaults, recomputes derived fields or drops unsigned
+//! fields a later schema signs; signed fields are never rewritten, so migrated
+//! attestations still verify. A frozen, signed
+//! example of every schema lives in `fixtures/attestation_v<N>.json`.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde_json::{Map, Value};
+
+use crate::{Attestation, Error, CURRENT_SCHEMA_VERSION};
+
+/// Parse attestation JSON of any known schema and upgrade it to the current one
+pub fn migrate_json(json: &str) -> Result<Attestation, Error> {
+    let value: Value = serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))?;
+    migrate_value(value)
+}
+
+/// Upgrade an already-parsed JSON object to the current schema
+pub fn migrate_value(mut value: Value) -> Result<Attestation, Error> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| Error::Json("attestation must be a JSON object".to_string()))?;
+
+    let mut version = match object.get("schema_version") {
+        None => 1,
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| Error::Json("schema_version must be an integer".to_string()))?,
+    };
+
+    if version == 0 || version > u64::from(CURRENT_SCHEMA_VERSION) {
+        return Err(Error::UnsupportedSchemaVersion(version));
+    }
+
+    while version < u64::from(CURRENT_SCHEMA_VERSION) {
+        match version {
+            1 => v1_to_v2(object),
+            2 => v2_to_v3(object),
+            3 => v3_to_v4(object),
+            4 => v4_to_v5(object),
+            5 => v5_to_v6(object),
+            6 => v6_to_v7(object),
+            7 => v7_to_v8(object),
+            8 => v8_to_v9(object),
+            9 => v9_to_v10(object),
+            10 => v10_to_v11(object),
+            11 => v11_to_v12(object),
+            12 => v12_to_v13(object),
+            13 => v13_to_v14(object),
+            14 => v14_to_v15(object),
+            15 => v15_to_v16(object),
+            16 => v16_to_v17(object),
+            17 => v17_to_v18(object),
+            18 => v18_to_v19(object),
+            19 => v19_to_v20(object),
+            20 => v20_to_v21(object),
+            21 => v21_to_v22(object),
+            22 => v22_to_v23(object),
+            23 => v23_to_v24(object),
+            _ => unreachable!("every version below current has a migration step"),
+        }
+        version += 1;
+    }
+    object.insert("schema_version".to_string(), Value::from(version));
+
+    serde_json::from_value(value).map_err(|e| Error::Json(e.to_string()))
+}
+
+/// Schema 2 introduced the (optional) Plaid environment; v1 attestations never signed one
+fn v1_to_v2(object: &mut Map<String, Value>) {
+    object.entry("environment").or_insert(Value::Null);
+    object.entry("environment_signed").or_insert(Value::Bool(false));
+}
+
+/// Schema 3 records the prover's handshake clock, which older sessions never sent
+fn v2_to_v3(object: &mut Map<String, Value>) {
+    object.entry("prover_timestamp").or_insert(Value::Null);
+}
+
+/// Schema 4 can attest the account inventory; older attestations never signed one
+fn v3_to_v4(object: &mut Map<String, Value>) {
+    object.entry("account_count").or_insert(Value::Null);
+    object.entry("accounts_commitment").or_insert(Value::Null);
+}
+
+/// Schema 5 records required response headers, which older verifiers never checked
+fn v4_to_v5(object: &mut Map<String, Value>) {
+    object.entry("response_headers").or_insert_with(|| Value::Object(Map::new()));
+}
+
+/// Schema 6 records the balance formula; older attestations always committed to `current`
+/// but never signed that, so the field stays empty
+fn v5_to_v6(object: &mut Map<String, Value>) {
+    object.entry("balance_formula").or_insert(Value::Null);
+}
+
+/// Schema 7 records how many Plaid Items were summed; older sessions only read one
+fn v6_to_v7(object: &mut Map<String, Value>) {
+    object.entry("item_count").or_insert(Value::Null);
+}
+
+/// Schema 8 distinguishes balance from income claims; older attestations were
+/// always balance claims but never signed that, so the field stays empty
+fn v7_to_v8(object: &mut Map<String, Value>) {
+    object.entry("claim_type").or_insert(Value::Null);
+}
+
+/// Schema 9 records which account types were summed; older sessions summed every account
+fn v8_to_v9(object: &mut Map<String, Value>) {
+    object.entry("account_filter").or_insert(Value::Null);
+}
+
+/// Schema 10 records the verifier build; which build issued older attestations is unknown
+fn v9_to_v10(object: &mut Map<String, Value>) {
+    object.entry("verifier_version").or_insert(Value::Null);
+}
+
+/// Schema 11 can record over-revelation; older verifiers never measured it
+fn v10_to_v11(object: &mut Map<String, Value>) {
+    object.entry("over_revealed_bytes").or_insert(Value::Null);
+}
+
+/// Schema 12 can bind a recipient; older attestations are unbound
+fn v11_to_v12(object: &mut Map<String, Value>) {
+    object.entry("recipient").or_insert(Value::Null);
+}
+
+/// Schema 13 lists the prover's transcript hash commitments; older attestations list none
+fn v12_to_v13(object: &mut Map<String, Value>) {
+    object.entry("transcript_commitments").or_insert_with(|| Value::Array(Vec::new()));
+}
+
+/// Schema 14 can store the signed digest; older attestations don't
+fn v13_to_v14(object: &mut Map<String, Value>) {
+    object.entry("digest").or_insert(Value::Null);
+}
+
+/// Schema 15 can carry a signed operator label; older attestations have none
+fn v14_to_v15(object: &mut Map<String, Value>) {
+    object.entry("label").or_insert(Value::Null);
+}
+
+/// Schema 16 can record accounts excluded as inactive; older verifiers summed them all
+fn v15_to_v16(object: &mut Map<String, Value>) {
+    object.entry("inactive_accounts").or_insert(Value::Null);
+}
+
+/// Schema 17 can be co-signed under a signer set; older attestations have one signer
+fn v16_to_v17(object: &mut Map<String, Value>) {
+    object.entry("signer_set_id").or_insert(Value::Null);
+    object.entry("partial_signatures").or_insert_with(|| Value::Array(Vec::new()));
+}
+
+/// Schema 18 can record a request template match; older verifiers never checked one
+fn v17_to_v18(object: &mut Map<String, Value>) {
+    object.entry("request_template").or_insert(Value::Null);
+}
+
+/// Schema 19 can record what the verifier saw of the transcript; older verifiers didn't count it
+fn v18_to_v19(object: &mut Map<String, Value>) {
+    object.entry("disclosure").or_insert(Value::Null);
+}
+
+/// Schema 20 can commit to each currency separately; older verifiers summed them into one total
+fn v19_to_v20(object: &mut Map<String, Value>) {
+    object.entry("balances_by_currency").or_insert_with(|| Value::Object(Map::new()));
+}
+
+/// Schema 21 can record the provider API version; older verifiers never checked it
+fn v20_to_v21(object: &mut Map<String, Value>) {
+    object.entry("api_version").or_insert(Value::Null);
+}
+
+/// Schema 22 can sign a declared claim set; older attestations signed the legacy layout
+fn v21_to_v22(object: &mut Map<String, Value>) {
+    object.entry("claim_set").or_insert(Value::Null);
+}
+
+/// Schema 23 exists only to record the unsigned response schema profile; it signs
+/// the same fields as schema 22
+fn v22_to_v23(object: &mut Map<String, Value>) {
+    object.entry("schema_profile").or_insert(Value::Null);
+}
+
+/// Schema 24 signs the prover timestamp, item count, over-revealed bytes and
+/// inactive accounts; older values were never signed, so they are dropped rather
+/// than presented as attested
+fn v23_to_v24(object: &mut Map<String, Value>) {
+    object.remove("prover_timestamp");
+    object.remove("item_count");
+    object.remove("over_revealed_bytes");
+    object.remove("inactive_accounts");
+}
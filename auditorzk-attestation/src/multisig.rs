@@ -0,0 +1,214 @@
+//! m-of-n co-signing of attestations by independent verifier operators.
+//!
+//! A [`SignerSet`] is `n` verifier keys and a threshold `m`, published by the
+//! operators and identified by [`SignerSet::id`]. An attestation issued under a
+//! set signs that id into its message (schema 17+), so a signature collected for
+//! one set can never be counted towards another. Every member signs the *same*
+//! digest with its own BIP-340 key; the issuing verifier's `signature` is its
+//! contribution, and co-signers' contributions are collected in
+//! `partial_signatures`. [`verify_quorum`] accepts the attestation once `m`
+//! distinct members' signatures verify.
+//!
+//! This is a plain multi-signature, not an aggregated one (FROST, MuSig2): the
+//! attestation grows by one signature per co-signer and a consumer checks each.
+//! That keeps every member's contribution independent and needs no interactive
+//! nonce rounds between operators.
+//!
+//! Aggregation assumptions:
+//!
+//! - Co-signers do not re-run MPC with the prover, which would produce a
+//!   different timestamp and commitment. They receive the issued attestation (or
+//!   its proof bundle), check it by their own policy, and sign its digest with
+//!   [`sign_partial`].
+//! - Whoever collects the contributions (the prover, or a coordinator) combines
+//!   them with [`merge_partials`]. Merging is untrusted: it only accepts
+//!   contributions over the same signed fields, and [`verify_quorum`] re-checks
+//!   every signature.
+//! - The signer set is distributed out of band and trusted by the consumer, like
+//!   a single verifier key; the attestation only carries its id.
+//!
+//! ```
+//! use auditorzk_attestation::multisig::{merge_partials, sign_partial, verify_quorum, SignerSet};
+//! use auditorzk_attestation::signature::{encode_signature, sign_digest};
+//! use auditorzk_attestation::{message::message_digest, vectors, Attestation, Error};
+//! use k256::schnorr::SigningKey;
+//! use sha2::{Digest, Sha256};
+//!
+//! let issuer = vectors::test_signing_key();
+//! let cosigner = SigningKey::from_bytes(&Sha256::digest(b"cosigner")).unwrap();
+//! let absent = SigningKey::from_bytes(&Sha256::digest(b"absent")).unwrap();
+//! let member = |key: &SigningKey| key.verifying_key().to_bytes().to_vec();
+//! let set = SignerSet::new(2, vec![member(&issuer), member(&cosigner), member(&absent)]).unwrap();
+//!
+//! // The issuing verifier signs under the set
+//! let json = include_str!("../fixtures/attestation_v16.json");
+//! let mut issued: Attestation = serde_json::from_str(json).unwrap();
+//! issued.schema_version = 17;
+//! issued.signer_set_id = Some(set.id().to_vec());
+//! let digest = message_digest(&issued.signed_message().unwrap());
+//! issued.signature = encode_signature(&sign_digest(&issuer, &digest, &[0u8; 32]));
+//! issued.digest = Some(hex::encode(digest));
+//! assert_eq!(verify_quorum(&issued, &set), Err(Error::QuorumNotMet { valid: 1, threshold: 2 }));
+//!
+//! // A co-signer checks it and contributes, and the contributions are merged
+//! let mut cosigned = issued.clone();
+//! cosigned.partial_signatures.push(sign_partial(&issued, &cosigner).unwrap());
+//! merge_partials(&mut issued, &cosigned).unwrap();
+//! assert_eq!(verify_quorum(&issued, &set), Ok(2));
+//!
+//! // Contributions only count for the set they were signed under
+//! let other = SignerSet::new(2, vec![member(&issuer), member(&cosigner)]).unwrap();
+//! assert_eq!(verify_quorum(&issued, &other), Err(Error::SignerSetMismatch));
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use k256::schnorr::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::message::message_digest;
+use crate::signature::{decode_signature, verify_digest};
+use crate::{Attestation, Error};
+
+/// Domain separator hashed into every signer-set id
+const SIGNER_SET_DOMAIN: &[u8] = b"auditorzk.signer_set.v1";
+
+/// Verifier keys that co-sign attestations, and how many of them must
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignerSet {
+    /// Signatures required, `1..=members.len()`
+    pub threshold: u32,
+    /// 32-byte x-only public keys, sorted and distinct
+    pub members: Vec<Vec<u8>>,
+}
+
+impl SignerSet {
+    /// Build a set, sorting the members so every operator derives the same id
+    pub fn new(threshold: u32, mut members: Vec<Vec<u8>>) -> Result<Self, Error> {
+        members.sort();
+        members.dedup();
+        if members.iter().any(|key| VerifyingKey::from_bytes(key).is_err()) {
+            return Err(Error::InvalidPublicKey);
+        }
+        if threshold == 0 || threshold as usize > members.len() {
+            return Err(Error::InvalidThreshold { threshold, members: members.len() });
+        }
+        Ok(Self { threshold, members })
+    }
+
+    /// `SHA-256(domain || threshold (u32 LE) || sorted members)`, signed into
+    /// attestations issued under the set
+    pub fn id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(SIGNER_SET_DOMAIN);
+        hasher.update(self.threshold.to_le_bytes());
+        for member in &self.members {
+            hasher.update(member);
+        }
+        hasher.finalize().into()
+    }
+
+    pub fn contains(&self, pubkey: &[u8]) -> bool {
+        self.members.iter().any(|member| member == pubkey)
+    }
+}
+
+/// One co-signer's BIP-340 signature over an attestation's digest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PartialSignature {
+    /// The co-signer's 32-byte x-only public key
+    pub signer: Vec<u8>,
+    /// BIP-340 signature (hex-encoded with 3-byte version prefix)
+    pub signature: String,
+}
+
+/// Contribute a co-signer's signature to an attestation issued under a signer set
+#[cfg(feature = "std")]
+pub fn sign_partial(attestation: &Attestation, key: &k256::schnorr::SigningKey) -> Result<PartialSignature, Error> {
+    if attestation.signer_set_id.is_none() {
+        return Err(Error::SignerSetMismatch);
+    }
+    let digest = message_digest(&attestation.signed_message()?);
+    Ok(PartialSignature {
+        signer: key.verifying_key().to_bytes().to_vec(),
+        signature: crate::signature::encode_signature(&crate::signature::sign_digest(key, &digest, &[0u8; 32])),
+    })
+}
+
+/// Add `other`'s partial signatures to `into`, skipping signers already present.
+///
+/// Both must sign the same message, so contributions for a different attestation
+/// are rejected rather than merged.
+pub fn merge_partials(into: &mut Attestation, other: &Attestation) -> Result<(), Error> {
+    if into.signed_message()? != other.signed_message()? {
+        return Err(Error::AttestationMismatch);
+    }
+    for partial in &other.partial_signatures {
+        let known = partial.signer == into.verifier_pubkey
+            || into.partial_signatures.iter().any(|existing| existing.signer == partial.signer);
+        if !known {
+            into.partial_signatures.push(partial.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Count distinct members of `set` whose signature verifies, and require the threshold.
+///
+/// The issuing verifier's own `signature` counts when its key is a member.
+/// Signatures from non-members, duplicates and invalid signatures are ignored,
+/// so anyone can append contributions without being able to forge a quorum.
+///
+/// ```
+/// use auditorzk_attestation::message::SignedField::*;
+/// use auditorzk_attestation::multisig::{verify_quorum, SignerSet};
+/// use auditorzk_attestation::signature::verify_legacy_attestation;
+/// use auditorzk_attestation::{Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v17.json");
+/// let issuer = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+/// let cosigner = hex::decode("5f6b5f22ac3d3abaa06818aad625c74d68fbd07ac25c93296ff931c8af66a48e").unwrap();
+/// let set = SignerSet::new(2, vec![issuer.clone(), cosigner]).unwrap();
+/// let fields = [
+///     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+///     AccountFilter, VerifierVersion, Recipient, CommitmentCount, CommitmentsDigest, Label, SignerSet,
+/// ];
+///
+/// let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+/// verify_legacy_attestation(&attestation, &issuer, &fields).unwrap();
+/// assert_eq!(verify_quorum(&attestation, &set), Ok(2));
+///
+/// // A duplicated contribution doesn't count twice
+/// attestation.partial_signatures[0].signer = issuer;
+/// assert_eq!(verify_quorum(&attestation, &set), Err(Error::QuorumNotMet { valid: 1, threshold: 2 }));
+/// ```
+pub fn verify_quorum(attestation: &Attestation, set: &SignerSet) -> Result<usize, Error> {
+    if attestation.signer_set_id.as_deref() != Some(&set.id()[..]) {
+        return Err(Error::SignerSetMismatch);
+    }
+    let digest = message_digest(&attestation.signed_message()?);
+
+    let contributions = core::iter::once((&attestation.verifier_pubkey, &attestation.signature))
+        .chain(attestation.partial_signatures.iter().map(|partial| (&partial.signer, &partial.signature)));
+
+    let mut signers: Vec<&Vec<u8>> = Vec::new();
+    for (signer, signature) in contributions {
+        if !set.contains(signer) || signers.contains(&signer) {
+            continue;
+        }
+        let verified = VerifyingKey::from_bytes(signer).ok().zip(decode_signature(signature).ok()).is_some_and(
+            |(key, signature)| verify_digest(&key, &digest, &signature).is_ok(),
+        );
+        if verified {
+            signers.push(signer);
+        }
+    }
+
+    let valid = signers.len();
+    if valid < set.threshold as usize {
+        return Err(Error::QuorumNotMet { valid, threshold: set.threshold });
+    }
+    Ok(valid)
+}
@@ -0,0 +1,256 @@
+//! Consumer-side checks applied after the signature verifies.
+
+use alloc::string::ToString;
+
+use crate::currency::{currency_commitment, map_commitment};
+use crate::inventory::accounts_commitment;
+use crate::{Attestation, Error};
+
+/// Reject attestations older than `max_age` seconds or more than `max_skew`
+/// seconds ahead of `now` (all in Unix seconds)
+pub fn check_freshness(attestation: &Attestation, now: u64, max_age: u64, max_skew: u64) -> Result<(), Error> {
+    if attestation.timestamp > now.saturating_add(max_skew) {
+        return Err(Error::FromFuture { timestamp: attestation.timestamp, now });
+    }
+
+    let age = now.saturating_sub(attestation.timestamp);
+    if age > max_age {
+        return Err(Error::Stale { age, max_age });
+    }
+
+    Ok(())
+}
+
+/// Require the attested server name to be one of `allowed`
+pub fn check_server_name(attestation: &Attestation, allowed: &[&str]) -> Result<(), Error> {
+    if allowed.iter().any(|name| *name == attestation.server_name) {
+        Ok(())
+    } else {
+        Err(Error::ServerNotAllowed(attestation.server_name.clone()))
+    }
+}
+
+/// Require a signed environment equal to `expected` (an unsigned label proves nothing)
+pub fn check_environment(attestation: &Attestation, expected: &str) -> Result<(), Error> {
+    match &attestation.environment {
+        Some(env) if attestation.environment_signed && env == expected => Ok(()),
+        actual => Err(Error::EnvironmentMismatch {
+            expected: expected.to_string(),
+            actual: actual.clone(),
+        }),
+    }
+}
+
+/// Require the attested account inventory to equal `account_ids`, e.g. the
+/// accounts the consumer sees for the same item via `/accounts/get`.
+///
+/// The verifier notarizes exactly one HTTP exchange per session (the transcript
+/// parser rejects a second message behind the first), so it can't require the
+/// prover to notarize `/accounts/get` alongside the balance call. This check is
+/// where that cross-check happens instead. Every case in `fixtures/account_sets.json`
+/// gets the expected verdict against the five accounts attested in schema 20:
+///
+/// ```
+/// use auditorzk_attestation::{policy, Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v20.json");
+/// let attestation: Attestation = serde_json::from_str(json).unwrap();
+///
+/// let cases: serde_json::Value = serde_json::from_str(include_str!("../fixtures/account_sets.json")).unwrap();
+/// for case in cases.as_array().unwrap() {
+///     let account_ids: Vec<&str> = case["accounts_get"]["accounts"]
+///         .as_array()
+///         .unwrap()
+///         .iter()
+///         .map(|account| account["account_id"].as_str().unwrap())
+///         .collect();
+///     let verdict = match policy::check_account_inventory(&attestation, &account_ids) {
+///         Ok(()) => "ok",
+///         Err(Error::AccountCountMismatch { actual: Some(5), .. }) => "count_mismatch",
+///         Err(Error::AccountIdsMismatch) => "ids_mismatch",
+///         Err(e) => panic!("{}: {}", case["name"], e),
+///     };
+///     assert_eq!(verdict, case["expect"], "{}", case["name"]);
+/// }
+///
+/// // Schemas before 4 carry no inventory to compare against
+/// let json = include_str!("../fixtures/attestation_v1.json");
+/// let attestation: Attestation = serde_json::from_str(json).unwrap();
+/// assert_eq!(
+///     policy::check_account_inventory(&attestation, &["acc-1"]),
+///     Err(Error::AccountCountMismatch { expected: 1, actual: None }),
+/// );
+/// ```
+pub fn check_account_inventory<S: AsRef<str>>(attestation: &Attestation, account_ids: &[S]) -> Result<(), Error> {
+    let expected = account_ids.len() as u32;
+    if attestation.account_count != Some(expected) {
+        return Err(Error::AccountCountMismatch { expected, actual: attestation.account_count });
+    }
+
+    match &attestation.accounts_commitment {
+        Some(commitment) if commitment[..] == accounts_commitment(account_ids)[..] => Ok(()),
+        _ => Err(Error::AccountIdsMismatch),
+    }
+}
+
+/// Reject attestations issued by any of the `distrusted` verifier versions (e.g.
+/// builds with a known vulnerability). Attestations that record no version
+/// (schema 9 and older) are rejected too, since they can't show they weren't.
+///
+/// The version is signed, so it can't be rewritten to dodge this check:
+///
+/// ```
+/// use auditorzk_attestation::message::SignedField::*;
+/// use auditorzk_attestation::signature::verify_legacy_attestation;
+/// use auditorzk_attestation::{policy, Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v10.json");
+/// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+/// let fields = [
+///     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+///     AccountFilter, VerifierVersion,
+/// ];
+/// let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+/// verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+///
+/// assert!(policy::check_verifier_version(&attestation, &["auditor-zk-verifier/0.1.0"]).is_err());
+///
+/// attestation.verifier_version = Some("auditor-zk-verifier/0.2.0".into());
+/// assert_eq!(verify_legacy_attestation(&attestation, &pubkey, &fields), Err(Error::InvalidSignature));
+/// ```
+pub fn check_verifier_version(attestation: &Attestation, distrusted: &[&str]) -> Result<(), Error> {
+    match &attestation.verifier_version {
+        Some(version) if !distrusted.contains(&version.as_str()) => Ok(()),
+        version => Err(Error::DistrustedVerifierVersion(version.clone())),
+    }
+}
+
+/// Reject attestations from builds whose fingerprint is in `distrusted`, or
+/// that don't record their build. Fingerprints are the SHA-256 of the version
+/// text, as listed by the verifier's `schemas export` in `build-fingerprints.json`:
+///
+/// ```
+/// use auditorzk_attestation::{policy, Attestation, Error};
+/// use sha2::{Digest, Sha256};
+///
+/// let json = include_str!("../fixtures/attestation_v10.json");
+/// let attestation: Attestation = serde_json::from_str(json).unwrap();
+///
+/// let fingerprint: [u8; 32] = Sha256::digest(b"auditor-zk-verifier/0.1.0").into();
+/// assert_eq!(attestation.build_fingerprint(), Some(fingerprint));
+///
+/// assert!(policy::check_build_fingerprint(&attestation, &[[0; 32]]).is_ok());
+/// assert_eq!(
+///     policy::check_build_fingerprint(&attestation, &[fingerprint]),
+///     Err(Error::DistrustedVerifierVersion(Some("auditor-zk-verifier/0.1.0".into()))),
+/// );
+/// ```
+pub fn check_build_fingerprint(attestation: &Attestation, distrusted: &[[u8; 32]]) -> Result<(), Error> {
+    match attestation.build_fingerprint() {
+        Some(fingerprint) if !distrusted.contains(&fingerprint) => Ok(()),
+        _ => Err(Error::DistrustedVerifierVersion(attestation.verifier_version.clone())),
+    }
+}
+
+/// Require the attestation to be bound to `recipient`, e.g. the hash of the
+/// wallet address redeeming it, so a leaked attestation can't be used by others.
+/// Unbound attestations (no recipient, or schema 11 and older) are rejected.
+///
+/// ```
+/// use auditorzk_attestation::{policy, Attestation, Error};
+/// use sha2::{Digest, Sha256};
+///
+/// let json = include_str!("../fixtures/attestation_v12.json");
+/// let attestation: Attestation = serde_json::from_str(json).unwrap();
+///
+/// let redeemer = Sha256::digest(b"0x00000000000000000000000000000000000000aa");
+/// policy::check_recipient(&attestation, &redeemer).unwrap();
+///
+/// let other = Sha256::digest(b"0x00000000000000000000000000000000000000bb");
+/// assert_eq!(policy::check_recipient(&attestation, &other), Err(Error::RecipientMismatch));
+/// ```
+///
+/// Claim-set attestations sign the recipient under its own tag (`0x0b`, see
+/// [`crate::claims`]), so it can't be moved to another 32-byte field or
+/// dropped without breaking the signature:
+///
+/// ```
+/// use auditorzk_attestation::claims::verify_message;
+/// use auditorzk_attestation::message::SignedField;
+/// use auditorzk_attestation::{policy, verify_attestation, Attestation, Error};
+/// use sha2::{Digest, Sha256};
+///
+/// let json = include_str!("../fixtures/attestation_v22.json");
+/// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+/// let mut bound: Attestation = serde_json::from_str(json).unwrap();
+/// verify_attestation(&bound, &pubkey).unwrap();
+/// policy::check_recipient(&bound, &Sha256::digest(b"0x00000000000000000000000000000000000000aa")).unwrap();
+///
+/// // The recipient is the last claim; retagging it as the signer set changes the signed bytes
+/// let mut message = bound.signed_message().unwrap();
+/// let tag = message.len() - 33;
+/// assert_eq!(message[tag], SignedField::Recipient.tag());
+/// message[tag] = SignedField::SignerSet.tag();
+/// assert_eq!(verify_message(&message, &bound.signature, &pubkey), Err(Error::InvalidSignature));
+///
+/// bound.digest = None;
+/// let mut moved = bound.clone();
+/// moved.claim_set = Some("extended".into());
+/// moved.signer_set_id = moved.recipient.take();
+/// assert_eq!(verify_attestation(&moved, &pubkey), Err(Error::InvalidSignature));
+///
+/// let mut unbound = bound.clone();
+/// unbound.recipient = None;
+/// assert_eq!(verify_attestation(&unbound, &pubkey), Err(Error::InvalidSignature));
+/// ```
+pub fn check_recipient(attestation: &Attestation, recipient: &[u8]) -> Result<(), Error> {
+    match &attestation.recipient {
+        Some(bound) if bound[..] == recipient[..] => Ok(()),
+        _ => Err(Error::RecipientMismatch),
+    }
+}
+
+/// Require the prover's request to have matched one of the `accepted` templates,
+/// e.g. `plaid.balance.v1`. Attestations without a template match (schema 17 and
+/// older, or sessions that declared none) are rejected.
+///
+/// ```
+/// use auditorzk_attestation::{policy, Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v18.json");
+/// let attestation: Attestation = serde_json::from_str(json).unwrap();
+///
+/// policy::check_request_template(&attestation, &["plaid.balance.v1"]).unwrap();
+/// assert_eq!(
+///     policy::check_request_template(&attestation, &["plaid.payroll_income.v1"]),
+///     Err(Error::RequestTemplateNotMatched(Some("plaid.balance.v1".into()))),
+/// );
+/// ```
+pub fn check_request_template(attestation: &Attestation, accepted: &[&str]) -> Result<(), Error> {
+    match &attestation.request_template {
+        Some(template) if template.matched && accepted.contains(&template.id.as_str()) => Ok(()),
+        template => Err(Error::RequestTemplateNotMatched(template.as_ref().map(|t| t.id.clone()))),
+    }
+}
+
+/// Require the attestation to commit `total` as its `currency` subtotal, opened with
+/// the prover's `currency_blinder`. The per-currency map must hash to the signed
+/// balance commitment, so check the signature first; see [`crate::currency`].
+pub fn check_currency_total(
+    attestation: &Attestation,
+    currency: &str,
+    total: &str,
+    currency_blinder: &[u8],
+) -> Result<(), Error> {
+    let committed = attestation
+        .balances_by_currency
+        .get(currency)
+        .ok_or_else(|| Error::CurrencyNotAttested(currency.to_string()))?;
+    if map_commitment(&attestation.balances_by_currency)?[..] != attestation.balance_commitment[..] {
+        return Err(Error::CurrencyMapMismatch);
+    }
+    if currency_commitment(total, currency_blinder)[..] != committed[..] {
+        return Err(Error::CurrencyTotalMismatch(currency.to_string()));
+    }
+    Ok(())
+}
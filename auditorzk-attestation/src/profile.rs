@@ -0,0 +1,41 @@
+//! Which response schema profile the verifier read the provider response with.
+//!
+//! Providers reshape their responses over time, so the verifier keeps several
+//! versioned *schema profiles* per provider: where each field lives, and when
+//! each profile applies. It tries the newest applicable profile first and falls
+//! back to older ones. It records the profile it used (schema 23+) as
+//! `schema_profile`, with a note for each profile it tried and skipped. The record
+//! is unsigned and informational: it helps explain a claim, but consumers
+//! shouldn't base policy on it.
+//!
+//! ```
+//! use auditorzk_attestation::{verify_attestation, Attestation};
+//!
+//! let json = include_str!("../fixtures/attestation_v23.json");
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+//! verify_attestation(&attestation, &pubkey).unwrap();
+//!
+//! let profile = attestation.schema_profile.as_ref().unwrap();
+//! assert_eq!(profile.id, "plaid.balance.2020-09-14");
+//! assert!(profile.notes.is_empty());
+//!
+//! // Unsigned, so it doesn't affect the signature
+//! attestation.schema_profile = None;
+//! verify_attestation(&attestation, &pubkey).unwrap();
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// The schema profile a claim was extracted with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ProfileSelection {
+    /// Profile id, e.g. `plaid.balance.2020-09-14`
+    pub id: String,
+    /// Why each newer applicable profile was passed over, newest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
@@ -0,0 +1,58 @@
+//! Request templates the verifier checked the prover's request against.
+//!
+//! When the prover keeps its request private, the verifier never sees it, so it
+//! can't read the endpoint off the transcript. Instead the prover commits to the
+//! request line and headers (a sent-direction hash commitment), and the verifier
+//! rebuilds those exact bytes from a per-provider template plus the few values
+//! the prover declares (e.g. `content-length`). A matching commitment proves the
+//! request's shape without revealing its body or credentials.
+//!
+//! The template id and whether the commitment matched are recorded as
+//! `request_template` and signed (schema 18+) as
+//! `SHA-256(id || matched byte)`, see [`RequestTemplateMatch::signed_hash`].
+//!
+//! ```
+//! use auditorzk_attestation::message::SignedField::*;
+//! use auditorzk_attestation::signature::verify_legacy_attestation;
+//! use auditorzk_attestation::{policy, Attestation, Error};
+//!
+//! let json = include_str!("../fixtures/attestation_v18.json");
+//! let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+//! let fields = [
+//!     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+//!     AccountFilter, VerifierVersion, Recipient, CommitmentCount, CommitmentsDigest, Label, SignerSet,
+//!     RequestTemplate,
+//! ];
+//! let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+//! verify_legacy_attestation(&attestation, &pubkey, &fields).unwrap();
+//! policy::check_request_template(&attestation, &["plaid.balance.v1"]).unwrap();
+//!
+//! // The match result is signed, so a failed match can't be passed off as a success
+//! attestation.request_template.as_mut().unwrap().matched = false;
+//! attestation.digest = None;
+//! assert_eq!(verify_legacy_attestation(&attestation, &pubkey, &fields), Err(Error::InvalidSignature));
+//! ```
+
+use alloc::string::String;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Which template the committed request was checked against, and the outcome
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct RequestTemplateMatch {
+    /// Template id, e.g. `plaid.balance.v1`
+    pub id: String,
+    /// Whether the prover's commitment equals the template rendered with its declared fields
+    pub matched: bool,
+}
+
+impl RequestTemplateMatch {
+    /// `SHA-256(id || matched byte)`, as it appears in the signed message
+    pub fn signed_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.id.as_bytes());
+        hasher.update([u8::from(self.matched)]);
+        hasher.finalize().into()
+    }
+}
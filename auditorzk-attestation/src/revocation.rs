@@ -0,0 +1,157 @@
+//! Signed revocation lists for attestations issued in error.
+//!
+//! If a verifier key leaks or a bug produced wrong attestations for a while, the
+//! operator publishes a [`RevocationList`] signed with its organization key
+//! (BIP-340, like attestations). Consumers verify the list against that key and
+//! then check each attestation against it; a revoked attestation fails with
+//! [`Error::Revoked`], distinct from [`Error::InvalidSignature`].
+//!
+//! The list signature covers `SHA-256(canonical JSON of {issued_at, entries})`.
+//!
+//! `fixtures/revocation_list.json` revokes the schema 2 fixture by signature,
+//! a co-signer's key, and the day the schema 1 fixture was issued:
+//!
+//! ```
+//! use auditorzk_attestation::revocation::{RevocationList, RevocationScope};
+//! use auditorzk_attestation::{Attestation, Error};
+//! use k256::schnorr::SigningKey;
+//! use sha2::{Digest, Sha256};
+//!
+//! let org = SigningKey::from_bytes(&Sha256::digest(b"organization")).unwrap();
+//! let org_pubkey = org.verifying_key().to_bytes();
+//! let fixture = |json: &str| -> Attestation { serde_json::from_str(json).unwrap() };
+//! let (v1, v2, v3) = (
+//!     fixture(include_str!("../fixtures/attestation_v1.json")),
+//!     fixture(include_str!("../fixtures/attestation_v2.json")),
+//!     fixture(include_str!("../fixtures/attestation_v3.json")),
+//! );
+//!
+//! let list: RevocationList = serde_json::from_str(include_str!("../fixtures/revocation_list.json")).unwrap();
+//! list.verify(&org_pubkey).unwrap();
+//!
+//! // Signing the same entries reproduces the published list
+//! assert_eq!(RevocationList::sign(list.issued_at, list.entries.clone(), &org).unwrap(), list);
+//!
+//! let revoked = |reason: &str| Err(Error::Revoked { reason: reason.into() });
+//! assert_eq!(list.check(&v1), revoked("Mixed-currency sums before the fix"));
+//! assert_eq!(list.check(&v2), revoked("Balance parsed from the wrong account"));
+//! assert_eq!(list.check(&v3), Ok(()));
+//!
+//! // Each scope on its own
+//! let by_signature = RevocationScope::Attestation { signature: v2.signature.to_uppercase() };
+//! assert!(by_signature.covers(&v2) && !by_signature.covers(&v3));
+//! let by_key = |key: &str| RevocationScope::Key { verifier_pubkey: key.into() };
+//! assert!(by_key("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").covers(&v3));
+//! assert!(!by_key("5f6b5f22ac3d3abaa06818aad625c74d68fbd07ac25c93296ff931c8af66a48e").covers(&v3));
+//! assert!(!by_key("not hex").covers(&v3));
+//! let window = RevocationScope::TimeWindow { from: v1.timestamp, until: v2.timestamp - 1 };
+//! assert!(window.covers(&v1) && !window.covers(&v2));
+//!
+//! // Any edit to the entries, or the wrong key, breaks the list signature
+//! let mut tampered = list.clone();
+//! tampered.entries.remove(1);
+//! assert_eq!(tampered.verify(&org_pubkey), Err(Error::InvalidRevocationList));
+//! let mut tampered = list.clone();
+//! tampered.entries[0].reason = "Routine rotation".into();
+//! assert_eq!(tampered.verify(&org_pubkey), Err(Error::InvalidRevocationList));
+//! let other = SigningKey::from_bytes(&Sha256::digest(b"other organization")).unwrap();
+//! assert_eq!(list.verify(&other.verifying_key().to_bytes()), Err(Error::InvalidRevocationList));
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use k256::schnorr::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::message::message_digest;
+use crate::signature::{decode_signature, verify_digest};
+use crate::{Attestation, Error};
+
+/// Revocation list published by the verifier operator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationList {
+    /// When the list was generated (Unix seconds)
+    pub issued_at: u64,
+    /// Revocations, append-only across successive lists
+    pub entries: Vec<Revocation>,
+    /// BIP-340 signature by the organization key (hex, 3-byte version prefix)
+    pub signature: String,
+}
+
+/// One revoked set of attestations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revocation {
+    /// Which attestations are revoked
+    #[serde(flatten)]
+    pub scope: RevocationScope,
+    /// Why, shown to consumers
+    pub reason: String,
+    /// When the revocation was recorded (Unix seconds)
+    pub revoked_at: u64,
+}
+
+/// Attestations covered by a revocation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum RevocationScope {
+    /// A single attestation, identified by its (hex) signature
+    Attestation { signature: String },
+    /// Everything signed by a verifier key (hex, x-only)
+    Key { verifier_pubkey: String },
+    /// Attestations whose `timestamp` lies in `from..=until`
+    TimeWindow { from: u64, until: u64 },
+}
+
+impl RevocationScope {
+    /// Whether the scope covers `attestation`
+    pub fn covers(&self, attestation: &Attestation) -> bool {
+        match self {
+            Self::Attestation { signature } => signature.eq_ignore_ascii_case(&attestation.signature),
+            Self::Key { verifier_pubkey } => {
+                hex::decode(verifier_pubkey).is_ok_and(|key| key == attestation.verifier_pubkey)
+            }
+            Self::TimeWindow { from, until } => (*from..=*until).contains(&attestation.timestamp),
+        }
+    }
+}
+
+impl RevocationList {
+    /// Exact bytes covered by the list signature
+    pub fn signed_message(issued_at: u64, entries: &[Revocation]) -> Result<Vec<u8>, Error> {
+        let mut object = Map::new();
+        object.insert("issued_at".to_string(), Value::from(issued_at));
+        object.insert(
+            "entries".to_string(),
+            serde_json::to_value(entries).map_err(|e| Error::Json(e.to_string()))?,
+        );
+        serde_json::to_vec(&Value::Object(object)).map_err(|e| Error::Json(e.to_string()))
+    }
+
+    /// Sign a list with the organization key
+    #[cfg(feature = "std")]
+    pub fn sign(issued_at: u64, entries: Vec<Revocation>, org_key: &k256::schnorr::SigningKey) -> Result<Self, Error> {
+        let digest = message_digest(&Self::signed_message(issued_at, &entries)?);
+        let signature = crate::signature::encode_signature(&crate::signature::sign_digest(org_key, &digest, &[0u8; 32]));
+        Ok(Self { issued_at, entries, signature })
+    }
+
+    /// Verify the list against the trusted organization key (32-byte x-only)
+    pub fn verify(&self, trusted_org_pubkey: &[u8]) -> Result<(), Error> {
+        let verifying_key = VerifyingKey::from_bytes(trusted_org_pubkey).map_err(|_| Error::InvalidPublicKey)?;
+        let signature = decode_signature(&self.signature)?;
+        let digest = message_digest(&Self::signed_message(self.issued_at, &self.entries)?);
+
+        verify_digest(&verifying_key, &digest, &signature).map_err(|_| Error::InvalidRevocationList)
+    }
+
+    /// Fail with [`Error::Revoked`] if any entry covers `attestation`.
+    ///
+    /// Only meaningful on a list that passed [`RevocationList::verify`].
+    pub fn check(&self, attestation: &Attestation) -> Result<(), Error> {
+        match self.entries.iter().find(|entry| entry.scope.covers(attestation)) {
+            Some(entry) => Err(Error::Revoked { reason: entry.reason.clone() }),
+            None => Ok(()),
+        }
+    }
+}
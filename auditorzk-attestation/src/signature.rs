@@ -0,0 +1,199 @@
+//! Versioned BIP-340 signature encoding and verification.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use k256::schnorr::{Signature, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::message::{message_digest, SignedField};
+use crate::{Attestation, Error};
+
+/// BIP-340 signature version 2.0.0, over the message digest itself
+pub const SIGNATURE_VERSION: [u8; 3] = [0x02, 0x00, 0x00];
+
+/// BIP-340 signature version 1.0.0, over `SHA-256(digest)`. Verifiers before
+/// 2.0.0 signed through the `Signer` trait, which hashes its input again; these
+/// signatures still verify but are no longer issued.
+pub const SIGNATURE_VERSION_PREHASHED: [u8; 3] = [0x01, 0x00, 0x00];
+
+/// A decoded signature, by what its version signs
+#[derive(Debug, Clone, Copy)]
+pub enum VersionedSignature {
+    /// 1.0.0: BIP-340 over `SHA-256(digest)`
+    Prehashed(Signature),
+    /// 2.0.0: BIP-340 over the digest itself
+    Digest(Signature),
+}
+
+/// Sign a 32-byte message digest with BIP-340.
+///
+/// The digest itself is the BIP-340 message; it is not hashed again, so an
+/// on-chain BIP-340 verifier checks the signature over the stored `digest` as
+/// is. `aux_rand` is BIP-340's auxiliary randomness: fresh random bytes when
+/// issuing, a fixed value for reproducible fixtures.
+pub fn sign_digest(key: &SigningKey, digest: &[u8; 32], aux_rand: &[u8; 32]) -> Signature {
+    key.sign_raw(digest, aux_rand).expect("BIP-340 signing only fails with negligible probability")
+}
+
+/// Verify a versioned BIP-340 signature over a 32-byte message digest (see
+/// [`sign_digest`]); version 1.0.0 signatures are checked over the digest's hash
+pub fn verify_digest(key: &VerifyingKey, digest: &[u8; 32], signature: &VersionedSignature) -> Result<(), Error> {
+    let verified = match signature {
+        VersionedSignature::Prehashed(signature) => key.verify_raw(&Sha256::digest(digest), signature),
+        VersionedSignature::Digest(signature) => key.verify_raw(digest, signature),
+    };
+    verified.map_err(|_| Error::InvalidSignature)
+}
+
+/// Hex-encode a signature with its 3-byte version prefix
+pub fn encode_signature(signature: &Signature) -> String {
+    let mut versioned_sig = Vec::with_capacity(67); // 3 + 64
+    versioned_sig.extend_from_slice(&SIGNATURE_VERSION);
+    versioned_sig.extend_from_slice(&signature.to_bytes());
+    hex::encode(versioned_sig)
+}
+
+/// Parse a hex-encoded versioned signature.
+///
+/// Attestations signed before 2.0.0 keep verifying; the frozen fixture below
+/// was signed by the schema 1 verifier and was never re-signed:
+///
+/// ```
+/// use auditorzk_attestation::message::SignedField::*;
+/// use auditorzk_attestation::signature::{decode_signature, verify_legacy_attestation, VersionedSignature};
+/// use auditorzk_attestation::{Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v1_prehashed.json");
+/// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+/// let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+/// assert!(matches!(decode_signature(&attestation.signature), Ok(VersionedSignature::Prehashed(_))));
+/// verify_legacy_attestation(&attestation, &pubkey, &[ServerName, Timestamp, Commitment]).unwrap();
+///
+/// // The same signature relabelled as 2.0.0 is checked over the digest itself, and fails
+/// attestation.signature.replace_range(..6, "020000");
+/// assert_eq!(
+///     verify_legacy_attestation(&attestation, &pubkey, &[ServerName, Timestamp, Commitment]),
+///     Err(Error::InvalidSignature),
+/// );
+/// ```
+pub fn decode_signature(encoded: &str) -> Result<VersionedSignature, Error> {
+    let bytes = hex::decode(encoded).map_err(|_| Error::InvalidSignatureEncoding)?;
+    if bytes.len() != 67 {
+        return Err(Error::InvalidSignatureEncoding);
+    }
+
+    let signature = Signature::try_from(&bytes[3..]).map_err(|_| Error::InvalidSignatureEncoding)?;
+    match [bytes[0], bytes[1], bytes[2]] {
+        SIGNATURE_VERSION => Ok(VersionedSignature::Digest(signature)),
+        SIGNATURE_VERSION_PREHASHED => Ok(VersionedSignature::Prehashed(signature)),
+        version => Err(Error::UnsupportedSignatureVersion(version)),
+    }
+}
+
+/// Verify an attestation against the trusted verifier public key (32-byte x-only).
+///
+/// The key embedded in the attestation must match `trusted_pubkey`; it is never
+/// trusted on its own. The signed message is rebuilt from the attestation's
+/// fields (see [`Attestation::signed_message`]).
+///
+/// Only claim-set messages (see [`crate::claims`]) verify here, since only they
+/// say which field each value is. A legacy message is untagged, so its values
+/// could be relabelled as other fields of the same width without breaking the
+/// signature; it fails with [`Error::LegacyLayout`], see
+/// [`verify_legacy_attestation`].
+///
+/// A stored `digest` (schema 14+) must equal the one rebuilt from the fields, so
+/// on-chain consumers that take the digest as given can't be fed a different one:
+///
+/// ```
+/// use auditorzk_attestation::{verify_attestation, Attestation, Error};
+///
+/// let json = include_str!("../fixtures/attestation_v22.json");
+/// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+/// let mut attestation: Attestation = serde_json::from_str(json).unwrap();
+/// verify_attestation(&attestation, &pubkey).unwrap();
+///
+/// attestation.digest = Some("00".repeat(32));
+/// assert_eq!(verify_attestation(&attestation, &pubkey), Err(Error::DigestMismatch));
+///
+/// let legacy: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v21.json")).unwrap();
+/// assert_eq!(verify_attestation(&legacy, &pubkey), Err(Error::LegacyLayout(21)));
+/// ```
+pub fn verify_attestation(attestation: &Attestation, trusted_pubkey: &[u8]) -> Result<(), Error> {
+    if attestation.declared_claim_set()?.is_none() {
+        return Err(Error::LegacyLayout(attestation.schema_version));
+    }
+    verify_signed_message(attestation, trusted_pubkey)
+}
+
+/// Verify a legacy (1.0.0) attestation against the fields the consumer expects
+/// it to sign, in message order.
+///
+/// Legacy messages append optional fields without tags and don't sign
+/// `schema_version`, so the same bytes verify under several field sets. Pinning
+/// `expected` to what the consumer's verifier issued rules the others out:
+///
+/// ```
+/// use auditorzk_attestation::message::SignedField::*;
+/// use auditorzk_attestation::signature::verify_legacy_attestation;
+/// use auditorzk_attestation::{verify_attestation, Attestation, Error};
+///
+/// let pubkey = hex::decode("42be9b6540564370d8a0e9d2c61f262b35cb24376614e743ff3a0fd80aeb63db").unwrap();
+///
+/// // A schema 12 recipient is the last 32 bytes of the message, where schema 17 puts a signer-set id
+/// let bound: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v12.json")).unwrap();
+/// let v12 = [
+///     ServerName, Timestamp, Commitment, AccountCount, AccountsCommitment, Formula, ClaimType,
+///     AccountFilter, VerifierVersion, Recipient,
+/// ];
+/// verify_legacy_attestation(&bound, &pubkey, &v12).unwrap();
+///
+/// let mut relabelled = bound.clone();
+/// relabelled.schema_version = 17;
+/// relabelled.signer_set_id = relabelled.recipient.take();
+/// assert_eq!(relabelled.signed_message(), bound.signed_message());
+/// assert_eq!(verify_legacy_attestation(&relabelled, &pubkey, &v12), Err(Error::SignedFieldsMismatch));
+/// assert_eq!(verify_attestation(&relabelled, &pubkey), Err(Error::LegacyLayout(17)));
+///
+/// // A schema 2 `sandbox` environment byte is where schema 6 puts a `current` formula byte
+/// let sandbox: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v2.json")).unwrap();
+/// let v2 = [ServerName, Timestamp, Commitment, Environment];
+/// verify_legacy_attestation(&sandbox, &pubkey, &v2).unwrap();
+///
+/// let mut relabelled = sandbox.clone();
+/// relabelled.schema_version = 6;
+/// relabelled.environment_signed = false;
+/// relabelled.balance_formula = Some("current".into());
+/// assert_eq!(relabelled.signed_message(), sandbox.signed_message());
+/// assert_eq!(verify_legacy_attestation(&relabelled, &pubkey, &v2), Err(Error::SignedFieldsMismatch));
+/// assert_eq!(verify_attestation(&relabelled, &pubkey), Err(Error::LegacyLayout(6)));
+/// ```
+pub fn verify_legacy_attestation(
+    attestation: &Attestation,
+    trusted_pubkey: &[u8],
+    expected: &[SignedField],
+) -> Result<(), Error> {
+    if attestation.signed_field_list()? != expected {
+        return Err(Error::SignedFieldsMismatch);
+    }
+    verify_signed_message(attestation, trusted_pubkey)
+}
+
+/// Check the key, the stored digest and the signature over the rebuilt message
+fn verify_signed_message(attestation: &Attestation, trusted_pubkey: &[u8]) -> Result<(), Error> {
+    if attestation.verifier_pubkey != trusted_pubkey {
+        return Err(Error::PublicKeyMismatch);
+    }
+
+    let verifying_key = VerifyingKey::from_bytes(trusted_pubkey).map_err(|_| Error::InvalidPublicKey)?;
+    let signature = decode_signature(&attestation.signature)?;
+    let digest = message_digest(&attestation.signed_message()?);
+
+    if let Some(stored) = &attestation.digest {
+        if !stored.eq_ignore_ascii_case(&hex::encode(digest)) {
+            return Err(Error::DigestMismatch);
+        }
+    }
+
+    verify_digest(&verifying_key, &digest, &signature)
+}
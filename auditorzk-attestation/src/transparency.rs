@@ -0,0 +1,348 @@
+//! Transparency log of issued attestations.
+//!
+//! The operator appends the [`content_hash`] of every attestation it issues to an
+//! append-only log, in issue order, and periodically publishes a
+//! [`SignedTreeHead`]: the log's size and Merkle root, signed with its
+//! organization key (BIP-340, like revocation lists). Only hashes are logged, never
+//! claims.
+//!
+//! An attestation holder fetches an [`InclusionProof`] for its sequence number and
+//! checks it with [`verify_inclusion`]. A monitor keeps every tree head it has seen
+//! and checks each new one against the last with [`verify_consistency`]. A
+//! verifier that showed different logs to different parties (a split view), or
+//! rewrote its history, fails one of the two.
+//!
+//! The tree is RFC 9162's: leaves are `SHA-256(0x00 || content hash)`, interior
+//! nodes `SHA-256(0x01 || left || right)`, the left subtree of `n` leaves holds
+//! the largest power of two below `n`, and the empty tree's root is `SHA-256("")`.
+//! The tree head signature covers `SHA-256(canonical JSON of {root_hash,
+//! timestamp, tree_size})`.
+//!
+//! ```
+//! use auditorzk_attestation::transparency::{
+//!     consistency_proof, content_hash, inclusion_proof, verify_consistency, verify_inclusion, SignedTreeHead,
+//! };
+//! use auditorzk_attestation::{Attestation, Error};
+//! use k256::schnorr::SigningKey;
+//! use sha2::{Digest, Sha256};
+//!
+//! let org_key = SigningKey::from_bytes(&Sha256::digest(b"org")).unwrap();
+//! let org_pubkey = org_key.verifying_key().to_bytes();
+//!
+//! // Seven issued attestations; ours is the fifth (sequence number 4)
+//! let ours: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v20.json")).unwrap();
+//! let mut leaves: Vec<[u8; 32]> = (0u8..7).map(|i| Sha256::digest([i]).into()).collect();
+//! leaves[4] = content_hash(&ours).unwrap();
+//!
+//! let sth = SignedTreeHead::sign(&leaves, 1_700_000_000, &org_key).unwrap();
+//! sth.verify(&org_pubkey).unwrap();
+//! let proof = inclusion_proof(&leaves, 4, 7).unwrap();
+//! verify_inclusion(&ours, &proof, &sth).unwrap();
+//!
+//! // A tampered attestation, or a proof for someone else's leaf, isn't included
+//! let mut tampered = ours.clone();
+//! tampered.timestamp += 1;
+//! assert_eq!(verify_inclusion(&tampered, &proof, &sth), Err(Error::NotInTransparencyLog));
+//! let other = inclusion_proof(&leaves, 3, 7).unwrap();
+//! assert_eq!(verify_inclusion(&ours, &other, &sth), Err(Error::NotInTransparencyLog));
+//!
+//! // The log grows; the new head extends the old one
+//! leaves.extend((7u8..12).map(|i| <[u8; 32]>::from(Sha256::digest([i]))));
+//! let next = SignedTreeHead::sign(&leaves, 1_700_000_300, &org_key).unwrap();
+//! let consistency = consistency_proof(&leaves, 7, 12).unwrap();
+//! verify_consistency(&sth, &next, &consistency).unwrap();
+//!
+//! // A log that rewrote an earlier leaf can't prove it only appended
+//! let mut rewritten = leaves.clone();
+//! rewritten[2] = [0; 32];
+//! let forked = SignedTreeHead::sign(&rewritten, 1_700_000_300, &org_key).unwrap();
+//! let consistency = consistency_proof(&rewritten, 7, 12).unwrap();
+//! assert_eq!(verify_consistency(&sth, &forked, &consistency), Err(Error::InconsistentTreeHeads));
+//!
+//! // Every pair of sizes round-trips
+//! for second in 1..=leaves.len() {
+//!     let head = SignedTreeHead::sign(&leaves[..second], 0, &org_key).unwrap();
+//!     for first in 1..=second {
+//!         let earlier = SignedTreeHead::sign(&leaves[..first], 0, &org_key).unwrap();
+//!         let proof = consistency_proof(&leaves, first as u64, second as u64).unwrap();
+//!         verify_consistency(&earlier, &head, &proof).unwrap();
+//!     }
+//! }
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use k256::schnorr::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::canonical::to_canonical_json;
+use crate::message::message_digest;
+use crate::signature::{decode_signature, verify_digest};
+use crate::{Attestation, Error};
+
+/// Domain prefix of a leaf hash
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain prefix of an interior node hash
+const NODE_PREFIX: u8 = 0x01;
+
+/// Signed size and root of the log at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    /// Number of leaves
+    pub tree_size: u64,
+    /// Merkle root over the first `tree_size` leaves (hex)
+    pub root_hash: String,
+    /// When the head was signed (Unix seconds)
+    pub timestamp: u64,
+    /// BIP-340 signature by the organization key (hex, 3-byte version prefix)
+    pub signature: String,
+}
+
+/// Audit path from one leaf to the root of a tree of `tree_size` leaves
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    /// Sequence number (leaf index) of the attestation
+    pub seq: u64,
+    pub tree_size: u64,
+    /// Sibling hashes from the leaf upwards (hex)
+    pub audit_path: Vec<String>,
+}
+
+/// Proof that the tree of `second_size` leaves extends the tree of `first_size`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub first_size: u64,
+    pub second_size: u64,
+    /// Subtree hashes (hex)
+    pub path: Vec<String>,
+}
+
+/// Hash logged for an attestation: `SHA-256` of its canonical JSON as issued
+pub fn content_hash(attestation: &Attestation) -> Result<[u8; 32], Error> {
+    Ok(Sha256::digest(to_canonical_json(attestation)?.as_bytes()).into())
+}
+
+/// Merkle root over a list of content hashes
+pub fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves {
+        [] => Sha256::digest([]).into(),
+        [leaf] => leaf_hash(leaf),
+        _ => {
+            let (left, right) = leaves.split_at(split(leaves.len()));
+            node_hash(&root(left), &root(right))
+        }
+    }
+}
+
+/// Inclusion proof for leaf `seq` in the tree of the first `tree_size` leaves
+pub fn inclusion_proof(leaves: &[[u8; 32]], seq: u64, tree_size: u64) -> Option<InclusionProof> {
+    let tree = leaves.get(..usize::try_from(tree_size).ok()?)?;
+    if seq >= tree_size {
+        return None;
+    }
+    let mut path = Vec::new();
+    audit_path(seq as usize, tree, &mut path);
+    Some(InclusionProof { seq, tree_size, audit_path: path.iter().map(hex::encode).collect() })
+}
+
+/// Consistency proof between the trees of the first `first_size` and
+/// `second_size` leaves
+pub fn consistency_proof(leaves: &[[u8; 32]], first_size: u64, second_size: u64) -> Option<ConsistencyProof> {
+    let tree = leaves.get(..usize::try_from(second_size).ok()?)?;
+    if first_size > second_size {
+        return None;
+    }
+    let mut path = Vec::new();
+    if first_size > 0 {
+        subproof(first_size as usize, tree, true, &mut path);
+    }
+    Some(ConsistencyProof { first_size, second_size, path: path.iter().map(hex::encode).collect() })
+}
+
+/// Check that `attestation` is leaf `proof.seq` of the tree `sth` commits to.
+///
+/// Only meaningful on a tree head that passed [`SignedTreeHead::verify`].
+pub fn verify_inclusion(attestation: &Attestation, proof: &InclusionProof, sth: &SignedTreeHead) -> Result<(), Error> {
+    if proof.tree_size != sth.tree_size || proof.seq >= proof.tree_size {
+        return Err(Error::InvalidTransparencyProof("proof is for a different tree size"));
+    }
+    let path = decode_hashes(&proof.audit_path)?;
+    let expected = decode_hash(&sth.root_hash)?;
+
+    let (mut index, mut last) = (proof.seq, proof.tree_size - 1);
+    let mut hash = leaf_hash(&content_hash(attestation)?);
+    for sibling in &path {
+        if last == 0 {
+            return Err(Error::NotInTransparencyLog);
+        }
+        if index & 1 == 1 || index == last {
+            hash = node_hash(sibling, &hash);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+
+    if last != 0 || hash != expected {
+        return Err(Error::NotInTransparencyLog);
+    }
+    Ok(())
+}
+
+/// Check that `second` only appended leaves to `first`.
+///
+/// Only meaningful on tree heads that passed [`SignedTreeHead::verify`].
+pub fn verify_consistency(
+    first: &SignedTreeHead,
+    second: &SignedTreeHead,
+    proof: &ConsistencyProof,
+) -> Result<(), Error> {
+    if (proof.first_size, proof.second_size) != (first.tree_size, second.tree_size) {
+        return Err(Error::InvalidTransparencyProof("proof is for different tree sizes"));
+    }
+    if first.tree_size > second.tree_size {
+        return Err(Error::InconsistentTreeHeads);
+    }
+    let mut path = decode_hashes(&proof.path)?;
+    let (first_root, second_root) = (decode_hash(&first.root_hash)?, decode_hash(&second.root_hash)?);
+
+    if first.tree_size == 0 {
+        return if path.is_empty() { Ok(()) } else { Err(Error::InconsistentTreeHeads) };
+    }
+    if first.tree_size == second.tree_size {
+        return if path.is_empty() && first_root == second_root { Ok(()) } else { Err(Error::InconsistentTreeHeads) };
+    }
+
+    // The old root is the first subtree hash unless the proof had to omit it
+    if first.tree_size.is_power_of_two() {
+        path.insert(0, first_root);
+    }
+    let (mut index, mut last) = (first.tree_size - 1, second.tree_size - 1);
+    while index & 1 == 1 {
+        index >>= 1;
+        last >>= 1;
+    }
+
+    let (start, rest) = path.split_first().ok_or(Error::InconsistentTreeHeads)?;
+    let (mut old, mut new) = (*start, *start);
+    for hash in rest {
+        if last == 0 {
+            return Err(Error::InconsistentTreeHeads);
+        }
+        if index & 1 == 1 || index == last {
+            old = node_hash(hash, &old);
+            new = node_hash(hash, &new);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            new = node_hash(&new, hash);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+
+    if last != 0 || old != first_root || new != second_root {
+        return Err(Error::InconsistentTreeHeads);
+    }
+    Ok(())
+}
+
+impl SignedTreeHead {
+    /// Exact bytes covered by the tree head signature
+    pub fn signed_message(tree_size: u64, root_hash: &str, timestamp: u64) -> Result<Vec<u8>, Error> {
+        let mut object = Map::new();
+        object.insert("tree_size".to_string(), Value::from(tree_size));
+        object.insert("root_hash".to_string(), Value::from(root_hash));
+        object.insert("timestamp".to_string(), Value::from(timestamp));
+        serde_json::to_vec(&Value::Object(object)).map_err(|e| Error::Json(e.to_string()))
+    }
+
+    /// Sign the head of a log holding `leaves` with the organization key
+    #[cfg(feature = "std")]
+    pub fn sign(leaves: &[[u8; 32]], timestamp: u64, org_key: &k256::schnorr::SigningKey) -> Result<Self, Error> {
+        let (tree_size, root_hash) = (leaves.len() as u64, hex::encode(root(leaves)));
+        let digest = message_digest(&Self::signed_message(tree_size, &root_hash, timestamp)?);
+        let signature = crate::signature::encode_signature(&crate::signature::sign_digest(org_key, &digest, &[0u8; 32]));
+        Ok(Self { tree_size, root_hash, timestamp, signature })
+    }
+
+    /// Verify the head against the trusted organization key (32-byte x-only)
+    pub fn verify(&self, trusted_org_pubkey: &[u8]) -> Result<(), Error> {
+        let verifying_key = VerifyingKey::from_bytes(trusted_org_pubkey).map_err(|_| Error::InvalidPublicKey)?;
+        let signature = decode_signature(&self.signature)?;
+        let digest = message_digest(&Self::signed_message(self.tree_size, &self.root_hash, self.timestamp)?);
+
+        verify_digest(&verifying_key, &digest, &signature).map_err(|_| Error::InvalidTreeHead)
+    }
+}
+
+fn leaf_hash(content_hash: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(content_hash);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Size of the left subtree of a tree of `n > 1` leaves: the largest power of two below `n`
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// RFC 9162 `PATH(m, D[n])`
+fn audit_path(index: usize, leaves: &[[u8; 32]], path: &mut Vec<[u8; 32]>) {
+    if leaves.len() <= 1 {
+        return;
+    }
+    let (left, right) = leaves.split_at(split(leaves.len()));
+    if index < left.len() {
+        audit_path(index, left, path);
+        path.push(root(right));
+    } else {
+        audit_path(index - left.len(), right, path);
+        path.push(root(left));
+    }
+}
+
+/// RFC 9162 `SUBPROOF(m, D[n], b)`
+fn subproof(size: usize, leaves: &[[u8; 32]], complete: bool, path: &mut Vec<[u8; 32]>) {
+    if size == leaves.len() {
+        if !complete {
+            path.push(root(leaves));
+        }
+        return;
+    }
+    let (left, right) = leaves.split_at(split(leaves.len()));
+    if size <= left.len() {
+        subproof(size, left, complete, path);
+        path.push(root(right));
+    } else {
+        subproof(size - left.len(), right, false, path);
+        path.push(root(left));
+    }
+}
+
+fn decode_hash(encoded: &str) -> Result<[u8; 32], Error> {
+    let bytes = hex::decode(encoded).map_err(|_| Error::InvalidTransparencyProof("hash is not hex"))?;
+    bytes.try_into().map_err(|_| Error::InvalidTransparencyProof("hash is not 32 bytes"))
+}
+
+fn decode_hashes(encoded: &[String]) -> Result<Vec<[u8; 32]>, Error> {
+    encoded.iter().map(|hash| decode_hash(hash)).collect()
+}
@@ -0,0 +1,623 @@
+//! Deterministic conformance vectors for the signed-message layout.
+//!
+//! Every schema version gets a base vector plus one per optional signed field it
+//! supports, signed with a fixed test key (the same key as the frozen fixtures).
+//! From schema 22 that is repeated for every registered claim set (see
+//! [`crate::claims`]), keeping the fields the set carries, e.g. `v22-minimal-base`
+//! or `v22-standard-recipient`.
+//! Contract implementations check that they rebuild `preimage` from the
+//! attestation fields and that `signature` verifies over `digest`.
+//!
+//! ```
+//! use auditorzk_attestation::signature::verify_legacy_attestation;
+//! use auditorzk_attestation::vectors;
+//!
+//! let pubkey = vectors::test_signing_key().verifying_key().to_bytes();
+//! let vectors = vectors::generate();
+//! for vector in &vectors {
+//!     // Generated here, so each vector's own field list is the one to pin
+//!     let fields = vector.attestation.signed_field_list().unwrap();
+//!     verify_legacy_attestation(&vector.attestation, &pubkey, &fields).unwrap();
+//!     assert_eq!(hex::encode(vector.attestation.signed_message().unwrap()), vector.preimage);
+//! }
+//!
+//! // Every field has a vector under the claim set that carries them all
+//! assert!(vectors.iter().any(|vector| vector.name == "v22-extended-api_version"));
+//! assert!(vectors.iter().any(|vector| vector.name == "v24-full-prover_timestamp"));
+//! assert!(!vectors.iter().any(|vector| vector.name == "v24-extended-prover_timestamp"));
+//! assert!(!vectors.iter().any(|vector| vector.name == "v22-minimal-recipient"));
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use k256::schnorr::SigningKey;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::claims::CLAIM_SETS;
+use crate::commitments::TranscriptHash;
+use crate::message::message_digest;
+use crate::multisig::SignerSet;
+use crate::request::RequestTemplateMatch;
+use crate::signature::{encode_signature, sign_digest, SIGNATURE_VERSION};
+use crate::{inventory, Attestation, CURRENT_SCHEMA_VERSION};
+
+/// Seed hashed into the test signing key
+pub const TEST_KEY_SEED: &[u8] = b"auditorzk fixture key";
+
+/// Fixed key the vectors (and fixtures) are signed with. Never use it for real attestations.
+///
+/// The frozen fixtures come out of the same signing path as the verifier's, so
+/// re-signing each one reproduces its signature exactly:
+///
+/// ```
+/// use auditorzk_attestation::message::message_digest;
+/// use auditorzk_attestation::multisig::sign_partial;
+/// use auditorzk_attestation::signature::{encode_signature, sign_digest};
+/// use auditorzk_attestation::{vectors, Attestation};
+/// use k256::schnorr::SigningKey;
+/// use sha2::{Digest, Sha256};
+///
+/// let key = vectors::test_signing_key();
+/// for json in [
+///     include_str!("../fixtures/attestation_v1.json"),
+///     include_str!("../fixtures/attestation_v2.json"),
+///     include_str!("../fixtures/attestation_v3.json"),
+///     include_str!("../fixtures/attestation_v4.json"),
+///     include_str!("../fixtures/attestation_v5.json"),
+///     include_str!("../fixtures/attestation_v6.json"),
+///     include_str!("../fixtures/attestation_v7.json"),
+///     include_str!("../fixtures/attestation_v8.json"),
+///     include_str!("../fixtures/attestation_v9.json"),
+///     include_str!("../fixtures/attestation_v10.json"),
+///     include_str!("../fixtures/attestation_v11.json"),
+///     include_str!("../fixtures/attestation_v12.json"),
+///     include_str!("../fixtures/attestation_v13.json"),
+///     include_str!("../fixtures/attestation_v14.json"),
+///     include_str!("../fixtures/attestation_v15.json"),
+///     include_str!("../fixtures/attestation_v16.json"),
+///     include_str!("../fixtures/attestation_v17.json"),
+///     include_str!("../fixtures/attestation_v18.json"),
+///     include_str!("../fixtures/attestation_v19.json"),
+///     include_str!("../fixtures/attestation_v20.json"),
+///     include_str!("../fixtures/attestation_v21.json"),
+///     include_str!("../fixtures/attestation_v22.json"),
+///     include_str!("../fixtures/attestation_v23.json"),
+///     include_str!("../fixtures/attestation_v24.json"),
+/// ] {
+///     let attestation: Attestation = serde_json::from_str(json).unwrap();
+///     let digest = message_digest(&attestation.signed_message().unwrap());
+///     assert_eq!(encode_signature(&sign_digest(&key, &digest, &[0u8; 32])), attestation.signature);
+/// }
+///
+/// // Co-signed fixtures carry a contribution from the `Sha256("cosigner")` key
+/// let cosigner = SigningKey::from_bytes(&Sha256::digest(b"cosigner")).unwrap();
+/// let attestation: Attestation = serde_json::from_str(include_str!("../fixtures/attestation_v17.json")).unwrap();
+/// assert_eq!(sign_partial(&attestation, &cosigner).unwrap(), attestation.partial_signatures[0]);
+/// ```
+pub fn test_signing_key() -> SigningKey {
+    SigningKey::from_bytes(&Sha256::digest(TEST_KEY_SEED)).expect("hash of the seed is a valid scalar")
+}
+
+/// One signed attestation with its exact preimage and digest
+#[derive(Debug, Clone, Serialize)]
+pub struct TestVector {
+    pub name: String,
+    pub attestation: Attestation,
+    /// Signed message, hex
+    pub preimage: String,
+    /// SHA-256 of the preimage, hex
+    pub digest: String,
+}
+
+/// Flat, hex-only form of a [`TestVector`] for Solidity and TypeScript harnesses
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatVector {
+    pub name: String,
+    pub schema_version: u32,
+    pub signature_version: String,
+    pub public_key: String,
+    pub preimage: String,
+    pub digest: String,
+    /// 64-byte BIP-340 signature without the version prefix
+    pub signature: String,
+}
+
+impl TestVector {
+    pub fn to_flat(&self) -> FlatVector {
+        let versioned = &self.attestation.signature;
+        FlatVector {
+            name: self.name.clone(),
+            schema_version: self.attestation.schema_version,
+            signature_version: format!("0x{}", hex::encode(SIGNATURE_VERSION)),
+            public_key: format!("0x{}", hex::encode(&self.attestation.verifier_pubkey)),
+            preimage: format!("0x{}", self.preimage),
+            digest: format!("0x{}", self.digest),
+            signature: format!("0x{}", &versioned[2 * SIGNATURE_VERSION.len()..]),
+        }
+    }
+}
+
+/// Optional signed fields a schema version can carry
+struct SignedFeatures {
+    environment: bool,
+    inventory: bool,
+    formula: bool,
+    claim_type: bool,
+    account_filter: bool,
+    verifier_version: bool,
+    recipient: bool,
+    transcript_commitments: bool,
+    label: bool,
+    signer_set: bool,
+    request_template: bool,
+    api_version: bool,
+    prover_timestamp: bool,
+    item_count: bool,
+    over_revealed_bytes: bool,
+    inactive_accounts: bool,
+}
+
+/// Which optional signed fields each schema supports.
+///
+/// Panics for versions without an entry, so bumping [`CURRENT_SCHEMA_VERSION`]
+/// without extending the generator fails the module doctest.
+fn features(schema_version: u32) -> SignedFeatures {
+    match schema_version {
+        1 => SignedFeatures {
+            environment: false,
+            inventory: false,
+            formula: false,
+            claim_type: false,
+            account_filter: false,
+            verifier_version: false,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        2 | 3 => SignedFeatures {
+            environment: true,
+            inventory: false,
+            formula: false,
+            claim_type: false,
+            account_filter: false,
+            verifier_version: false,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        4 | 5 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: false,
+            claim_type: false,
+            account_filter: false,
+            verifier_version: false,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        6 | 7 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: false,
+            account_filter: false,
+            verifier_version: false,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        8 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: false,
+            verifier_version: false,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        9 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: false,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        10 | 11 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: false,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        12 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: false,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        13 | 14 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: true,
+            label: false,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        15 | 16 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: true,
+            label: true,
+            signer_set: false,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        17 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: true,
+            label: true,
+            signer_set: true,
+            request_template: false,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        18..=20 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: true,
+            label: true,
+            signer_set: true,
+            request_template: true,
+            api_version: false,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        21..=23 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: true,
+            label: true,
+            signer_set: true,
+            request_template: true,
+            api_version: true,
+            prover_timestamp: false,
+            item_count: false,
+            over_revealed_bytes: false,
+            inactive_accounts: false,
+        },
+        24 => SignedFeatures {
+            environment: true,
+            inventory: true,
+            formula: true,
+            claim_type: true,
+            account_filter: true,
+            verifier_version: true,
+            recipient: true,
+            transcript_commitments: true,
+            label: true,
+            signer_set: true,
+            request_template: true,
+            api_version: true,
+            prover_timestamp: true,
+            item_count: true,
+            over_revealed_bytes: true,
+            inactive_accounts: true,
+        },
+        version => panic!("no test vector generator for schema version {}", version),
+    }
+}
+
+/// Vectors for every schema version up to the current one
+pub fn generate() -> Vec<TestVector> {
+    let key = test_signing_key();
+    (1..=CURRENT_SCHEMA_VERSION)
+        .flat_map(|version| vectors_for(version, &key))
+        .collect()
+}
+
+fn vectors_for(schema_version: u32, key: &SigningKey) -> Vec<TestVector> {
+    let features = features(schema_version);
+    let base = Attestation {
+        schema_version,
+        server_name: "sandbox.plaid.com".to_string(),
+        timestamp: 1763308800,
+        balance_commitment: Sha256::digest(b"20912.75mock_blinder_for_testing").to_vec(),
+        signature: String::new(),
+        verifier_pubkey: key.verifying_key().to_bytes().to_vec(),
+        environment: None,
+        environment_signed: false,
+        prover_timestamp: None,
+        account_count: None,
+        accounts_commitment: None,
+        response_headers: BTreeMap::new(),
+        balance_formula: None,
+        item_count: None,
+        claim_type: None,
+        account_filter: None,
+        verifier_version: None,
+        over_revealed_bytes: None,
+        recipient: None,
+        transcript_commitments: Vec::new(),
+        digest: None,
+        label: None,
+        inactive_accounts: None,
+        signer_set_id: None,
+        partial_signatures: Vec::new(),
+        request_template: None,
+        disclosure: None,
+        balances_by_currency: BTreeMap::new(),
+        api_version: None,
+        claim_set: None,
+        schema_profile: None,
+    };
+
+    let mut cases = vec![("base", base.clone())];
+    if features.environment {
+        cases.push((
+            "environment",
+            Attestation { environment: Some("production".to_string()), environment_signed: true, ..base.clone() },
+        ));
+    }
+    if features.inventory {
+        cases.push((
+            "inventory",
+            Attestation {
+                account_count: Some(2),
+                accounts_commitment: Some(inventory::accounts_commitment(&["acc-1", "acc-2"]).to_vec()),
+                ..base.clone()
+            },
+        ));
+    }
+    if features.formula {
+        cases.push((
+            "formula",
+            Attestation { balance_formula: Some("available_minus_holds".to_string()), ..base.clone() },
+        ));
+    }
+
+    if features.claim_type {
+        cases.push((
+            "income",
+            Attestation {
+                balance_formula: Some("latest_pay_annualized".to_string()),
+                claim_type: Some("income".to_string()),
+                ..base.clone()
+            },
+        ));
+    }
+
+    if features.account_filter {
+        cases.push((
+            "account_filter",
+            Attestation { account_filter: Some("include=depository;exclude=".to_string()), ..base.clone() },
+        ));
+    }
+
+    if features.verifier_version {
+        cases.push((
+            "verifier_version",
+            Attestation { verifier_version: Some("auditor-zk-verifier/0.1.0".to_string()), ..base.clone() },
+        ));
+    }
+
+    if features.recipient {
+        cases.push((
+            "recipient",
+            Attestation {
+                recipient: Some(Sha256::digest(b"0x00000000000000000000000000000000000000aa").to_vec()),
+                ..base.clone()
+            },
+        ));
+    }
+
+    if features.transcript_commitments {
+        let received = |ranges: Vec<[u64; 2]>, opening: &[u8]| TranscriptHash {
+            direction: "received".to_string(),
+            ranges,
+            algorithm: "sha256".to_string(),
+            hash: Sha256::digest(opening).to_vec(),
+        };
+        cases.push((
+            "transcript_commitments",
+            Attestation {
+                transcript_commitments: vec![
+                    received(vec![[412, 431]], b"\"current\": 20000.00"),
+                    received(vec![[1187, 1203], [1240, 1251]], b"\"current\": 912.75"),
+                ],
+                ..base.clone()
+            },
+        ));
+    }
+
+    if features.label {
+        cases.push(("label", Attestation { label: Some("staging".to_string()), ..base.clone() }));
+    }
+
+    if features.signer_set {
+        let set = SignerSet::new(1, vec![key.verifying_key().to_bytes().to_vec()])
+            .expect("the test key is a valid signer set");
+        cases.push(("signer_set", Attestation { signer_set_id: Some(set.id().to_vec()), ..base.clone() }));
+    }
+
+    if features.request_template {
+        cases.push((
+            "request_template",
+            Attestation {
+                request_template: Some(RequestTemplateMatch { id: "plaid.balance.v1".to_string(), matched: true }),
+                ..base.clone()
+            },
+        ));
+    }
+
+    if features.api_version {
+        cases.push(("api_version", Attestation { api_version: Some("2020-09-14".to_string()), ..base.clone() }));
+    }
+
+    if features.prover_timestamp {
+        cases.push(("prover_timestamp", Attestation { prover_timestamp: Some(1763308795), ..base.clone() }));
+    }
+
+    if features.item_count {
+        cases.push(("item_count", Attestation { item_count: Some(2), ..base.clone() }));
+    }
+
+    if features.over_revealed_bytes {
+        cases.push(("over_revealed_bytes", Attestation { over_revealed_bytes: Some(4096), ..base.clone() }));
+    }
+
+    if features.inactive_accounts {
+        cases.push(("inactive_accounts", Attestation { inactive_accounts: Some(2), ..base.clone() }));
+    }
+
+    if schema_version < 22 {
+        return cases
+            .into_iter()
+            .map(|(case, attestation)| sign_vector(format!("v{}-{}", schema_version, case), attestation, key))
+            .collect();
+    }
+
+    // Every case the set can sign, under each registered claim set
+    CLAIM_SETS
+        .into_iter()
+        .flat_map(|set| {
+            cases.iter().filter_map(move |(case, attestation)| {
+                let attestation = Attestation { claim_set: Some(set.name.to_string()), ..attestation.clone() };
+                attestation.signed_message().ok()?;
+                Some(sign_vector(format!("v{}-{}-{}", schema_version, set.name, case), attestation, key))
+            })
+        })
+        .collect()
+}
+
+fn sign_vector(name: String, mut attestation: Attestation, key: &SigningKey) -> TestVector {
+    let preimage = attestation.signed_message().expect("generated fields are valid for their schema");
+    let digest = message_digest(&preimage);
+    attestation.signature = encode_signature(&sign_digest(key, &digest, &[0u8; 32]));
+    // Schema 14+ attestations store the digest too, as the verifier writes them
+    if attestation.schema_version >= 14 {
+        attestation.digest = Some(hex::encode(digest));
+    }
+
+    TestVector {
+        name,
+        attestation,
+        preimage: hex::encode(&preimage),
+        digest: hex::encode(digest),
+    }
+}
@@ -0,0 +1,108 @@
+//! Baselines for the per-session hot paths: streaming balance extraction over
+//! responses of growing account counts, SHA-256 over the signed message, and
+//! BIP-340 signing of its digest.
+//!
+//! Run with `cargo bench`; criterion keeps the previous run under
+//! `target/criterion` and reports regressions against it.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use k256::schnorr::{Signature, SigningKey};
+
+use auditor_zk_verifier::balance::{BalanceFormula, BalanceParser};
+use auditor_zk_verifier::plaid::PlaidParser;
+use auditorzk_attestation::claims::EXTENDED;
+use auditorzk_attestation::message::{
+    encode_claims, encode_message, message_digest, SignedFields, SignedInventory, CLAIM_BALANCE, ENV_PRODUCTION, FORMULA_CURRENT,
+};
+use auditorzk_attestation::signature::sign_digest;
+
+/// Account counts of the small, medium and large response fixtures
+const ACCOUNT_COUNTS: [(&str, usize); 3] = [("small", 2), ("medium", 50), ("large", 500)];
+
+/// A `/accounts/balance/get` response with `count` depository accounts
+fn balance_fixture(count: usize) -> Vec<u8> {
+    let accounts: Vec<String> = (0..count)
+        .map(|i| {
+            format!(
+                r#"{{"account_id":"acc{i:06}","balances":{{"available":{avail}.25,"current":{cur}.50,"holds":1.25,"iso_currency_code":"USD","limit":null}},"mask":"{mask:04}","name":"Checking {i}","official_name":"Plaid Gold Standard 0% Interest Checking","type":"depository","subtype":"checking"}}"#,
+                avail = 1000 + i,
+                cur = 1100 + i,
+                mask = i % 10_000,
+            )
+        })
+        .collect();
+    format!(r#"{{"accounts":[{}],"item":{{"item_id":"item0","institution_id":"ins_109508"}},"request_id":"bench"}}"#, accounts.join(","))
+        .into_bytes()
+}
+
+fn plaid_parser(formula: BalanceFormula) -> PlaidParser {
+    PlaidParser { formula, ..PlaidParser::default() }
+}
+
+fn balance_extraction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("balance_extraction");
+    for (name, count) in ACCOUNT_COUNTS {
+        let body = balance_fixture(count);
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        for formula in [BalanceFormula::Current, BalanceFormula::AvailableMinusHolds] {
+            let parser = plaid_parser(formula);
+            group.bench_with_input(BenchmarkId::new(formula.as_str(), name), &body, |b, body| {
+                b.iter(|| parser.extract(black_box(body)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Fields of a current-schema balance attestation
+fn signed_fields<'a>(commitment: &'a [u8], accounts_commitment: &'a [u8], recipient: &'a [u8]) -> SignedFields<'a> {
+    SignedFields {
+        server_name: "production.plaid.com",
+        timestamp: 1_763_308_795,
+        commitment,
+        environment: Some(ENV_PRODUCTION),
+        inventory: Some(SignedInventory { account_count: 2, accounts_commitment }),
+        formula: Some(FORMULA_CURRENT),
+        claim_type: Some(CLAIM_BALANCE),
+        account_filter: Some("include=depository;exclude="),
+        verifier_version: Some("0.1.0+bench"),
+        recipient: Some(recipient),
+        transcript_commitments: None,
+        label: None,
+        signer_set: None,
+        request_template: None,
+        api_version: None,
+        prover_timestamp: None,
+        item_count: None,
+        over_revealed_bytes: None,
+        inactive_accounts: None,
+    }
+}
+
+fn message_hashing(c: &mut Criterion) {
+    let (commitment, accounts_commitment, recipient) = ([0x11; 32], [0x22; 32], [0x33; 32]);
+    let fields = signed_fields(&commitment, &accounts_commitment, &recipient);
+    let message = encode_message(&fields).unwrap();
+
+    let mut group = c.benchmark_group("signed_message");
+    group.bench_function("encode", |b| b.iter(|| encode_message(black_box(&fields)).unwrap()));
+    group.bench_function("encode_claims", |b| b.iter(|| encode_claims(&EXTENDED, black_box(&fields)).unwrap()));
+    group.throughput(Throughput::Bytes(message.len() as u64));
+    group.bench_function("sha256", |b| b.iter(|| message_digest(black_box(&message))));
+    group.finish();
+}
+
+fn signing(c: &mut Criterion) {
+    let signing_key = SigningKey::from_bytes(&[0x42; 32]).unwrap();
+    let digest = message_digest(b"auditorzk benchmark message");
+
+    let mut group = c.benchmark_group("sign");
+    // BIP-340 Schnorr is the only signature scheme the verifier supports
+    group.bench_function("bip340", |b| {
+        b.iter(|| -> Signature { sign_digest(&signing_key, black_box(&digest), &[0u8; 32]) })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, balance_extraction, message_hashing, signing);
+criterion_main!(benches);
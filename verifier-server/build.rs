@@ -0,0 +1,24 @@
+use std::process::Command;
+
+/// Record the short git commit as `AUDITORZK_BUILD_COMMIT` so every build signs
+/// a distinct `verifier_version`, unless the builder already set it (e.g. in a
+/// Docker build without the `.git` directory)
+fn main() {
+    println!("cargo:rerun-if-env-changed=AUDITORZK_BUILD_COMMIT");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+
+    if std::env::var_os("AUDITORZK_BUILD_COMMIT").is_some() {
+        return;
+    }
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=7", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()) {
+        println!("cargo:rustc-env=AUDITORZK_BUILD_COMMIT={}", commit);
+    }
+}
@@ -0,0 +1,416 @@
+//! Operator endpoints, served on their own listener (`AUDITORZK_ADMIN_ADDR`).
+//!
+//! - `GET /health` reports `ok` or `draining` and the in-flight session count;
+//! - `POST /drain` (operator authentication, see [`OperatorAuth`]) starts the
+//!   same drain as SIGTERM: the WebSocket listener stops accepting, in-flight
+//!   sessions get the shutdown grace period, then the process exits;
+//! - `GET /metrics` serves the MPC traffic histograms of finished sessions in
+//!   the Prometheus text format (see [`crate::traffic`]), followed by today's
+//!   usage counters, the last sampled RSS and the shed-session count;
+//! - `GET /usage` lists the daily MPC usage and budget (see [`crate::usage`]);
+//! - `GET /capabilities` describes the build (see [`crate::capabilities`]).
+//!
+//! The admin listener keeps answering until exit, so orchestrators can watch a
+//! drain through `/health`. Requests are a single head with no body; every
+//! response but `/metrics` is JSON, and each closes the connection.
+
+use anyhow::{bail, Result};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::capabilities::Capabilities;
+use crate::config::ServerConfig;
+use crate::hmac::hmac_sha256;
+use crate::memory::SessionRegistry;
+use crate::shutdown::ShutdownReport;
+use crate::tasks::TaskRegistry;
+
+/// Largest request head accepted on the admin listener
+const MAX_HEAD_LEN: usize = 8 * 1024;
+
+/// How long an admin client has to send its request head
+pub(crate) const HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shared drain state: set once, by `POST /drain` or a shutdown signal
+pub struct Drain {
+    draining: watch::Sender<bool>,
+    report: ShutdownReport,
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Self { draining: watch::Sender::new(false), report: ShutdownReport::default() }
+    }
+}
+
+impl Drain {
+    /// Start draining; returns whether this call started it
+    pub fn trigger(&self) -> bool {
+        !self.draining.send_replace(true)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// What became of the connections the drain caught, see [`crate::shutdown`]
+    pub fn report(&self) -> &ShutdownReport {
+        &self.report
+    }
+
+    /// Resolves once draining has started
+    pub async fn triggered(&self) {
+        let mut draining = self.draining.subscribe();
+        let _ = draining.wait_for(|&draining| draining).await;
+    }
+
+    /// Accept the next connection, or `None` once draining has started.
+    ///
+    /// The caller closes the listener on `None` (see [`crate::shutdown::close_listener`]),
+    /// so later connections are refused.
+    pub async fn accept(&self, listener: &TcpListener) -> Option<io::Result<(TcpStream, SocketAddr)>> {
+        tokio::select! {
+            biased;
+            _ = self.triggered() => None,
+            accepted = listener.accept() => Some(accepted),
+        }
+    }
+}
+
+/// Authorization scheme of HMAC-signed operator requests
+pub const HMAC_SCHEME: &str = "AZK-HMAC-SHA256";
+
+/// Why an operator request was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("operator credentials required")]
+    Missing,
+    #[error("invalid operator token")]
+    BadToken,
+    #[error("malformed AZK-HMAC-SHA256 authorization")]
+    Malformed,
+    #[error("request timestamp outside the replay window")]
+    Stale,
+    #[error("invalid request signature")]
+    BadSignature,
+    #[error("request signature already used")]
+    Replayed,
+}
+
+/// Credentials `POST /drain` accepts.
+///
+/// With `AUDITORZK_OPERATOR_HMAC_SECRET` set, an operator signs each request
+/// instead of sending a reusable secret:
+///
+/// ```text
+/// Authorization: AZK-HMAC-SHA256 timestamp=<unix secs>,signature=<hex>
+/// ```
+///
+/// where the signature is HMAC-SHA256 with the shared secret over
+/// `<timestamp>\n<method>\n<path>`. Timestamps more than
+/// `AUDITORZK_OPERATOR_HMAC_WINDOW_SECS` away from the server's clock are
+/// refused, and so is a signature already used within that window. The static
+/// bearer token `AUDITORZK_OPERATOR_TOKEN` keeps working when it is also set.
+pub struct OperatorAuth {
+    token: Option<String>,
+    hmac_secret: Option<Vec<u8>>,
+    window: Duration,
+    /// Signatures accepted within the window, with their timestamps
+    seen: Mutex<HashMap<[u8; 32], u64>>,
+}
+
+impl OperatorAuth {
+    pub fn new(token: Option<String>, hmac_secret: Option<Vec<u8>>, window: Duration) -> Self {
+        Self { token, hmac_secret, window, seen: Mutex::new(HashMap::new()) }
+    }
+
+    /// The configured credentials, if any
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        if config.operator_token.is_none() && config.operator_hmac_secret.is_none() {
+            return None;
+        }
+        Some(Self::new(
+            config.operator_token.clone(),
+            config.operator_hmac_secret.as_ref().map(|secret| secret.as_bytes().to_vec()),
+            Duration::from_secs(config.operator_hmac_window_secs),
+        ))
+    }
+
+    /// Check a request's `Authorization` value at `now`
+    pub fn check(
+        &self,
+        method: &str,
+        path: &str,
+        authorization: Option<&str>,
+        now: SystemTime,
+    ) -> std::result::Result<(), AuthError> {
+        let authorization = authorization.ok_or(AuthError::Missing)?;
+        if let Some(presented) = authorization.strip_prefix("Bearer ") {
+            let token = self.token.as_deref().ok_or(AuthError::BadToken)?;
+            return token_matches(presented, token).then_some(()).ok_or(AuthError::BadToken);
+        }
+
+        let (secret, params) = match (&self.hmac_secret, authorization.strip_prefix(HMAC_SCHEME)) {
+            (Some(secret), Some(params)) => (secret, params),
+            _ => return Err(AuthError::Missing),
+        };
+        let (timestamp, signature) = parse_hmac_params(params).ok_or(AuthError::Malformed)?;
+
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.abs_diff(timestamp) > self.window.as_secs() {
+            return Err(AuthError::Stale);
+        }
+        let expected = hmac_sha256(secret, signed_text(timestamp, method, path).as_bytes());
+        // Digests of both, so the comparison time doesn't depend on where they differ
+        if Sha256::digest(signature) != Sha256::digest(expected) {
+            return Err(AuthError::BadSignature);
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window = self.window.as_secs();
+        seen.retain(|_, &mut used| now.abs_diff(used) <= window);
+        if seen.insert(expected, timestamp).is_some() {
+            return Err(AuthError::Replayed);
+        }
+        Ok(())
+    }
+}
+
+/// `Authorization` value for an HMAC-signed operator request sent at `timestamp`
+pub fn sign_request(secret: &[u8], method: &str, path: &str, timestamp: u64) -> String {
+    let signature = hmac_sha256(secret, signed_text(timestamp, method, path).as_bytes());
+    format!("{} timestamp={},signature={}", HMAC_SCHEME, timestamp, hex::encode(signature))
+}
+
+/// What an operator request's HMAC covers
+fn signed_text(timestamp: u64, method: &str, path: &str) -> String {
+    format!("{}\n{}\n{}", timestamp, method, path)
+}
+
+/// `timestamp=<secs>,signature=<hex>`, in either order
+fn parse_hmac_params(params: &str) -> Option<(u64, Vec<u8>)> {
+    let (mut timestamp, mut signature) = (None, None);
+    for param in params.trim().split(',') {
+        match param.trim().split_once('=')? {
+            ("timestamp", value) => timestamp = Some(value.parse().ok()?),
+            ("signature", value) => signature = Some(hex::decode(value).ok()?),
+            _ => return None,
+        }
+    }
+    Some((timestamp?, signature?))
+}
+
+/// Status code and JSON body for one request head
+pub fn respond(head: &[u8], auth: &OperatorAuth, drain: &Drain, in_flight: usize) -> (u16, serde_json::Value) {
+    let Some((method, path, authorization)) = parse_head(head) else {
+        return (400, json!({ "error": "malformed request" }));
+    };
+
+    match (method, path) {
+        ("GET", "/health") => {
+            let status = if drain.is_draining() { "draining" } else { "ok" };
+            (200, json!({ "status": status, "in_flight": in_flight }))
+        }
+        ("POST", "/drain") => {
+            if let Err(e) = auth.check(method, path, authorization, SystemTime::now()) {
+                warn!("⚠️  Operator request to {} refused: {}", path, e);
+                return (401, json!({ "error": e.to_string() }));
+            }
+            if drain.trigger() {
+                info!("🛑 Drain requested by operator, no longer accepting connections ({} in flight)", in_flight);
+            }
+            (202, json!({ "draining": true, "in_flight": in_flight }))
+        }
+        (_, "/health" | "/drain" | "/metrics" | "/usage" | "/capabilities") => (405, json!({ "error": "method not allowed" })),
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+/// Method, path and `Authorization` value of a request head
+pub(crate) fn parse_head(head: &[u8]) -> Option<(&str, &str, Option<&str>)> {
+    let head = std::str::from_utf8(head).ok()?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let (method, path) = (request_line.next()?, request_line.next()?);
+
+    let authorization = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim());
+    Some((method, path, authorization))
+}
+
+/// Compare digests so the comparison time doesn't depend on where the tokens differ
+fn token_matches(presented: &str, token: &str) -> bool {
+    Sha256::digest(presented.as_bytes()) == Sha256::digest(token.as_bytes())
+}
+
+/// Serve the operator endpoints until the process exits
+pub async fn serve(
+    listener: TcpListener,
+    auth: OperatorAuth,
+    drain: Arc<Drain>,
+    sessions: Arc<SessionRegistry>,
+    tasks: Arc<TaskRegistry>,
+) {
+    let auth = Arc::new(auth);
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("⚠️  Failed to accept admin connection: {}", e);
+                continue;
+            }
+        };
+
+        let (auth, drain, sessions) = (Arc::clone(&auth), Arc::clone(&drain), Arc::clone(&sessions));
+        tasks.spawn("admin", None, Some(HEAD_TIMEOUT * 2), async move {
+            if let Err(e) = handle(stream, &auth, &drain, &sessions).await {
+                warn!("⚠️  Admin request from {} failed: {}", peer_addr, e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, auth: &OperatorAuth, drain: &Drain, sessions: &SessionRegistry) -> Result<()> {
+    let head = tokio::time::timeout(HEAD_TIMEOUT, read_head(&mut stream)).await??;
+    if let Some(("GET", "/metrics", _)) = parse_head(&head) {
+        let metrics =
+            sessions.traffic().render() + &sessions.usage().render(SystemTime::now()) + &sessions.render_memory();
+        return write_response(&mut stream, 200, "text/plain; version=0.0.4", &metrics).await;
+    }
+    if let Some(("GET", "/capabilities", _)) = parse_head(&head) {
+        return write_json(&mut stream, 200, &json!(Capabilities::current())).await;
+    }
+    if let Some(("GET", "/usage", _)) = parse_head(&head) {
+        return write_json(&mut stream, 200, &sessions.usage().to_json(SystemTime::now())).await;
+    }
+    let (status, body) = respond(&head, auth, drain, sessions.in_flight());
+    write_json(&mut stream, status, &body).await
+}
+
+/// Write a JSON response and close the connection
+pub(crate) async fn write_json(stream: &mut TcpStream, status: u16, body: &serde_json::Value) -> Result<()> {
+    write_response(stream, status, "application/json", &body.to_string()).await
+}
+
+async fn write_response(stream: &mut TcpStream, status: u16, content_type: &str, body: &str) -> Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Method Not Allowed",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Read up to the blank line ending the request head
+pub(crate) async fn read_head(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_LEN {
+            bail!("Request head exceeds {} bytes", MAX_HEAD_LEN);
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed before the end of the request head");
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_stops_accepting_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let drain = Arc::new(Drain::default());
+
+        let (accepted_tx, mut accepted) = tokio::sync::mpsc::unbounded_channel();
+        let accept_loop = tokio::spawn({
+            let drain = Arc::clone(&drain);
+            async move {
+                while let Some(Ok((stream, _))) = drain.accept(&listener).await {
+                    accepted_tx.send(stream).unwrap();
+                }
+            }
+        });
+        let _client = TcpStream::connect(addr).await.unwrap();
+        let _session = accepted.recv().await.unwrap();
+
+        // Without the operator token nothing happens
+        let auth = OperatorAuth::new(Some("s3cret".to_string()), None, Duration::from_secs(60));
+        let (status, _) = respond(b"POST /drain HTTP/1.1\r\n\r\n", &auth, &drain, 1);
+        assert_eq!(status, 401);
+        assert!(!drain.is_draining());
+
+        let (status, body) = respond(b"POST /drain HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n", &auth, &drain, 1);
+        assert_eq!(status, 202);
+        assert_eq!(body["in_flight"], 1);
+        assert_eq!(respond(b"GET /health HTTP/1.1\r\n\r\n", &auth, &drain, 1).1["status"], "draining");
+
+        // The accept loop stops and drops the listener; new connections are refused
+        accept_loop.await.unwrap();
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[test]
+    fn signed_requests_are_fresh_and_single_use() {
+        let auth = OperatorAuth::new(None, Some(b"shared secret".to_vec()), Duration::from_secs(30));
+        let now = UNIX_EPOCH + Duration::from_secs(1_763_308_800);
+        let signed = sign_request(b"shared secret", "POST", "/drain", 1_763_308_790);
+        assert_eq!(
+            signed,
+            "AZK-HMAC-SHA256 timestamp=1763308790,\
+             signature=e16b7e799b47b7b51d74e25498ca61ab47205927c84691dcc09d62e429f8b196"
+        );
+
+        // Valid once; the same signature can't be replayed within the window
+        assert_eq!(auth.check("POST", "/drain", Some(&signed), now), Ok(()));
+        assert_eq!(auth.check("POST", "/drain", Some(&signed), now), Err(AuthError::Replayed));
+
+        // Expired, or from a client clock too far ahead
+        let old = sign_request(b"shared secret", "POST", "/drain", 1_763_308_700);
+        assert_eq!(auth.check("POST", "/drain", Some(&old), now), Err(AuthError::Stale));
+        let ahead = sign_request(b"shared secret", "POST", "/drain", 1_763_308_900);
+        assert_eq!(auth.check("POST", "/drain", Some(&ahead), now), Err(AuthError::Stale));
+
+        // Tampered: another path, a moved timestamp, or the wrong secret
+        let fresh = sign_request(b"shared secret", "POST", "/drain", 1_763_308_795);
+        assert_eq!(auth.check("POST", "/usage", Some(&fresh), now), Err(AuthError::BadSignature));
+        let moved = fresh.replace("1763308795", "1763308799");
+        assert_eq!(auth.check("POST", "/drain", Some(&moved), now), Err(AuthError::BadSignature));
+        let forged = sign_request(b"guessed", "POST", "/drain", 1_763_308_795);
+        assert_eq!(auth.check("POST", "/drain", Some(&forged), now), Err(AuthError::BadSignature));
+
+        // Without a configured token, bearer credentials are refused
+        assert_eq!(auth.check("POST", "/drain", Some("Bearer shared secret"), now), Err(AuthError::BadToken));
+        assert_eq!(auth.check("POST", "/drain", None, now), Err(AuthError::Missing));
+    }
+}
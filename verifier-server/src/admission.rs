@@ -0,0 +1,335 @@
+//! Admission control: whether a new session may start, and when to retry if not.
+//!
+//! A session is refused while resident memory is above the soft limit, while the
+//! day's usage budget is exhausted and enforced (see [`crate::usage`]), while
+//! `AUDITORZK_MAX_SESSIONS` sessions are in flight, while the sessions in flight
+//! have reserved all of `AUDITORZK_MEMORY_BUDGET_MB`, or once its client address
+//! has started `AUDITORZK_RATE_LIMIT_PER_MINUTE` sessions in the current minute. The
+//! refusal happens after the WebSocket upgrade, so the prover gets a `retry_later`
+//! frame with a suggested `retry_after` and a `1013` close instead of a dropped
+//! connection.
+
+use futures::SinkExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::config::ServerConfig;
+use crate::control::{VerifierClose, VerifierMessage};
+use crate::memory::MemoryWatchdog;
+use crate::usage::UsageLedger;
+use crate::verifier::SESSION_BUFFER_BYTES;
+
+/// Length of a rate-limit window
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Why a session was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RefusalReason {
+    /// Resident memory is above the soft limit
+    MemoryPressure,
+    /// The maximum number of sessions is in flight
+    AtCapacity,
+    /// The client address started too many sessions this minute
+    RateLimited,
+    /// Sessions in flight have reserved the whole memory budget
+    MemoryBudget,
+    /// Today's usage budget is used up
+    QuotaExhausted,
+}
+
+impl RefusalReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MemoryPressure => "memory_pressure",
+            Self::AtCapacity => "at_capacity",
+            Self::RateLimited => "rate_limited",
+            Self::MemoryBudget => "memory_budget",
+            Self::QuotaExhausted => "quota_exhausted",
+        }
+    }
+}
+
+impl fmt::Display for RefusalReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A refused session, with how long the prover should wait before reconnecting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Refusal {
+    pub reason: RefusalReason,
+    pub retry_after: Duration,
+}
+
+impl Refusal {
+    /// Whole seconds to wait, rounded up so a prover never retries early
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs() + u64::from(self.retry_after.subsec_nanos() > 0)
+    }
+
+    /// Control frame sent before the close frame
+    pub fn message(&self) -> VerifierMessage {
+        VerifierMessage::RetryLater { reason: self.reason, retry_after: self.retry_after_secs() }
+    }
+
+    /// Send the `retry_later` frame and close with `1013`.
+    pub async fn send<S>(&self, ws_stream: &mut WebSocketStream<S>)
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let _ = ws_stream.send(Message::Text(self.message().to_json())).await;
+        let reason = format!("{}: retry after {}s", self.reason, self.retry_after_secs());
+        let _ = ws_stream.send(Message::Close(Some(VerifierClose::TryAgainLater.frame(&reason)))).await;
+    }
+}
+
+/// Sessions started per client address, counted in fixed one-minute windows
+pub struct RateLimiter {
+    per_minute: u32,
+    windows: Mutex<HashMap<IpAddr, Window>>,
+}
+
+struct Window {
+    started: Instant,
+    sessions: u32,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self { per_minute, windows: Mutex::new(HashMap::new()) }
+    }
+
+    /// Count a session from `ip` at `now`, or return how long until its window
+    /// resets if the address is over the limit. Refused attempts aren't counted.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, window| now.saturating_duration_since(window.started) < RATE_WINDOW);
+
+        let window = windows.entry(ip).or_insert(Window { started: now, sessions: 0 });
+        if window.sessions >= self.per_minute {
+            return Err(RATE_WINDOW.saturating_sub(now.saturating_duration_since(window.started)));
+        }
+        window.sessions += 1;
+        Ok(())
+    }
+}
+
+/// Everything that decides whether a new session may start
+pub struct Admission {
+    watchdog: Option<Arc<MemoryWatchdog>>,
+    sessions: Option<Arc<Semaphore>>,
+    rate_limiter: Option<RateLimiter>,
+    /// Bytes of buffers sessions may reserve between them, one permit per byte
+    memory_budget: Option<Arc<Semaphore>>,
+    /// Daily usage, refusing sessions once an enforced budget is used up
+    usage: Option<Arc<UsageLedger>>,
+    /// Suggested wait when the refusal has no natural end time
+    retry_after: Duration,
+}
+
+impl Admission {
+    pub fn new(
+        watchdog: Option<Arc<MemoryWatchdog>>,
+        max_sessions: Option<usize>,
+        rate_limiter: Option<RateLimiter>,
+        retry_after: Duration,
+    ) -> Self {
+        Self {
+            watchdog,
+            sessions: max_sessions.map(|max| Arc::new(Semaphore::new(max))),
+            rate_limiter,
+            memory_budget: None,
+            usage: None,
+            retry_after,
+        }
+    }
+
+    /// Also refuse sessions while `usage`'s budget is exhausted and enforced.
+    ///
+    /// The prover is told to retry at the next UTC midnight.
+    pub fn with_usage(mut self, usage: Arc<UsageLedger>) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Also refuse sessions once those in flight have reserved `bytes` of buffers
+    /// between them, [`SESSION_BUFFER_BYTES`] each.
+    ///
+    /// Unlike the RSS watchdog this holds before memory is allocated, so a burst
+    /// of sessions can't overshoot it.
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(Arc::new(Semaphore::new(bytes.min(Semaphore::MAX_PERMITS))));
+        self
+    }
+
+    pub fn from_config(config: &ServerConfig, watchdog: Option<Arc<MemoryWatchdog>>) -> Self {
+        let admission = Self::new(
+            watchdog,
+            Some(config.max_sessions).filter(|&max| max > 0),
+            Some(config.rate_limit_per_minute).filter(|&limit| limit > 0).map(RateLimiter::new),
+            Duration::from_secs(config.retry_after_secs),
+        );
+        match config.memory_budget_mb {
+            Some(mb) => admission.with_memory_budget(usize::try_from(mb << 20).unwrap_or(usize::MAX)),
+            None => admission,
+        }
+    }
+
+    /// Bytes of the memory budget no session has reserved, if there is a budget
+    pub fn memory_budget_available(&self) -> Option<usize> {
+        self.memory_budget.as_ref().map(|budget| budget.available_permits())
+    }
+
+    /// Admit a session from `ip`, holding a session slot (if sessions are capped)
+    /// and its memory reservation (if memory is budgeted) until the returned
+    /// permit is dropped.
+    ///
+    /// A rate-limited client is told to retry when its window resets.
+    pub fn admit(&self, ip: IpAddr, now: Instant) -> Result<SessionPermit, Refusal> {
+        if self.watchdog.as_ref().is_some_and(|watchdog| !watchdog.admitting()) {
+            return Err(Refusal { reason: RefusalReason::MemoryPressure, retry_after: self.retry_after });
+        }
+
+        if let Some(usage) = &self.usage {
+            usage
+                .check(SystemTime::now())
+                .map_err(|retry_after| Refusal { reason: RefusalReason::QuotaExhausted, retry_after })?;
+        }
+
+        let slot = match &self.sessions {
+            Some(sessions) => Some(
+                Arc::clone(sessions)
+                    .try_acquire_owned()
+                    .map_err(|_| Refusal { reason: RefusalReason::AtCapacity, retry_after: self.retry_after })?,
+            ),
+            None => None,
+        };
+
+        let memory = match &self.memory_budget {
+            Some(budget) => Some(
+                Arc::clone(budget)
+                    .try_acquire_many_owned(SESSION_BUFFER_BYTES as u32)
+                    .map_err(|_| Refusal { reason: RefusalReason::MemoryBudget, retry_after: self.retry_after })?,
+            ),
+            None => None,
+        };
+
+        // Checked last, so a refusal for capacity doesn't use up the client's quota
+        if let Some(limiter) = &self.rate_limiter {
+            limiter
+                .check(ip, now)
+                .map_err(|retry_after| Refusal { reason: RefusalReason::RateLimited, retry_after })?;
+        }
+
+        Ok(SessionPermit { _slot: slot, _memory: memory })
+    }
+}
+
+/// A session's slot and memory reservation, released when dropped
+#[must_use]
+pub struct SessionPermit {
+    _slot: Option<OwnedSemaphorePermit>,
+    _memory: Option<OwnedSemaphorePermit>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use tokio::net::TcpListener;
+
+    use crate::usage::{BudgetEnforcement, UsageBudget};
+
+    #[tokio::test]
+    async fn refusal_is_a_retry_later_frame_then_a_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let refusal = Refusal { reason: RefusalReason::RateLimited, retry_after: Duration::from_secs(45) };
+            refusal.send(&mut ws_stream).await;
+        });
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap();
+        let Some(Ok(Message::Text(text))) = client.next().await else { panic!("expected a retry_later frame") };
+        let frame: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(frame["type"], "retry_later");
+        assert_eq!(frame["reason"], "rate_limited");
+        assert_eq!(frame["retry_after"], 45);
+
+        let Some(Ok(Message::Close(Some(close)))) = client.next().await else { panic!("expected a close frame") };
+        assert_eq!(u16::from(close.code), 1013);
+        assert_eq!(close.reason, "rate_limited: retry after 45s");
+    }
+
+    #[test]
+    fn exhausted_usage_budget_refuses_sessions() {
+        let budget = UsageBudget { mpc_seconds: None, bytes_forwarded: Some(1 << 20), enforcement: BudgetEnforcement::Reject };
+        let usage = Arc::new(UsageLedger::new(Some(budget)));
+        let admission = Admission::new(None, None, None, Duration::from_secs(30)).with_usage(Arc::clone(&usage));
+        let ip = "203.0.113.7".parse().unwrap();
+        assert!(admission.admit(ip, Instant::now()).is_ok());
+
+        usage.record(SystemTime::now(), Duration::from_secs(40), 2 << 20);
+        let Err(refusal) = admission.admit(ip, Instant::now()) else { panic!("expected a refusal") };
+        assert_eq!(refusal.reason, RefusalReason::QuotaExhausted);
+        assert!(refusal.retry_after_secs() <= 86_400);
+    }
+
+    #[test]
+    fn memory_budget_reserves_each_session_buffers() {
+        let admission = Admission::new(None, None, None, Duration::from_secs(30))
+            .with_memory_budget(3 * SESSION_BUFFER_BYTES + SESSION_BUFFER_BYTES / 2);
+        let ip = "203.0.113.7".parse().unwrap();
+
+        let held: Vec<_> = (0..3).map(|_| admission.admit(ip, Instant::now()).unwrap()).collect();
+        assert_eq!(admission.memory_budget_available(), Some(SESSION_BUFFER_BYTES / 2));
+
+        // Half a session's buffers are left, so the fourth waits
+        let Err(refusal) = admission.admit(ip, Instant::now()) else { panic!("expected a refusal") };
+        assert_eq!(refusal.reason, RefusalReason::MemoryBudget);
+
+        // A finished session gives its reservation back
+        drop(held);
+        assert!(admission.admit(ip, Instant::now()).is_ok());
+    }
+
+    #[test]
+    fn rate_limit_and_session_slots() {
+        let admission = Admission::new(None, Some(1), Some(RateLimiter::new(2)), Duration::from_secs(30));
+        let ip = "203.0.113.7".parse().unwrap();
+        let start = Instant::now();
+
+        drop(admission.admit(ip, start).unwrap());
+        drop(admission.admit(ip, start + Duration::from_secs(5)).unwrap());
+
+        let Err(refusal) = admission.admit(ip, start + Duration::from_millis(15_500)) else { panic!("expected a refusal") };
+        assert_eq!(refusal.reason, RefusalReason::RateLimited);
+        assert_eq!(refusal.retry_after_secs(), 45);
+        assert_eq!(
+            refusal.message().to_json(),
+            r#"{"type":"retry_later","reason":"rate_limited","retry_after":45}"#,
+        );
+
+        // Other addresses aren't rate limited, but there is only one session slot
+        let held = admission.admit("198.51.100.1".parse().unwrap(), start).unwrap();
+        let Err(refusal) = admission.admit("198.51.100.2".parse().unwrap(), start) else { panic!("expected a refusal") };
+        assert_eq!(refusal.reason, RefusalReason::AtCapacity);
+        assert_eq!(refusal.retry_after_secs(), 30);
+        drop(held);
+
+        // A new window starts a minute after the first session
+        assert!(admission.admit(ip, start + Duration::from_secs(60)).is_ok());
+    }
+}
@@ -0,0 +1,144 @@
+//! Offline re-validation of a session's verifier output.
+//!
+//! With `AUDITORZK_DUMP_OUTPUT` set, every session that completes MPC writes
+//! what it learned, [`SessionFacts`] (server name, partial transcript and
+//! commitments), to `<capture dir>/<session id>.output.json`, before
+//! validation so failed sessions can be examined too. `analyze --input
+//! <dump>` reads it back and re-runs connection validation and balance
+//! extraction against the current configuration, without MPC. Unlike `replay`,
+//! which feeds a capture through the protocol again, this starts from the
+//! output the protocol already produced.
+//!
+//! Dumps hold the revealed transcript, so they are only written when transcript
+//! contents may be logged (`AUDITORZK_LOG_SENSITIVE`, `test-endpoints` builds).
+//!
+//! A dump rehydrates to the same facts and the same analysis.
+
+use anyhow::{bail, Context};
+use auditorzk_attestation::profile::ProfileSelection;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::attestation::extract_balance_commitment;
+use crate::balance::BalanceFormula;
+use crate::capture::create_private;
+use crate::config::ServerConfig;
+use crate::error::Result;
+use crate::http::HeadLimits;
+use crate::parsed::ParsedSession;
+use crate::plaid::validate_plaid_connection;
+use crate::session::SessionFacts;
+
+/// Version of the dump layout, bumped when [`SessionFacts`] changes shape
+pub const DUMP_VERSION: u32 = 1;
+
+/// One session's verifier output, as written to `<session id>.output.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputDump {
+    pub version: u32,
+    pub session_id: String,
+    pub facts: SessionFacts,
+}
+
+/// What re-running the checks on a dump produced
+#[derive(Debug, Clone, Serialize)]
+pub struct Analysis {
+    pub session_id: String,
+    /// The server name as validated, see [`SessionFacts::normalized_server_name`]
+    pub server_name: Option<String>,
+    pub formula: &'static str,
+    /// Canonical decimal balance, e.g. `-12.05`
+    pub balance: String,
+    pub currency: String,
+    pub account_count: usize,
+    /// Schema profile the response was read with, see [`crate::profiles`]
+    pub schema_profile: Option<ProfileSelection>,
+    /// Balance commitment (hex) the attestation would sign
+    pub balance_commitment: String,
+}
+
+/// Write `facts` to `<dir>/<session id>.output.json`, readable by the verifier's user only
+pub fn write_dump(dir: &Path, session_id: &str, facts: &SessionFacts) -> anyhow::Result<PathBuf> {
+    let dump = OutputDump { version: DUMP_VERSION, session_id: session_id.to_string(), facts: facts.clone() };
+    let path = dir.join(format!("{}.output.json", session_id));
+    let mut file = create_private(&path)?;
+    file.write_all(&serde_json::to_vec(&dump)?)
+        .with_context(|| format!("Failed to write output dump {}", path.display()))?;
+    Ok(path)
+}
+
+/// Read a dump written by [`write_dump`]
+pub fn read_dump(path: &Path) -> anyhow::Result<OutputDump> {
+    let json = fs::read(path).with_context(|| format!("Failed to read output dump {}", path.display()))?;
+    let dump: OutputDump =
+        serde_json::from_slice(&json).with_context(|| format!("Malformed output dump {}", path.display()))?;
+    if dump.version != DUMP_VERSION {
+        bail!("Output dump {} has version {} (expected {})", path.display(), dump.version, DUMP_VERSION);
+    }
+    Ok(dump)
+}
+
+/// Re-run connection validation and balance extraction on a dumped session.
+///
+/// `blinder` is the session's: the prover's, or the one the verifier returned
+/// in the `blinder` frame.
+pub fn analyze(
+    dump: &OutputDump,
+    config: &ServerConfig,
+    formula: BalanceFormula,
+    blinder: &[u8],
+) -> Result<Analysis> {
+    let facts = &dump.facts;
+    let parsed = ParsedSession::new(facts, &HeadLimits::from_config(config));
+    validate_plaid_connection(facts, &parsed, None, config)?;
+
+    let (commitment, _, balance) = extract_balance_commitment(&parsed, formula, blinder, config)?;
+    Ok(Analysis {
+        session_id: dump.session_id.clone(),
+        server_name: facts.normalized_server_name(),
+        formula: formula.as_str(),
+        balance: balance.canonical(),
+        currency: balance.currency,
+        account_count: balance.account_count,
+        schema_profile: balance.schema_profile,
+        balance_commitment: hex::encode(commitment),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tlsn_core::transcript::Direction;
+
+    use crate::fixture::SessionFixture;
+
+    #[test]
+    fn dump_analyzes_like_the_live_session() {
+        let config = ServerConfig::from_env().unwrap();
+        let body = include_bytes!("../fixtures/balance_single_account.json");
+        let facts = SessionFixture::new("sandbox.plaid.com")
+            .sent(&b"POST /accounts/balance/get HTTP/1.1\r\nhost: sandbox.plaid.com\r\ncontent-length: 0\r\n\r\n"[..])
+            .json_response(body)
+            .hash_commitment(Direction::Received, vec![0..16], b"blinder")
+            .build();
+
+        let dir = std::env::temp_dir().join(format!("auditorzk-analyze-{}", std::process::id()));
+        let path = write_dump(&dir, "0123abcd", &facts).unwrap();
+        let dump = read_dump(&path).unwrap();
+        assert_eq!(dump.session_id, "0123abcd");
+        assert_eq!(dump.facts, facts);
+
+        let blinder = b"0123456789abcdef";
+        let analysis = analyze(&dump, &config, BalanceFormula::Current, blinder).unwrap();
+        assert_eq!(analysis.server_name.as_deref(), Some("sandbox.plaid.com"));
+        assert_eq!(analysis.account_count, 1);
+        assert_eq!(analysis.schema_profile.unwrap().id, "plaid.balance.2020-09-14");
+
+        let live = OutputDump { version: DUMP_VERSION, session_id: "0123abcd".to_string(), facts };
+        let live = analyze(&live, &config, BalanceFormula::Current, blinder).unwrap();
+        assert_eq!(analysis.balance_commitment, live.balance_commitment);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
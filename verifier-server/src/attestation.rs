@@ -1,182 +1,422 @@
-use anyhow::{Result, Context};
+use auditorzk_attestation::{
+    claims::FULL,
+    currency,
+    eas::AttestationRequestData,
+    inventory,
+    message::{self, message_digest},
+    profile::ProfileSelection,
+    signature::{encode_signature, sign_digest},
+    CURRENT_SCHEMA_VERSION,
+};
 use k256::{
-    schnorr::{SigningKey, Signature, VerifyingKey, signature::Signer},
-    elliptic_curve::rand_core::OsRng,
+    schnorr::SigningKey,
+    elliptic_curve::rand_core::{OsRng, RngCore},
 };
-use k256::sha2::{Digest, Sha256};
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
-use tracing::{info, warn};
-use tlsn_core::VerifierOutput;
+use std::time::Instant;
+use tracing::{debug, info, trace, warn};
+
+use crate::balance::{parser_for, BalanceFormula, BalanceResult, BodyLimits};
+use crate::bundle::write_proof_bundle;
+use crate::config::ServerConfig;
+use crate::control::{AttestationFormat, Hello, SigningPolicy};
+use crate::delivery;
+use crate::disclosure::{check_disclosure, DisclosureReport};
+use crate::error::{Check, Error, Result};
+use crate::events;
+use crate::headers::{check_api_version, check_required_headers};
+use crate::income::{check_endpoint, extract_income, PAYROLL_INCOME_PATH};
+use crate::parsed::ParsedSession;
+use crate::plaid::{check_min_accounts, check_single_currency, transcript_hashes};
+use crate::profiles::ResponseContext;
+use crate::session::SessionFacts;
+use crate::templates::match_request_template;
+use crate::transparency::TransparencyLog;
+
+pub use auditorzk_attestation::Attestation;
 
 const KEY_PATH: &str = "config/notary_key.pem";
 const PUBKEY_PATH: &str = "config/notary_pubkey.pem";
-const SIGNATURE_VERSION: [u8; 3] = [0x01, 0x00, 0x00]; // BIP-340 signature version 1.0.0
-
-/// Attestation structure that will be signed
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Attestation {
-    /// The server that was connected to
-    pub server_name: String,
-    /// Timestamp of the session
-    pub timestamp: u64,
-    /// Commitment to the balance data (first hash commitment)
-    pub balance_commitment: Vec<u8>,
-    /// BIP-340 signature (hex-encoded with 3-byte version prefix)
-    pub signature: String,
-    /// The verifier's public key (for signature verification)
-    pub verifier_pubkey: Vec<u8>,
+
+/// Build recorded (and signed) in every attestation, e.g. `auditor-zk-verifier/0.1.0+1a2b3c4`.
+/// The commit is appended when `AUDITORZK_BUILD_COMMIT` is set at compile time,
+/// which `build.rs` does from git when it can.
+pub fn verifier_version() -> String {
+    let version = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    match option_env!("AUDITORZK_BUILD_COMMIT") {
+        Some(commit) => format!("{}+{}", version, commit),
+        None => version,
+    }
+}
+
+/// Fingerprint consumers see for this build, the signed hash of [`verifier_version`]
+pub fn build_fingerprint() -> String {
+    hex::encode(message::verifier_version_hash(&verifier_version()))
 }
 
 /// Sign the verification output as an attestation
-pub async fn sign_attestation(mut output: VerifierOutput) -> Result<Vec<u8>> {
-    info!("🔏 Creating and signing attestation...");
+pub async fn sign_attestation(
+    facts: &SessionFacts,
+    parsed: &ParsedSession<'_>,
+    hello: &Hello,
+    disclosure: &DisclosureReport,
+    session_id: &str,
+    config: &ServerConfig,
+    transparency: Option<&TransparencyLog>,
+) -> Result<Vec<u8>> {
+    debug!("🔏 Creating and signing attestation...");
+    let started = Instant::now();
 
     // Load or generate signing key
     let signing_key = load_or_generate_key()?;
-    let verifying_key = signing_key.verifying_key();
-
-    // Extract server name
-    let server_name = output.server_name.take()
-        .map(|sn| format!("{:?}", sn.as_str()))
-        .unwrap_or_else(|| "unknown".to_string());
 
     // Get current timestamp
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)?
         .as_secs();
 
-    // Extract balance commitment (first hash commitment from received data)
-    let balance_commitment = extract_balance_commitment(&output)?;
+    let attestation = issue_attestation(facts, parsed, hello, disclosure, config, &signing_key, timestamp)?;
+    let message_hash = message_digest(&attestation.signed_message()?);
+
+    events::Signing {
+        session_id,
+        schema_version: attestation.schema_version,
+        duration: started.elapsed(),
+        server_name: &attestation.server_name,
+        timestamp: attestation.timestamp,
+        claim_type: attestation.claim_type.as_deref().unwrap_or_default(),
+        formula: attestation.balance_formula.as_deref().unwrap_or_default(),
+        environment: attestation.environment.as_deref().filter(|_| attestation.environment_signed),
+        account_count: attestation.account_count,
+        account_filter: attestation.account_filter.as_deref(),
+        verifier_version: attestation.verifier_version.as_deref().unwrap_or_default(),
+        transcript_commitments: attestation.transcript_commitments.len(),
+        label: attestation.label.as_deref(),
+        request_template: attestation.request_template.as_ref().map(|t| t.id.as_str()),
+        request_template_matched: attestation.request_template.as_ref().map(|t| t.matched),
+        api_version: attestation.api_version.as_deref(),
+        commitment: &attestation.balance_commitment,
+        accounts_commitment: attestation.accounts_commitment.as_deref(),
+        recipient: attestation.recipient.as_deref(),
+        message_hash: &message_hash,
+        signature: &attestation.signature,
+    }
+    .emit();
+
+    // Serialize attestation in the format the prover asked for
+    let attestation_bytes = match hello.format {
+        AttestationFormat::Json => serde_json::to_vec_pretty(&attestation).map_err(Error::Serialize)?,
+        AttestationFormat::Compact => {
+            let compact = attestation.to_compact()?;
+            save_compact_attestation(&compact, &config.attestation_dir)?;
+            compact
+        }
+        AttestationFormat::Eas => {
+            // `check_format` already refused the hello without a schema
+            let schema = config.eas_schema.as_ref().ok_or_else(|| {
+                Error::check(Check::Hello)(anyhow::anyhow!("This verifier has no EAS schema configured"))
+            })?;
+            let request = AttestationRequestData::new(schema.encode(&attestation)?, config.eas_revocable).abi_encode();
+            save_eas_request(&request, &config.attestation_dir)?;
+            request
+        }
+    };
+
+    // Save attestation to file for contract simulator
+    save_attestation(&attestation, &config.attestation_dir)?;
+
+    // Pushed to the configured sinks by the delivery worker
+    if !config.delivery_sinks.is_empty() {
+        delivery::enqueue(config, session_id, &attestation)?;
+    }
+
+    if let Some(log) = transparency {
+        let seq = log.append(&attestation)?;
+        info!("📜 Attestation logged for transparency as leaf {}", seq);
+    }
 
-    // Pad server_name to 32 bytes (right-padded with zeros)
-    let mut server_name_padded = [0u8; 32];
-    let server_name_bytes = server_name.as_bytes();
-    if server_name_bytes.len() > 32 {
-        anyhow::bail!("Server name too long: {} bytes (max 32)", server_name_bytes.len());
+    // Optionally archive everything needed to re-check this session later
+    if config.proof_bundle {
+        write_proof_bundle(&config.attestation_dir, session_id, &attestation, facts, disclosure, config.sensitive_logging())
+            .map_err(Error::check(Check::ProofBundle))?;
     }
-    server_name_padded[..server_name_bytes.len()].copy_from_slice(server_name_bytes);
-
-    // Pad timestamp to 32 bytes (right-padded with zeros)
-    let mut timestamp_padded = [0u8; 32];
-    let timestamp_bytes = timestamp.to_le_bytes(); // 8 bytes
-    timestamp_padded[..8].copy_from_slice(&timestamp_bytes);
-
-    info!("📝 Attestation details:");
-    info!("   Server: {} (padded to 32 bytes)", server_name);
-    info!("   Server bytes: {}", hex::encode(&server_name_padded));
-    info!("   Timestamp: {} (padded to 32 bytes)", timestamp);
-    info!("   Timestamp bytes: {}", hex::encode(&timestamp_padded));
-    info!("   Commitment: {}", hex::encode(&balance_commitment));
-
-    // Create message to sign (server_name + timestamp + balance_commitment)
-    // All fields are now 32 bytes each
-    let mut message = Vec::new();
-    message.extend_from_slice(&server_name_padded);
-    message.extend_from_slice(&timestamp_padded);
-    message.extend_from_slice(&balance_commitment);
-
-    // Hash the message
-    let message_hash = Sha256::digest(&message);
-    info!("   hash: {}", hex::encode(message_hash));
-
-    // Sign with BIP-340 Schnorr
-    let signature: Signature = signing_key.sign(&message_hash);
-
-    // Create hex-encoded signature with 3-byte version prefix
-    let sig_bytes = signature.to_bytes();
-    let mut versioned_sig = Vec::with_capacity(67); // 3 + 64
-    versioned_sig.extend_from_slice(&SIGNATURE_VERSION);
-    versioned_sig.extend_from_slice(&sig_bytes);
-    let hex_signature = hex::encode(versioned_sig);
-
-    info!("✅ Attestation signed with BIP-340 Schnorr");
-    info!("   Signature: {}...", &hex_signature);
-
-    // Create attestation structure
-    let attestation = Attestation {
+
+    Ok(attestation_bytes)
+}
+
+/// Check the session against the prover's signing policy and sign the
+/// attestation at `timestamp` with `signing_key`.
+///
+/// Only reads the session facts and configuration: loading the key, reading the
+/// clock and writing the attestation out are left to [`sign_attestation`], so
+/// `fixture::SessionFixture` sessions can be signed in tests.
+pub fn issue_attestation(
+    facts: &SessionFacts,
+    parsed: &ParsedSession<'_>,
+    hello: &Hello,
+    disclosure: &DisclosureReport,
+    config: &ServerConfig,
+    signing_key: &SigningKey,
+    timestamp: u64,
+) -> Result<Attestation> {
+    let verifying_key = signing_key.verifying_key();
+
+    // Sign the server name exactly as validate_plaid_connection checked it
+    let server_name = facts.normalized_server_name().unwrap_or_else(|| "unknown".to_string());
+
+    // Don't feed the claim parsers a response from an API version they weren't written for
+    let api_version = check_api_version(parsed.request(), config.plaid_version.as_deref())?;
+
+    // Produce the signed claim requested by the prover's (already validated) policy
+    let claim = match hello.policy {
+        SigningPolicy::Commitment => balance_claim(parsed, BalanceFormula::Current, hello, config)?,
+        SigningPolicy::SpendableCommitment => {
+            balance_claim(parsed, BalanceFormula::AvailableMinusHolds, hello, config)?
+        }
+        SigningPolicy::IncomeCommitment => income_claim(parsed, hello, config)?,
+    };
+    let Claim {
+        commitment: balance_commitment,
+        claim_type,
+        formula,
+        account_ids,
+        item_count,
+        account_filter,
+        inactive_accounts,
+        balances_by_currency,
+        schema_profile,
+    } = claim;
+
+    // Nudge provers towards minimal disclosure
+    let over_revealed_bytes = check_disclosure(facts, config).map_err(Error::check(Check::Disclosure))?;
+
+    // Record the operator-required headers proving this is a genuine API response
+    let response_headers =
+        check_required_headers(parsed, &config.required_headers).map_err(Error::check(Check::Headers))?;
+
+    // Attest the account inventory so consumers can detect accounts left out of the item
+    let account_count = account_ids.as_ref().map(|ids| ids.len() as u32);
+    let accounts_commitment = account_ids.as_deref().map(|ids| inventory::accounts_commitment(ids).to_vec());
+
+    // List every hash commitment so each can be checked against its own opening
+    let transcript_commitments = transcript_hashes(facts, &config.commitment_directions)?;
+
+    // Check the prover's committed request head against its declared template
+    let request_template = match_request_template(facts, hello.request_template.as_ref(), config)
+        .map_err(Error::check(Check::RequestTemplate))?;
+
+    // Bind the issuing build so consumers can distrust vulnerable versions
+    let version = verifier_version();
+
+    // Co-signed attestations sign the set's id, and this verifier must be in the set
+    let signer_set_id = match &config.signer_set {
+        Some(set) if !set.contains(&verifying_key.to_bytes()) => {
+            return Err(Error::NotInSignerSet { pubkey: hex::encode(verifying_key.to_bytes()) });
+        }
+        Some(set) => Some(set.id().to_vec()),
+        None => None,
+    };
+
+    // Optionally bind the Plaid environment so on-chain consumers can enforce it
+    let signed_environment = config.environment.filter(|_| config.sign_environment);
+
+    // Always sign a tagged claim set: the untagged legacy layout lets values be
+    // relabelled as other fields of the same width
+    let claim_set = config.claim_set.unwrap_or(&FULL);
+
+    let mut attestation = Attestation {
+        schema_version: CURRENT_SCHEMA_VERSION,
         server_name,
         timestamp,
         balance_commitment,
-        signature: hex_signature,
+        signature: String::new(),
         verifier_pubkey: verifying_key.to_bytes().to_vec(),
+        environment: config.environment.map(|env| env.to_string()),
+        environment_signed: signed_environment.is_some(),
+        prover_timestamp: hello.timestamp,
+        account_count,
+        accounts_commitment,
+        response_headers,
+        balance_formula: Some(formula.to_string()),
+        item_count,
+        claim_type: Some(claim_type.to_string()),
+        account_filter,
+        verifier_version: Some(version),
+        over_revealed_bytes,
+        recipient: hello.recipient.clone(),
+        transcript_commitments,
+        digest: None,
+        label: config.attestation_label.clone(),
+        inactive_accounts,
+        signer_set_id,
+        partial_signatures: Vec::new(),
+        request_template,
+        disclosure: Some(disclosure.counts()),
+        balances_by_currency,
+        api_version,
+        claim_set: Some(claim_set.name.to_string()),
+        schema_profile,
     };
 
-    // Serialize attestation
-    let attestation_bytes = serde_json::to_vec_pretty(&attestation)?;
+    // The claim set signs only its own claims; the rest stay out of the attestation
+    claim_set.strip(&mut attestation);
+
+    // Rebuild the message exactly as consumers will (the claim set's layout,
+    // see auditorzk_attestation::claims), hash it
+    // and sign the hash itself as the BIP-340 message, with fresh aux randomness
+    let message_hash = message_digest(&attestation.signed_message()?);
+    let mut aux_rand = [0u8; 32];
+    OsRng.fill_bytes(&mut aux_rand);
+    let signature = sign_digest(signing_key, &message_hash, &aux_rand);
+
+    // Hex-encoded signature with 3-byte version prefix
+    attestation.signature = encode_signature(&signature);
+    attestation.digest = config.emit_digest.then(|| hex::encode(message_hash));
+    Ok(attestation)
+}
 
-    // Save attestation to file for contract simulator
-    save_attestation(&attestation)?;
+/// A committed claim ready to be signed, with the fields that describe it
+struct Claim {
+    commitment: Vec<u8>,
+    claim_type: &'static str,
+    formula: &'static str,
+    account_ids: Option<Vec<String>>,
+    item_count: Option<u32>,
+    /// Canonical description of the accounts summed, for balance claims
+    account_filter: Option<String>,
+    /// Accounts excluded as closed or errored, when the operator excludes them
+    inactive_accounts: Option<u32>,
+    /// Per-currency commitments the commitment hashes, when committing by currency
+    balances_by_currency: BTreeMap<String, Vec<u8>>,
+    /// Response schema profile the figure was read with, for balance claims
+    schema_profile: Option<ProfileSelection>,
+}
 
-    Ok(attestation_bytes)
+/// Commit to the account balance computed with `formula`
+fn balance_claim(parsed: &ParsedSession<'_>, formula: BalanceFormula, hello: &Hello, config: &ServerConfig) -> Result<Claim> {
+    let (commitment, balances_by_currency, balance) =
+        extract_balance_commitment(parsed, formula, session_blinder(hello)?, config)?;
+
+    Ok(Claim {
+        commitment,
+        claim_type: "balance",
+        formula: formula.as_str(),
+        account_ids: balance.account_ids,
+        item_count: Some(balance.item_count as u32),
+        account_filter: Some(config.account_filter.canonical()),
+        inactive_accounts: config.exclude_inactive_accounts.then_some(balance.inactive_accounts as u32),
+        balances_by_currency,
+        schema_profile: balance.schema_profile,
+    })
 }
 
-/// Extract the balance commitment from transcript commitments
-/// MOCK IMPLEMENTATION: Creates a fake commitment from the transcript data
-fn extract_balance_commitment(output: &VerifierOutput) -> Result<Vec<u8>> {
+/// Commit to annualized gross income from a payroll income response.
+/// MOCK IMPLEMENTATION: like the balance commitment, the verifier computes the
+/// figure from the revealed transcript instead of checking a prover commitment
+fn income_claim(parsed: &ParsedSession<'_>, hello: &Hello, config: &ServerConfig) -> Result<Claim> {
+    let target = parsed.request_target().map_err(Error::check(Check::Endpoint))?;
+    check_endpoint(target, PAYROLL_INCOME_PATH).map_err(Error::check(Check::Endpoint))?;
+
+    let formula = config.income_formula;
+    let body = parsed.json_body().map_err(Error::check(Check::Transcript))?;
+    let income = extract_income(body, formula, &BodyLimits::from_config(config))
+        .map_err(|e| Error::Claim { claim: "income", provider: "plaid", source: e.into() })?;
+
+    info!("💼 Annual income (extracted): {} from {} employer(s)", income.canonical(), income.employers);
+
+    let commitment = mock_commitment(&income.canonical(), session_blinder(hello)?);
+
+    Ok(Claim {
+        commitment,
+        claim_type: "income",
+        formula: formula.as_str(),
+        account_ids: None,
+        item_count: None,
+        account_filter: None,
+        inactive_accounts: None,
+        balances_by_currency: BTreeMap::new(),
+        schema_profile: None,
+    })
+}
+
+/// Extract the balance commitment from transcript commitments, along with the
+/// per-currency commitments it hashes (empty unless committing by currency) and
+/// the parsed balance (account ids and item count) it covers
+/// MOCK IMPLEMENTATION: Creates a fake commitment from the transcript data,
+/// blinded with the session's blinder (see [`session_blinder`])
+pub fn extract_balance_commitment(
+    parsed: &ParsedSession<'_>,
+    formula: BalanceFormula,
+    blinder: &[u8],
+    config: &ServerConfig,
+) -> Result<(Vec<u8>, BTreeMap<String, Vec<u8>>, BalanceResult)> {
     // TEMPORARY MOCK: Extract balance from transcript and create commitment
     // In production, this should come from the prover's selective disclosure
 
-    let transcript = output.transcript.as_ref()
-        .context("No transcript available")?;
-
-    let received_bytes = transcript.received_unsafe();
+    let body = parsed.json_body().map_err(Error::check(Check::Transcript))?;
 
-    // Find the JSON body (skip HTTP headers - look for "\r\n\r\n")
-    let body_start = received_bytes
-        .windows(4)
-        .position(|window| window == b"\r\n\r\n")
-        .context("No HTTP body separator found")?
-        + 4;
-
-    let json_body = &received_bytes[body_start..];
-
-    let json_str = std::str::from_utf8(json_body)
-        .context("Invalid UTF-8 in JSON body")?;
+    #[cfg(feature = "test-endpoints")]
+    if config.log_sensitive {
+        info!("📄 Response body (for mocking commitment):");
+        info!("{}", String::from_utf8_lossy(body));
+    }
 
-    info!("📄 JSON body (for mocking commitment):");
-    info!("{}", json_str);
+    // Delegate source-specific parsing to the configured parser, which picks the
+    // schema profile by the request's API version and the response's shape
+    let balance = parser_for(config, formula, ResponseContext::of(parsed)).extract(body)?;
+    let balance_string = balance.canonical();
 
-    // Parse JSON to extract balance
-    let json: serde_json::Value = serde_json::from_str(json_str)
-        .context("Failed to parse JSON response")?;
+    info!("💰 Total balance (extracted): {} {}", balance_string, balance.currency);
+    if balance.item_count > 1 {
+        info!("🏦 Summed across {} Plaid Items", balance.item_count);
+    }
 
-    let accounts = json["accounts"].as_array()
-        .context("No accounts array found")?;
+    // Distinct from the max-accounts guard: some consumers want evidence of several linked accounts
+    check_min_accounts(balance.account_count, config.min_accounts)?;
 
-    let mut total_balance = 0.0;
-    for account in accounts {
-        // Try to get balance from "current" field (can be f64 or i64)
-        if let Some(current) = account["balances"]["current"].as_f64() {
-            total_balance += current;
-        } else if let Some(current) = account["balances"]["current"].as_i64() {
-            total_balance += current as f64;
-        } else if let Some(current) = account["balances"]["current"].as_u64() {
-            total_balance += current as f64;
+    // Above the default minimum the signed account count is what proves it, so it must be recorded
+    if balance.account_ids.is_none() {
+        if config.require_account_inventory || config.min_accounts > 1 {
+            return Err(Error::AccountInventoryRequired);
         }
+        warn!("⚠️  Response has accounts without account_id, attesting without an account inventory");
     }
 
-    if total_balance == 0.0 {
-        anyhow::bail!("No balance found in accounts");
+    // Per currency, the signed commitment hashes one commitment per currency total
+    // instead of committing to a sum across currencies
+    if config.commit_by_currency {
+        for (code, total) in balance.currency_totals() {
+            info!("💱 {} total (extracted): {}", code, total);
+        }
+        let by_currency = balance.currency_commitments(blinder);
+        let commitment = currency::map_commitment(&by_currency)?.to_vec();
+        return Ok((commitment, by_currency, balance));
     }
+    check_single_currency(&balance.by_currency, config.allow_mixed_currency)?;
 
-    info!("💰 Total balance (extracted): ${:.2}", total_balance);
+    Ok((mock_commitment(&balance_string, blinder), BTreeMap::new(), balance))
+}
 
-    // Create mock commitment: hash(balance_string || mock_blinder)
-    let balance_string = format!("{:.2}", total_balance);
-    let mock_blinder = b"mock_blinder_for_testing"; // In production, from MPC
+/// The blinder the claim commitment is made with: the prover's (already
+/// checked), or the random one the verifier picked when the prover sent none
+fn session_blinder(hello: &Hello) -> Result<&[u8]> {
+    hello.blinder.as_deref().ok_or_else(|| Error::check(Check::Hello)(anyhow::anyhow!("The session has no blinder")))
+}
 
+/// Create mock commitment: hash(value_string || blinder)
+fn mock_commitment(value: &str, blinder: &[u8]) -> Vec<u8> {
     let mut commitment_preimage = Vec::new();
-    commitment_preimage.extend_from_slice(balance_string.as_bytes());
-    commitment_preimage.extend_from_slice(mock_blinder);
+    commitment_preimage.extend_from_slice(value.as_bytes());
+    commitment_preimage.extend_from_slice(blinder);
 
     use sha2::{Digest, Sha256};
     let commitment_hash = Sha256::digest(&commitment_preimage);
 
-    info!("🔐 Mock commitment created: {}...", hex::encode(&commitment_hash));
+    trace!("🔐 Mock commitment created: {}", hex::encode(commitment_hash));
 
-    Ok(commitment_hash.to_vec())
+    commitment_hash.to_vec()
 }
 
 /// Load existing key or generate new one
@@ -192,24 +432,41 @@ fn load_or_generate_key() -> Result<SigningKey> {
     let signing_key = SigningKey::random(&mut OsRng);
 
     // Ensure config directory exists
-    fs::create_dir_all("config").context("Failed to create config directory")?;
+    fs::create_dir_all("config").map_err(|source| Error::Save { artifact: "config directory", source })?;
 
     // Save public key for verification
     let verifying_key = signing_key.verifying_key();
     let pubkey_bytes = verifying_key.to_bytes();
     fs::write(PUBKEY_PATH, hex::encode(pubkey_bytes))
-        .context("Failed to save public key")?;
+        .map_err(|source| Error::Save { artifact: "public key", source })?;
     info!("💾 Public key saved to {}", PUBKEY_PATH);
 
     Ok(signing_key)
 }
 
+/// Save the ABI-encoded EAS request data next to the JSON attestation
+fn save_eas_request(request: &[u8], dir: &Path) -> Result<()> {
+    let path = dir.join("auditor_zk_attestation.eas");
+    fs::write(&path, request).map_err(|source| Error::Save { artifact: "EAS request data", source })?;
+    info!("💾 EAS request data ({} bytes) saved to {}", request.len(), path.display());
+    Ok(())
+}
+
+/// Save the compact form next to the JSON attestation
+fn save_compact_attestation(compact: &[u8], dir: &Path) -> Result<()> {
+    let path = dir.join("auditor_zk_attestation.azkc");
+    fs::write(&path, compact)
+        .map_err(|source| Error::Save { artifact: "compact attestation", source })?;
+    info!("💾 Compact attestation ({} bytes) saved to {}", compact.len(), path.display());
+    Ok(())
+}
+
 /// Save attestation to file for contract simulator
-fn save_attestation(attestation: &Attestation) -> Result<()> {
-    let attestation_json = serde_json::to_string_pretty(attestation)?;
-    let path = "/tmp/auditor_zk_attestation.json";
-    fs::write(path, attestation_json)
-        .context("Failed to save attestation")?;
-    info!("💾 Attestation saved to {}", path);
+fn save_attestation(attestation: &Attestation, dir: &Path) -> Result<()> {
+    let attestation_json = serde_json::to_string_pretty(attestation).map_err(Error::Serialize)?;
+    let path = dir.join("auditor_zk_attestation.json");
+    fs::write(&path, attestation_json)
+        .map_err(|source| Error::Save { artifact: "attestation", source })?;
+    info!("💾 Attestation saved to {}", path.display());
     Ok(())
 }
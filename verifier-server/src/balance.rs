@@ -0,0 +1,581 @@
+use anyhow::{bail, Context, Result};
+use auditorzk_attestation::profile::ProfileSelection;
+use auditorzk_attestation::{currency, message};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::ServerConfig;
+use crate::plaid::PlaidParser;
+use crate::profiles::{ProfileSet, ResponseContext};
+
+/// Total balance extracted from a data source response
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceResult {
+    /// Sum of the balances of every account admitted by the account filter, in cents
+    pub total_cents: i64,
+    /// ISO 4217 currency code of the total
+    pub currency: String,
+    /// Ids of every summed account, `None` if any of them had no id
+    pub account_ids: Option<Vec<String>>,
+    /// Number of summed accounts, whether or not they have ids
+    pub account_count: usize,
+    /// Number of Plaid Items (linked institutions) the accounts came from
+    pub item_count: usize,
+    /// Accounts left out for a closed or errored status (0 unless excluded)
+    pub inactive_accounts: usize,
+    /// Sum of the summed accounts' balances per ISO 4217 code, in each currency's
+    /// minor units (accounts without a code count as USD)
+    pub by_currency: BTreeMap<String, i64>,
+    /// Schema profile the response was read with, see [`crate::profiles`]
+    pub schema_profile: Option<ProfileSelection>,
+}
+
+impl BalanceResult {
+    /// Canonical decimal string that gets committed, e.g. `-12.05`
+    pub fn canonical(&self) -> String {
+        format_minor_units(self.total_cents, 2)
+    }
+
+    /// Commitment to each currency's canonical total, blinded per currency, for
+    /// verifiers that commit by currency instead of summing across currencies.
+    /// `balance_commitment` is then [`currency::map_commitment`] of the result.
+    pub fn currency_commitments(&self, blinder: &[u8]) -> BTreeMap<String, Vec<u8>> {
+        self.currency_totals()
+            .into_iter()
+            .map(|(code, total)| {
+                let commitment = currency::currency_commitment(&total, &currency::currency_blinder(blinder, &code));
+                (code, commitment.to_vec())
+            })
+            .collect()
+    }
+
+    /// Canonical decimal total of each currency, with its minor-unit places
+    pub fn currency_totals(&self) -> BTreeMap<String, String> {
+        self.by_currency
+            .iter()
+            .map(|(code, &units)| (code.clone(), format_minor_units(units, minor_unit_exponent(code))))
+            .collect()
+    }
+}
+
+/// Plain decimal text for an amount in minor units, e.g. `-12.05` (exponent 2)
+/// or `120000` (exponent 0)
+pub fn format_minor_units(units: i64, exponent: usize) -> String {
+    let sign = if units < 0 { "-" } else { "" };
+    let units = units.unsigned_abs();
+    if exponent == 0 {
+        return format!("{}{}", sign, units);
+    }
+    let scale = 10u64.pow(exponent as u32);
+    format!("{}{}.{:0width$}", sign, units / scale, units % scale, width = exponent)
+}
+
+/// How per-account balances are combined into the committed total.
+///
+/// Plaid's own balance object has no holds field: its `available` already nets
+/// out pending transactions. [`BalanceFormula::AvailableMinusHolds`] reads the
+/// schema profile's `holds` pointer (`/balances/holds` by default), which only
+/// responses extended by the institution or an aggregator in front of Plaid
+/// carry. A standard Plaid response therefore fails under it rather than being
+/// committed as `sum(available)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceFormula {
+    /// `sum(current)`
+    Current,
+    /// `sum(available) - sum(holds)`; every account must report both. `holds` is
+    /// an extension field, not part of Plaid's schema
+    AvailableMinusHolds,
+}
+
+impl BalanceFormula {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Current => "current",
+            Self::AvailableMinusHolds => "available_minus_holds",
+        }
+    }
+
+    /// Single-byte encoding appended to the signed message
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            Self::Current => message::FORMULA_CURRENT,
+            Self::AvailableMinusHolds => message::FORMULA_AVAILABLE_MINUS_HOLDS,
+        }
+    }
+}
+
+impl fmt::Display for BalanceFormula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How string-typed balances (e.g. `"1.234,56"`) are read.
+///
+/// JSON numbers are always accepted as they are. In `strict` mode a string
+/// balance carries no amount, as before; otherwise it is normalized to a plain
+/// decimal by dropping the locale's thousands separators (which must group
+/// digits in threes) and mapping its decimal separator to `.`.
+///
+/// The sample responses in `fixtures/balance_us.json` and `balance_eu.json`
+/// both total 21,234.56, each only in its own locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberLocale {
+    #[default]
+    Strict,
+    /// `1,234.56`
+    Us,
+    /// `1.234,56`
+    Eu,
+}
+
+impl NumberLocale {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "strict",
+            Self::Us => "us",
+            Self::Eu => "eu",
+        }
+    }
+
+    /// Plain decimal text (`-1234.56`) for a locale-formatted amount, or `None`
+    /// if the text isn't a well-formed amount in this locale
+    pub fn normalize(&self, text: &str) -> Option<String> {
+        let (thousands, decimal) = match self {
+            Self::Strict => return None,
+            Self::Us => (',', '.'),
+            Self::Eu => ('.', ','),
+        };
+
+        let text = text.trim();
+        let (sign, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text),
+        };
+        let (integer, fraction) = match unsigned.split_once(decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let groups: Vec<&str> = integer.split(thousands).collect();
+        let grouped = match groups.split_first() {
+            Some((first, [])) => all_digits(first),
+            Some((first, rest)) => {
+                all_digits(first) && first.len() <= 3 && rest.iter().all(|g| g.len() == 3 && all_digits(g))
+            }
+            None => false,
+        };
+        if !grouped || fraction.is_some_and(|f| !all_digits(f)) {
+            return None;
+        }
+
+        let mut plain = format!("{}{}", sign, groups.concat());
+        if let Some(fraction) = fraction {
+            plain.push('.');
+            plain.push_str(fraction);
+        }
+        Some(plain)
+    }
+
+    /// Amount in cents carried by a balance field: JSON numbers as they are,
+    /// strings normalized per the locale (an error if malformed), anything else `None`.
+    ///
+    /// Numbers are read from their decimal text, never through f64, so large
+    /// balances keep every cent.
+    pub fn amount(&self, value: &serde_json::Value) -> Result<Option<i64>> {
+        self.amount_in(value, 2)
+    }
+
+    /// Like [`amount`](Self::amount), in units with `exponent` decimal places
+    pub fn amount_in(&self, value: &serde_json::Value, exponent: usize) -> Result<Option<i64>> {
+        match value {
+            serde_json::Value::String(text) if *self != Self::Strict => match self.normalize(text) {
+                Some(plain) => decimal_to_minor_units(&plain, exponent).map(Some),
+                None => bail!("Balance {:?} is not a valid {} amount", text, self),
+            },
+            serde_json::Value::Number(number) => decimal_to_minor_units(&number.to_string(), exponent).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Convert plain decimal text (`-1234.565`) to integer cents.
+///
+/// Digits past the cent are rounded half away from zero, so the result depends
+/// only on the text. Exponent notation is rejected.
+pub fn decimal_to_cents(text: &str) -> Result<i64> {
+    decimal_to_minor_units(text, 2)
+}
+
+/// Convert plain decimal text to integer units with `exponent` decimal places,
+/// rounding like [`decimal_to_cents`]
+pub fn decimal_to_minor_units(text: &str, exponent: usize) -> Result<i64> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        bail!("Balance {} must be a plain decimal", text);
+    }
+
+    let kept = &fraction[..fraction.len().min(exponent)];
+    let minor_digits = format!("{:0<width$}", kept, width = exponent);
+    let round_up = fraction.as_bytes().get(exponent).is_some_and(|&digit| digit >= b'5');
+
+    let units = whole
+        .parse::<i64>()
+        .ok()
+        .and_then(|whole| whole.checked_mul(10i64.checked_pow(exponent as u32)?))
+        .and_then(|units| units.checked_add(if exponent == 0 { 0 } else { minor_digits.parse::<i64>().ok()? }))
+        .and_then(|units| units.checked_add(i64::from(round_up)))
+        .with_context(|| format!("Balance {} overflows", text))?;
+    Ok(if negative { -units } else { units })
+}
+
+impl fmt::Display for NumberLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NumberLocale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "us" => Ok(Self::Us),
+            "eu" => Ok(Self::Eu),
+            other => bail!("Unknown number locale '{}' (expected strict, us or eu)", other),
+        }
+    }
+}
+
+/// Plaid account `type`, optionally narrowed to one `subtype` (`depository/checking`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AccountTypeRule {
+    pub account_type: String,
+    pub subtype: Option<String>,
+}
+
+impl AccountTypeRule {
+    fn matches(&self, account_type: Option<&str>, subtype: Option<&str>) -> bool {
+        let type_matches = account_type.is_some_and(|t| t.eq_ignore_ascii_case(&self.account_type));
+        let subtype_matches = match &self.subtype {
+            Some(rule) => subtype.is_some_and(|s| s.eq_ignore_ascii_case(rule)),
+            None => true,
+        };
+        type_matches && subtype_matches
+    }
+}
+
+impl fmt::Display for AccountTypeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.subtype {
+            Some(subtype) => write!(f, "{}/{}", self.account_type, subtype),
+            None => f.write_str(&self.account_type),
+        }
+    }
+}
+
+impl FromStr for AccountTypeRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        let (account_type, subtype) = match s.split_once('/') {
+            Some((account_type, subtype)) => (account_type.trim(), Some(subtype.trim())),
+            None => (s.as_str(), None),
+        };
+        if account_type.is_empty() || subtype.is_some_and(str::is_empty) {
+            bail!("Invalid account type rule '{}' (expected type or type/subtype)", s);
+        }
+        Ok(Self { account_type: account_type.to_string(), subtype: subtype.map(str::to_string) })
+    }
+}
+
+/// Which accounts are summed, by Plaid `type`/`subtype`.
+///
+/// An account is summed if it matches any `include` rule (or `include` is empty)
+/// and no `exclude` rule. Accounts without a `type` only pass an empty `include`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountFilter {
+    include: Vec<AccountTypeRule>,
+    exclude: Vec<AccountTypeRule>,
+}
+
+impl AccountFilter {
+    pub fn new(mut include: Vec<AccountTypeRule>, mut exclude: Vec<AccountTypeRule>) -> Self {
+        // Sorted so equivalent configurations sign the same canonical description
+        include.sort();
+        include.dedup();
+        exclude.sort();
+        exclude.dedup();
+        Self { include, exclude }
+    }
+
+    /// Whether an account with this `type` and `subtype` is summed
+    pub fn admits(&self, account_type: Option<&str>, subtype: Option<&str>) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|rule| rule.matches(account_type, subtype));
+        included && !self.exclude.iter().any(|rule| rule.matches(account_type, subtype))
+    }
+
+    /// Description recorded in the attestation and signed as its hash,
+    /// e.g. `include=depository;exclude=depository/cd`
+    pub fn canonical(&self) -> String {
+        let join = |rules: &[AccountTypeRule]| rules.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+        format!("include={};exclude={}", join(&self.include), join(&self.exclude))
+    }
+}
+
+/// Extracts a balance from the response of one data source
+pub trait BalanceParser: Send + Sync {
+    /// Parse the response body into a balance
+    fn extract(&self, body: &[u8]) -> crate::error::Result<BalanceResult>;
+}
+
+/// Data sources with a built-in parser
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParserKind {
+    Plaid,
+}
+
+impl FromStr for ParserKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "plaid" => Ok(Self::Plaid),
+            other => bail!("Unknown balance parser '{}' (expected plaid)", other),
+        }
+    }
+}
+
+/// Build the parser selected in the configuration, computing `formula` from a
+/// response whose exchange `context` describes
+pub fn parser_for(config: &ServerConfig, formula: BalanceFormula, context: ResponseContext) -> Box<dyn BalanceParser> {
+    let limits = BodyLimits::from_config(config);
+
+    match config.balance_parser {
+        ParserKind::Plaid => Box::new(PlaidParser {
+            strict_minor_units: config.strict_minor_units,
+            number_locale: config.number_locale,
+            exclude_inactive: config.exclude_inactive_accounts,
+            formula,
+            account_filter: config.account_filter.clone(),
+            limits,
+            profiles: ProfileSet::plaid(),
+            context,
+        }),
+    }
+}
+
+/// Optional bounds on the response body, tighter than the MPC data limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BodyLimits {
+    pub max_body_bytes: Option<usize>,
+    pub max_accounts: Option<usize>,
+    pub max_json_depth: Option<usize>,
+}
+
+/// A response body exceeded one of the configured [`BodyLimits`]
+#[derive(Debug, thiserror::Error)]
+pub enum LimitError {
+    #[error("body_too_large: response body is {size} bytes (max {max})")]
+    BodyTooLarge { size: usize, max: usize },
+    #[error("too_many_accounts: too many accounts ({count} > {max})")]
+    TooManyAccounts { count: usize, max: usize },
+    #[error("json_too_deep: response nests deeper than {max} levels")]
+    JsonTooDeep { max: usize },
+}
+
+impl BodyLimits {
+    /// The operator's limits. By default only the account cap is set, at 1000,
+    /// so `fixtures/balance_too_many_accounts.json` (1001 accounts) is refused.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_body_bytes: config.max_body_bytes,
+            max_accounts: Some(config.max_accounts).filter(|&max| max > 0),
+            max_json_depth: config.max_json_depth,
+        }
+    }
+
+    /// Check size and nesting depth before the body is parsed.
+    ///
+    /// The depth scan is a single pass over the bytes that only tracks brackets
+    /// outside strings, so a pathological body is rejected in linear time before
+    /// the deserializer recurses into it. The account cap is enforced by the
+    /// parser while it streams the accounts.
+    ///
+    /// The limits apply to every response the verifier reads. There is a single
+    /// provider (Plaid), so there is nothing to scope them to yet.
+    pub fn check_body(&self, body: &[u8]) -> Result<(), LimitError> {
+        if let Some(max) = self.max_body_bytes {
+            if body.len() > max {
+                return Err(LimitError::BodyTooLarge { size: body.len(), max });
+            }
+        }
+
+        if let Some(max) = self.max_json_depth {
+            let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+            for &byte in body {
+                if in_string {
+                    match byte {
+                        _ if escaped => escaped = false,
+                        b'\\' => escaped = true,
+                        b'"' => in_string = false,
+                        _ => {}
+                    }
+                    continue;
+                }
+                match byte {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => {
+                        depth += 1;
+                        if depth > max {
+                            return Err(LimitError::JsonTooDeep { max });
+                        }
+                    }
+                    b'}' | b']' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of decimal places in the smallest unit of an ISO 4217 currency
+pub fn minor_unit_exponent(currency: &str) -> usize {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF"
+        | "UGX" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auditorzk_attestation::Attestation;
+
+    use crate::error::Error;
+    use crate::plaid::PlaidError;
+
+    #[test]
+    fn currency_totals_match_the_schema_20_fixture() {
+        let parser = PlaidParser { strict_minor_units: true, ..PlaidParser::default() };
+        let balance = parser.extract(include_str!("../fixtures/balance_multi_currency.json").as_bytes()).unwrap();
+        assert_eq!(balance.currency_totals()["JPY"], "120000");
+        assert_eq!(balance.currency_totals()["BHD"], "12.345");
+        assert_eq!(balance.currency_totals()["USD"], "1334.56");
+
+        // The frozen schema 20 fixture was issued for this response
+        let attestation: Attestation =
+            serde_json::from_str(include_str!("../../auditorzk-attestation/fixtures/attestation_v20.json")).unwrap();
+        let commitments = balance.currency_commitments(b"mock_blinder_for_testing");
+        assert_eq!(commitments, attestation.balances_by_currency);
+        assert_eq!(currency::map_commitment(&commitments).unwrap().to_vec(), attestation.balance_commitment);
+    }
+
+    #[test]
+    fn available_minus_holds_requires_holds() {
+        let parser = |formula| PlaidParser { strict_minor_units: true, formula, ..PlaidParser::default() };
+
+        // (1100.00 - 250.50) + (20000.00 - 0), against 1234.56 + 20000.00 current
+        let holds = include_str!("../fixtures/balance_holds.json").as_bytes();
+        assert_eq!(parser(BalanceFormula::AvailableMinusHolds).extract(holds).unwrap().canonical(), "20849.50");
+        assert_eq!(parser(BalanceFormula::Current).extract(holds).unwrap().canonical(), "21234.56");
+
+        let standard = include_str!("../fixtures/balance_single_account.json").as_bytes();
+        let Err(Error::Plaid(PlaidError::ResponseJson(e))) = parser(BalanceFormula::AvailableMinusHolds).extract(standard) else {
+            panic!("expected a response without holds to be rejected")
+        };
+        assert!(e.to_string().starts_with("Account 1 has no balances.holds for available_minus_holds"));
+    }
+
+    #[test]
+    fn string_balances_are_read_in_their_locale() {
+        let parser = |number_locale| PlaidParser { strict_minor_units: true, number_locale, ..PlaidParser::default() };
+        let us = include_str!("../fixtures/balance_us.json").as_bytes();
+        let eu = include_str!("../fixtures/balance_eu.json").as_bytes();
+
+        let balance = parser(NumberLocale::Us).extract(us).unwrap();
+        assert_eq!((balance.canonical().as_str(), balance.currency.as_str()), ("21234.56", "USD"));
+        let balance = parser(NumberLocale::Eu).extract(eu).unwrap();
+        assert_eq!((balance.canonical().as_str(), balance.currency.as_str()), ("21234.56", "EUR"));
+
+        // In the other locale the strings are malformed and fail the session
+        for (locale, body, value) in [(NumberLocale::Eu, us, "1,234.56"), (NumberLocale::Us, eu, "1.234,56")] {
+            let Err(Error::Plaid(PlaidError::BalanceNotLocale { value: rejected, .. })) = parser(locale).extract(body) else {
+                panic!("expected {} to reject {}", locale, value)
+            };
+            assert_eq!(rejected, value);
+        }
+
+        // Strict mode reads no amount from a string
+        for body in [us, eu] {
+            let err = PlaidParser { strict_minor_units: false, ..parser(NumberLocale::Strict) }.extract(body).unwrap_err();
+            assert!(matches!(err, Error::Plaid(PlaidError::NoBalance { excluded: 0, inactive: 0 })));
+        }
+    }
+
+    #[test]
+    fn amounts_never_pass_through_f64() {
+        // 2^53 + 1 dollars and one cent has no exact f64 representation
+        let value: serde_json::Value = serde_json::from_str("9007199254740993.01").unwrap();
+        assert_ne!((value.as_f64().unwrap() * 100.0) as i64, 900719925474099301);
+        assert_eq!(NumberLocale::Strict.amount(&value).unwrap(), Some(900719925474099301));
+
+        let value = serde_json::json!("9.007.199.254.740.993,01");
+        assert_eq!(NumberLocale::Eu.amount(&value).unwrap(), Some(900719925474099301));
+    }
+
+    #[test]
+    fn default_limits_cap_accounts_at_one_thousand() {
+        let parser = PlaidParser { limits: BodyLimits::from_config(&ServerConfig::from_env().unwrap()), ..PlaidParser::default() };
+
+        let body = include_str!("../fixtures/balance_too_many_accounts.json");
+        let err = parser.extract(body.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Limit(LimitError::TooManyAccounts { count: 1001, max: 1000 })));
+        assert_eq!(err.code(), "too_many_accounts");
+        assert_eq!(err.to_string(), "too_many_accounts: too many accounts (1001 > 1000)");
+
+        // One account fewer is exactly at the cap
+        let mut response: serde_json::Value = serde_json::from_str(body).unwrap();
+        response["accounts"].as_array_mut().unwrap().pop();
+        let balance = parser.extract(&serde_json::to_vec(&response).unwrap()).unwrap();
+        assert_eq!((balance.account_count, balance.canonical().as_str()), (1000, "10.00"));
+    }
+
+    #[test]
+    fn body_limits_cases() {
+        let parser = |limits| PlaidParser { limits, ..PlaidParser::default() };
+
+        let cases: serde_json::Value = serde_json::from_str(include_str!("../fixtures/body_limits.json")).unwrap();
+        for case in cases.as_array().unwrap() {
+            let limit = |name: &str| case["limits"][name].as_u64().map(|n| n as usize);
+            let limits = BodyLimits {
+                max_body_bytes: limit("max_body_bytes"),
+                max_accounts: limit("max_accounts"),
+                max_json_depth: limit("max_json_depth"),
+            };
+            let verdict = match parser(limits).extract(case["body"].as_str().unwrap().as_bytes()) {
+                Ok(_) => "ok",
+                Err(e) => e.code(),
+            };
+            assert_eq!(verdict, case["expect"], "{}", case["name"]);
+        }
+
+        // A nesting bomb is turned away by the byte scan, before any parsing
+        let bomb = format!(r#"{{"accounts":{}"#, "[".repeat(1_000_000));
+        let limits = BodyLimits { max_json_depth: Some(64), ..BodyLimits::default() };
+        assert_eq!(parser(limits).extract(bomb.as_bytes()).unwrap_err().code(), "json_too_deep");
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::attestation::Attestation;
+use crate::disclosure::DisclosureReport;
+use crate::session::SessionFacts;
+
+/// Write a per-session proof bundle for archival and dispute handling.
+///
+/// The zip contains the attestation, the verifier public key, the transcript
+/// commitment metadata and the disclosure report (which byte ranges were
+/// revealed, only committed or hidden). The (redacted) transcript is only included when
+/// `include_sensitive` is set, since revealed bytes may contain balances and
+/// request credentials.
+///
+/// The zip is written under a hidden `.partial` name and renamed when complete,
+/// so retention (see [`crate::retention`]) never prunes a bundle being written.
+pub fn write_proof_bundle(
+    dir: &Path,
+    session_id: &str,
+    attestation: &Attestation,
+    facts: &SessionFacts,
+    disclosure: &DisclosureReport,
+    include_sensitive: bool,
+) -> Result<PathBuf> {
+    let path = dir.join(format!("{}.zip", session_id));
+    let partial = dir.join(format!(".{}.zip.partial", session_id));
+    let file = File::create(&partial)
+        .with_context(|| format!("Failed to create proof bundle {}", partial.display()))?;
+
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("attestation.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(attestation)?)?;
+
+    zip.start_file("verifier_pubkey.hex", options)?;
+    zip.write_all(hex::encode(&attestation.verifier_pubkey).as_bytes())?;
+
+    zip.start_file("commitments.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&facts.commitments)?)?;
+
+    zip.start_file("disclosure.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(disclosure)?)?;
+
+    if include_sensitive {
+        if let Some(transcript) = &facts.transcript {
+            // Unrevealed bytes are already zeroed in the partial transcript
+            zip.start_file("transcript/sent.bin", options)?;
+            zip.write_all(&transcript.sent)?;
+
+            zip.start_file("transcript/received.bin", options)?;
+            zip.write_all(&transcript.received)?;
+        }
+    }
+
+    zip.finish().context("Failed to finalize proof bundle")?;
+    fs::rename(&partial, &path).with_context(|| format!("Failed to move proof bundle to {}", path.display()))?;
+
+    info!("📦 Proof bundle saved to {}", path.display());
+    Ok(path)
+}
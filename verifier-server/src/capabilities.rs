@@ -0,0 +1,59 @@
+//! What this build of the verifier can do, printed by the `capabilities`
+//! subcommand and served as `GET /capabilities` on the admin listener.
+//!
+//! `test_endpoints` tells whether the binary was built with the `test-endpoints`
+//! feature. Without it the test-only affordances aren't compiled in at all:
+//!
+//! - `localhost`, `127.0.0.1` and `::1` as allowed hosts;
+//! - transcript contents in logs and proof bundles (`AUDITORZK_LOG_SENSITIVE`).
+//!
+//! Setting one of them anyway fails configuration, naming the feature, while a
+//! test build keeps them working (see `tests/config_env.rs`).
+
+use auditorzk_attestation::CURRENT_SCHEMA_VERSION;
+use serde::Serialize;
+
+use crate::attestation::{build_fingerprint, verifier_version};
+use crate::control::SigningPolicy;
+
+/// Whether this binary was built with the `test-endpoints` feature
+pub const TEST_ENDPOINTS: bool = cfg!(feature = "test-endpoints");
+
+/// Capabilities of the running build
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    /// e.g. `auditor-zk-verifier/0.1.0+1a2b3c4`
+    pub verifier_version: String,
+    /// Hash of `verifier_version`, as signed into attestations
+    pub build_fingerprint: String,
+    /// Attestation schema version this build issues
+    pub schema_version: u32,
+    /// Signing policy modes this build can sign
+    pub policies: &'static [&'static str],
+    /// Whether the test-only affordances are compiled in
+    pub test_endpoints: bool,
+}
+
+impl Capabilities {
+    pub fn current() -> Self {
+        Self {
+            verifier_version: verifier_version(),
+            build_fingerprint: build_fingerprint(),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            policies: SigningPolicy::MODES,
+            test_endpoints: TEST_ENDPOINTS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoints_are_reported() {
+        let capabilities = Capabilities::current();
+        assert_eq!(capabilities.test_endpoints, cfg!(feature = "test-endpoints"));
+        assert_eq!(serde_json::to_value(&capabilities).unwrap()["test_endpoints"], TEST_ENDPOINTS);
+    }
+}
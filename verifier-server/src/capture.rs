@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::config::ServerConfig;
+use crate::error::{self, report};
+
+/// First bytes of every capture file, versioning the record layout below
+pub const CAPTURE_MAGIC: &[u8; 8] = b"AZKCAP01";
+
+/// What a capture record holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureKind {
+    /// MPC bytes the prover sent, as written into the verifier's stream
+    Inbound,
+    /// MPC bytes the verifier sent back to the prover
+    Outbound,
+    /// How `run_verifier` ended: `ok`, or the error chain
+    Outcome,
+}
+
+impl CaptureKind {
+    fn as_byte(&self) -> u8 {
+        match self {
+            Self::Inbound => 0,
+            Self::Outbound => 1,
+            Self::Outcome => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Inbound),
+            1 => Some(Self::Outbound),
+            2 => Some(Self::Outcome),
+            _ => None,
+        }
+    }
+}
+
+/// One record of a capture file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    pub kind: CaptureKind,
+    /// Milliseconds since the capture started
+    pub elapsed_ms: u32,
+    pub data: Vec<u8>,
+}
+
+/// Writes the capture format: [`CAPTURE_MAGIC`], then per record a kind byte,
+/// the elapsed milliseconds and the data length (both u32 big-endian) and the data.
+///
+/// Records round-trip through [`read_capture`].
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    started: Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(CAPTURE_MAGIC)?;
+        Ok(Self { writer, started: Instant::now() })
+    }
+
+    pub fn record(&mut self, kind: CaptureKind, data: &[u8]) -> io::Result<()> {
+        let elapsed_ms = u32::try_from(self.started.elapsed().as_millis()).unwrap_or(u32::MAX);
+        let len = u32::try_from(data.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record too large"))?;
+        self.writer.write_all(&[kind.as_byte()])?;
+        self.writer.write_all(&elapsed_ms.to_be_bytes())?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Read every record of a capture, failing on a bad magic or a truncated record
+pub fn read_capture<R: Read>(mut reader: R) -> Result<Vec<CaptureRecord>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic).context("Capture is shorter than its header")?;
+    if &magic != CAPTURE_MAGIC {
+        bail!("Not an AuditorZK capture (bad magic)");
+    }
+
+    let mut records = Vec::new();
+    loop {
+        let mut kind = [0u8; 1];
+        match reader.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(records),
+            Err(e) => return Err(e.into()),
+        }
+        let kind = CaptureKind::from_byte(kind[0])
+            .with_context(|| format!("Unknown capture record kind {} (record {})", kind[0], records.len()))?;
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).with_context(|| format!("Truncated capture record {}", records.len()))?;
+        let elapsed_ms = u32::from_be_bytes(header[..4].try_into()?);
+        let len = u32::from_be_bytes(header[4..].try_into()?) as usize;
+
+        let mut data = vec![0u8; len];
+        reader.read_exact(&mut data).with_context(|| format!("Truncated capture record {}", records.len()))?;
+        records.push(CaptureRecord { kind, elapsed_ms, data });
+    }
+}
+
+/// Records one session's MPC stream to `<capture dir>/<session id>.azkcap`.
+///
+/// Both forwarders share the handle. A write error is logged once and stops the
+/// capture; it never fails the session.
+#[derive(Clone)]
+pub struct SessionCapture {
+    /// `None` when capture is disabled or has failed
+    writer: Option<Arc<Mutex<Option<CaptureWriter<BufWriter<File>>>>>>,
+}
+
+impl SessionCapture {
+    /// Open the session's capture file if `AUDITORZK_CAPTURE_SESSIONS` is set
+    pub fn open(config: &ServerConfig, session_id: &str) -> Self {
+        if !config.capture_sessions {
+            return Self { writer: None };
+        }
+
+        let path = config.capture_dir.join(format!("{}.azkcap", session_id));
+        match create_private(&path).and_then(|file| Ok(CaptureWriter::new(BufWriter::new(file))?)) {
+            Ok(writer) => {
+                info!("🎙️  Capturing MPC stream to {}", path.display());
+                Self { writer: Some(Arc::new(Mutex::new(Some(writer)))) }
+            }
+            Err(e) => {
+                warn!("⚠️  Cannot capture session {}: {:#}", session_id, e);
+                Self { writer: None }
+            }
+        }
+    }
+
+    pub fn record(&self, kind: CaptureKind, data: &[u8]) {
+        let Some(writer) = &self.writer else { return };
+        let mut guard = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(capture) = guard.as_mut() {
+            if let Err(e) = capture.record(kind, data) {
+                warn!("⚠️  Session capture stopped: {}", e);
+                *guard = None;
+            }
+        }
+    }
+
+    /// Record how `run_verifier` ended and flush the file
+    pub fn finish<T>(&self, outcome: &error::Result<T>) {
+        self.record(CaptureKind::Outcome, outcome_text(outcome).as_bytes());
+
+        let Some(writer) = &self.writer else { return };
+        let mut guard = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(Err(e)) = guard.as_mut().map(CaptureWriter::flush) {
+            warn!("⚠️  Session capture not flushed: {}", e);
+        }
+    }
+}
+
+/// How an outcome is recorded: `ok`, or the error chain on one line
+pub fn outcome_text<T>(outcome: &error::Result<T>) -> String {
+    match outcome {
+        Ok(_) => "ok".to_string(),
+        Err(e) => report(e),
+    }
+}
+
+/// Create a capture file readable by the verifier's user only
+pub(crate) fn create_private(path: &Path) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create capture directory {}", dir.display()))?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).with_context(|| format!("Failed to create capture {}", path.display()))
+}
+
+/// Load a capture file written by [`SessionCapture`]
+pub fn load(path: &Path) -> Result<Vec<CaptureRecord>> {
+    let file = File::open(path).with_context(|| format!("Failed to open capture {}", path.display()))?;
+    read_capture(io::BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_read_back_in_order() {
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.record(CaptureKind::Inbound, b"prover bytes").unwrap();
+        writer.record(CaptureKind::Outbound, b"verifier bytes").unwrap();
+        writer.record(CaptureKind::Outcome, b"ok").unwrap();
+
+        let records = read_capture(writer.into_inner().as_slice()).unwrap();
+        let kinds: Vec<_> = records.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, [CaptureKind::Inbound, CaptureKind::Outbound, CaptureKind::Outcome]);
+        assert_eq!(records[0].data, b"prover bytes");
+        assert_eq!(records[2].data, b"ok");
+    }
+}
@@ -0,0 +1,864 @@
+use anyhow::Result;
+use auditorzk_attestation::claims::{ClaimSet, CLAIM_SETS};
+use auditorzk_attestation::eas::EasSchema;
+use auditorzk_attestation::message::{SignedField, MAX_LABEL_LEN};
+use auditorzk_attestation::multisig::SignerSet;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::balance::{AccountFilter, AccountTypeRule, NumberLocale, ParserKind};
+use crate::headers::RequiredHeader;
+use crate::control::SigningPolicy;
+use crate::delivery::SinkConfig;
+use crate::disclosure::OverRevealPolicy;
+use crate::income::IncomeFormula;
+use crate::proxy::{ClientIpSource, TrustedProxies};
+use crate::templates;
+use crate::usage::BudgetEnforcement;
+use crate::verifier::SESSION_BUFFER_BYTES;
+use crate::plaid::{host_matches, parse_direction, PlaidEnvironment, TlsVersion};
+use crate::profiles::ProfileSet;
+use tlsn_core::transcript::Direction;
+
+const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:7047";
+const DEFAULT_ATTESTATION_DIR: &str = "/tmp";
+const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 300;
+const DEFAULT_MAX_ACCOUNTS: usize = 1000;
+/// A balance is only ever attested over at least one summed account
+const DEFAULT_MIN_ACCOUNTS: usize = 1;
+const DEFAULT_MAX_HTTP_HEADERS: usize = 100;
+const DEFAULT_MAX_HTTP_HEAD_BYTES: usize = 8192;
+const DEFAULT_OVER_REVEAL_THRESHOLD_BYTES: usize = 1024;
+/// Depository only: credit and loan balances are liabilities, not reserves
+const DEFAULT_INCLUDED_ACCOUNT_TYPES: &str = "depository";
+const DEFAULT_MEMORY_CHECK_INTERVAL_SECS: u64 = 5;
+const DEFAULT_RETRY_AFTER_SECS: u64 = 30;
+const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
+const DEFAULT_SESSION_OVERDUE_SECS: u64 = 600;
+const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 30;
+const DEFAULT_TCP_KEEPALIVE_INTERVAL_SECS: u64 = 10;
+const DEFAULT_PROGRESS_EXPECTED_BYTES: u64 = 40 * 1024 * 1024;
+const DEFAULT_PROGRESS_INTERVAL_MS: u64 = 1000;
+const DEFAULT_TRANSPARENCY_INTERVAL_SECS: u64 = 300;
+const DEFAULT_DELIVERY_RETRY_SECS: u64 = 60;
+const DEFAULT_ATTESTATION_PRUNE_INTERVAL_SECS: u64 = 300;
+const DEFAULT_OPERATOR_HMAC_WINDOW_SECS: u64 = 60;
+
+/// Hosts accepted by default (Plaid domains, plus [`LOOPBACK_HOSTS`] in
+/// `test-endpoints` builds). Entries starting with `.` match any subdomain.
+const DEFAULT_ALLOWED_HOSTS: &[&str] = &[
+    ".plaid.com",
+    "production.plaid.com",
+    "sandbox.plaid.com",
+    "development.plaid.com",
+];
+
+/// Local test servers, only allowed in `test-endpoints` builds
+const LOOPBACK_HOSTS: &[&str] = &["localhost", "127.0.0.1", "::1"];
+
+/// Variables only `test-endpoints` builds read; a default build refuses to start with one set
+#[cfg(not(feature = "test-endpoints"))]
+const TEST_ONLY_VARS: &[&str] = &["AUDITORZK_LOG_SENSITIVE"];
+
+/// Server configuration, read once at startup from `AUDITORZK_*` environment variables
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address the WebSocket listener binds to (`AUDITORZK_LISTEN_ADDR`)
+    pub listen_addr: String,
+    /// Server names a prover may notarize (`AUDITORZK_ALLOWED_HOSTS`, comma-separated)
+    pub allowed_hosts: Vec<String>,
+    /// Plaid environment this deployment serves (`AUDITORZK_PLAID_ENV`)
+    pub environment: Option<PlaidEnvironment>,
+    /// Append the environment byte to the signed message (`AUDITORZK_SIGN_ENVIRONMENT`)
+    pub sign_environment: bool,
+    /// Signing policy modes provers may request (`AUDITORZK_ALLOWED_POLICIES`, comma-separated)
+    pub allowed_policies: Vec<String>,
+    /// Largest accepted difference between prover and verifier clocks (`AUDITORZK_MAX_CLOCK_SKEW_SECS`)
+    pub max_clock_skew_secs: u64,
+    /// Only warn when the prover clock exceeds the skew (`AUDITORZK_CLOCK_SKEW_WARN_ONLY`)
+    pub clock_skew_warn_only: bool,
+    /// Oldest TLS version a notarized session may use (`AUDITORZK_MIN_TLS_VERSION`)
+    pub min_tls_version: TlsVersion,
+    /// Parser used to extract the balance from the response (`AUDITORZK_BALANCE_PARSER`)
+    pub balance_parser: ParserKind,
+    /// Require balances to be exact multiples of the currency's smallest unit (`AUDITORZK_STRICT_MINOR_UNITS`)
+    pub strict_minor_units: bool,
+    /// How string-typed balances are normalized before parsing (`AUDITORZK_NUMBER_LOCALE`)
+    pub number_locale: NumberLocale,
+    /// How pay stubs are annualized for `income_commitment` sessions (`AUDITORZK_INCOME_FORMULA`)
+    pub income_formula: IncomeFormula,
+    /// Plaid account types summed into the balance (`AUDITORZK_INCLUDE_ACCOUNT_TYPES`, `AUDITORZK_EXCLUDE_ACCOUNT_TYPES`)
+    pub account_filter: AccountFilter,
+    /// Refuse to sign unless every account in the response has an `account_id` (`AUDITORZK_REQUIRE_ACCOUNT_INVENTORY`)
+    pub require_account_inventory: bool,
+    /// Leave closed or errored accounts out of the balance (`AUDITORZK_EXCLUDE_INACTIVE_ACCOUNTS`)
+    pub exclude_inactive_accounts: bool,
+    /// Refuse sessions whose hello doesn't bind a recipient (`AUDITORZK_REQUIRE_RECIPIENT`)
+    pub require_recipient: bool,
+    /// Request templates a prover may declare (`AUDITORZK_REQUEST_TEMPLATES`, default all built-in)
+    pub request_templates: Vec<String>,
+    /// Refuse sessions whose committed request doesn't match a declared template (`AUDITORZK_REQUIRE_REQUEST_TEMPLATE`)
+    pub require_request_template: bool,
+    /// Refuse sessions whose hello doesn't open a received commitment to the balance (`AUDITORZK_CHECK_BALANCE_OPENING`)
+    pub check_balance_opening: bool,
+    /// Commit to each currency's total separately instead of one sum (`AUDITORZK_COMMIT_BY_CURRENCY`)
+    pub commit_by_currency: bool,
+    /// Legacy: sum several currencies into one total instead of failing (`AUDITORZK_ALLOW_MIXED_CURRENCY`)
+    pub allow_mixed_currency: bool,
+    /// Reject upgrades that don't offer the `auditorzk.v1` subprotocol (`AUDITORZK_REQUIRE_SUBPROTOCOL`)
+    pub require_subprotocol: bool,
+    /// What to do when the prover reveals more than it commits (`AUDITORZK_OVER_REVEAL_POLICY`)
+    pub over_reveal_policy: OverRevealPolicy,
+    /// Revealed-minus-committed bytes tolerated before the policy applies (`AUDITORZK_OVER_REVEAL_THRESHOLD_BYTES`)
+    pub over_reveal_threshold_bytes: usize,
+    /// Fewest transcript commitments a prover may make, 0 accepts none (`AUDITORZK_MIN_TRANSCRIPT_COMMITMENTS`)
+    pub min_transcript_commitments: usize,
+    /// Largest accepted response body in bytes (`AUDITORZK_MAX_BODY_BYTES`)
+    pub max_body_bytes: Option<usize>,
+    /// Most accounts accepted in one response, across all Items; 0 disables the cap (`AUDITORZK_MAX_ACCOUNTS`)
+    pub max_accounts: usize,
+    /// Fewest summed accounts a balance claim may cover (`AUDITORZK_MIN_ACCOUNTS`)
+    pub min_accounts: usize,
+    /// Deepest accepted JSON nesting in the response body (`AUDITORZK_MAX_JSON_DEPTH`)
+    pub max_json_depth: Option<usize>,
+    /// Most header fields in the notarized request or response (`AUDITORZK_MAX_HTTP_HEADERS`)
+    pub max_http_headers: usize,
+    /// Largest notarized request or response head in bytes (`AUDITORZK_MAX_HTTP_HEAD_BYTES`)
+    pub max_http_head_bytes: usize,
+    /// Transcript directions a balance hash commitment may cover (`AUDITORZK_COMMITMENT_DIRECTIONS`)
+    pub commitment_directions: Vec<Direction>,
+    /// Headers the notarized response must carry (`AUDITORZK_REQUIRED_HEADERS`)
+    pub required_headers: Vec<RequiredHeader>,
+    /// Plaid API version the prover's request must ask for, e.g. `2020-09-14` (`AUDITORZK_PLAID_VERSION`)
+    pub plaid_version: Option<String>,
+    /// Directory attestations and proof bundles are written to (`AUDITORZK_ATTESTATION_DIR`)
+    pub attestation_dir: PathBuf,
+    /// Write a per-session proof bundle zip (`AUDITORZK_PROOF_BUNDLE`)
+    pub proof_bundle: bool,
+    /// Per-session files kept in the attestation directory, newest first (`AUDITORZK_ATTESTATION_MAX_FILES`)
+    pub attestation_max_files: Option<usize>,
+    /// Age past which per-session files are pruned (`AUDITORZK_ATTESTATION_TTL_SECS`)
+    pub attestation_ttl_secs: Option<u64>,
+    /// Time between retention passes over the attestation directory (`AUDITORZK_ATTESTATION_PRUNE_INTERVAL_SECS`)
+    pub attestation_prune_interval_secs: u64,
+    /// Store the signed digest in attestations (`AUDITORZK_EMIT_DIGEST`)
+    pub emit_digest: bool,
+    /// Free-form tag signed into every attestation, e.g. `staging` (`AUDITORZK_ATTESTATION_LABEL`)
+    pub attestation_label: Option<String>,
+    /// Claim set attestations sign, e.g. `standard` (`AUDITORZK_CLAIM_SET`); `full` when unset
+    pub claim_set: Option<&'static ClaimSet>,
+    /// EAS schema provers can ask the attestation to be ABI-encoded for, e.g.
+    /// `string serverName, uint256 timestamp, bytes32 balanceCommitment` (`AUDITORZK_EAS_SCHEMA`)
+    pub eas_schema: Option<EasSchema>,
+    /// Whether the EAS schema was registered as revocable (`AUDITORZK_EAS_REVOCABLE`)
+    pub eas_revocable: bool,
+    /// Sinks every signed attestation is pushed to, e.g. `s3,http` (`AUDITORZK_DELIVERY_SINKS`)
+    pub delivery_sinks: Vec<SinkConfig>,
+    /// Time between attempts to deliver a spooled attestation (`AUDITORZK_DELIVERY_RETRY_SECS`)
+    pub delivery_retry_secs: u64,
+    /// Record every session's raw MPC stream for offline replay (`AUDITORZK_CAPTURE_SESSIONS`)
+    pub capture_sessions: bool,
+    /// Directory session captures are written to (`AUDITORZK_CAPTURE_DIR`, default `<attestation dir>/captures`)
+    pub capture_dir: PathBuf,
+    /// Dump every session's verifier output to the capture directory for `analyze` (`AUDITORZK_DUMP_OUTPUT`)
+    pub dump_output: bool,
+    /// Directory per-session MPC traffic distributions are dumped to (`--traffic-profile <dir>`)
+    pub traffic_profile_dir: Option<PathBuf>,
+    /// Verifier keys co-signing attestations, this verifier's among them
+    /// (`AUDITORZK_SIGNER_SET`, hex x-only keys, with `AUDITORZK_SIGNER_THRESHOLD`)
+    pub signer_set: Option<SignerSet>,
+    /// Allow transcript contents in logs and bundles (`AUDITORZK_LOG_SENSITIVE`, `test-endpoints` builds only)
+    #[cfg(feature = "test-endpoints")]
+    pub log_sensitive: bool,
+    /// RSS above which new sessions are refused (`AUDITORZK_MEMORY_SOFT_LIMIT_MB`)
+    pub memory_soft_limit_mb: Option<u64>,
+    /// RSS above which the largest session is aborted (`AUDITORZK_MEMORY_HARD_LIMIT_MB`)
+    pub memory_hard_limit_mb: Option<u64>,
+    /// How often the memory watchdog samples RSS (`AUDITORZK_MEMORY_CHECK_INTERVAL_SECS`)
+    pub memory_check_interval_secs: u64,
+    /// Buffer bytes sessions in flight may reserve between them (`AUDITORZK_MEMORY_BUDGET_MB`)
+    pub memory_budget_mb: Option<u64>,
+    /// Sessions allowed in flight at once, 0 for no cap (`AUDITORZK_MAX_SESSIONS`)
+    pub max_sessions: usize,
+    /// Sessions one client address may start per minute, 0 for no limit (`AUDITORZK_RATE_LIMIT_PER_MINUTE`)
+    pub rate_limit_per_minute: u32,
+    /// Suggested wait sent to provers refused for memory or capacity (`AUDITORZK_RETRY_AFTER_SECS`)
+    pub retry_after_secs: u64,
+    /// Wall-clock MPC seconds the deployment may use per UTC day (`AUDITORZK_DAILY_MPC_SECONDS`)
+    pub daily_mpc_seconds: Option<u64>,
+    /// Bytes the deployment may forward per UTC day (`AUDITORZK_DAILY_BYTES_FORWARDED`)
+    pub daily_bytes_forwarded: Option<u64>,
+    /// Whether an exhausted daily budget only warns or refuses sessions (`AUDITORZK_BUDGET_ENFORCEMENT`)
+    pub budget_enforcement: BudgetEnforcement,
+    /// Where the effective client address comes from (`AUDITORZK_CLIENT_IP_SOURCE`)
+    pub client_ip_source: ClientIpSource,
+    /// Proxies whose forwarding headers or PROXY preambles are believed (`AUDITORZK_TRUSTED_PROXIES`, CIDRs)
+    pub trusted_proxies: TrustedProxies,
+    /// Disable Nagle's algorithm on accepted connections (`AUDITORZK_TCP_NODELAY`)
+    pub tcp_nodelay: bool,
+    /// Idle time before TCP keepalive probes start, 0 disables (`AUDITORZK_TCP_KEEPALIVE_SECS`)
+    pub tcp_keepalive_secs: u64,
+    /// Time between TCP keepalive probes (`AUDITORZK_TCP_KEEPALIVE_INTERVAL_SECS`)
+    pub tcp_keepalive_interval_secs: u64,
+    /// Session age after which the task watchdog warns (`AUDITORZK_SESSION_OVERDUE_SECS`)
+    pub session_overdue_secs: u64,
+    /// How long shutdown waits for in-flight sessions before aborting them (`AUDITORZK_SHUTDOWN_GRACE_SECS`)
+    pub shutdown_grace_secs: u64,
+    /// Address of the operator endpoints (`/health`, `/drain`), off when unset (`AUDITORZK_ADMIN_ADDR`)
+    pub admin_addr: Option<String>,
+    /// Bearer token accepted by `POST /drain` (`AUDITORZK_OPERATOR_TOKEN`)
+    pub operator_token: Option<String>,
+    /// Shared secret operators sign `POST /drain` requests with (`AUDITORZK_OPERATOR_HMAC_SECRET`)
+    pub operator_hmac_secret: Option<String>,
+    /// How far a signed request's timestamp may be from now (`AUDITORZK_OPERATOR_HMAC_WINDOW_SECS`)
+    pub operator_hmac_window_secs: u64,
+    /// Organization key signing transparency tree heads; the log is off when unset
+    /// (`AUDITORZK_TRANSPARENCY_KEY_FILE`)
+    pub transparency_key_file: Option<PathBuf>,
+    /// Address of the public transparency feed (`AUDITORZK_TRANSPARENCY_ADDR`)
+    pub transparency_addr: Option<String>,
+    /// Time between signed tree heads (`AUDITORZK_TRANSPARENCY_INTERVAL_SECS`)
+    pub transparency_interval_secs: u64,
+    /// Send `progress` control frames to the prover (`AUDITORZK_PROGRESS_EVENTS`)
+    pub progress_events: bool,
+    /// MPC traffic (both directions) that counts as 100%, calibrated from real sessions
+    /// (`AUDITORZK_PROGRESS_EXPECTED_BYTES`)
+    pub progress_expected_bytes: u64,
+    /// Minimum time between MPC percentage frames (`AUDITORZK_PROGRESS_INTERVAL_MS`)
+    pub progress_interval_ms: u64,
+}
+
+impl ServerConfig {
+    /// Build the configuration from the process environment
+    pub fn from_env() -> Result<Self> {
+        #[cfg(not(feature = "test-endpoints"))]
+        if let Some(name) = TEST_ONLY_VARS.iter().find(|name| env::var_os(name).is_some()) {
+            anyhow::bail!("{} is only available in builds with the `test-endpoints` feature", name);
+        }
+
+        let listen_addr = env::var("AUDITORZK_LISTEN_ADDR")
+            .unwrap_or_else(|_| DEFAULT_LISTEN_ADDR.to_string());
+
+        let allowed_hosts = match env::var("AUDITORZK_ALLOWED_HOSTS") {
+            Ok(list) => parse_list(&list),
+            Err(_) => default_allowed_hosts(),
+        };
+
+        let allowed_policies = match env::var("AUDITORZK_ALLOWED_POLICIES") {
+            Ok(list) => parse_list(&list),
+            Err(_) => vec![SigningPolicy::default().mode().to_string()],
+        };
+
+        let request_templates = match env::var("AUDITORZK_REQUEST_TEMPLATES") {
+            Ok(list) => parse_list(&list),
+            Err(_) => templates::TEMPLATES.iter().map(|t| t.id.to_string()).collect(),
+        };
+
+        let required_headers = match env::var("AUDITORZK_REQUIRED_HEADERS") {
+            Ok(list) => list
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(str::parse)
+                .collect::<Result<_>>()?,
+            Err(_) => Vec::new(),
+        };
+
+        let account_type_rules = |name: &str, default: &str| -> Result<Vec<AccountTypeRule>> {
+            parse_list(&env::var(name).unwrap_or_else(|_| default.to_string()))
+                .iter()
+                .map(|rule| rule.parse())
+                .collect()
+        };
+        let account_filter = AccountFilter::new(
+            account_type_rules("AUDITORZK_INCLUDE_ACCOUNT_TYPES", DEFAULT_INCLUDED_ACCOUNT_TYPES)?,
+            account_type_rules("AUDITORZK_EXCLUDE_ACCOUNT_TYPES", "")?,
+        );
+
+        let commitment_directions = match env::var("AUDITORZK_COMMITMENT_DIRECTIONS") {
+            Ok(list) => parse_list(&list)
+                .iter()
+                .map(String::as_str)
+                .map(parse_direction)
+                .collect::<Result<_, _>>()?,
+            Err(_) => vec![Direction::Received],
+        };
+
+        let attestation_dir: PathBuf = env::var("AUDITORZK_ATTESTATION_DIR")
+            .unwrap_or_else(|_| DEFAULT_ATTESTATION_DIR.to_string())
+            .into();
+        let capture_dir = env::var("AUDITORZK_CAPTURE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| attestation_dir.join("captures"));
+
+        let signer_set = signer_set_from_env()?;
+
+        let claim_set = match env::var("AUDITORZK_CLAIM_SET") {
+            Ok(name) if !name.is_empty() => Some(ClaimSet::find(&name).ok_or_else(|| {
+                let known: Vec<&str> = CLAIM_SETS.iter().map(|set| set.name).collect();
+                anyhow::anyhow!("Unknown claim set '{}' in AUDITORZK_CLAIM_SET (known: {})", name, known.join(", "))
+            })?),
+            _ => None,
+        };
+
+        let eas_schema = match env::var("AUDITORZK_EAS_SCHEMA") {
+            Ok(schema) if !schema.trim().is_empty() => {
+                Some(schema.parse().map_err(|e| anyhow::anyhow!("Invalid AUDITORZK_EAS_SCHEMA: {}", e))?)
+            }
+            _ => None,
+        };
+
+        let delivery_sinks = parse_list(&env::var("AUDITORZK_DELIVERY_SINKS").unwrap_or_default())
+            .iter()
+            .map(|kind| SinkConfig::from_env(kind))
+            .collect::<Result<_>>()?;
+
+        let trusted_proxies = TrustedProxies(
+            parse_list(&env::var("AUDITORZK_TRUSTED_PROXIES").unwrap_or_default())
+                .iter()
+                .map(|cidr| cidr.parse())
+                .collect::<Result<_>>()?,
+        );
+
+        let config = Self {
+            listen_addr,
+            allowed_hosts,
+            environment: env_parse("AUDITORZK_PLAID_ENV")?,
+            sign_environment: env_flag("AUDITORZK_SIGN_ENVIRONMENT"),
+            allowed_policies,
+            max_clock_skew_secs: env_parse("AUDITORZK_MAX_CLOCK_SKEW_SECS")?
+                .unwrap_or(DEFAULT_MAX_CLOCK_SKEW_SECS),
+            clock_skew_warn_only: env_flag("AUDITORZK_CLOCK_SKEW_WARN_ONLY"),
+            min_tls_version: env_parse("AUDITORZK_MIN_TLS_VERSION")?.unwrap_or(TlsVersion::V1_2),
+            balance_parser: env_parse("AUDITORZK_BALANCE_PARSER")?.unwrap_or(ParserKind::Plaid),
+            strict_minor_units: env_flag("AUDITORZK_STRICT_MINOR_UNITS"),
+            number_locale: env_parse("AUDITORZK_NUMBER_LOCALE")?.unwrap_or_default(),
+            account_filter,
+            income_formula: env_parse("AUDITORZK_INCOME_FORMULA")?.unwrap_or(IncomeFormula::LatestPay),
+            require_account_inventory: env_flag("AUDITORZK_REQUIRE_ACCOUNT_INVENTORY"),
+            exclude_inactive_accounts: env_flag("AUDITORZK_EXCLUDE_INACTIVE_ACCOUNTS"),
+            require_recipient: env_flag("AUDITORZK_REQUIRE_RECIPIENT"),
+            request_templates,
+            require_request_template: env_flag("AUDITORZK_REQUIRE_REQUEST_TEMPLATE"),
+            check_balance_opening: env_flag("AUDITORZK_CHECK_BALANCE_OPENING"),
+            commit_by_currency: env_flag("AUDITORZK_COMMIT_BY_CURRENCY"),
+            allow_mixed_currency: env_flag("AUDITORZK_ALLOW_MIXED_CURRENCY"),
+            require_subprotocol: env_flag("AUDITORZK_REQUIRE_SUBPROTOCOL"),
+            over_reveal_policy: env_parse("AUDITORZK_OVER_REVEAL_POLICY")?.unwrap_or(OverRevealPolicy::Warn),
+            over_reveal_threshold_bytes: env_parse("AUDITORZK_OVER_REVEAL_THRESHOLD_BYTES")?
+                .unwrap_or(DEFAULT_OVER_REVEAL_THRESHOLD_BYTES),
+            min_transcript_commitments: env_parse("AUDITORZK_MIN_TRANSCRIPT_COMMITMENTS")?.unwrap_or(0),
+            max_body_bytes: env_parse("AUDITORZK_MAX_BODY_BYTES")?,
+            max_accounts: env_parse("AUDITORZK_MAX_ACCOUNTS")?.unwrap_or(DEFAULT_MAX_ACCOUNTS),
+            min_accounts: env_parse("AUDITORZK_MIN_ACCOUNTS")?.unwrap_or(DEFAULT_MIN_ACCOUNTS),
+            max_json_depth: env_parse("AUDITORZK_MAX_JSON_DEPTH")?,
+            max_http_headers: env_parse("AUDITORZK_MAX_HTTP_HEADERS")?.unwrap_or(DEFAULT_MAX_HTTP_HEADERS),
+            max_http_head_bytes: env_parse("AUDITORZK_MAX_HTTP_HEAD_BYTES")?.unwrap_or(DEFAULT_MAX_HTTP_HEAD_BYTES),
+            commitment_directions,
+            required_headers,
+            plaid_version: env::var("AUDITORZK_PLAID_VERSION").ok().filter(|version| !version.is_empty()),
+            attestation_dir,
+            proof_bundle: env_flag("AUDITORZK_PROOF_BUNDLE"),
+            attestation_max_files: env_parse("AUDITORZK_ATTESTATION_MAX_FILES")?,
+            attestation_ttl_secs: env_parse("AUDITORZK_ATTESTATION_TTL_SECS")?,
+            attestation_prune_interval_secs: env_parse("AUDITORZK_ATTESTATION_PRUNE_INTERVAL_SECS")?
+                .unwrap_or(DEFAULT_ATTESTATION_PRUNE_INTERVAL_SECS),
+            emit_digest: env_flag_or("AUDITORZK_EMIT_DIGEST", true),
+            attestation_label: env::var("AUDITORZK_ATTESTATION_LABEL").ok().filter(|label| !label.is_empty()),
+            claim_set,
+            eas_schema,
+            eas_revocable: env_flag_or("AUDITORZK_EAS_REVOCABLE", true),
+            delivery_sinks,
+            delivery_retry_secs: env_parse("AUDITORZK_DELIVERY_RETRY_SECS")?.unwrap_or(DEFAULT_DELIVERY_RETRY_SECS),
+            signer_set,
+            capture_sessions: env_flag("AUDITORZK_CAPTURE_SESSIONS"),
+            capture_dir,
+            dump_output: env_flag("AUDITORZK_DUMP_OUTPUT"),
+            traffic_profile_dir: None,
+            #[cfg(feature = "test-endpoints")]
+            log_sensitive: env_flag("AUDITORZK_LOG_SENSITIVE"),
+            memory_soft_limit_mb: env_parse("AUDITORZK_MEMORY_SOFT_LIMIT_MB")?,
+            memory_hard_limit_mb: env_parse("AUDITORZK_MEMORY_HARD_LIMIT_MB")?,
+            memory_check_interval_secs: env_parse("AUDITORZK_MEMORY_CHECK_INTERVAL_SECS")?
+                .unwrap_or(DEFAULT_MEMORY_CHECK_INTERVAL_SECS),
+            memory_budget_mb: env_parse("AUDITORZK_MEMORY_BUDGET_MB")?,
+            max_sessions: env_parse("AUDITORZK_MAX_SESSIONS")?.unwrap_or(0),
+            rate_limit_per_minute: env_parse("AUDITORZK_RATE_LIMIT_PER_MINUTE")?.unwrap_or(0),
+            retry_after_secs: env_parse("AUDITORZK_RETRY_AFTER_SECS")?.unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+            daily_mpc_seconds: env_parse("AUDITORZK_DAILY_MPC_SECONDS")?,
+            daily_bytes_forwarded: env_parse("AUDITORZK_DAILY_BYTES_FORWARDED")?,
+            budget_enforcement: env_parse("AUDITORZK_BUDGET_ENFORCEMENT")?.unwrap_or(BudgetEnforcement::Warn),
+            client_ip_source: env_parse("AUDITORZK_CLIENT_IP_SOURCE")?.unwrap_or_default(),
+            trusted_proxies,
+            tcp_nodelay: env_flag_or("AUDITORZK_TCP_NODELAY", true),
+            tcp_keepalive_secs: env_parse("AUDITORZK_TCP_KEEPALIVE_SECS")?
+                .unwrap_or(DEFAULT_TCP_KEEPALIVE_SECS),
+            tcp_keepalive_interval_secs: env_parse("AUDITORZK_TCP_KEEPALIVE_INTERVAL_SECS")?
+                .unwrap_or(DEFAULT_TCP_KEEPALIVE_INTERVAL_SECS),
+            session_overdue_secs: env_parse("AUDITORZK_SESSION_OVERDUE_SECS")?
+                .unwrap_or(DEFAULT_SESSION_OVERDUE_SECS),
+            shutdown_grace_secs: env_parse("AUDITORZK_SHUTDOWN_GRACE_SECS")?
+                .unwrap_or(DEFAULT_SHUTDOWN_GRACE_SECS),
+            admin_addr: env::var("AUDITORZK_ADMIN_ADDR").ok().filter(|addr| !addr.trim().is_empty()),
+            operator_token: env::var("AUDITORZK_OPERATOR_TOKEN").ok().filter(|token| !token.is_empty()),
+            operator_hmac_secret: env::var("AUDITORZK_OPERATOR_HMAC_SECRET").ok().filter(|secret| !secret.is_empty()),
+            operator_hmac_window_secs: env_parse("AUDITORZK_OPERATOR_HMAC_WINDOW_SECS")?
+                .unwrap_or(DEFAULT_OPERATOR_HMAC_WINDOW_SECS),
+            transparency_key_file: env::var("AUDITORZK_TRANSPARENCY_KEY_FILE")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            transparency_addr: env::var("AUDITORZK_TRANSPARENCY_ADDR").ok().filter(|addr| !addr.trim().is_empty()),
+            transparency_interval_secs: env_parse("AUDITORZK_TRANSPARENCY_INTERVAL_SECS")?
+                .unwrap_or(DEFAULT_TRANSPARENCY_INTERVAL_SECS),
+            progress_events: env_flag("AUDITORZK_PROGRESS_EVENTS"),
+            progress_expected_bytes: env_parse("AUDITORZK_PROGRESS_EXPECTED_BYTES")?
+                .unwrap_or(DEFAULT_PROGRESS_EXPECTED_BYTES),
+            progress_interval_ms: env_parse("AUDITORZK_PROGRESS_INTERVAL_MS")?
+                .unwrap_or(DEFAULT_PROGRESS_INTERVAL_MS),
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Sanity-check option combinations, warning on risky but legal setups
+    fn validate(&self) -> Result<()> {
+        if self.allowed_hosts.is_empty() {
+            anyhow::bail!("AUDITORZK_ALLOWED_HOSTS must contain at least one host");
+        }
+        #[cfg(not(feature = "test-endpoints"))]
+        if let Some(host) = self.allowed_hosts.iter().find(|host| LOOPBACK_HOSTS.contains(&host.as_str())) {
+            anyhow::bail!("Allowed host '{}' is only accepted in builds with the `test-endpoints` feature", host);
+        }
+
+        for mode in &self.allowed_policies {
+            if !SigningPolicy::MODES.contains(&mode.as_str()) {
+                anyhow::bail!(
+                    "Unknown signing policy '{}' in AUDITORZK_ALLOWED_POLICIES (known: {})",
+                    mode,
+                    SigningPolicy::MODES.join(", ")
+                );
+            }
+        }
+
+        for id in &self.request_templates {
+            if templates::find(id).is_none() {
+                let known: Vec<&str> = templates::TEMPLATES.iter().map(|t| t.id).collect();
+                anyhow::bail!("Unknown request template '{}' in AUDITORZK_REQUEST_TEMPLATES (known: {})", id, known.join(", "));
+            }
+        }
+        if self.require_request_template && self.request_templates.is_empty() {
+            anyhow::bail!("AUDITORZK_REQUIRE_REQUEST_TEMPLATE needs at least one template in AUDITORZK_REQUEST_TEMPLATES");
+        }
+
+        if self.min_accounts == 0 {
+            anyhow::bail!("AUDITORZK_MIN_ACCOUNTS must be at least 1");
+        }
+        if self.max_accounts > 0 && self.min_accounts > self.max_accounts {
+            anyhow::bail!(
+                "AUDITORZK_MIN_ACCOUNTS ({}) exceeds AUDITORZK_MAX_ACCOUNTS ({})",
+                self.min_accounts,
+                self.max_accounts
+            );
+        }
+
+        if self.max_http_headers == 0 || self.max_http_head_bytes == 0 {
+            anyhow::bail!("AUDITORZK_MAX_HTTP_HEADERS and AUDITORZK_MAX_HTTP_HEAD_BYTES must be at least 1");
+        }
+
+        if let (Some(soft), Some(hard)) = (self.memory_soft_limit_mb, self.memory_hard_limit_mb) {
+            if soft > hard {
+                anyhow::bail!("AUDITORZK_MEMORY_SOFT_LIMIT_MB ({}) exceeds the hard limit ({})", soft, hard);
+            }
+        }
+
+        if let Some(mb) = self.memory_budget_mb {
+            if mb.saturating_mul(1 << 20) < SESSION_BUFFER_BYTES as u64 {
+                anyhow::bail!(
+                    "AUDITORZK_MEMORY_BUDGET_MB ({}) is below one session's {} bytes of buffers",
+                    mb,
+                    SESSION_BUFFER_BYTES
+                );
+            }
+        }
+
+        for (i, sink) in self.delivery_sinks.iter().enumerate() {
+            if self.delivery_sinks[..i].iter().any(|other| other.name() == sink.name()) {
+                anyhow::bail!("Delivery sink '{}' is listed twice in AUDITORZK_DELIVERY_SINKS", sink.name());
+            }
+        }
+        if self.delivery_retry_secs == 0 {
+            anyhow::bail!("AUDITORZK_DELIVERY_RETRY_SECS must be at least 1");
+        }
+        if self.attestation_max_files == Some(0) || self.attestation_ttl_secs == Some(0) {
+            anyhow::bail!("AUDITORZK_ATTESTATION_MAX_FILES and AUDITORZK_ATTESTATION_TTL_SECS must be at least 1 when set");
+        }
+        if self.attestation_prune_interval_secs == 0 {
+            anyhow::bail!("AUDITORZK_ATTESTATION_PRUNE_INTERVAL_SECS must be at least 1");
+        }
+
+        if self.dump_output && !self.sensitive_logging() {
+            anyhow::bail!("AUDITORZK_DUMP_OUTPUT writes transcript contents and needs AUDITORZK_LOG_SENSITIVE");
+        }
+
+        if self.commitment_directions.is_empty() {
+            anyhow::bail!("AUDITORZK_COMMITMENT_DIRECTIONS must contain at least one direction");
+        }
+
+        if self.sign_environment && self.environment.is_none() {
+            anyhow::bail!("AUDITORZK_SIGN_ENVIRONMENT requires AUDITORZK_PLAID_ENV to be set");
+        }
+
+        if let Some(label) = self.attestation_label.as_ref().filter(|label| label.len() > MAX_LABEL_LEN) {
+            anyhow::bail!("AUDITORZK_ATTESTATION_LABEL is {} bytes (max {})", label.len(), MAX_LABEL_LEN);
+        }
+
+        if let Some(set) = self.claim_set {
+            let required = [
+                (self.sign_environment, SignedField::Environment, "AUDITORZK_SIGN_ENVIRONMENT"),
+                (self.require_recipient, SignedField::Recipient, "AUDITORZK_REQUIRE_RECIPIENT"),
+                (self.attestation_label.is_some(), SignedField::Label, "AUDITORZK_ATTESTATION_LABEL"),
+                (self.signer_set.is_some(), SignedField::SignerSet, "AUDITORZK_SIGNER_SET"),
+                (
+                    self.over_reveal_policy == OverRevealPolicy::Record,
+                    SignedField::OverRevealedBytes,
+                    "AUDITORZK_OVER_REVEAL_POLICY=record",
+                ),
+                (
+                    self.exclude_inactive_accounts,
+                    SignedField::InactiveAccounts,
+                    "AUDITORZK_EXCLUDE_INACTIVE_ACCOUNTS",
+                ),
+            ];
+            if let Some((_, field, var)) = required.iter().find(|(enabled, field, _)| *enabled && !set.contains(*field)) {
+                anyhow::bail!("{} needs the {} claim, which claim set '{}' does not carry", var, field.name(), set.name);
+            }
+        }
+
+        if self.client_ip_source != ClientIpSource::Peer && self.trusted_proxies.is_empty() {
+            anyhow::bail!(
+                "AUDITORZK_CLIENT_IP_SOURCE={} requires AUDITORZK_TRUSTED_PROXIES",
+                self.client_ip_source
+            );
+        }
+
+        if self.progress_events && self.progress_expected_bytes == 0 {
+            anyhow::bail!("AUDITORZK_PROGRESS_EXPECTED_BYTES must be positive");
+        }
+        if self.progress_events && self.progress_interval_ms == 0 {
+            anyhow::bail!("AUDITORZK_PROGRESS_INTERVAL_MS must be positive");
+        }
+
+        if self.daily_mpc_seconds == Some(0) || self.daily_bytes_forwarded == Some(0) {
+            anyhow::bail!("AUDITORZK_DAILY_MPC_SECONDS and AUDITORZK_DAILY_BYTES_FORWARDED must be positive");
+        }
+        if self.budget_enforcement == BudgetEnforcement::Reject
+            && self.daily_mpc_seconds.is_none()
+            && self.daily_bytes_forwarded.is_none()
+        {
+            anyhow::bail!(
+                "AUDITORZK_BUDGET_ENFORCEMENT=reject requires AUDITORZK_DAILY_MPC_SECONDS or AUDITORZK_DAILY_BYTES_FORWARDED"
+            );
+        }
+
+        if self.admin_addr.is_some() && self.operator_token.is_none() && self.operator_hmac_secret.is_none() {
+            anyhow::bail!("AUDITORZK_ADMIN_ADDR requires AUDITORZK_OPERATOR_HMAC_SECRET or AUDITORZK_OPERATOR_TOKEN");
+        }
+        if self.operator_hmac_secret.is_some() && self.operator_hmac_window_secs == 0 {
+            anyhow::bail!("AUDITORZK_OPERATOR_HMAC_WINDOW_SECS must be positive");
+        }
+        if self.transparency_addr.is_some() && self.transparency_key_file.is_none() {
+            anyhow::bail!("AUDITORZK_TRANSPARENCY_ADDR requires AUDITORZK_TRANSPARENCY_KEY_FILE");
+        }
+        if self.transparency_key_file.is_some() && self.transparency_interval_secs == 0 {
+            anyhow::bail!("AUDITORZK_TRANSPARENCY_INTERVAL_SECS must be positive");
+        }
+
+        if self.environment == Some(PlaidEnvironment::Production) {
+            for pattern in &self.allowed_hosts {
+                if host_matches(pattern, "sandbox.plaid.com") {
+                    warn!("⚠️  Production deployment allows sandbox host: {}", pattern);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Log the effective configuration
+    pub fn log_summary(&self) {
+        info!("⚙️  Configuration:");
+        info!("   Allowed hosts: {}", self.allowed_hosts.join(", "));
+        info!("   Signing policies: {}", self.allowed_policies.join(", "));
+        info!(
+            "   Max prover clock skew: {}s ({})",
+            self.max_clock_skew_secs,
+            if self.clock_skew_warn_only { "warn" } else { "reject" }
+        );
+        info!("   Minimum TLS version: {}", self.min_tls_version);
+        info!("   Balance parser: {:?}", self.balance_parser);
+        if self.allowed_policies.iter().any(|mode| mode == SigningPolicy::IncomeCommitment.mode()) {
+            info!("   Income formula: {}", self.income_formula);
+        }
+        match self.environment {
+            Some(env) => info!("   Plaid environment: {} (signed: {})", env, self.sign_environment),
+            None => info!("   Plaid environment: any"),
+        }
+        if self.strict_minor_units {
+            info!("   Strict minor-unit balances: enabled");
+        }
+        if self.number_locale != NumberLocale::Strict {
+            info!("   String balances: parsed as {} numbers", self.number_locale);
+        }
+        info!("   Account filter: {}", self.account_filter.canonical());
+        if self.require_account_inventory {
+            info!("   Account inventory: required");
+        }
+        if self.exclude_inactive_accounts {
+            info!("   Inactive accounts: excluded");
+        }
+        if self.require_recipient {
+            info!("   Recipient binding: required");
+        }
+        if self.require_request_template {
+            info!("   Request templates: required ({})", self.request_templates.join(", "));
+        }
+        if self.check_balance_opening {
+            info!("   Balance opening: required");
+        }
+        if self.commit_by_currency {
+            info!("   Balance commitment: per currency");
+        } else if self.allow_mixed_currency {
+            warn!("   Balance commitment: mixed currencies summed into one total (legacy)");
+        }
+        if self.require_subprotocol {
+            info!("   WebSocket subprotocol: {} required", crate::upgrade::SUBPROTOCOL);
+        }
+        info!("   Commitment directions: {:?}", self.commitment_directions);
+        info!(
+            "   Over-revelation: {} (threshold {} bytes)",
+            self.over_reveal_policy, self.over_reveal_threshold_bytes
+        );
+        if self.min_transcript_commitments > 0 {
+            info!("   Min transcript commitments: {}", self.min_transcript_commitments);
+        }
+        if let Some(max) = self.max_body_bytes {
+            info!("   Max response body: {} bytes", max);
+        }
+        match self.max_accounts {
+            0 => warn!("⚠️  Max accounts per response: unlimited"),
+            max => info!("   Max accounts per response: {}", max),
+        }
+        if self.min_accounts > DEFAULT_MIN_ACCOUNTS {
+            info!("   Min summed accounts: {}", self.min_accounts);
+        }
+        if let Some(max) = self.max_json_depth {
+            info!("   Max JSON depth: {}", max);
+        }
+        info!("   Max HTTP head: {} header fields, {} bytes", self.max_http_headers, self.max_http_head_bytes);
+        if !self.required_headers.is_empty() {
+            let names: Vec<&str> = self.required_headers.iter().map(|h| h.name.as_str()).collect();
+            info!("   Required response headers: {}", names.join(", "));
+        }
+        if let Some(version) = &self.plaid_version {
+            info!("   Expected Plaid API version: {}", version);
+        }
+        let plaid = ProfileSet::plaid();
+        let profiles: Vec<&str> = plaid.profiles().iter().map(|p| p.id.as_str()).collect();
+        info!("   Plaid schema profiles: {}", profiles.join(", "));
+        info!("   Attestation directory: {}", self.attestation_dir.display());
+        if self.proof_bundle {
+            info!("   Proof bundles: enabled");
+        }
+        if self.attestation_max_files.is_some() || self.attestation_ttl_secs.is_some() {
+            info!(
+                "   Attestation retention: newest {} file(s), TTL {}s, pruned every {}s",
+                self.attestation_max_files.map_or("-".to_string(), |max| max.to_string()),
+                self.attestation_ttl_secs.map_or("-".to_string(), |secs| secs.to_string()),
+                self.attestation_prune_interval_secs
+            );
+        }
+        if !self.emit_digest {
+            info!("   Signed digest in attestations: disabled");
+        }
+        if let Some(label) = &self.attestation_label {
+            info!("   Attestation label: {}", label);
+        }
+        if let Some(set) = self.claim_set {
+            info!("   Claim set: {} (id {})", set.name, hex::encode(set.id()));
+        }
+        if let Some(schema) = &self.eas_schema {
+            info!("   EAS schema: {} ({})", schema, if self.eas_revocable { "revocable" } else { "irrevocable" });
+        }
+        if !self.delivery_sinks.is_empty() {
+            let sinks: Vec<String> = self.delivery_sinks.iter().map(SinkConfig::describe).collect();
+            info!("   Attestation delivery: {}, retried every {}s", sinks.join(", "), self.delivery_retry_secs);
+        }
+        if let Some(set) = &self.signer_set {
+            info!(
+                "   Signer set: {}-of-{} (id {})",
+                set.threshold,
+                set.members.len(),
+                hex::encode(set.id())
+            );
+        }
+        if self.memory_soft_limit_mb.is_some() || self.memory_hard_limit_mb.is_some() {
+            info!(
+                "   Memory limits: soft {} MB, hard {} MB",
+                self.memory_soft_limit_mb.map_or("-".to_string(), |mb| mb.to_string()),
+                self.memory_hard_limit_mb.map_or("-".to_string(), |mb| mb.to_string())
+            );
+        }
+        if let Some(mb) = self.memory_budget_mb {
+            info!(
+                "   Memory budget: {} MB ({} sessions of {} KB buffers)",
+                mb,
+                mb.saturating_mul(1 << 20) / SESSION_BUFFER_BYTES as u64,
+                SESSION_BUFFER_BYTES / 1024
+            );
+        }
+        if self.max_sessions > 0 || self.rate_limit_per_minute > 0 {
+            let cap = |limit: u64| if limit == 0 { "-".to_string() } else { limit.to_string() };
+            info!(
+                "   Admission: max sessions {}, {} per client per minute, retry after {}s",
+                cap(self.max_sessions as u64),
+                cap(u64::from(self.rate_limit_per_minute)),
+                self.retry_after_secs
+            );
+        }
+        if self.daily_mpc_seconds.is_some() || self.daily_bytes_forwarded.is_some() {
+            info!(
+                "   Daily budget: {} MPC seconds, {} bytes forwarded ({})",
+                self.daily_mpc_seconds.map_or("-".to_string(), |secs| secs.to_string()),
+                self.daily_bytes_forwarded.map_or("-".to_string(), |bytes| bytes.to_string()),
+                self.budget_enforcement
+            );
+        }
+        if self.client_ip_source != ClientIpSource::Peer {
+            info!("   Client address: {} from trusted proxies {}", self.client_ip_source, self.trusted_proxies);
+        }
+        match self.tcp_keepalive_secs {
+            0 => info!("   TCP: nodelay {}, keepalive off", self.tcp_nodelay),
+            idle => info!(
+                "   TCP: nodelay {}, keepalive after {}s every {}s",
+                self.tcp_nodelay, idle, self.tcp_keepalive_interval_secs
+            ),
+        }
+        info!(
+            "   Sessions: overdue after {}s, {}s shutdown grace",
+            self.session_overdue_secs, self.shutdown_grace_secs
+        );
+        if let Some(addr) = &self.admin_addr {
+            info!("   Operator endpoints: {} (/health, /drain, /metrics, /usage, /capabilities)", addr);
+            let auth = match (&self.operator_hmac_secret, &self.operator_token) {
+                (Some(_), Some(_)) => "signed requests, bearer token fallback",
+                (Some(_), None) => "signed requests",
+                _ => "bearer token",
+            };
+            info!("   Operator authentication: {}", auth);
+        }
+        if self.transparency_key_file.is_some() {
+            let addr = self.transparency_addr.as_deref().unwrap_or("not served");
+            info!("   Transparency log: tree head every {}s, feed {}", self.transparency_interval_secs, addr);
+        }
+        if self.progress_events {
+            info!(
+                "   Progress events: every {}ms, {} bytes = 100%",
+                self.progress_interval_ms, self.progress_expected_bytes
+            );
+        }
+        if self.capture_sessions {
+            warn!("⚠️  Raw MPC streams will be captured to {}", self.capture_dir.display());
+        }
+        if self.dump_output {
+            warn!("⚠️  Verifier output, transcripts included, will be dumped to {}", self.capture_dir.display());
+        }
+        if let Some(dir) = &self.traffic_profile_dir {
+            info!("   Traffic profiles: {}", dir.display());
+        }
+        #[cfg(feature = "test-endpoints")]
+        if self.log_sensitive {
+            warn!("⚠️  Sensitive transcript data will be logged and bundled");
+        }
+    }
+
+    /// Whether transcript contents may be logged and bundled
+    #[cfg(feature = "test-endpoints")]
+    pub fn sensitive_logging(&self) -> bool {
+        self.log_sensitive
+    }
+
+    /// Whether transcript contents may be logged and bundled: never in a default build
+    #[cfg(not(feature = "test-endpoints"))]
+    pub fn sensitive_logging(&self) -> bool {
+        false
+    }
+}
+
+/// [`DEFAULT_ALLOWED_HOSTS`], plus the loopback hosts in `test-endpoints` builds
+fn default_allowed_hosts() -> Vec<String> {
+    let hosts = DEFAULT_ALLOWED_HOSTS.iter();
+    #[cfg(feature = "test-endpoints")]
+    let hosts = hosts.chain(LOOPBACK_HOSTS);
+    hosts.map(|host| host.to_string()).collect()
+}
+
+/// Signer set from `AUDITORZK_SIGNER_SET` and `AUDITORZK_SIGNER_THRESHOLD`, if configured
+fn signer_set_from_env() -> Result<Option<SignerSet>> {
+    let members = parse_list(&env::var("AUDITORZK_SIGNER_SET").unwrap_or_default());
+    let threshold: Option<u32> = env_parse("AUDITORZK_SIGNER_THRESHOLD")?;
+    if members.is_empty() {
+        if threshold.is_some() {
+            anyhow::bail!("AUDITORZK_SIGNER_THRESHOLD requires AUDITORZK_SIGNER_SET");
+        }
+        return Ok(None);
+    }
+
+    let threshold = threshold
+        .ok_or_else(|| anyhow::anyhow!("AUDITORZK_SIGNER_SET requires AUDITORZK_SIGNER_THRESHOLD"))?;
+    let members = members
+        .iter()
+        .map(|key| hex::decode(key).map_err(|e| anyhow::anyhow!("Invalid key '{}' in AUDITORZK_SIGNER_SET: {}", key, e)))
+        .collect::<Result<_>>()?;
+    let set = SignerSet::new(threshold, members).map_err(|e| anyhow::anyhow!("Invalid AUDITORZK_SIGNER_SET: {}", e))?;
+    Ok(Some(set))
+}
+
+/// Split a comma-separated list, dropping empty entries
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse an optional variable, failing on values that don't parse
+fn env_parse<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Interpret `1`/`true`/`yes` (case-insensitive) as enabled
+fn env_flag(name: &str) -> bool {
+    env_flag_or(name, false)
+}
+
+/// Like `env_flag`, but `default` when unset
+fn env_flag_or(name: &str, default: bool) -> bool {
+    env::var(name)
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(default)
+}
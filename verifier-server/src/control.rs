@@ -0,0 +1,682 @@
+use anyhow::{bail, Result};
+use auditorzk_attestation::message::SignedField;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tracing::warn;
+
+use crate::admission::RefusalReason;
+use crate::config::ServerConfig;
+use crate::disclosure::DisclosureReport;
+
+/// Control messages a prover may send as WebSocket text frames.
+///
+/// Binary frames carry the MPC stream; text frames carry these JSON messages,
+/// tagged by `type`. Provers that send no control messages get the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProverMessage {
+    /// Optional first message, sent before any MPC bytes
+    Hello(Hello),
+    /// Keepalive, legal in every phase and otherwise ignored
+    Ping,
+}
+
+impl ProverMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("control messages always serialize")
+    }
+
+    /// The message's `type` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Hello(_) => "hello",
+            Self::Ping => "ping",
+        }
+    }
+}
+
+/// Session phases, deciding which control messages the prover may send.
+///
+/// | Message  | PreMpc  | Mpc     | PostMpc |
+/// |----------|---------|---------|---------|
+/// | `hello`  | → Mpc   | reject  | reject  |
+/// | `ping`   | stay    | stay    | stay    |
+/// | binary   | → Mpc   | stay    | stay (trailing frames are dropped) |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionPhase {
+    /// Before any MPC bytes; the `hello` may still arrive
+    PreMpc,
+    /// MPC bytes are being forwarded
+    Mpc,
+    /// The verifier finished MPC and is validating and signing
+    PostMpc,
+}
+
+/// A control message arrived in a phase where it isn't allowed
+#[derive(Debug, thiserror::Error)]
+#[error("Control message '{message}' is not allowed {phase}")]
+pub struct PhaseError {
+    pub message: &'static str,
+    pub phase: SessionPhase,
+}
+
+impl SessionPhase {
+    /// Apply a prover control message
+    pub fn on_message(self, message: &ProverMessage) -> Result<Self, PhaseError> {
+        match (self, message) {
+            (_, ProverMessage::Ping) => Ok(self),
+            (Self::PreMpc, ProverMessage::Hello(_)) => Ok(Self::Mpc),
+            (phase, message) => Err(PhaseError { message: message.kind(), phase }),
+        }
+    }
+
+    /// Apply a binary (MPC) frame
+    pub fn on_mpc_frame(self) -> Self {
+        match self {
+            Self::PreMpc => Self::Mpc,
+            phase => phase,
+        }
+    }
+
+    /// The verifier finished the MPC protocol
+    pub fn on_mpc_complete(self) -> Self {
+        Self::PostMpc
+    }
+}
+
+impl fmt::Display for SessionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::PreMpc => "before MPC",
+            Self::Mpc => "during MPC",
+            Self::PostMpc => "after MPC",
+        })
+    }
+}
+
+/// Session parameters requested by the prover before MPC starts
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Hello {
+    /// Shape of the attestation the prover wants signed
+    #[serde(default)]
+    pub policy: SigningPolicy,
+    /// Prover's clock (Unix seconds), compared against ours to catch replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// Prover-chosen blinder for the claim commitment (hex). Without one the
+    /// verifier picks a random blinder for the session and returns it in a
+    /// `blinder` frame once the attestation is signed
+    #[serde(default, deserialize_with = "deserialize_hex")]
+    #[serde(serialize_with = "serialize_hex", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub blinder: Option<Vec<u8>>,
+    /// 32-byte value (hex) to bind the attestation to, e.g. a hash of the wallet
+    /// address or DID that will redeem it
+    #[serde(default, deserialize_with = "deserialize_hex")]
+    #[serde(serialize_with = "serialize_hex", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub recipient: Option<Vec<u8>>,
+    /// Request size the prover expects to notarize, in bytes, checked against the
+    /// verifier's limits before MPC starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_sent_bytes: Option<u64>,
+    /// Response size the prover expects, e.g. from a HEAD or dry run of its own call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_recv_bytes: Option<u64>,
+    /// Encoding the attestation is delivered in, besides the JSON file
+    #[serde(default)]
+    pub format: AttestationFormat,
+    /// Request template the prover's committed request line and headers follow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_template: Option<TemplateDeclaration>,
+    /// Opening of the prover's received-direction hash commitment over its balance,
+    /// checked when the operator sets `AUDITORZK_CHECK_BALANCE_OPENING`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_opening: Option<BalanceOpening>,
+    /// Ask for a `summary` frame after signing, reporting which transcript bytes
+    /// the verifier saw
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disclosure_report: bool,
+}
+
+/// The values a request template leaves open, declared by the prover (see
+/// [`crate::templates`])
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateDeclaration {
+    /// Template id, e.g. `plaid.balance.v1`
+    pub id: String,
+    /// The request's `content-length`, in bytes
+    pub content_length: u64,
+    /// Blinder of the sent-direction hash commitment over the request head (hex)
+    pub blinder: String,
+}
+
+/// A prover-supplied opening `(balance, blinder)` of its balance commitment (see
+/// [`crate::plaid::check_balance_opening`])
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BalanceOpening {
+    /// The balance exactly as the committed response bytes spell it, e.g. `1234.56`
+    pub balance: String,
+    /// Blinder of the received-direction hash commitment over those bytes (hex)
+    #[serde(deserialize_with = "deserialize_hex_bytes", serialize_with = "serialize_hex_bytes")]
+    #[schemars(with = "String")]
+    pub blinder: Vec<u8>,
+}
+
+impl Hello {
+    /// Whether the prover declared any expected size, and so waits for `limits`
+    pub fn declares_size(&self) -> bool {
+        self.expected_sent_bytes.is_some() || self.expected_recv_bytes.is_some()
+    }
+}
+
+/// Declared and notarized sizes further apart than this factor are flagged
+pub const SIZE_DIVERGENCE_FACTOR: u64 = 2;
+
+/// Smallest blinder accepted from a prover
+pub const MIN_BLINDER_LEN: usize = 16;
+
+/// Length of the blinder the verifier picks for a prover that sent none
+pub const SESSION_BLINDER_LEN: usize = 32;
+
+/// Exact length of a prover recipient, one signed-message field
+pub const RECIPIENT_LEN: usize = 32;
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<Vec<u8>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|text| hex::decode(text).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_hex_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+    hex::decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn serialize_hex<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    bytes.as_ref().map(hex::encode).serialize(serializer)
+}
+
+fn serialize_hex_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+/// How the signed attestation is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AttestationFormat {
+    /// Only the JSON attestation file
+    #[default]
+    Json,
+    /// Also the compact binary form, for QR codes and calldata
+    /// (see `auditorzk_attestation::compact`)
+    Compact,
+    /// Also the EAS `AttestationRequestData`, ABI-encoded for the operator's
+    /// `AUDITORZK_EAS_SCHEMA` (see `auditorzk_attestation::eas`)
+    Eas,
+}
+
+/// Attestation shape requested by the prover, e.g. `{"mode": "commitment"}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SigningPolicy {
+    /// Sign a hash commitment to the total balance
+    #[default]
+    Commitment,
+    /// Sign a hash commitment to `sum(available) - sum(holds)`, the spendable balance
+    SpendableCommitment,
+    /// Sign a hash commitment to annualized gross income from `/credit/payroll_income/get`
+    IncomeCommitment,
+}
+
+impl SigningPolicy {
+    /// Every mode this verifier knows how to sign
+    pub const MODES: &'static [&'static str] = &["commitment", "spendable_commitment", "income_commitment"];
+
+    pub fn mode(&self) -> &'static str {
+        match self {
+            Self::Commitment => "commitment",
+            Self::SpendableCommitment => "spendable_commitment",
+            Self::IncomeCommitment => "income_commitment",
+        }
+    }
+}
+
+/// Control messages the verifier sends to the prover
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VerifierMessage {
+    /// The session is being aborted
+    Error { message: String },
+    /// The prover's declared sizes fit; the session limits, in bytes. Only sent in
+    /// reply to a `hello` that declared a size
+    Limits { max_sent_bytes: usize, max_recv_bytes: usize },
+    /// The prover's MPC protocol configuration exceeds the verifier's limits; sent
+    /// before the close frame instead of leaving the prover with a dropped connection
+    ConfigMismatch {
+        /// tlsn configuration field, e.g. `max_recv_data`
+        field: Cow<'static, str>,
+        /// The prover's value, when tlsn reported it
+        #[serde(skip_serializing_if = "Option::is_none")]
+        prover_value: Option<u64>,
+        /// The most the verifier accepts
+        verifier_limit: u64,
+    },
+    /// Best-effort progress update; frames may be dropped when the prover falls behind
+    Progress {
+        stage: ProgressStage,
+        /// Coarse MPC completion estimate, only for `exchanging`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percent: Option<u8>,
+    },
+    /// The attestation was signed; what the verifier saw of the transcript. Only
+    /// sent, just before the close frame, to provers that asked for it in `hello`
+    Summary { disclosure: DisclosureReport },
+    /// The attestation was signed with a commitment under this blinder (hex),
+    /// which the verifier picked because the prover sent none. Sent before the
+    /// close frame, and needed to open the commitment later
+    Blinder {
+        #[serde(deserialize_with = "deserialize_hex_bytes", serialize_with = "serialize_hex_bytes")]
+        #[schemars(with = "String")]
+        blinder: Vec<u8>,
+    },
+    /// The session was refused before it started; sent before the close frame so
+    /// the prover can back off instead of reconnecting at once
+    RetryLater {
+        reason: RefusalReason,
+        /// Seconds to wait before reconnecting
+        retry_after: u64,
+    },
+}
+
+/// Session stages reported in `progress` frames, in the order they occur
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressStage {
+    /// Handshake accepted, waiting for MPC
+    Connected,
+    /// The first MPC bytes arrived
+    MpcStarted,
+    /// MPC bytes are flowing
+    Exchanging,
+    /// MPC finished and the TLS transcript verified
+    Verified,
+    /// The connection and response passed the operator's checks
+    Validated,
+    /// The attestation was signed
+    Signed,
+}
+
+impl VerifierMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("control messages always serialize")
+    }
+}
+
+/// Close codes the verifier ends a session with.
+///
+/// Verifier-specific reasons use the private range (4000-4999); the frame's
+/// reason text carries the error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifierClose {
+    /// The attestation was signed (1000)
+    Done,
+    /// The `hello` was rejected, e.g. a policy the operator doesn't permit (4000)
+    HandshakeRejected,
+    /// A control message arrived out of phase (4001)
+    ProtocolViolation,
+    /// MPC, validation or signing failed (4002)
+    VerificationFailed,
+    /// The session was refused for capacity or rate limiting, or shed under memory
+    /// pressure; retry later (1013)
+    TryAgainLater,
+}
+
+/// Longest reason a close frame can carry (125-byte control payload minus the code)
+const MAX_CLOSE_REASON_LEN: usize = 123;
+
+impl VerifierClose {
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Done => 1000,
+            Self::HandshakeRejected => 4000,
+            Self::ProtocolViolation => 4001,
+            Self::VerificationFailed => 4002,
+            Self::TryAgainLater => 1013,
+        }
+    }
+
+    /// Close frame with `reason`, truncated to fit
+    pub fn frame(&self, reason: &str) -> CloseFrame<'static> {
+        let mut end = reason.len().min(MAX_CLOSE_REASON_LEN);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        CloseFrame { code: self.code().into(), reason: Cow::Owned(reason[..end].to_string()) }
+    }
+}
+
+/// How the prover's side of the WebSocket ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProverDisconnect {
+    /// The prover sent a close frame, with its code and reason when present
+    Closed { code: Option<u16>, reason: String },
+    /// The connection ended without a close handshake (TCP reset, transport error)
+    Dropped(String),
+    /// The verifier stopped reading before the prover closed
+    Open,
+}
+
+impl ProverDisconnect {
+    pub fn from_frame(frame: Option<&CloseFrame<'_>>) -> Self {
+        Self::Closed {
+            code: frame.map(|frame| u16::from(frame.code)),
+            reason: frame.map(|frame| frame.reason.to_string()).unwrap_or_default(),
+        }
+    }
+
+    /// Classify how the session ended from the prover's side, or `None` if the
+    /// verifier ended it first. `mpc_complete` tells a clean goodbye after MPC
+    /// from a user cancelling mid-flow.
+    pub fn outcome(&self, mpc_complete: bool) -> Option<SessionOutcome> {
+        match self {
+            Self::Closed { code, .. } => Some(match code {
+                // Normal closure, going away, or no status code at all
+                None | Some(1000 | 1001 | 1005) if mpc_complete => SessionOutcome::Completed,
+                None | Some(1000 | 1001 | 1005) => SessionOutcome::UserCancelled,
+                // Reserved for the transport; a prover sending it was cut off
+                Some(1006) => SessionOutcome::NetworkDrop,
+                Some(_) => SessionOutcome::ProverError,
+            }),
+            Self::Dropped(_) => Some(SessionOutcome::NetworkDrop),
+            Self::Open => None,
+        }
+    }
+}
+
+impl fmt::Display for ProverDisconnect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed { code: Some(code), reason } if !reason.is_empty() => write!(f, "closed with {} ({})", code, reason),
+            Self::Closed { code: Some(code), .. } => write!(f, "closed with {}", code),
+            Self::Closed { code: None, .. } => f.write_str("closed without a code"),
+            Self::Dropped(error) => write!(f, "dropped ({})", error),
+            Self::Open => f.write_str("still open"),
+        }
+    }
+}
+
+/// How a session ended, as seen from the prover's side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionOutcome {
+    /// The prover closed normally after MPC
+    Completed,
+    /// The prover closed normally before MPC finished, e.g. the user cancelled
+    UserCancelled,
+    /// The prover closed with an error code, e.g. a protocol error or a crash it caught
+    ProverError,
+    /// The connection ended without a close handshake
+    NetworkDrop,
+}
+
+impl fmt::Display for SessionOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Completed => "completed",
+            Self::UserCancelled => "user_cancelled",
+            Self::ProverError => "prover_error",
+            Self::NetworkDrop => "network_drop",
+        })
+    }
+}
+
+/// Parse a prover's text frame
+pub fn parse_prover_message(text: &str) -> serde_json::Result<ProverMessage> {
+    serde_json::from_str(text)
+}
+
+/// Parse a verifier's text frame
+pub fn parse_verifier_message(text: &str) -> serde_json::Result<VerifierMessage> {
+    serde_json::from_str(text)
+}
+
+/// Check a requested policy against the modes the operator permits.
+///
+/// Unknown modes never get this far: the `hello` carrying one fails to parse,
+/// and the operator can't permit one either.
+pub fn validate_policy(policy: &SigningPolicy, config: &ServerConfig) -> Result<()> {
+    if !config.allowed_policies.iter().any(|mode| mode == policy.mode()) {
+        bail!(
+            "Signing policy '{}' is not permitted by this verifier (allowed: {})",
+            policy.mode(),
+            config.allowed_policies.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Reject prover blinders that are too short or obviously low-entropy.
+///
+/// A guessable blinder makes the commitment brute-forceable over plausible
+/// balances, so a buggy or malicious prover would weaken the user's privacy.
+/// This is a sanity check, not an entropy estimate: it catches constant,
+/// repeating and counting patterns, which random bytes essentially never hit.
+pub fn check_blinder(blinder: &[u8]) -> Result<()> {
+    if blinder.len() < MIN_BLINDER_LEN {
+        bail!("Blinder is {} bytes, at least {} required", blinder.len(), MIN_BLINDER_LEN);
+    }
+
+    let mut seen = [false; 256];
+    for &byte in blinder {
+        seen[usize::from(byte)] = true;
+    }
+    let distinct = seen.iter().filter(|&&s| s).count();
+    if distinct < MIN_BLINDER_LEN / 2 {
+        bail!("Blinder has only {} distinct byte values, it looks constant or repeating", distinct);
+    }
+
+    let step = blinder[1].wrapping_sub(blinder[0]);
+    if blinder.windows(2).all(|pair| pair[1].wrapping_sub(pair[0]) == step) {
+        bail!("Blinder is a counting sequence");
+    }
+
+    Ok(())
+}
+
+/// Check the prover's recipient length, and that one was sent if the operator requires it
+pub fn check_recipient(recipient: Option<&[u8]>, config: &ServerConfig) -> Result<()> {
+    match recipient {
+        Some(recipient) if recipient.len() != RECIPIENT_LEN => {
+            bail!("Recipient is {} bytes, expected exactly {}", recipient.len(), RECIPIENT_LEN)
+        }
+        None if config.require_recipient => bail!("This verifier requires a recipient in the hello"),
+        Some(_) if config.claim_set.is_some_and(|set| !set.contains(SignedField::Recipient)) => {
+            bail!("This verifier's claim set does not sign a recipient")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the verifier can deliver the requested attestation format
+pub fn check_format(format: AttestationFormat, config: &ServerConfig) -> Result<()> {
+    if format == AttestationFormat::Eas && config.eas_schema.is_none() {
+        bail!("This verifier has no EAS schema configured");
+    }
+    Ok(())
+}
+
+/// Reject a session up front whose declared sizes already exceed the MPC limits,
+/// instead of letting it fail minutes into MPC
+pub fn check_declared_size(hello: &Hello, max_sent: usize, max_recv: usize) -> Result<()> {
+    let over = |declared: Option<u64>, max: usize| declared.filter(|&bytes| bytes > max as u64);
+    if let Some(bytes) = over(hello.expected_sent_bytes, max_sent) {
+        bail!("Declared request of {} bytes exceeds the limit (sent ≤ {}, received ≤ {} bytes)", bytes, max_sent, max_recv);
+    }
+    if let Some(bytes) = over(hello.expected_recv_bytes, max_recv) {
+        bail!("Declared response of {} bytes exceeds the limit (sent ≤ {}, received ≤ {} bytes)", bytes, max_sent, max_recv);
+    }
+    Ok(())
+}
+
+/// Whether a declared size is more than [`SIZE_DIVERGENCE_FACTOR`] off the actual one
+pub fn size_diverges(declared: u64, actual: u64) -> bool {
+    actual > declared.saturating_mul(SIZE_DIVERGENCE_FACTOR) || declared > actual.saturating_mul(SIZE_DIVERGENCE_FACTOR)
+}
+
+/// Compare the prover's clock with ours, warning or bailing past the configured skew
+pub fn check_clock_skew(prover_timestamp: u64, now: u64, config: &ServerConfig) -> Result<()> {
+    let skew = prover_timestamp.abs_diff(now);
+    if skew <= config.max_clock_skew_secs {
+        return Ok(());
+    }
+
+    let direction = if prover_timestamp > now { "ahead of" } else { "behind" };
+    if config.clock_skew_warn_only {
+        warn!("⚠️  Prover clock is {}s {} verifier clock", skew, direction);
+        return Ok(());
+    }
+
+    bail!(
+        "Prover clock is {}s {} verifier clock (max skew {}s)",
+        skew, direction, config.max_clock_skew_secs
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    #[test]
+    fn control_messages_are_legal_per_phase() {
+        use SessionPhase::*;
+
+        let hello = ProverMessage::Hello(Hello::default());
+        let table = [
+            (PreMpc, &hello, Some(Mpc)),
+            (Mpc, &hello, None),
+            (PostMpc, &hello, None),
+            (PreMpc, &ProverMessage::Ping, Some(PreMpc)),
+            (Mpc, &ProverMessage::Ping, Some(Mpc)),
+            (PostMpc, &ProverMessage::Ping, Some(PostMpc)),
+        ];
+        for (phase, message, expected) in table {
+            match (phase.on_message(message), expected) {
+                (Ok(next), Some(expected)) => assert_eq!(next, expected, "{} {}", message.kind(), phase),
+                (Err(e), None) => {
+                    assert_eq!((e.message, e.phase), (message.kind(), phase));
+                    assert_eq!(e.to_string(), format!("Control message '{}' is not allowed {}", message.kind(), phase));
+                }
+                (result, _) => panic!("{} {}: {:?}", message.kind(), phase, result),
+            }
+        }
+
+        for (phase, expected) in [(PreMpc, Mpc), (Mpc, Mpc), (PostMpc, PostMpc)] {
+            assert_eq!(phase.on_mpc_frame(), expected);
+            assert_eq!(phase.on_mpc_complete(), PostMpc);
+        }
+    }
+
+    #[test]
+    fn close_codes_map_to_outcomes() {
+        use SessionOutcome::*;
+
+        let closed = |code| ProverDisconnect::Closed { code, reason: String::new() };
+        let table = [
+            // (close, outcome before MPC completed, outcome after)
+            (closed(None), Some(UserCancelled), Some(Completed)),
+            (closed(Some(1000)), Some(UserCancelled), Some(Completed)),
+            (closed(Some(1001)), Some(UserCancelled), Some(Completed)),
+            (closed(Some(1005)), Some(UserCancelled), Some(Completed)),
+            (closed(Some(1006)), Some(NetworkDrop), Some(NetworkDrop)),
+            (closed(Some(1002)), Some(ProverError), Some(ProverError)),
+            (closed(Some(1011)), Some(ProverError), Some(ProverError)),
+            (closed(Some(4000)), Some(ProverError), Some(ProverError)),
+            (ProverDisconnect::Dropped("connection reset by peer".into()), Some(NetworkDrop), Some(NetworkDrop)),
+            (ProverDisconnect::Open, None, None),
+        ];
+        for (disconnect, before, after) in table {
+            assert_eq!(disconnect.outcome(false), before, "{}", disconnect);
+            assert_eq!(disconnect.outcome(true), after, "{}", disconnect);
+        }
+
+        // The code and reason are kept from the prover's close frame
+        let frame = CloseFrame { code: CloseCode::Away, reason: "tab closed".into() };
+        let disconnect = ProverDisconnect::from_frame(Some(&frame));
+        assert_eq!(disconnect, ProverDisconnect::Closed { code: Some(1001), reason: "tab closed".into() });
+        assert_eq!(disconnect.to_string(), "closed with 1001 (tab closed)");
+        assert_eq!(ProverDisconnect::from_frame(None), closed(None));
+    }
+
+    #[test]
+    fn only_permitted_policies_are_signed() {
+        let policy = |mode: &str| {
+            let text = format!(r#"{{"type": "hello", "policy": {{"mode": "{}"}}}}"#, mode);
+            match parse_prover_message(&text) {
+                Ok(ProverMessage::Hello(hello)) => Ok(hello.policy),
+                Ok(other) => panic!("expected a hello, got {}", other.kind()),
+                Err(e) => Err(e),
+            }
+        };
+
+        // Every permitted mode is accepted
+        let mut config = ServerConfig::from_env().unwrap();
+        config.allowed_policies = SigningPolicy::MODES.iter().map(|mode| mode.to_string()).collect();
+        for mode in SigningPolicy::MODES {
+            let requested = policy(mode).unwrap();
+            assert_eq!(requested.mode(), *mode);
+            validate_policy(&requested, &config).unwrap();
+        }
+
+        // By default only `commitment` is permitted
+        let config = ServerConfig::from_env().unwrap();
+        validate_policy(&SigningPolicy::Commitment, &config).unwrap();
+        for disallowed in [SigningPolicy::SpendableCommitment, SigningPolicy::IncomeCommitment] {
+            let err = validate_policy(&disallowed, &config).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Signing policy '{}' is not permitted by this verifier (allowed: commitment)", disallowed.mode()),
+            );
+        }
+
+        // Unknown modes are rejected when the hello is parsed (and at startup, see tests/config_env.rs)
+        for unknown in ["threshold", "Commitment", ""] {
+            assert!(policy(unknown).unwrap_err().to_string().starts_with("unknown variant"));
+        }
+    }
+
+    #[test]
+    fn constant_or_counting_blinders_are_refused() {
+        let mut random = [0u8; 32];
+        OsRng.fill_bytes(&mut random);
+        check_blinder(&random).unwrap();
+
+        let err = check_blinder(&[0u8; 32]).unwrap_err();
+        assert_eq!(err.to_string(), "Blinder has only 1 distinct byte values, it looks constant or repeating");
+
+        assert!(check_blinder(&[0xab, 0xcd].repeat(16)).is_err());
+        assert_eq!(check_blinder(&(0..32).collect::<Vec<u8>>()).unwrap_err().to_string(), "Blinder is a counting sequence");
+        assert!(check_blinder(&random[..15]).is_err());
+    }
+
+    #[test]
+    fn prover_clock_must_be_within_skew() {
+        let mut config = ServerConfig::from_env().unwrap();
+        config.max_clock_skew_secs = 300;
+        let now = 1_763_308_800;
+
+        check_clock_skew(now + 300, now, &config).unwrap();
+        check_clock_skew(now - 300, now, &config).unwrap();
+
+        // A timestamp a day ahead, e.g. pre-generated for a later replay
+        let err = check_clock_skew(now + 86_400, now, &config).unwrap_err();
+        assert_eq!(err.to_string(), "Prover clock is 86400s ahead of verifier clock (max skew 300s)");
+        assert!(check_clock_skew(now - 301, now, &config).is_err());
+
+        config.clock_skew_warn_only = true;
+        check_clock_skew(now + 86_400, now, &config).unwrap();
+    }
+}
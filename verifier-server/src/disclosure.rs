@@ -0,0 +1,307 @@
+use anyhow::{bail, Result};
+use auditorzk_attestation::disclosure::{DirectionCounts, DisclosureCounts};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tlsn_core::transcript::Direction;
+use tracing::{info, warn};
+
+use crate::config::ServerConfig;
+use crate::session::SessionFacts;
+
+/// What the verifier does when the prover reveals much more of the response
+/// than the claim needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverRevealPolicy {
+    /// Don't measure
+    Off,
+    /// Log a warning and sign anyway
+    Warn,
+    /// Refuse to sign
+    Reject,
+    /// Sign and record the excess in the attestation as `over_revealed_bytes`
+    Record,
+}
+
+impl fmt::Display for OverRevealPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Warn => "warn",
+            Self::Reject => "reject",
+            Self::Record => "record",
+        })
+    }
+}
+
+impl FromStr for OverRevealPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "reject" => Ok(Self::Reject),
+            "record" => Ok(Self::Record),
+            other => bail!("Unknown over-reveal policy '{}' (expected off, warn, reject or record)", other),
+        }
+    }
+}
+
+/// How much of the received data the prover revealed versus committed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disclosure {
+    /// Received bytes revealed in plaintext to the verifier
+    pub revealed: usize,
+    /// Received bytes covered by hash commitments
+    pub committed: usize,
+}
+
+impl Disclosure {
+    pub fn of(facts: &SessionFacts) -> Self {
+        let revealed = facts.transcript.as_ref().map_or(0, |t| t.revealed_len(Direction::Received));
+        let committed = facts
+            .hashes()
+            .filter(|hash| hash.direction == Direction::Received)
+            .map(|hash| hash.len())
+            .sum();
+
+        Self { revealed, committed }
+    }
+
+    /// `revealed - committed`: bytes the user disclosed beyond the committed region
+    pub fn over_revealed(&self) -> usize {
+        self.revealed.saturating_sub(self.committed)
+    }
+}
+
+/// Apply the configured over-revelation policy.
+///
+/// Returns the excess to record in the attestation, if the policy records it.
+///
+/// A session that reveals the whole response but commits only to the first
+/// account's balance is over the default 1024-byte threshold.
+pub fn check_disclosure(facts: &SessionFacts, config: &ServerConfig) -> Result<Option<u64>> {
+    if config.over_reveal_policy == OverRevealPolicy::Off {
+        return Ok(None);
+    }
+
+    let disclosure = Disclosure::of(facts);
+    let excess = disclosure.over_revealed();
+    let significant = excess > config.over_reveal_threshold_bytes;
+    info!(
+        "🙈 Disclosure: {} bytes revealed, {} committed, {} over",
+        disclosure.revealed, disclosure.committed, excess
+    );
+
+    match config.over_reveal_policy {
+        OverRevealPolicy::Off => Ok(None),
+        OverRevealPolicy::Warn => {
+            if significant {
+                warn!(
+                    "⚠️  Prover revealed {} bytes beyond the committed region (threshold {})",
+                    excess, config.over_reveal_threshold_bytes
+                );
+            }
+            Ok(None)
+        }
+        OverRevealPolicy::Reject => {
+            if significant {
+                bail!(
+                    "Prover revealed {} bytes beyond the committed region (max {}); reveal only what the claim needs",
+                    excess,
+                    config.over_reveal_threshold_bytes
+                );
+            }
+            Ok(None)
+        }
+        OverRevealPolicy::Record => Ok(Some(excess as u64)),
+    }
+}
+
+/// What the verifier saw of one byte range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// Revealed in plaintext, whether or not also committed
+    Revealed,
+    /// Only committed to by hash
+    Committed,
+    /// Neither revealed nor committed
+    Hidden,
+}
+
+/// A `[start, end)` byte range of one direction and what the verifier saw of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Interval {
+    pub start: u64,
+    pub end: u64,
+    pub visibility: Visibility,
+}
+
+/// What the verifier saw of one direction: byte counts, and the direction split
+/// into adjacent intervals that cover it exactly
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DirectionReport {
+    pub counts: DirectionCounts,
+    pub intervals: Vec<Interval>,
+}
+
+impl DirectionReport {
+    /// Classify `[0, len)` against the revealed and committed ranges, which may be
+    /// unsorted, overlap each other or run past `len`. Revealed wins over committed.
+    pub fn classify(len: u64, revealed: &[[u64; 2]], committed: &[[u64; 2]]) -> Self {
+        let revealed = normalize(revealed, len);
+        let committed = normalize(committed, len);
+
+        // Visibility is constant between consecutive range boundaries
+        let mut boundaries: Vec<u64> = revealed.iter().chain(&committed).flatten().copied().chain([0, len]).collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut report = Self::default();
+        for pair in boundaries.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let covers = |ranges: &[[u64; 2]]| ranges.iter().any(|&[s, e]| s <= start && end <= e);
+            let visibility = if covers(&revealed) {
+                Visibility::Revealed
+            } else if covers(&committed) {
+                Visibility::Committed
+            } else {
+                Visibility::Hidden
+            };
+
+            let bytes = end - start;
+            match visibility {
+                Visibility::Revealed => report.counts.revealed += bytes,
+                Visibility::Committed => report.counts.committed += bytes,
+                Visibility::Hidden => report.counts.hidden += bytes,
+            }
+            match report.intervals.last_mut() {
+                Some(last) if last.visibility == visibility => last.end = end,
+                _ => report.intervals.push(Interval { start, end, visibility }),
+            }
+        }
+        report
+    }
+}
+
+/// What the verifier observed of the whole transcript.
+///
+/// Stored in the proof bundle, summarized as counts in the attestation's
+/// `disclosure`, and sent to provers that ask for it in the `summary` frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DisclosureReport {
+    pub sent: DirectionReport,
+    pub received: DirectionReport,
+}
+
+impl DisclosureReport {
+    /// Classify the notarized transcript from its revealed indices and hash
+    /// commitments. Without a revealed transcript its length is unknown, so each
+    /// direction is taken to end with its last committed byte
+    pub fn of(facts: &SessionFacts) -> Self {
+        let bounds = |range: &std::ops::Range<usize>| [range.start as u64, range.end as u64];
+        let direction = |direction: Direction| {
+            let committed: Vec<[u64; 2]> = facts
+                .hashes()
+                .filter(|hash| hash.direction == direction)
+                .flat_map(|hash| hash.ranges.iter().map(bounds))
+                .collect();
+
+            let (len, revealed) = match &facts.transcript {
+                Some(transcript) => {
+                    let revealed = transcript.revealed(direction).iter().map(bounds).collect();
+                    (transcript.data(direction).len() as u64, revealed)
+                }
+                None => (committed.iter().map(|&[_, end]| end).max().unwrap_or(0), Vec::new()),
+            };
+
+            DirectionReport::classify(len, &revealed, &committed)
+        };
+
+        Self { sent: direction(Direction::Sent), received: direction(Direction::Received) }
+    }
+
+    /// The counts recorded in the attestation
+    pub fn counts(&self) -> DisclosureCounts {
+        DisclosureCounts { sent: self.sent.counts, received: self.received.counts }
+    }
+}
+
+/// Sort, clip to `len` and merge overlapping or adjacent ranges
+fn normalize(ranges: &[[u64; 2]], len: u64) -> Vec<[u64; 2]> {
+    let mut clipped: Vec<[u64; 2]> =
+        ranges.iter().map(|&[start, end]| [start.min(len), end.min(len)]).filter(|[start, end]| start < end).collect();
+    clipped.sort_unstable();
+
+    let mut merged: Vec<[u64; 2]> = Vec::with_capacity(clipped.len());
+    for [start, end] in clipped {
+        match merged.last_mut() {
+            Some(last) if start <= last[1] => last[1] = last[1].max(end),
+            _ => merged.push([start, end]),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::SessionFixture;
+
+    #[test]
+    fn over_reveal_policies() {
+        let session = SessionFixture::new("sandbox.plaid.com")
+            .json_response(include_bytes!("../fixtures/balance_multi_currency.json"));
+        let facts = session.clone().build();
+        let received = &facts.transcript.as_ref().unwrap().received;
+        let start = received.windows(11).position(|w| w == b"\"current\": ").unwrap() + 11;
+        let balance = start..start + received[start..].iter().position(|&b| b == b',').unwrap();
+
+        let over = session.clone().hash_commitment(Direction::Received, vec![balance.clone()], b"blinder").build();
+        let excess = received.len() - balance.len();
+        assert!(excess > 1024);
+        assert_eq!(Disclosure::of(&over).over_revealed(), excess);
+
+        let mut config = ServerConfig::from_env().unwrap();
+        let mut verdict = |policy| {
+            config.over_reveal_policy = policy;
+            check_disclosure(&over, &config).map_err(|e| e.to_string())
+        };
+        assert_eq!(verdict(OverRevealPolicy::Off), Ok(None));
+        assert_eq!(verdict(OverRevealPolicy::Warn), Ok(None));
+        assert_eq!(verdict(OverRevealPolicy::Record), Ok(Some(excess as u64)));
+        assert_eq!(
+            verdict(OverRevealPolicy::Reject),
+            Err(format!(
+                "Prover revealed {} bytes beyond the committed region (max 1024); reveal only what the claim needs",
+                excess
+            )),
+        );
+
+        // Revealing only the committed balance passes even under `reject`
+        let minimal = session
+            .reveal(Direction::Received, [balance.clone()])
+            .hash_commitment(Direction::Received, vec![balance], b"blinder")
+            .build();
+        assert_eq!(Disclosure::of(&minimal).over_revealed(), 0);
+        check_disclosure(&minimal, &config).unwrap();
+    }
+
+    #[test]
+    fn disclosure_modes_cases() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/disclosure_modes.json")).unwrap();
+        for case in fixture["cases"].as_array().unwrap() {
+            for direction in ["sent", "received"] {
+                let input = &case[direction];
+                let ranges = |key: &str| -> Vec<[u64; 2]> { serde_json::from_value(input[key].clone()).unwrap() };
+                let report = DirectionReport::classify(input["len"].as_u64().unwrap(), &ranges("revealed"), &ranges("committed"));
+                assert_eq!(serde_json::to_value(&report).unwrap(), input["expected"], "{} {}", case["name"], direction);
+                assert_eq!(report.counts.total(), input["len"].as_u64().unwrap());
+            }
+        }
+    }
+}
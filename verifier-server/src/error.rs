@@ -0,0 +1,316 @@
+//! Crate error type, shaped like the prover-facing taxonomy.
+//!
+//! Session code returns [`Error`]; `anyhow` is only used by the binary and by
+//! the helper modules whose failures are wrapped here with the step they came
+//! from. Close codes, `config_mismatch` frames and log codes are derived from
+//! the variant alone ([`Error::close`], [`Error::control_frame`], [`Error::code`]),
+//! never from error text. Sources are kept, so [`report`] still logs the whole
+//! chain.
+//!
+//! The mapping layer's source is checked so that nothing goes back to matching
+//! error text.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::SystemTimeError;
+use tokio_tungstenite::tungstenite;
+
+use crate::balance::LimitError;
+use crate::control::{PhaseError, ProverDisconnect, SessionPhase, VerifierClose, VerifierMessage};
+use crate::http::HttpError;
+use crate::plaid::PlaidError;
+use crate::upgrade::FrameError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors from other crates and from the `anyhow` helper modules, source chain intact
+pub type BoxError = Box<dyn StdError + Send + Sync + 'static>;
+
+/// What a shed session's prover is told, in the `error` frame and the close reason
+const SHED_MESSAGE: &str = "Session shed due to memory pressure, retry later";
+
+/// Step of a session an error arose in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading and checking the `hello`
+    Handshake,
+    /// Forwarding and running MPC-TLS
+    Mpc,
+    /// Checking the connection and response against the operator's policy
+    Validation,
+    /// Extracting the claim and signing the attestation
+    Signing,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::Mpc => "mpc",
+            Self::Validation => "validation",
+            Self::Signing => "signing",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Helper checks whose `anyhow` errors are wrapped in [`Error::Check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// `hello` policy, blinder, recipient, declared sizes and clock
+    Hello,
+    /// Revealed transcript layout
+    Transcript,
+    /// Selective disclosure limits
+    Disclosure,
+    /// Operator-required response headers
+    Headers,
+    /// Request template commitment
+    RequestTemplate,
+    /// Requested endpoint of an income claim
+    Endpoint,
+    /// Proof bundle archive
+    ProofBundle,
+}
+
+impl Check {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hello => "hello",
+            Self::Transcript => "transcript",
+            Self::Disclosure => "disclosure",
+            Self::Headers => "headers",
+            Self::RequestTemplate => "request_template",
+            Self::Endpoint => "endpoint",
+            Self::ProofBundle => "proof_bundle",
+        }
+    }
+
+    fn phase(&self) -> Phase {
+        match self {
+            Self::Hello => Phase::Handshake,
+            Self::Transcript | Self::Disclosure | Self::Headers | Self::RequestTemplate | Self::Endpoint => {
+                Phase::Validation
+            }
+            Self::ProofBundle => Phase::Signing,
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A failure tagged with the session it ended
+    #[error("Session {session_id} failed")]
+    Session { session_id: String, #[source] source: Box<Error> },
+
+    /// A text frame that isn't a control message
+    #[error("Invalid control message {phase}")]
+    ControlMessage { phase: SessionPhase, #[source] source: serde_json::Error },
+    /// A control message out of phase
+    #[error(transparent)]
+    Phase(#[from] PhaseError),
+    /// A malformed length-prefixed frame
+    #[error(transparent)]
+    Frame(#[from] FrameError),
+    /// The prover went away while the verifier waited for the handshake
+    #[error("WebSocket {0} before MPC started{}", outcome_suffix(.0))]
+    LeftBeforeMpc(ProverDisconnect),
+
+    /// tlsn rejected the prover's protocol configuration. Displays like [`Error::Mpc`]
+    /// so recorded capture outcomes stay comparable; the fields carry the detail
+    #[error("Verification failed")]
+    ConfigMismatch {
+        field: &'static str,
+        prover_value: Option<u64>,
+        verifier_limit: u64,
+        #[source]
+        source: BoxError,
+    },
+    /// The verifier could not be configured
+    #[error("Failed to configure the verifier")]
+    Setup(#[source] BoxError),
+    /// MPC-TLS failed
+    #[error("Verification failed")]
+    Mpc(#[source] BoxError),
+    /// The memory watchdog shed the session (see [`crate::memory::MemoryWatchdog`])
+    #[error("{}", SHED_MESSAGE)]
+    Shed,
+
+    #[error(transparent)]
+    Plaid(#[from] PlaidError),
+    /// The notarized request or response has ambiguous or malformed HTTP framing
+    #[error(transparent)]
+    Http(#[from] HttpError),
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    /// The response could not be turned into a claim
+    #[error("Failed to extract the {claim} claim from the {provider} response")]
+    Claim { claim: &'static str, provider: &'static str, #[source] source: BoxError },
+    /// Some summed account has no `account_id` and the operator requires an inventory
+    #[error("Account inventory required but not every account in the response has an account_id")]
+    AccountInventoryRequired,
+    /// A helper check failed
+    #[error("{check} check failed")]
+    Check { check: Check, #[source] source: BoxError },
+
+    /// This verifier's key isn't in the configured signer set
+    #[error("Verifier key {pubkey} is not a member of the configured signer set")]
+    NotInSignerSet { pubkey: String },
+    #[error(transparent)]
+    Attestation(#[from] auditorzk_attestation::Error),
+    #[error("Failed to serialize the attestation")]
+    Serialize(#[source] serde_json::Error),
+    /// An artifact could not be written
+    #[error("Failed to save {artifact}")]
+    Save { artifact: &'static str, #[source] source: std::io::Error },
+    #[error("System clock is before the Unix epoch")]
+    Clock(#[from] SystemTimeError),
+
+    #[error("WebSocket error")]
+    WebSocket(#[from] tungstenite::Error),
+    #[error("Session task failed")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl Error {
+    /// Tag the error with the session it ended
+    pub fn in_session(self, session_id: &str) -> Self {
+        Self::Session { session_id: session_id.to_string(), source: Box::new(self) }
+    }
+
+    /// Wrap a helper module's error as a failed `check`
+    pub fn check(check: Check) -> impl Fn(anyhow::Error) -> Self + Copy {
+        move |source| Self::Check { check, source: source.into() }
+    }
+
+    /// Session step the error arose in
+    pub fn phase(&self) -> Phase {
+        match self {
+            Self::Session { source, .. } => source.phase(),
+            Self::ControlMessage { phase: SessionPhase::PreMpc, .. } | Self::LeftBeforeMpc(_) => Phase::Handshake,
+            Self::ControlMessage { .. } | Self::Phase(_) | Self::Frame(_) => Phase::Mpc,
+            Self::ConfigMismatch { .. }
+            | Self::Setup(_)
+            | Self::Mpc(_)
+            | Self::Shed
+            | Self::WebSocket(_)
+            | Self::Task(_) => Phase::Mpc,
+            Self::Plaid(_) | Self::Http(_) | Self::AccountInventoryRequired => Phase::Validation,
+            Self::Check { check, .. } => check.phase(),
+            Self::Limit(_)
+            | Self::Claim { .. }
+            | Self::NotInSignerSet { .. }
+            | Self::Attestation(_)
+            | Self::Serialize(_)
+            | Self::Save { .. }
+            | Self::Clock(_) => Phase::Signing,
+        }
+    }
+
+    /// Stable snake_case code for logs and metrics labels
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Session { source, .. } => source.code(),
+            Self::ControlMessage { .. } => "invalid_control_message",
+            Self::Phase(_) => "control_message_out_of_phase",
+            Self::Frame(_) => "malformed_frame",
+            Self::LeftBeforeMpc(_) => "left_before_mpc",
+            Self::ConfigMismatch { .. } => "config_mismatch",
+            Self::Setup(_) => "setup_failed",
+            Self::Mpc(_) => "mpc_failed",
+            Self::Shed => "shed_memory_pressure",
+            Self::Plaid(error) => error.code(),
+            Self::Http(error) => error.code(),
+            Self::Limit(LimitError::BodyTooLarge { .. }) => "body_too_large",
+            Self::Limit(LimitError::TooManyAccounts { .. }) => "too_many_accounts",
+            Self::Limit(LimitError::JsonTooDeep { .. }) => "json_too_deep",
+            Self::Claim { .. } => "claim_extraction_failed",
+            Self::AccountInventoryRequired => "account_inventory_required",
+            Self::Check { check: Check::Hello, .. } => "hello_rejected",
+            Self::Check { check: Check::Transcript, .. } => "transcript_invalid",
+            Self::Check { check: Check::Disclosure, .. } => "disclosure_rejected",
+            Self::Check { check: Check::Headers, .. } => "header_check_failed",
+            Self::Check { check: Check::RequestTemplate, .. } => "request_template_mismatch",
+            Self::Check { check: Check::Endpoint, .. } => "endpoint_mismatch",
+            Self::Check { check: Check::ProofBundle, .. } => "proof_bundle_failed",
+            Self::NotInSignerSet { .. } => "not_in_signer_set",
+            Self::Attestation(_) => "attestation_invalid",
+            Self::Serialize(_) => "serialize_failed",
+            Self::Save { .. } => "save_failed",
+            Self::Clock(_) => "clock_error",
+            Self::WebSocket(_) => "websocket_error",
+            Self::Task(_) => "task_failed",
+        }
+    }
+
+    /// Close code the verifier ends the session with
+    pub fn close(&self) -> VerifierClose {
+        match self {
+            Self::Session { source, .. } => source.close(),
+            Self::ControlMessage { phase: SessionPhase::PreMpc, .. }
+            | Self::LeftBeforeMpc(_)
+            | Self::Check { check: Check::Hello, .. } => VerifierClose::HandshakeRejected,
+            Self::ControlMessage { .. } | Self::Phase(_) | Self::Frame(_) => VerifierClose::ProtocolViolation,
+            Self::Shed => VerifierClose::TryAgainLater,
+            _ => VerifierClose::VerificationFailed,
+        }
+    }
+
+    /// Structured control frame sent before the close frame, if the error has one
+    pub fn control_frame(&self) -> Option<VerifierMessage> {
+        match self {
+            Self::Session { source, .. } => source.control_frame(),
+            &Self::ConfigMismatch { field, prover_value, verifier_limit, .. } => {
+                Some(VerifierMessage::ConfigMismatch { field: field.into(), prover_value, verifier_limit })
+            }
+            Self::Shed => Some(VerifierMessage::Error { message: SHED_MESSAGE.into() }),
+            _ => None,
+        }
+    }
+}
+
+/// Log an error with its whole source chain on one line, `outer: inner: ...`
+pub fn report(error: &(dyn StdError + 'static)) -> String {
+    let mut text = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        text.push_str(": ");
+        text.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    text
+}
+
+/// ` (outcome user_cancelled)`, when the disconnect has an outcome
+fn outcome_suffix(disconnect: &ProverDisconnect) -> String {
+    disconnect.outcome(false).map(|outcome| format!(" (outcome {})", outcome)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn phase_mapping_never_inspects_messages() {
+        let source = include_str!("error.rs");
+        let mapping = &source[source.find("pub fn phase(&self)").unwrap()..source.find("/// Log an error").unwrap()];
+        for forbidden in [".to_string()", "contains(", "chain()", "split_once", "starts_with", "format!"] {
+            assert!(!mapping.contains(forbidden), "mapping uses {}", forbidden);
+        }
+
+        // Nor does the session code that turns errors into close frames
+        let verifier = include_str!("verifier.rs");
+        for forbidden in ["chain()", ".to_string().contains", "downcast"] {
+            assert!(!verifier.contains(forbidden), "verifier.rs uses {}", forbidden);
+        }
+    }
+}
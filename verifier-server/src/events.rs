@@ -0,0 +1,205 @@
+//! Catalog of structured log events operators build alerts and dashboards on.
+//!
+//! Every catalogued event is logged under [`TARGET`] with an `event` field
+//! holding its name, so `RUST_LOG=auditorzk::events=info` selects exactly these.
+//! Renaming an event or a field is a breaking change for operators: add new
+//! fields instead, and list them here.
+//!
+//! Levels are tiered by how identifying the fields are:
+//!
+//! | Event | Level | Fields |
+//! |-------|-------|--------|
+//! | [`ATTESTATION_SIGNED`] | info | [`ATTESTATION_SIGNED_FIELDS`] |
+//! | [`ATTESTATION_DETAILS`] | debug | [`ATTESTATION_DETAILS_FIELDS`] |
+//! | [`ATTESTATION_SECRETS`] | trace | [`ATTESTATION_SECRETS_FIELDS`] |
+//! | [`ATTESTATION_DELIVERY`] | info, warn on failure | [`ATTESTATION_DELIVERY_FIELDS`] |
+//!
+//! Hex values that can correlate a user across systems (commitments, the
+//! message hash, the recipient) only appear at trace level.
+
+use std::time::Duration;
+use tracing::{debug, info, trace, Level};
+
+/// Log target of every catalogued event
+pub const TARGET: &str = "auditorzk::events";
+
+/// An attestation was signed; one per successful session
+pub const ATTESTATION_SIGNED: &str = "attestation_signed";
+pub const ATTESTATION_SIGNED_FIELDS: &[&str] = &["event", "session_id", "scheme", "schema_version", "duration_ms"];
+
+/// The signed fields in clear, without any hex
+pub const ATTESTATION_DETAILS: &str = "attestation_details";
+pub const ATTESTATION_DETAILS_FIELDS: &[&str] = &[
+    "event",
+    "session_id",
+    "server_name",
+    "timestamp",
+    "claim_type",
+    "formula",
+    "environment",
+    "account_count",
+    "account_filter",
+    "verifier_version",
+    "transcript_commitments",
+    "label",
+    "request_template",
+    "request_template_matched",
+    "api_version",
+];
+
+/// The signed message's identifying hex values
+pub const ATTESTATION_SECRETS: &str = "attestation_secrets";
+pub const ATTESTATION_SECRETS_FIELDS: &[&str] =
+    &["event", "session_id", "commitment", "accounts_commitment", "recipient", "message_hash", "signature"];
+
+/// One attempt to push a signed attestation to a delivery sink
+/// (see [`crate::delivery`]); `outcome` is `delivered` or an error code
+pub const ATTESTATION_DELIVERY: &str = "attestation_delivery";
+pub const ATTESTATION_DELIVERY_FIELDS: &[&str] = &["event", "session_id", "sink", "attempt", "outcome"];
+
+/// Signature scheme reported in [`ATTESTATION_SIGNED`]
+pub const SCHEME_BIP340: &str = "bip340";
+
+/// Everything logged about one signed attestation, emitted as one event per tier.
+///
+/// At info level a signing run logs exactly one event, with no hex.
+pub struct Signing<'a> {
+    pub session_id: &'a str,
+    pub schema_version: u32,
+    pub duration: Duration,
+    pub server_name: &'a str,
+    pub timestamp: u64,
+    pub claim_type: &'a str,
+    pub formula: &'a str,
+    pub environment: Option<&'a str>,
+    pub account_count: Option<u32>,
+    pub account_filter: Option<&'a str>,
+    pub verifier_version: &'a str,
+    pub transcript_commitments: usize,
+    pub label: Option<&'a str>,
+    pub request_template: Option<&'a str>,
+    pub request_template_matched: Option<bool>,
+    pub api_version: Option<&'a str>,
+    pub commitment: &'a [u8],
+    pub accounts_commitment: Option<&'a [u8]>,
+    pub recipient: Option<&'a [u8]>,
+    pub message_hash: &'a [u8],
+    pub signature: &'a str,
+}
+
+impl Signing<'_> {
+    pub fn emit(&self) {
+        info!(
+            target: TARGET,
+            event = ATTESTATION_SIGNED,
+            session_id = self.session_id,
+            scheme = SCHEME_BIP340,
+            schema_version = self.schema_version,
+            duration_ms = self.duration.as_millis() as u64,
+            "✅ Attestation signed"
+        );
+
+        debug!(
+            target: TARGET,
+            event = ATTESTATION_DETAILS,
+            session_id = self.session_id,
+            server_name = self.server_name,
+            timestamp = self.timestamp,
+            claim_type = self.claim_type,
+            formula = self.formula,
+            environment = self.environment,
+            account_count = self.account_count,
+            account_filter = self.account_filter,
+            verifier_version = self.verifier_version,
+            transcript_commitments = self.transcript_commitments,
+            label = self.label,
+            request_template = self.request_template,
+            request_template_matched = self.request_template_matched,
+            api_version = self.api_version,
+            "📝 Attestation details"
+        );
+
+        if tracing::enabled!(target: TARGET, Level::TRACE) {
+            let accounts_commitment = self.accounts_commitment.map(hex::encode);
+            let recipient = self.recipient.map(hex::encode);
+            trace!(
+                target: TARGET,
+                event = ATTESTATION_SECRETS,
+                session_id = self.session_id,
+                commitment = %hex::encode(self.commitment),
+                accounts_commitment = accounts_commitment.as_deref(),
+                recipient = recipient.as_deref(),
+                message_hash = %hex::encode(self.message_hash),
+                signature = self.signature,
+                "🔐 Attestation commitments"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn signed_event_carries_every_field_and_no_hex() {
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_ansi(false)
+            .without_time()
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let signing = Signing {
+            session_id: "0123456789abcdef0123456789abcdef",
+            schema_version: 18,
+            duration: Duration::from_millis(42),
+            server_name: "sandbox.plaid.com",
+            timestamp: 1_763_308_795,
+            claim_type: "balance",
+            formula: "current",
+            environment: Some("sandbox"),
+            account_count: Some(2),
+            account_filter: Some("include=depository;exclude="),
+            verifier_version: "auditor-zk-verifier/0.1.0",
+            transcript_commitments: 1,
+            label: None,
+            request_template: Some("plaid.balance.v1"),
+            request_template_matched: Some(true),
+            api_version: Some("2020-09-14"),
+            commitment: &[0xab; 32],
+            accounts_commitment: Some(&[0xcd; 32]),
+            recipient: Some(&[0xef; 32]),
+            message_hash: &[0x12; 32],
+            signature: "000001abcdef",
+        };
+        tracing::subscriber::with_default(subscriber, || signing.emit());
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1, "{}", output);
+        assert!(lines[0].contains(ATTESTATION_SIGNED));
+        for field in ATTESTATION_SIGNED_FIELDS {
+            assert!(lines[0].contains(&format!("{}=", field)), "missing {}: {}", field, lines[0]);
+        }
+        // No commitment, hash or recipient hex
+        for secret in ["abab", "cdcd", "efef", "1212"] {
+            assert!(!output.contains(secret), "{}", output);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! [`SessionFacts`] assembled by hand, for tests (feature `test-fixtures`).
+//!
+//! A real `VerifierOutput` only comes out of an MPC-TLS session, so tests of the
+//! checks past [`SessionFacts::from_output`] build the facts directly: the
+//! server name, raw sent and received bytes (or fixture files), the revealed
+//! ranges and synthetic commitments. The crate's own unit tests get them under
+//! `cfg(test)`; the feature exposes them to integration tests and benches.
+//!
+//! A revealed balance session validates and signs an attestation that verifies.
+//!
+//! The server name is normalized once, so a session to `Sandbox.Plaid.Com.` is
+//! validated against the allowlist and signed as the same `sandbox.plaid.com`.
+//!
+//! Validation rejects the wrong host, an old TLS version and hash commitments
+//! over the request only.
+//!
+//! Unrevealed bytes read as zeros, as in a real partial transcript, so a hidden
+//! request line can't be parsed while the disclosure report still sees it.
+//!
+//! A synthetic commitment over a request head rendered from a template opens
+//! to it, and is listed in the attestation's commitment order.
+
+use auditorzk_attestation::commitments::HASH_SHA256;
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+use tlsn_core::transcript::Direction;
+
+use crate::plaid::TlsVersion;
+use crate::session::{CommitmentFact, HashFact, SessionFacts, TranscriptFacts};
+
+/// Builder for the facts of one notarized session
+#[derive(Debug, Clone)]
+pub struct SessionFixture {
+    server_name: Option<String>,
+    tls_version: Option<TlsVersion>,
+    sent: Vec<u8>,
+    received: Vec<u8>,
+    /// Revealed ranges; the whole direction when `None`
+    sent_revealed: Option<Vec<Range<usize>>>,
+    received_revealed: Option<Vec<Range<usize>>>,
+    with_transcript: bool,
+    commitments: Vec<CommitmentFact>,
+}
+
+impl SessionFixture {
+    /// A session with `server_name`, an empty fully revealed transcript and no commitments
+    pub fn new(server_name: &str) -> Self {
+        Self {
+            server_name: Some(server_name.to_string()),
+            tls_version: None,
+            sent: Vec::new(),
+            received: Vec::new(),
+            sent_revealed: None,
+            received_revealed: None,
+            with_transcript: true,
+            commitments: Vec::new(),
+        }
+    }
+
+    pub fn without_server_name(mut self) -> Self {
+        self.server_name = None;
+        self
+    }
+
+    pub fn tls_version(mut self, version: TlsVersion) -> Self {
+        self.tls_version = Some(version);
+        self
+    }
+
+    /// Request bytes, in plaintext
+    pub fn sent(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.sent = data.into();
+        self
+    }
+
+    /// Response bytes, in plaintext
+    pub fn received(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.received = data.into();
+        self
+    }
+
+    /// A `200 OK` JSON response carrying `body`, e.g. a file from `fixtures/`
+    pub fn json_response(self, body: &[u8]) -> Self {
+        let head = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json; charset=utf-8\r\ncontent-length: {}\r\n\r\n",
+            body.len()
+        );
+        self.received([head.as_bytes(), body].concat())
+    }
+
+    /// Reveal only `ranges` of one direction; the rest reads as zeros
+    pub fn reveal(mut self, direction: Direction, ranges: impl IntoIterator<Item = Range<usize>>) -> Self {
+        let mut ranges: Vec<Range<usize>> = ranges.into_iter().filter(|range| !range.is_empty()).collect();
+        ranges.sort_by_key(|range| range.start);
+        match direction {
+            Direction::Sent => self.sent_revealed = Some(ranges),
+            Direction::Received => self.received_revealed = Some(ranges),
+        }
+        self
+    }
+
+    /// Reveal no transcript at all, leaving only commitments
+    pub fn without_transcript(mut self) -> Self {
+        self.with_transcript = false;
+        self
+    }
+
+    /// A SHA-256 hash commitment to `ranges` of the plaintext set so far,
+    /// `SHA-256(bytes in ranges || blinder)`
+    pub fn hash_commitment(self, direction: Direction, ranges: Vec<Range<usize>>, blinder: &[u8]) -> Self {
+        let data = match direction {
+            Direction::Sent => &self.sent,
+            Direction::Received => &self.received,
+        };
+        let mut hasher = Sha256::new();
+        for range in &ranges {
+            hasher.update(&data[range.clone()]);
+        }
+        hasher.update(blinder);
+        let hash = hasher.finalize().to_vec();
+
+        self.commitment(CommitmentFact::Hash(HashFact { direction, ranges, algorithm: HASH_SHA256, hash }))
+    }
+
+    pub fn encoding_commitment(self) -> Self {
+        self.commitment(CommitmentFact::Encoding)
+    }
+
+    /// Any commitment as given, e.g. one with an unsupported algorithm
+    pub fn commitment(mut self, commitment: CommitmentFact) -> Self {
+        self.commitments.push(commitment);
+        self
+    }
+
+    pub fn build(self) -> SessionFacts {
+        let transcript = self.with_transcript.then(|| {
+            let (sent, sent_revealed) = redact(self.sent, self.sent_revealed);
+            let (received, received_revealed) = redact(self.received, self.received_revealed);
+            TranscriptFacts { sent, received, sent_revealed, received_revealed }
+        });
+
+        SessionFacts {
+            server_name: self.server_name,
+            tls_version: self.tls_version,
+            transcript,
+            commitments: self.commitments,
+        }
+    }
+}
+
+/// Zero everything outside `revealed` (the whole data when `None`)
+fn redact(mut data: Vec<u8>, revealed: Option<Vec<Range<usize>>>) -> (Vec<u8>, Vec<Range<usize>>) {
+    let Some(revealed) = revealed else {
+        let all = if data.is_empty() { Vec::new() } else { vec![0..data.len()] };
+        return (data, all);
+    };
+
+    let mut next = 0;
+    for range in revealed.iter().chain([&(data.len()..data.len())]) {
+        data[next.min(range.start)..range.start].fill(0);
+        next = next.max(range.end);
+    }
+    (data, revealed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auditorzk_attestation::policy::check_server_name;
+    use auditorzk_attestation::verify_attestation;
+    use k256::schnorr::SigningKey;
+
+    use crate::attestation::issue_attestation;
+    use crate::config::ServerConfig;
+    use crate::control::{Hello, TemplateDeclaration};
+    use crate::disclosure::DisclosureReport;
+    use crate::http::HeadLimits;
+    use crate::parsed::ParsedSession;
+    use crate::plaid::{transcript_hashes, validate_plaid_connection};
+    use crate::templates::{self, match_request_template};
+
+    #[test]
+    fn fixture_issues_a_verifiable_attestation() {
+        let config = ServerConfig::from_env().unwrap();
+        let facts = SessionFixture::new("sandbox.plaid.com")
+            .sent(&b"POST /accounts/balance/get HTTP/1.1\r\nhost: sandbox.plaid.com\r\ncontent-length: 0\r\n\r\n"[..])
+            .json_response(include_bytes!("../fixtures/balance_single_account.json"))
+            .build();
+        let parsed = ParsedSession::new(&facts, &HeadLimits::from_config(&config));
+        validate_plaid_connection(&facts, &parsed, None, &config).unwrap();
+
+        let key = SigningKey::from_bytes(&Sha256::digest(b"fixture")).unwrap();
+        let disclosure = DisclosureReport::of(&facts);
+        let hello = Hello { blinder: Some(hex::decode("3f8a1c9e47d2b6057ec1a4f0935d68b2").unwrap()), ..Hello::default() };
+        let attestation = issue_attestation(&facts, &parsed, &hello, &disclosure, &config, &key, 1_763_308_800).unwrap();
+        verify_attestation(&attestation, &key.verifying_key().to_bytes()).unwrap();
+        assert_eq!(attestation.account_count, Some(1));
+        assert_eq!(attestation.disclosure.unwrap().sent.hidden, 0);
+        assert_eq!(attestation.schema_profile.unwrap().id, "plaid.balance.2020-09-14");
+    }
+
+    #[test]
+    fn server_name_is_normalized() {
+        let mut config = ServerConfig::from_env().unwrap();
+        config.allowed_hosts = vec!["sandbox.plaid.com".to_string()];
+        let facts = SessionFixture::new("Sandbox.Plaid.Com.")
+            .json_response(include_bytes!("../fixtures/balance_single_account.json"))
+            .build();
+        let parsed = ParsedSession::new(&facts, &HeadLimits::from_config(&config));
+        validate_plaid_connection(&facts, &parsed, None, &config).unwrap();
+
+        let key = SigningKey::from_bytes(&Sha256::digest(b"fixture")).unwrap();
+        let disclosure = DisclosureReport::of(&facts);
+        let hello = Hello { blinder: Some(hex::decode("3f8a1c9e47d2b6057ec1a4f0935d68b2").unwrap()), ..Hello::default() };
+        let attestation = issue_attestation(&facts, &parsed, &hello, &disclosure, &config, &key, 1_763_308_800).unwrap();
+        assert_eq!(facts.normalized_server_name().as_deref(), Some("sandbox.plaid.com"));
+        assert_eq!(attestation.server_name, "sandbox.plaid.com");
+        check_server_name(&attestation, &["sandbox.plaid.com"]).unwrap();
+    }
+
+    #[test]
+    fn connection_checks_reject_bad_fixtures() {
+        let mut config = ServerConfig::from_env().unwrap();
+        config.min_tls_version = TlsVersion::V1_3;
+        let rejected = |facts: SessionFacts| {
+            let parsed = ParsedSession::new(&facts, &HeadLimits::from_config(&config));
+            validate_plaid_connection(&facts, &parsed, None, &config).unwrap_err().code()
+        };
+        let body = include_bytes!("../fixtures/balance_single_account.json");
+
+        assert_eq!(rejected(SessionFixture::new("evil.example.com").json_response(body).build()), "host_not_allowed");
+        assert_eq!(rejected(SessionFixture::new("sandbox.plaid.com").without_server_name().build()), "missing_server_name");
+        assert_eq!(
+            rejected(SessionFixture::new("sandbox.plaid.com").tls_version(TlsVersion::V1_2).json_response(body).build()),
+            "tls_version_too_old"
+        );
+        assert_eq!(
+            rejected(
+                SessionFixture::new("sandbox.plaid.com")
+                    .tls_version(TlsVersion::V1_3)
+                    .sent(&b"POST /accounts/balance/get HTTP/1.1\r\n\r\n"[..])
+                    .hash_commitment(Direction::Sent, vec![0..10], b"blinder")
+                    .json_response(body)
+                    .build()
+            ),
+            "disallowed_commitment_direction"
+        );
+    }
+
+    #[test]
+    fn reveal_hides_the_rest_of_the_transcript() {
+        let request = b"GET /credit/payroll_income/get HTTP/1.1\r\nhost: sandbox.plaid.com\r\n\r\n";
+        let facts = SessionFixture::new("sandbox.plaid.com")
+            .sent(&request[..])
+            .reveal(Direction::Sent, [4..30])
+            .json_response(b"{}")
+            .build();
+        assert!(facts.transcript.as_ref().unwrap().sent.starts_with(b"\0\0\0\0/credit"));
+
+        let parsed = ParsedSession::new(&facts, &HeadLimits { max_headers: 16, max_head_bytes: 1024 });
+        assert!(parsed.request_target().is_err());
+        assert_eq!(parsed.body().unwrap(), b"{}");
+
+        let sent = DisclosureReport::of(&facts).sent.counts;
+        assert_eq!((sent.revealed, sent.hidden), (26, request.len() as u64 - 26));
+    }
+
+    #[test]
+    fn template_commitments_match_the_rendered_head() {
+        let config = ServerConfig::from_env().unwrap();
+        let head = templates::find("plaid.balance.v1").unwrap().render("sandbox.plaid.com", 2).unwrap();
+        let blinder = [7u8; 16];
+        let facts = SessionFixture::new("sandbox.plaid.com")
+            .sent([&head[..], b"{}"].concat())
+            .reveal(Direction::Sent, [])
+            .hash_commitment(Direction::Sent, vec![0..head.len()], &blinder)
+            .encoding_commitment()
+            .json_response(b"{}")
+            .build();
+
+        let declaration = TemplateDeclaration { id: "plaid.balance.v1".to_string(), content_length: 2, blinder: hex::encode(blinder) };
+        let matched = match_request_template(&facts, Some(&declaration), &config).unwrap().unwrap();
+        assert!(matched.matched);
+
+        let hashes = transcript_hashes(&facts, &[Direction::Sent, Direction::Received]).unwrap();
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes[0].ranges, [[0, head.len() as u64]]);
+    }
+}
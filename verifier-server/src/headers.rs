@@ -0,0 +1,231 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::http::HttpMessage;
+use crate::parsed::ParsedSession;
+use crate::plaid::PlaidError;
+
+/// Request header Plaid selects its API version by
+pub const PLAID_VERSION_HEADER: &str = "plaid-version";
+
+/// A header the notarized response must carry, e.g. `plaid-request-id` or
+/// `content-type=application/json*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredHeader {
+    /// Lowercased header name
+    pub name: String,
+    /// Expected value, where `*` matches any run of characters
+    pub pattern: Option<String>,
+}
+
+impl FromStr for RequiredHeader {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, pattern) = match s.split_once('=') {
+            Some((name, pattern)) => (name, Some(pattern.trim().to_string())),
+            None => (s, None),
+        };
+
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            bail!("Empty header name in required header '{}'", s);
+        }
+
+        Ok(Self { name, pattern })
+    }
+}
+
+impl RequiredHeader {
+    /// The header's value in `headers`, if present and matching the pattern
+    pub fn check<'a>(&self, headers: &'a BTreeMap<String, String>) -> Result<&'a String, PlaidError> {
+        let Some(value) = headers.get(&self.name) else {
+            return Err(PlaidError::MissingHeader { name: self.name.clone() });
+        };
+
+        if let Some(pattern) = &self.pattern {
+            if !wildcard_matches(pattern, value) {
+                return Err(PlaidError::HeaderMismatch {
+                    name: self.name.clone(),
+                    value: value.clone(),
+                    pattern: pattern.clone(),
+                });
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Check the response against the required headers, returning the matched values.
+///
+/// The values are recorded in the attestation's `response_headers` but not
+/// signed. The signed message is a fixed binary layout that contracts parse,
+/// and an open-ended map of header text doesn't fit it. What the signature does
+/// vouch for is that this check passed: the verifier refuses to sign otherwise.
+/// Consumers should treat the recorded values as diagnostics, e.g. a Plaid
+/// request id to quote in a support ticket.
+///
+/// A response without a required header is rejected.
+pub fn check_required_headers(
+    parsed: &ParsedSession<'_>,
+    required: &[RequiredHeader],
+) -> Result<BTreeMap<String, String>> {
+    if required.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    let headers = parsed.headers();
+    let mut matched = BTreeMap::new();
+
+    for header in required {
+        let value = header.check(headers).inspect_err(|e| warn!("❌ {}", e))?;
+        matched.insert(header.name.clone(), value.clone());
+    }
+
+    info!("✅ {} required response headers present", matched.len());
+    Ok(matched)
+}
+
+/// Check the Plaid API version the request asked for against the expected one,
+/// returning the version to record in the attestation.
+///
+/// Plaid versions its API by the `Plaid-Version` request header, not the path,
+/// and a response from another version may not have the schema the balance
+/// parsers expect. Every value of a repeated header must match. The check is
+/// skipped (and logged as such) when no version is expected, the request head
+/// wasn't revealed, or it carries no revealed version.
+pub fn check_api_version(
+    request: Option<&HttpMessage>,
+    expected: Option<&str>,
+) -> Result<Option<String>, PlaidError> {
+    let Some(expected) = expected else {
+        return Ok(None);
+    };
+    let Some(request) = request else {
+        info!("ℹ️  Request head not revealed, skipping Plaid API version check");
+        return Ok(None);
+    };
+
+    // Hidden request bytes read as zeros
+    let versions: Vec<&str> = request.values(PLAID_VERSION_HEADER).collect();
+    if versions.is_empty() || versions.iter().any(|version| version.contains('\0')) {
+        info!("ℹ️  No revealed {} request header, skipping Plaid API version check", PLAID_VERSION_HEADER);
+        return Ok(None);
+    }
+
+    if let Some(actual) = versions.into_iter().find(|&version| version != expected) {
+        warn!("❌ Request asked for Plaid API version {}, expected {}", actual, expected);
+        return Err(PlaidError::ApiVersionMismatch { expected: expected.to_string(), actual: actual.to_string() });
+    }
+
+    info!("✅ Request asked for Plaid API version {}", expected);
+    Ok(Some(expected.to_string()))
+}
+
+/// The Plaid API version the request asked for, whether or not one is expected.
+/// `None` unless every `Plaid-Version` value is revealed and they all agree
+pub fn requested_api_version(request: Option<&HttpMessage>) -> Option<&str> {
+    let mut versions = request?.values(PLAID_VERSION_HEADER);
+    let first = versions.next()?;
+    let agreed = !first.contains('\0') && versions.all(|version| version == first);
+    agreed.then_some(first)
+}
+
+/// Match `value` against a pattern where `*` stands for any run of characters
+fn wildcard_matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No `*` at all: exact match
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::SessionFixture;
+    use crate::http::{parse_request, parse_response, HeadLimits};
+
+    #[test]
+    fn required_headers_cases() {
+        let fixture: serde_json::Value = serde_json::from_str(include_str!("../fixtures/required_headers.json")).unwrap();
+        let required: Vec<RequiredHeader> =
+            fixture["required"].as_array().unwrap().iter().map(|h| h.as_str().unwrap().parse().unwrap()).collect();
+        let limits = HeadLimits { max_headers: 16, max_head_bytes: 1024 };
+        for case in fixture["cases"].as_array().unwrap() {
+            let headers = parse_response(case["response"].as_str().unwrap().as_bytes(), &limits).unwrap().headers();
+            let verdict = match required.iter().try_for_each(|header| header.check(&headers).map(|_| ())) {
+                Ok(()) => "ok",
+                Err(e) => e.code(),
+            };
+            assert_eq!(verdict, case["expect"], "{}", case["name"]);
+        }
+
+        let matches = |pattern: &str, value: &str| {
+            let header: RequiredHeader = format!("x-test={}", pattern).parse().unwrap();
+            header.check(&[("x-test".to_string(), value.to_string())].into()).is_ok()
+        };
+        assert!(matches("application/json", "application/json"));
+        assert!(!matches("application/json", "application/json; charset=utf-8"));
+        assert!(matches("application/json*", "application/json; charset=utf-8"));
+        assert!(matches("*json*", "application/json; charset=utf-8"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "a-b-b-c"));
+        assert!(!matches("a*b*c", "a-c-b"));
+        assert!(!matches("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn matched_headers_are_returned() {
+        let fixture: serde_json::Value = serde_json::from_str(include_str!("../fixtures/required_headers.json")).unwrap();
+        let required: Vec<RequiredHeader> =
+            fixture["required"].as_array().unwrap().iter().map(|h| h.as_str().unwrap().parse().unwrap()).collect();
+        let limits = HeadLimits { max_headers: 16, max_head_bytes: 1024 };
+        let response = |i: usize| fixture["cases"][i]["response"].as_str().unwrap().as_bytes().to_vec();
+
+        let facts = SessionFixture::new("sandbox.plaid.com").received(response(0)).build();
+        let matched = check_required_headers(&ParsedSession::new(&facts, &limits), &required).unwrap();
+        assert_eq!(matched["plaid-request-id"], "q7LWzQn2kbxFMJD");
+        assert_eq!(matched["content-type"], "application/json; charset=utf-8");
+
+        let facts = SessionFixture::new("sandbox.plaid.com").received(response(1)).build();
+        let err = check_required_headers(&ParsedSession::new(&facts, &limits), &required).unwrap_err();
+        let err = err.downcast_ref::<PlaidError>().unwrap();
+        assert!(matches!(err, PlaidError::MissingHeader { name } if name == "plaid-request-id"));
+    }
+
+    #[test]
+    fn plaid_version_cases() {
+        let fixture: serde_json::Value = serde_json::from_str(include_str!("../fixtures/plaid_version.json")).unwrap();
+        let expected = fixture["expected"].as_str();
+        let limits = HeadLimits { max_headers: 16, max_head_bytes: 1024 };
+        for case in fixture["cases"].as_array().unwrap() {
+            let request = parse_request(case["request"].as_str().unwrap().as_bytes(), &limits).unwrap();
+            let verdict = match check_api_version(Some(&request), expected) {
+                Ok(Some(version)) => version,
+                Ok(None) => "skipped".to_string(),
+                Err(e) => e.code().to_string(),
+            };
+            assert_eq!(verdict, case["expect"], "{}", case["name"]);
+        }
+
+        assert_eq!(check_api_version(None, expected).unwrap(), None);
+    }
+}
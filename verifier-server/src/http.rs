@@ -0,0 +1,401 @@
+//! Strict HTTP/1.1 framing for the notarized request and response.
+//!
+//! Policy checks (the endpoint, status, required headers, the body a claim is
+//! read from) all parse bytes the prover and the server chose. Anything two HTTP
+//! parsers could read differently is therefore rejected outright, never resolved
+//! one way:
+//!
+//! - line endings other than CRLF (a bare LF or CR) in the head;
+//! - obsolete line folding, a header line starting with a space or tab;
+//! - header names that aren't tokens, e.g. `Content-Length :` with a space;
+//! - more than one `Content-Length` or `Transfer-Encoding`, or (requests) `Host`;
+//! - `Content-Length` with `Transfer-Encoding`, a `Content-Length` that isn't
+//!   plain digits, or any transfer coding but a lone `chunked`;
+//! - a body longer or shorter than its framing says, e.g. a second message
+//!   smuggled behind the first.
+//!
+//! The number of header fields and the size of the head are capped by
+//! [`HeadLimits`]. Header names are lowercased once, here, and looked up
+//! lowercased everywhere else. NUL bytes are allowed in header values, since
+//! transcript bytes the prover hid read as zeros.
+//!
+//! Every case in `fixtures/http_framing.json`, malicious and benign, gets the
+//! expected verdict.
+
+use serde::de::IgnoredAny;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::config::ServerConfig;
+
+/// Separator between the HTTP head and body
+const HEAD_END: &[u8] = b"\r\n\r\n";
+
+/// Trailing bytes shown, hex encoded, in [`HttpError::TrailingData`]
+const TRAILING_PREVIEW_BYTES: usize = 8;
+
+/// Bounds on a message head
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadLimits {
+    /// Header fields allowed, not counting the start line
+    pub max_headers: usize,
+    /// Bytes allowed from the start line to the end of the last header field
+    pub max_head_bytes: usize,
+}
+
+impl HeadLimits {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self { max_headers: config.max_http_headers, max_head_bytes: config.max_http_head_bytes }
+    }
+}
+
+/// A parsed request or response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpMessage {
+    /// Request line or status line
+    pub start_line: String,
+    /// Header fields in order, with lowercased names
+    pub fields: Vec<(String, String)>,
+    /// Byte range of the body within the parsed data
+    pub body: Range<usize>,
+}
+
+impl HttpMessage {
+    /// First value of a header, by lowercased name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Header fields by name; repeated headers keep their first value
+    pub fn headers(&self) -> BTreeMap<String, String> {
+        let mut headers = BTreeMap::new();
+        for (name, value) in &self.fields {
+            headers.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        headers
+    }
+
+    /// Request target, e.g. `/accounts/balance/get` (the second word of a request line)
+    pub fn target(&self) -> Option<&str> {
+        self.start_line.split(' ').nth(1)
+    }
+
+    pub(crate) fn values(&self, name: &'static str) -> impl Iterator<Item = &str> {
+        self.fields.iter().filter(move |(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Why a transcript's HTTP framing was rejected
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HttpError {
+    #[error("No end of the HTTP head found")]
+    MissingHeadEnd,
+    #[error("HTTP head is {size} bytes (max {max})")]
+    HeadTooLarge { size: usize, max: usize },
+    #[error("HTTP head has {count} header fields (max {max})")]
+    TooManyHeaders { count: usize, max: usize },
+    #[error("Malformed {kind} line {line:?}")]
+    InvalidStartLine { kind: &'static str, line: String },
+    #[error("Line ending at byte {offset} is not CRLF")]
+    BareLineEnding { offset: usize },
+    #[error("Header line {line} is folded onto the previous one")]
+    ObsoleteFolding { line: usize },
+    #[error("Header name {name:?} is not a token")]
+    InvalidHeaderName { name: String },
+    #[error("Header {name} has control characters in its value")]
+    InvalidHeaderValue { name: String },
+    #[error("Header {name} appears more than once")]
+    DuplicateHeader { name: &'static str },
+    #[error("Invalid Content-Length {value:?}")]
+    InvalidContentLength { value: String },
+    #[error("Content-Length and Transfer-Encoding are both present")]
+    ContentLengthWithTransferEncoding,
+    #[error("Unsupported Transfer-Encoding {value:?}")]
+    UnsupportedTransferEncoding { value: String },
+    #[error("Body is {actual} bytes but its framing declares {declared}")]
+    BodyLengthMismatch { declared: usize, actual: usize },
+    #[error("Malformed chunked body at body byte {offset}")]
+    InvalidChunk { offset: usize },
+    #[error("{direction} body continues past its JSON document at transcript byte {offset} ({preview})")]
+    TrailingData { direction: &'static str, offset: usize, preview: String },
+}
+
+impl HttpError {
+    /// Stable snake_case code, see [`crate::error::Error::code`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingHeadEnd | Self::InvalidStartLine { .. } | Self::InvalidHeaderValue { .. } => "malformed_http",
+            Self::HeadTooLarge { .. } | Self::TooManyHeaders { .. } => "http_head_too_large",
+            Self::BareLineEnding { .. }
+            | Self::ObsoleteFolding { .. }
+            | Self::InvalidHeaderName { .. }
+            | Self::DuplicateHeader { .. }
+            | Self::InvalidContentLength { .. }
+            | Self::ContentLengthWithTransferEncoding
+            | Self::UnsupportedTransferEncoding { .. }
+            | Self::BodyLengthMismatch { .. }
+            | Self::InvalidChunk { .. } => "ambiguous_http_framing",
+            Self::TrailingData { .. } => "trailing_body_data",
+        }
+    }
+}
+
+/// Whether the message is a request (no framing headers means no body) or a
+/// response (no framing headers means the body runs to the end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Request,
+    Response,
+}
+
+/// Parse the notarized request: `METHOD SP target SP HTTP/1.x`, headers, body
+pub fn parse_request(data: &[u8], limits: &HeadLimits) -> Result<HttpMessage, HttpError> {
+    let message = parse_message(data, limits, Kind::Request)?;
+
+    let parts: Vec<&str> = message.start_line.split(' ').collect();
+    let valid = match parts.as_slice() {
+        [method, target, version] => {
+            is_token(method.as_bytes()) && !target.is_empty() && matches!(*version, "HTTP/1.1" | "HTTP/1.0")
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(HttpError::InvalidStartLine { kind: "request", line: message.start_line });
+    }
+    if message.values("host").count() > 1 {
+        return Err(HttpError::DuplicateHeader { name: "host" });
+    }
+    Ok(message)
+}
+
+/// Parse the notarized response: `HTTP/1.x SP status [SP reason]`, headers, body
+pub fn parse_response(data: &[u8], limits: &HeadLimits) -> Result<HttpMessage, HttpError> {
+    let message = parse_message(data, limits, Kind::Response)?;
+
+    let mut parts = message.start_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    let status = parts.next().unwrap_or_default();
+    let valid = matches!(version, "HTTP/1.1" | "HTTP/1.0") && status.len() == 3 && status.bytes().all(|b| b.is_ascii_digit());
+    if !valid {
+        return Err(HttpError::InvalidStartLine { kind: "status", line: message.start_line });
+    }
+    Ok(message)
+}
+
+fn parse_message(data: &[u8], limits: &HeadLimits, kind: Kind) -> Result<HttpMessage, HttpError> {
+    let head_len = find(data, HEAD_END);
+    let size = head_len.unwrap_or(data.len());
+    if size > limits.max_head_bytes {
+        return Err(HttpError::HeadTooLarge { size, max: limits.max_head_bytes });
+    }
+
+    // Every CR and LF in the head, including the separator's first CRLF, must pair up
+    let scanned = &data[..head_len.map_or(data.len(), |len| len + 2)];
+    for (offset, &byte) in scanned.iter().enumerate() {
+        let paired = match byte {
+            b'\r' => scanned.get(offset + 1) == Some(&b'\n'),
+            b'\n' => offset > 0 && scanned[offset - 1] == b'\r',
+            _ => true,
+        };
+        if !paired {
+            return Err(HttpError::BareLineEnding { offset });
+        }
+    }
+    let head_len = head_len.ok_or(HttpError::MissingHeadEnd)?;
+
+    let mut lines = data[..head_len].split(|&b| b == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+    let start_line = lines.next().unwrap_or_default();
+    let start_line = std::str::from_utf8(start_line)
+        .map_err(|_| HttpError::InvalidStartLine { kind: "start", line: String::from_utf8_lossy(start_line).into_owned() })?
+        .to_string();
+
+    let mut fields = Vec::new();
+    for (index, line) in lines.enumerate() {
+        if fields.len() == limits.max_headers {
+            let count = data[..head_len].split(|&b| b == b'\n').count() - 1;
+            return Err(HttpError::TooManyHeaders { count, max: limits.max_headers });
+        }
+        if line.first().is_some_and(|&b| b == b' ' || b == b'\t') {
+            return Err(HttpError::ObsoleteFolding { line: index + 2 });
+        }
+
+        let colon = line.iter().position(|&b| b == b':');
+        let name = &line[..colon.unwrap_or(line.len())];
+        if colon.is_none() || !is_token(name) {
+            return Err(HttpError::InvalidHeaderName { name: String::from_utf8_lossy(name).into_owned() });
+        }
+        let name = String::from_utf8_lossy(name).to_ascii_lowercase();
+
+        let value = trim_whitespace(&line[colon.unwrap_or_default() + 1..]);
+        if value.iter().any(|&b| (b < 0x20 && b != b'\t' && b != 0) || b == 0x7f) {
+            return Err(HttpError::InvalidHeaderValue { name });
+        }
+        fields.push((name, String::from_utf8_lossy(value).into_owned()));
+    }
+
+    let message = HttpMessage { start_line, fields, body: head_len + HEAD_END.len()..data.len() };
+    check_framing(&message, &data[message.body.clone()], kind)?;
+    Ok(message)
+}
+
+/// The JSON document a body starts with, as a range of `data`.
+///
+/// `body` is the body's range in `data`, the whole transcript direction, so
+/// offsets line up with revealed ranges. Whitespace after the first complete
+/// document is dropped; anything else, a second document or a stray byte, is
+/// [`HttpError::TrailingData`] at its transcript offset, previewed as at most
+/// 8 hex-encoded bytes. A body whose first document doesn't parse is returned
+/// whole, for the claim parser to report.
+pub fn json_document(data: &[u8], body: Range<usize>, direction: &'static str) -> Result<Range<usize>, HttpError> {
+    let mut documents = serde_json::Deserializer::from_slice(&data[body.clone()]).into_iter::<IgnoredAny>();
+    if !matches!(documents.next(), Some(Ok(_))) {
+        return Ok(body);
+    }
+    let end = body.start + documents.byte_offset();
+
+    // JSON whitespace only; form feeds and NULs (hidden bytes) are trailing data
+    match data[end..body.end].iter().position(|b| !matches!(b, b' ' | b'\t' | b'\r' | b'\n')) {
+        None => Ok(body.start..end),
+        Some(index) => {
+            let offset = end + index;
+            let preview = &data[offset..body.end.min(offset + TRAILING_PREVIEW_BYTES)];
+            Err(HttpError::TrailingData { direction, offset, preview: hex::encode(preview) })
+        }
+    }
+}
+
+/// Require exactly one unambiguous way to find the end of the body, and the body
+/// to end there
+fn check_framing(message: &HttpMessage, body: &[u8], kind: Kind) -> Result<(), HttpError> {
+    let mut lengths = message.values("content-length");
+    let mut encodings = message.values("transfer-encoding");
+    let (length, encoding) = (lengths.next(), encodings.next());
+    if lengths.next().is_some() {
+        return Err(HttpError::DuplicateHeader { name: "content-length" });
+    }
+    if encodings.next().is_some() {
+        return Err(HttpError::DuplicateHeader { name: "transfer-encoding" });
+    }
+
+    match (length, encoding) {
+        (Some(_), Some(_)) => Err(HttpError::ContentLengthWithTransferEncoding),
+        (Some(value), None) => {
+            let declared = Some(value)
+                .filter(|value| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or_else(|| HttpError::InvalidContentLength { value: value.to_string() })?;
+            if body.len() != declared {
+                return Err(HttpError::BodyLengthMismatch { declared, actual: body.len() });
+            }
+            Ok(())
+        }
+        (None, Some(value)) if value.eq_ignore_ascii_case("chunked") => check_chunked(body),
+        (None, Some(value)) => Err(HttpError::UnsupportedTransferEncoding { value: value.to_string() }),
+        (None, None) if kind == Kind::Request && !body.is_empty() => {
+            Err(HttpError::BodyLengthMismatch { declared: 0, actual: body.len() })
+        }
+        (None, None) => Ok(()),
+    }
+}
+
+/// Walk a chunked body to its last chunk, which must end the data (no trailers)
+fn check_chunked(body: &[u8]) -> Result<(), HttpError> {
+    let mut offset = 0;
+    loop {
+        let line_len = find(&body[offset..], b"\r\n").ok_or(HttpError::InvalidChunk { offset })?;
+        let line = &body[offset..offset + line_len];
+        let size = line.split(|&b| b == b';').next().unwrap_or_default();
+        let size = Some(size)
+            .filter(|size| !size.is_empty() && size.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|size| usize::from_str_radix(std::str::from_utf8(size).ok()?, 16).ok())
+            .ok_or(HttpError::InvalidChunk { offset })?;
+        offset += line_len + 2;
+        if size == 0 {
+            break;
+        }
+
+        let data_end = offset.checked_add(size).filter(|&end| end + 2 <= body.len());
+        let data_end = data_end.ok_or(HttpError::InvalidChunk { offset })?;
+        if &body[data_end..data_end + 2] != b"\r\n" {
+            return Err(HttpError::InvalidChunk { offset: data_end });
+        }
+        offset = data_end + 2;
+    }
+
+    match &body[offset..] {
+        b"\r\n" => Ok(()),
+        rest if rest.starts_with(b"\r\n") => {
+            Err(HttpError::BodyLengthMismatch { declared: offset + 2, actual: body.len() })
+        }
+        _ => Err(HttpError::InvalidChunk { offset }),
+    }
+}
+
+/// RFC 9110 token characters, as allowed in methods and header names
+fn is_token(bytes: &[u8]) -> bool {
+    !bytes.is_empty()
+        && bytes.iter().all(|&b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn trim_whitespace(mut bytes: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = bytes {
+        bytes = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = bytes {
+        bytes = rest;
+    }
+    bytes
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_framing_cases() {
+        let cases: serde_json::Value = serde_json::from_str(include_str!("../fixtures/http_framing.json")).unwrap();
+        let limits = HeadLimits { max_headers: 16, max_head_bytes: 1024 };
+        for case in cases.as_array().unwrap() {
+            let data = case["data"].as_str().unwrap().as_bytes();
+            let parsed = match case["direction"].as_str().unwrap() {
+                "request" => parse_request(data, &limits),
+                _ => parse_response(data, &limits),
+            };
+            let verdict = match &parsed {
+                Ok(_) => "ok",
+                Err(e) => e.code(),
+            };
+            assert_eq!(verdict, case["expect"], "{}: {:?}", case["name"], parsed);
+            if let (Ok(message), Some(body)) = (&parsed, case["body"].as_str()) {
+                assert_eq!(&data[message.body.clone()], body.as_bytes(), "{}", case["name"]);
+            }
+        }
+    }
+
+    #[test]
+    fn json_trailing_cases() {
+        let cases: serde_json::Value = serde_json::from_str(include_str!("../fixtures/json_trailing.json")).unwrap();
+        let limits = HeadLimits { max_headers: 16, max_head_bytes: 1024 };
+        for case in cases.as_array().unwrap() {
+            let body = case["body"].as_str().unwrap().as_bytes();
+            let data = [format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len()).as_bytes(), body].concat();
+            let response = parse_response(&data, &limits).unwrap();
+            match json_document(&data, response.body.clone(), "Response") {
+                Ok(document) => {
+                    assert_eq!(case["expect"], "ok", "{}", case["name"]);
+                    assert_eq!(&data[document], case["document"].as_str().unwrap().as_bytes(), "{}", case["name"]);
+                }
+                Err(e) => {
+                    assert_eq!(e.code(), case["expect"], "{}", case["name"]);
+                    let offset = response.body.start + case["body_offset"].as_u64().unwrap() as usize;
+                    let expected = format!("at transcript byte {} ({})", offset, case["preview"].as_str().unwrap());
+                    assert!(e.to_string().ends_with(&expected), "{}: {}", case["name"], e);
+                }
+            }
+        }
+    }
+}
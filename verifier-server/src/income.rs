@@ -0,0 +1,257 @@
+use anyhow::{bail, Context, Result};
+use auditorzk_attestation::message;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::balance::BodyLimits;
+
+/// Plaid endpoint income claims must be notarized from
+pub const PAYROLL_INCOME_PATH: &str = "/credit/payroll_income/get";
+
+/// How pay stubs are turned into an annual income figure.
+///
+/// All arithmetic is in integer cents. Per employer:
+///
+/// - `LatestPay`: gross pay of the most recent stub × pay periods per year
+/// - `TrailingAverage`: sum of all stubs' gross pay × pay periods per year ÷ number
+///   of stubs, rounded down to the cent
+///
+/// Employers are summed. The pay frequency is taken from each employer's most
+/// recent stub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncomeFormula {
+    LatestPay,
+    TrailingAverage,
+}
+
+impl IncomeFormula {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LatestPay => "latest_pay_annualized",
+            Self::TrailingAverage => "trailing_average_annualized",
+        }
+    }
+
+    /// Single-byte encoding appended to the signed message
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            Self::LatestPay => message::FORMULA_LATEST_PAY_ANNUALIZED,
+            Self::TrailingAverage => message::FORMULA_TRAILING_AVERAGE_ANNUALIZED,
+        }
+    }
+}
+
+impl fmt::Display for IncomeFormula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for IncomeFormula {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "latest_pay" | "latest_pay_annualized" => Ok(Self::LatestPay),
+            "trailing_average" | "trailing_average_annualized" => Ok(Self::TrailingAverage),
+            other => bail!("Unknown income formula '{}' (expected latest_pay or trailing_average)", other),
+        }
+    }
+}
+
+/// Annualized income extracted from a payroll income response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomeResult {
+    /// Annual gross income in cents
+    pub annual_cents: i64,
+    /// Number of distinct employers summed
+    pub employers: usize,
+}
+
+impl IncomeResult {
+    /// Canonical decimal string that gets committed, e.g. `85000.00`
+    pub fn canonical(&self) -> String {
+        format!("{}.{:02}", self.annual_cents / 100, self.annual_cents % 100)
+    }
+}
+
+#[derive(Deserialize)]
+struct IncomeResponse {
+    #[serde(default)]
+    items: Vec<IncomeItem>,
+}
+
+#[derive(Deserialize)]
+struct IncomeItem {
+    #[serde(default)]
+    payroll_income: Vec<PayrollIncome>,
+}
+
+#[derive(Deserialize)]
+struct PayrollIncome {
+    #[serde(default)]
+    pay_stubs: Vec<PayStub>,
+}
+
+#[derive(Deserialize)]
+struct PayStub {
+    #[serde(default)]
+    employer: Employer,
+    pay_period_details: PayPeriodDetails,
+}
+
+#[derive(Deserialize, Default)]
+struct Employer {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PayPeriodDetails {
+    pay_frequency: Option<String>,
+    gross_earnings: Option<serde_json::Number>,
+    pay_date: Option<String>,
+}
+
+/// Require the notarized request target to be a call to `path`
+pub fn check_endpoint(target: &str, path: &str) -> Result<()> {
+    if target.split('?').next() != Some(path) {
+        bail!("Request was to '{}', income claims require {}", target, path);
+    }
+    Ok(())
+}
+
+/// Annualize the gross pay in a `/credit/payroll_income/get` response.
+///
+/// Responses without any pay stub are rejected; stubs whose gross pay sums to
+/// zero produce a zero-income claim. Pay frequencies other than weekly,
+/// biweekly, semi-monthly and monthly can't be annualized and are rejected.
+pub fn extract_income(body: &[u8], formula: IncomeFormula, limits: &BodyLimits) -> Result<IncomeResult> {
+    limits.check_body(body)?;
+
+    let response: IncomeResponse = serde_json::from_slice(body).context("Failed to parse payroll income response")?;
+
+    // Group stubs by employer; unnamed employers are kept apart per payroll entry
+    let mut employers: BTreeMap<String, Vec<PayStub>> = BTreeMap::new();
+    for (item_index, item) in response.items.into_iter().enumerate() {
+        for (income_index, income) in item.payroll_income.into_iter().enumerate() {
+            for stub in income.pay_stubs {
+                let key = stub
+                    .employer
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("unnamed employer {}.{}", item_index + 1, income_index + 1));
+                employers.entry(key).or_default().push(stub);
+            }
+        }
+    }
+
+    if employers.is_empty() {
+        bail!("No pay stubs in payroll income response");
+    }
+
+    let employer_count = employers.len();
+    let mut annual_cents: i64 = 0;
+    for (employer, mut stubs) in employers {
+        // ISO dates sort chronologically as strings; undated stubs sort first
+        stubs.sort_by(|a, b| a.pay_period_details.pay_date.cmp(&b.pay_period_details.pay_date));
+        let latest = stubs.last().expect("employers only hold non-empty stub lists");
+
+        let frequency = latest.pay_period_details.pay_frequency.as_deref().unwrap_or("UNKNOWN");
+        let periods = periods_per_year(frequency)
+            .with_context(|| format!("Cannot annualize {} pay frequency for {}", frequency, employer))?;
+
+        let gross: Vec<i64> = stubs
+            .iter()
+            .map(|stub| {
+                let amount = stub.pay_period_details.gross_earnings.as_ref();
+                amount
+                    .with_context(|| format!("Pay stub for {} has no gross_earnings", employer))
+                    .and_then(decimal_to_cents)
+            })
+            .collect::<Result<_>>()?;
+
+        let employer_cents = match formula {
+            IncomeFormula::LatestPay => gross[gross.len() - 1].checked_mul(periods),
+            IncomeFormula::TrailingAverage => gross
+                .iter()
+                .try_fold(0i64, |sum, &cents| sum.checked_add(cents))
+                .and_then(|sum| sum.checked_mul(periods))
+                .map(|total| total / gross.len() as i64),
+        }
+        .context("Income overflows")?;
+
+        info!("💼 {}: {} stubs, {} pay, {} cents/year", employer, stubs.len(), frequency, employer_cents);
+        annual_cents = annual_cents.checked_add(employer_cents).context("Income overflows")?;
+    }
+
+    Ok(IncomeResult { annual_cents, employers: employer_count })
+}
+
+/// Pay periods per year for Plaid's pay frequency names
+fn periods_per_year(frequency: &str) -> Option<i64> {
+    match frequency.to_uppercase().as_str() {
+        "WEEKLY" => Some(52),
+        "BIWEEKLY" => Some(26),
+        "SEMI_MONTHLY" | "SEMIMONTHLY" => Some(24),
+        "MONTHLY" => Some(12),
+        _ => None,
+    }
+}
+
+/// Convert a JSON amount to integer cents without going through f64
+fn decimal_to_cents(amount: &serde_json::Number) -> Result<i64> {
+    let text = amount.to_string();
+    if text.starts_with('-') || text.contains(['e', 'E']) {
+        bail!("Gross earnings {} must be a plain non-negative decimal", text);
+    }
+
+    let (whole, fraction) = text.split_once('.').unwrap_or((text.as_str(), ""));
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > 2 {
+        bail!("Gross earnings {} has fractional cents", text);
+    }
+
+    let whole: i64 = whole.parse().with_context(|| format!("Invalid amount {}", text))?;
+    let fraction: i64 = format!("{:0<2}", fraction).parse().with_context(|| format!("Invalid amount {}", text))?;
+    whole
+        .checked_mul(100)
+        .and_then(|cents| cents.checked_add(fraction))
+        .with_context(|| format!("Amount {} overflows", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pay_is_annualized_per_employer() {
+        let limits = BodyLimits::default();
+        let income = |body: &str, formula| extract_income(body.as_bytes(), formula, &limits);
+
+        // Biweekly 2500.00 then 2600.00, monthly 1000.50 then 1200.00 (listed out of
+        // order), and an unnamed weekly employer on a second item at 300.00
+        let multiple = include_str!("../fixtures/income_multiple_employers.json");
+        let latest = income(multiple, IncomeFormula::LatestPay).unwrap();
+        assert_eq!(latest, IncomeResult { annual_cents: 2600_00 * 26 + 1200_00 * 12 + 300_00 * 52, employers: 3 });
+        assert_eq!(latest.canonical(), "97600.00");
+        let average = income(multiple, IncomeFormula::TrailingAverage).unwrap();
+        assert_eq!(average.canonical(), "95103.00");
+
+        // The most recent stub's frequency decides, and an irregular one has no annual figure
+        let irregular = include_str!("../fixtures/income_irregular_frequency.json");
+        for formula in [IncomeFormula::LatestPay, IncomeFormula::TrailingAverage] {
+            let err = income(irregular, formula).unwrap_err();
+            assert_eq!(err.to_string(), "Cannot annualize IRREGULAR pay frequency for Gig Platform");
+        }
+
+        // Zero gross pay is a zero-income claim; no pay stubs at all is an error
+        let zero = include_str!("../fixtures/income_zero.json");
+        assert_eq!(income(zero, IncomeFormula::LatestPay).unwrap().canonical(), "0.00");
+        assert_eq!(income(zero, IncomeFormula::TrailingAverage).unwrap(), IncomeResult { annual_cents: 0, employers: 1 });
+        let err = income(r#"{"items": [{"payroll_income": []}]}"#, IncomeFormula::LatestPay).unwrap_err();
+        assert_eq!(err.to_string(), "No pay stubs in payroll income response");
+    }
+}
@@ -0,0 +1,43 @@
+//! AuditorZK verifier server.
+//!
+//! The binary (`main.rs`) only wires these modules to the listener; they are a
+//! library so benchmarks can drive the parsing and signing paths directly.
+
+pub mod admin;
+pub mod admission;
+pub mod analyze;
+pub mod attestation;
+pub mod balance;
+pub mod bundle;
+pub mod capabilities;
+pub mod capture;
+pub mod config;
+pub mod control;
+pub mod delivery;
+pub mod disclosure;
+pub mod error;
+pub mod events;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod fixture;
+pub mod headers;
+pub mod hmac;
+pub mod http;
+pub mod income;
+pub mod memory;
+pub mod parsed;
+pub mod plaid;
+pub mod profiles;
+pub mod progress;
+pub mod prover_protocol;
+pub mod proxy;
+pub mod retention;
+pub mod schemas;
+pub mod session;
+pub mod shutdown;
+pub mod tasks;
+pub mod templates;
+pub mod traffic;
+pub mod transparency;
+pub mod upgrade;
+pub mod usage;
+pub mod verifier;
@@ -1,12 +1,34 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use socket2::{SockRef, TcpKeepalive};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tokio_tungstenite::accept_async;
-use tracing::{info, error};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tracing::{info, error, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-mod verifier;
-mod attestation;
-mod plaid;
+use auditor_zk_verifier::{
+    admin, admission, analyze, attestation, capabilities, capture, delivery, memory, proxy, retention, schemas,
+    shutdown, tasks, transparency, upgrade, usage, verifier,
+};
+
+use auditor_zk_verifier::balance::BalanceFormula;
+use auditor_zk_verifier::config::ServerConfig;
+use auditorzk_attestation::compact::CompactAttestation;
+use auditorzk_attestation::Attestation;
+use admin::Drain;
+use admission::Admission;
+use delivery::Delivery;
+use memory::{cgroup_limit_bytes, MemoryWatchdog, ProcSampler, SessionGuard, SessionRegistry};
+use proxy::{ClientAddr, ClientIpSource};
+use retention::RetentionPolicy;
+use tasks::TaskRegistry;
+use transparency::TransparencyLog;
+use upgrade::Framing;
+use usage::UsageLedger;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -15,24 +37,127 @@ async fn main() -> Result<()> {
         .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
         .with(tracing_subscriber::fmt::layer())
         .init();
+    tasks::install_panic_hook();
+
+    // Offline subcommands write artifacts for integrators and exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut traffic_profile = None;
+    match args.as_slice() {
+        ["schemas", "export", rest @ ..] => {
+            return schemas::export(Path::new(rest.first().copied().unwrap_or("schemas")));
+        }
+        ["vectors", "export", rest @ ..] => {
+            return export_vectors(Path::new(rest.first().copied().unwrap_or("vectors")));
+        }
+        ["capabilities"] => {
+            println!("{}", serde_json::to_string_pretty(&capabilities::Capabilities::current())?);
+            return Ok(());
+        }
+        ["replay", path] => return replay_capture(Path::new(path)).await,
+        ["analyze", "--input", path, options @ ..] => return analyze_dump(Path::new(path), options),
+        ["compact", "inspect", path] => return inspect_compact(Path::new(path)),
+        // Debug flag: also dump every session's full traffic distribution
+        ["--traffic-profile", dir] => traffic_profile = Some(PathBuf::from(dir)),
+        _ => {}
+    }
+
+    let mut config = ServerConfig::from_env()?;
+    config.traffic_profile_dir = traffic_profile;
+    let config = Arc::new(config);
 
-    let addr = "0.0.0.0:7047";
-    let listener = TcpListener::bind(addr).await?;
+    let addr = config.listen_addr.clone();
+    let listener = TcpListener::bind(&addr).await?;
 
     info!("🔐 AuditorZK Verifier Server");
     info!("================================");
     info!("📡 Listening on: {}", addr);
+    info!("🏷️  Build: {} (fingerprint {})", attestation::verifier_version(), attestation::build_fingerprint());
+    if capabilities::TEST_ENDPOINTS {
+        warn!("⚠️  Built with the test-endpoints feature: test-only affordances are enabled, not for production");
+    }
+    config.log_summary();
     info!("✅ Ready to verify TLS sessions from prover clients");
     info!("");
 
-    loop {
-        match listener.accept().await {
+    let tasks = Arc::new(TaskRegistry::default());
+    let usage = Arc::new(UsageLedger::from_config(&config));
+    let sessions = Arc::new(SessionRegistry::new(Arc::clone(&tasks), Arc::clone(&usage)));
+    let watchdog = spawn_memory_watchdog(&config, &tasks, &sessions);
+    let admission = Arc::new(Admission::from_config(&config, watchdog).with_usage(usage));
+    let transparency = TransparencyLog::from_config(&config)?;
+    tasks.spawn("task-watchdog", None, None, Arc::clone(&tasks).run_watchdog());
+
+    // SIGTERM and `POST /drain` start the same drain
+    let drain = Arc::new(Drain::default());
+    tasks.spawn("shutdown-signal", None, None, {
+        let drain = Arc::clone(&drain);
+        async move {
+            shutdown_signal().await;
+            drain.trigger();
+        }
+    });
+    if let (Some(admin_addr), Some(auth)) = (&config.admin_addr, admin::OperatorAuth::from_config(&config)) {
+        let admin_listener = TcpListener::bind(admin_addr).await?;
+        info!("🛠️  Operator endpoints on: {}", admin_addr);
+        tasks.spawn(
+            "admin-listener",
+            None,
+            None,
+            admin::serve(admin_listener, auth, Arc::clone(&drain), Arc::clone(&sessions), Arc::clone(&tasks)),
+        );
+    }
+
+    if let Some(log) = &transparency {
+        let interval = Duration::from_secs(config.transparency_interval_secs);
+        tasks.spawn("transparency-publisher", None, None, Arc::clone(log).run_publisher(interval));
+        if let Some(transparency_addr) = &config.transparency_addr {
+            let transparency_listener = TcpListener::bind(transparency_addr).await?;
+            info!("📜 Transparency feed on: {}", transparency_addr);
+            tasks.spawn(
+                "transparency-listener",
+                None,
+                None,
+                transparency::serve(transparency_listener, Arc::clone(log), Arc::clone(&tasks)),
+            );
+        }
+    }
+
+    if let Some(delivery) = Delivery::from_config(&config) {
+        tasks.spawn("attestation-delivery", None, None, delivery.run());
+    }
+
+    if let Some(retention) = RetentionPolicy::from_config(&config) {
+        let interval = Duration::from_secs(config.attestation_prune_interval_secs);
+        tasks.spawn("attestation-retention", None, None, retention.run(config.attestation_dir.clone(), interval));
+    }
+
+    let session_overdue = Duration::from_secs(config.session_overdue_secs);
+    while let Some(accepted) = drain.accept(&listener).await {
+        match accepted {
             Ok((stream, peer_addr)) => {
                 info!("📥 New connection from: {}", peer_addr);
 
-                tokio::spawn(async move {
-                    if let Err(e) = handle_client(stream, peer_addr).await {
-                        error!("❌ Error handling client {}: {}", peer_addr, e);
+                let config = Arc::clone(&config);
+                let admission = Arc::clone(&admission);
+                let transparency = transparency.clone();
+                let session = sessions.register();
+                let session_id = session.id();
+                let session_tasks = Arc::clone(&tasks);
+                let drain = Arc::clone(&drain);
+                tasks.spawn("session", Some(session_id), Some(session_overdue), async move {
+                    let client = handle_client(
+                        stream,
+                        peer_addr,
+                        config,
+                        admission,
+                        transparency,
+                        session,
+                        session_tasks,
+                        drain,
+                    );
+                    if let Err(e) = client.await {
+                        error!("❌ Error handling client {}: {:#}", peer_addr, e);
                     }
                 });
             }
@@ -41,21 +166,236 @@ async fn main() -> Result<()> {
             }
         }
     }
+
+    // Stop accepting (refusing what the backlog already holds), then let upgraded sessions finish
+    let queued = shutdown::close_listener(listener, &drain).await;
+    info!("🛑 Draining {} in-flight session(s), refused {} queued connection(s)", sessions.in_flight(), queued);
+    let aborted = tasks.shutdown(Duration::from_secs(config.shutdown_grace_secs)).await;
+    drain.report().aborted(aborted);
+    drain.report().log();
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                warn!("⚠️  Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    info!("🛑 Shutdown signal received, no longer accepting connections");
+}
+
+/// Write the signed-message test vectors as JSON and as a flat hex-only variant
+fn export_vectors(dir: &Path) -> Result<()> {
+    let vectors = auditorzk_attestation::vectors::generate();
+    let flat: Vec<_> = vectors.iter().map(|v| v.to_flat()).collect();
+
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("vectors.json"), serde_json::to_string_pretty(&vectors)?)?;
+    fs::write(dir.join("vectors.flat.json"), serde_json::to_string_pretty(&flat)?)?;
+
+    info!("🧪 Wrote {} test vectors to {}", vectors.len(), dir.display());
+    Ok(())
+}
+
+/// Expand a compact attestation back to JSON on stdout
+fn inspect_compact(path: &Path) -> Result<()> {
+    let bytes = fs::read(path)?;
+    let compact = CompactAttestation::from_bytes(&bytes)?;
+    let attestation = Attestation::from_compact(&bytes)?;
+
+    info!(
+        "📦 {}: {} byte core, {} byte metadata",
+        path.display(),
+        compact.core_len(),
+        compact.metadata.as_ref().map_or(0, Vec::len)
+    );
+    println!("{}", serde_json::to_string_pretty(&attestation)?);
+    Ok(())
+}
+
+/// Re-run validation and balance extraction on a dumped verifier output, printing the analysis
+fn analyze_dump(path: &Path, options: &[&str]) -> Result<()> {
+    let config = ServerConfig::from_env()?;
+    let (mut formula, mut blinder) = (BalanceFormula::Current, None);
+    for option in options.chunks(2) {
+        match option {
+            ["--formula", "current"] => formula = BalanceFormula::Current,
+            ["--formula", "available_minus_holds"] => formula = BalanceFormula::AvailableMinusHolds,
+            ["--blinder", hex] => blinder = Some(hex::decode(hex)?),
+            _ => anyhow::bail!(
+                "Usage: analyze --input <dump.json> --blinder <hex> [--formula current|available_minus_holds]"
+            ),
+        }
+    }
+    let blinder = blinder.context("analyze needs the session's --blinder to rebuild the commitment")?;
+
+    let dump = analyze::read_dump(path)?;
+    info!("🔬 Analyzing session {} from {}", dump.session_id, path.display());
+    let analysis = analyze::analyze(&dump, &config, formula, &blinder)?;
+    println!("{}", serde_json::to_string_pretty(&analysis)?);
+    Ok(())
+}
+
+/// Run a recorded session's MPC stream through the verifier again and compare outcomes
+async fn replay_capture(path: &Path) -> Result<()> {
+    let records = capture::load(path)?;
+    info!("🔁 Replaying {} ({} records)", path.display(), records.len());
+
+    let report = verifier::replay(&records).await?;
+    info!("   Recorded outcome: {}", report.recorded_outcome.as_deref().unwrap_or("(none, session cut short)"));
+    info!("   Replayed outcome: {}", report.replayed_outcome);
+    match report.outbound_divergence {
+        Some(offset) => info!("   Outbound stream diverged at byte {} of {}", offset, report.outbound_bytes),
+        None => info!("   Outbound stream matched the recording ({} bytes)", report.outbound_bytes),
+    }
+
+    if report.reproduced() {
+        info!("✅ Outcome reproduced");
+    } else {
+        warn!("⚠️  Outcome not reproduced");
+    }
+    Ok(())
+}
+
+/// Start the memory watchdog if any memory limit is configured
+fn spawn_memory_watchdog(
+    config: &ServerConfig,
+    tasks: &Arc<TaskRegistry>,
+    sessions: &Arc<SessionRegistry>,
+) -> Option<Arc<MemoryWatchdog>> {
+    if config.memory_soft_limit_mb.is_none() && config.memory_hard_limit_mb.is_none() {
+        return None;
+    }
+
+    if let Some(limit) = cgroup_limit_bytes() {
+        info!("🧠 cgroup memory limit: {} MB", limit >> 20);
+    }
+
+    let watchdog = Arc::new(MemoryWatchdog::new(
+        Box::new(ProcSampler),
+        config.memory_soft_limit_mb.map(|mb| mb << 20),
+        config.memory_hard_limit_mb.map(|mb| mb << 20),
+    ));
+    tasks.spawn(
+        "memory-watchdog",
+        None,
+        None,
+        Arc::clone(&watchdog).run(Arc::clone(sessions), Duration::from_secs(config.memory_check_interval_secs)),
+    );
+
+    Some(watchdog)
+}
+
+/// Apply the configured TCP_NODELAY and keepalive options to an accepted socket
+fn configure_socket(stream: &tokio::net::TcpStream, config: &ServerConfig) -> Result<()> {
+    stream.set_nodelay(config.tcp_nodelay)?;
+
+    if config.tcp_keepalive_secs > 0 {
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(config.tcp_keepalive_secs))
+            .with_interval(Duration::from_secs(config.tcp_keepalive_interval_secs));
+        SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+    }
+
+    Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_client(
-    stream: tokio::net::TcpStream,
+    mut stream: tokio::net::TcpStream,
     peer_addr: std::net::SocketAddr,
+    config: Arc<ServerConfig>,
+    admission: Arc<Admission>,
+    transparency: Option<Arc<TransparencyLog>>,
+    session: SessionGuard,
+    tasks: Arc<TaskRegistry>,
+    drain: Arc<Drain>,
 ) -> Result<()> {
-    info!("🤝 Upgrading connection to WebSocket for {}", peer_addr);
+    // MPC traffic is many small latency-sensitive writes; set socket options
+    // before the upgrade so they apply from the first handshake byte
+    configure_socket(&stream, &config)?;
+
+    // Behind a trusted proxy the TCP peer is the proxy, not the prover
+    let mut client = ClientAddr::direct(peer_addr);
+    if config.client_ip_source == ClientIpSource::ProxyProtocol {
+        client = proxy::read_proxy_v2(&mut stream, peer_addr, &config.trusted_proxies).await?;
+    }
 
-    // Accept WebSocket connection
-    let ws_stream = accept_async(stream).await?;
-    info!("✅ WebSocket established with {}", peer_addr);
+    // A drain that starts before the upgrade request is in answers it with a 503
+    let Some(stream) = shutdown::read_upgrade_request(stream, &drain).await? else {
+        info!("🛑 Refused {} before its WebSocket upgrade: draining", client);
+        return Ok(());
+    };
 
-    // Handle verification
-    verifier::handle_verification(ws_stream, peer_addr).await?;
+    info!("🤝 Upgrading connection to WebSocket for {}", client);
+
+    // Accept WebSocket connection, negotiating our subprotocol and reading
+    // forwarding headers off the upgrade request
+    let negotiate = upgrade::negotiate_subprotocol(config.require_subprotocol);
+    let mut forwarded = None;
+    let mut framing = Framing::Raw;
+    let mut refused = false;
+    let callback = |request: &Request, response: Response| {
+        if drain.is_draining() {
+            refused = true;
+            return Err(shutdown::unavailable_response());
+        }
+        if config.client_ip_source == ClientIpSource::Forwarded {
+            forwarded = Some(proxy::resolve_forwarded(request.headers(), peer_addr, &config.trusted_proxies));
+        }
+        let response = negotiate(request, response)?;
+        framing = Framing::negotiated(&response);
+        Ok(response)
+    };
+    let upgraded = accept_hdr_async(stream, callback).await;
+    if refused {
+        drain.report().refused(1);
+        info!("🛑 Refused {} before its WebSocket upgrade: draining", client);
+        return Ok(());
+    }
+    let mut ws_stream = upgraded?;
+    if let Some(forwarded) = forwarded {
+        client = forwarded;
+    }
+    info!("✅ WebSocket established with {}", client);
+
+    // Refused after the upgrade, by client address, so the prover learns when to retry;
+    // the permit holds a session slot and memory reservation until verification ends
+    let _permit = match admission.admit(client.ip, Instant::now()) {
+        Ok(permit) => permit,
+        Err(refusal) => {
+            warn!("🚫 Refusing {}: {} (retry after {}s)", client, refusal.reason, refusal.retry_after_secs());
+            refusal.send(&mut ws_stream).await;
+            return Ok(());
+        }
+    };
+
+    // Handle verification; an upgraded session is served to its end even once a drain starts
+    let verified =
+        verifier::handle_verification(ws_stream, &client, framing, &config, transparency.as_deref(), &session, &tasks)
+            .await;
+    if drain.is_draining() {
+        drain.report().session_completed();
+    }
+    verified?;
 
-    info!("✓ Verification complete for {}", peer_addr);
+    info!("✓ Verification complete for {}", client);
     Ok(())
 }
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::tasks::TaskRegistry;
+use crate::traffic::TrafficMetrics;
+use crate::usage::UsageLedger;
+
+/// Source of the process's current memory usage
+pub trait MemorySampler: Send + Sync {
+    /// Resident set size in bytes, `None` if it can't be read
+    fn rss_bytes(&self) -> Option<u64>;
+}
+
+/// Reads RSS from `/proc/self/status` (Linux)
+pub struct ProcSampler;
+
+impl MemorySampler for ProcSampler {
+    fn rss_bytes(&self) -> Option<u64> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    }
+}
+
+/// Memory limit of the enclosing cgroup (v2, then v1), if any
+pub fn cgroup_limit_bytes() -> Option<u64> {
+    ["/sys/fs/cgroup/memory.max", "/sys/fs/cgroup/memory/memory.limit_in_bytes"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok()?.trim().parse().ok())
+}
+
+/// In-flight sessions that the watchdog may shed under memory pressure
+pub struct SessionRegistry {
+    next_id: AtomicU64,
+    sessions: Mutex<HashMap<u64, SessionEntry>>,
+    /// Sessions told to end at the last check, aborted if still running at the next
+    shedding: Mutex<Vec<u64>>,
+    shed_count: AtomicU64,
+    /// Resident set size at the watchdog's last check, 0 before the first
+    rss_bytes: AtomicU64,
+    /// Supervised tasks, used to abort every task of a shed session
+    tasks: Arc<TaskRegistry>,
+    /// MPC traffic of finished sessions
+    traffic: TrafficMetrics,
+    /// Daily usage of finished sessions
+    usage: Arc<UsageLedger>,
+}
+
+struct SessionEntry {
+    bytes_forwarded: Arc<AtomicU64>,
+    shed: Arc<Notify>,
+}
+
+/// Registration of one session; unregisters on drop
+pub struct SessionGuard {
+    registry: Arc<SessionRegistry>,
+    id: u64,
+    /// Bytes forwarded in either direction, updated by the forwarding tasks
+    pub bytes_forwarded: Arc<AtomicU64>,
+    shed: Arc<Notify>,
+}
+
+impl SessionRegistry {
+    pub fn new(tasks: Arc<TaskRegistry>, usage: Arc<UsageLedger>) -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            sessions: Mutex::new(HashMap::new()),
+            shedding: Mutex::new(Vec::new()),
+            shed_count: AtomicU64::new(0),
+            rss_bytes: AtomicU64::new(0),
+            tasks,
+            traffic: TrafficMetrics::default(),
+            usage,
+        }
+    }
+
+    /// Register a new session before its task is spawned
+    pub fn register(self: &Arc<Self>) -> SessionGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let bytes_forwarded = Arc::new(AtomicU64::new(0));
+        let shed = Arc::new(Notify::new());
+        self.sessions.lock().unwrap().insert(
+            id,
+            SessionEntry { bytes_forwarded: Arc::clone(&bytes_forwarded), shed: Arc::clone(&shed) },
+        );
+        SessionGuard { registry: Arc::clone(self), id, bytes_forwarded, shed }
+    }
+
+    /// Aggregate MPC traffic histograms, for `/metrics`
+    pub fn traffic(&self) -> &TrafficMetrics {
+        &self.traffic
+    }
+
+    /// Daily usage counters, for `/usage` and `/metrics`
+    pub fn usage(&self) -> &UsageLedger {
+        &self.usage
+    }
+
+    /// Number of sessions currently registered
+    pub fn in_flight(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Sessions shed under memory pressure since startup
+    pub fn shed_count(&self) -> u64 {
+        self.shed_count.load(Ordering::Relaxed)
+    }
+
+    /// Memory gauges and the shed counter, in the Prometheus text format
+    pub fn render_memory(&self) -> String {
+        let mut out = String::new();
+        let rss = self.rss_bytes.load(Ordering::Relaxed);
+        if rss > 0 {
+            out.push_str("# HELP auditorzk_memory_rss_bytes Resident set size at the memory watchdog's last check.\n");
+            out.push_str("# TYPE auditorzk_memory_rss_bytes gauge\n");
+            let _ = writeln!(out, "auditorzk_memory_rss_bytes {}", rss);
+        }
+        out.push_str("# HELP auditorzk_sessions_shed_total Sessions shed under memory pressure.\n");
+        out.push_str("# TYPE auditorzk_sessions_shed_total counter\n");
+        let _ = writeln!(out, "auditorzk_sessions_shed_total {}", self.shed_count());
+        out
+    }
+
+    /// Shed the session that has forwarded the most bytes, returning its traffic.
+    ///
+    /// The session is told to end, so it can tell its prover why; one told at an
+    /// earlier check that is still running is aborted first.
+    fn shed_largest(&self) -> Option<u64> {
+        for id in self.shedding.lock().unwrap().drain(..) {
+            self.tasks.abort_session(id);
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let (&id, _) = sessions
+            .iter()
+            .filter(|(&id, _)| self.tasks.session_running(id))
+            .max_by_key(|(_, entry)| entry.bytes_forwarded.load(Ordering::Relaxed))?;
+
+        let entry = sessions.remove(&id)?;
+        entry.shed.notify_one();
+        self.shedding.lock().unwrap().push(id);
+        self.shed_count.fetch_add(1, Ordering::Relaxed);
+        Some(entry.bytes_forwarded.load(Ordering::Relaxed))
+    }
+}
+
+impl SessionGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Where the session merges its traffic profile when it ends
+    pub fn traffic(&self) -> &TrafficMetrics {
+        self.registry.traffic()
+    }
+
+    /// Where the session adds its MPC time and forwarded bytes when it ends
+    pub fn usage(&self) -> &UsageLedger {
+        self.registry.usage()
+    }
+
+    /// Resolves once the watchdog sheds this session under memory pressure
+    pub async fn shed(&self) {
+        self.shed.notified().await
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.registry.sessions.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Sheds load before the OOM killer does.
+///
+/// Above the soft limit new sessions are refused; above the hard limit the
+/// largest in-flight session is shed on every check until usage drops. A shed
+/// session is told to end (see [`SessionGuard::shed`]) and sends its prover an
+/// error before closing with `1013`; if it is still running at the next check,
+/// its tasks are aborted.
+pub struct MemoryWatchdog {
+    sampler: Box<dyn MemorySampler>,
+    soft_limit: Option<u64>,
+    hard_limit: Option<u64>,
+    admitting: AtomicBool,
+}
+
+impl MemoryWatchdog {
+    pub fn new(sampler: Box<dyn MemorySampler>, soft_limit: Option<u64>, hard_limit: Option<u64>) -> Self {
+        Self {
+            sampler,
+            soft_limit,
+            hard_limit,
+            admitting: AtomicBool::new(true),
+        }
+    }
+
+    /// Whether new sessions may be accepted
+    pub fn admitting(&self) -> bool {
+        self.admitting.load(Ordering::Relaxed)
+    }
+
+    /// Sample memory once and apply the thresholds
+    pub fn check(&self, sessions: &SessionRegistry) {
+        let Some(rss) = self.sampler.rss_bytes() else {
+            return;
+        };
+        sessions.rss_bytes.store(rss, Ordering::Relaxed);
+
+        let over_soft = self.soft_limit.is_some_and(|limit| rss >= limit);
+        let was_admitting = self.admitting.swap(!over_soft, Ordering::Relaxed);
+        if over_soft && was_admitting {
+            warn!("⚠️  Memory {} MB above soft limit, refusing new sessions", rss >> 20);
+        } else if !over_soft && !was_admitting {
+            info!("✅ Memory {} MB back under soft limit, accepting sessions", rss >> 20);
+        }
+
+        if self.hard_limit.is_some_and(|limit| rss >= limit) {
+            match sessions.shed_largest() {
+                Some(bytes) => warn!(
+                    "🧯 Session shed due to memory pressure ({} MB RSS, {} bytes forwarded, {} shed total)",
+                    rss >> 20,
+                    bytes,
+                    sessions.shed_count()
+                ),
+                None => warn!("⚠️  Memory {} MB above hard limit with no session to shed", rss >> 20),
+            }
+        }
+    }
+
+    /// Run `check` forever on the given interval
+    pub async fn run(self: Arc<Self>, sessions: Arc<SessionRegistry>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.check(&sessions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn watchdog_refuses_then_sheds_the_largest_session() {
+        struct Sampled(Arc<AtomicU64>);
+        impl MemorySampler for Sampled {
+            fn rss_bytes(&self) -> Option<u64> {
+                Some(self.0.load(Ordering::Relaxed))
+            }
+        }
+
+        let rss = Arc::new(AtomicU64::new(100 << 20));
+        let watchdog = MemoryWatchdog::new(Box::new(Sampled(Arc::clone(&rss))), Some(200 << 20), Some(300 << 20));
+        let tasks = Arc::new(TaskRegistry::default());
+        let sessions = Arc::new(SessionRegistry::new(Arc::clone(&tasks), Arc::new(UsageLedger::new(None))));
+
+        // Sessions are admitted under the soft limit, refused above it, and admitted again once usage drops
+        watchdog.check(&sessions);
+        assert!(watchdog.admitting());
+        rss.store(250 << 20, Ordering::Relaxed);
+        watchdog.check(&sessions);
+        assert!(!watchdog.admitting());
+        assert!(sessions.render_memory().contains(&format!("auditorzk_memory_rss_bytes {}", 250 << 20)));
+        rss.store(150 << 20, Ordering::Relaxed);
+        watchdog.check(&sessions);
+        assert!(watchdog.admitting());
+        assert_eq!(sessions.shed_count(), 0);
+
+        // Above the hard limit the session that forwarded the most is told to end
+        let spawn = |session: &SessionGuard| tasks.spawn("session", Some(session.id()), None, std::future::pending::<()>());
+        let (small, large) = (sessions.register(), sessions.register());
+        small.bytes_forwarded.store(1 << 10, Ordering::Relaxed);
+        large.bytes_forwarded.store(1 << 20, Ordering::Relaxed);
+        let (small_task, large_task) = (spawn(&small), spawn(&large));
+        rss.store(350 << 20, Ordering::Relaxed);
+        watchdog.check(&sessions);
+        large.shed().await;
+        assert_eq!(sessions.shed_count(), 1);
+        assert!(!large_task.is_finished());
+
+        // It ignored that, so the next check aborts it and sheds the next largest
+        watchdog.check(&sessions);
+        assert!(large_task.await.unwrap_err().is_cancelled());
+        small.shed().await;
+        assert_eq!(sessions.shed_count(), 2);
+        assert!(sessions.render_memory().contains("auditorzk_sessions_shed_total 2"));
+        drop(small_task);
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::http::{json_document, parse_request, parse_response, HeadLimits, HttpError, HttpMessage};
+use crate::session::SessionFacts;
+
+/// The notarized request and response, split once after verification.
+///
+/// Validation, header checks, extraction and signing all read the transcript
+/// through this, so they agree on where the response body starts instead of each
+/// searching for it, and sub-parses (the header map) run at most once however
+/// many checks consult them. Both heads go through the strict parser in
+/// [`crate::http`]; [`ParsedSession::check_framing`] fails the session if either
+/// is ambiguous, and [`ParsedSession::check_trailing_data`] if either body runs
+/// past its JSON document. The body itself is still streamed by the parsers,
+/// which never build a JSON DOM.
+pub struct ParsedSession<'a> {
+    /// Sent and received bytes, when the prover revealed the transcript
+    transcript: Option<(&'a [u8], &'a [u8])>,
+    /// Authenticated ranges of the sent bytes
+    sent_revealed: &'a [Range<usize>],
+    /// The request, when the prover revealed its start
+    request: Option<Result<HttpMessage, HttpError>>,
+    /// The response, when the prover revealed the transcript
+    response: Option<Result<HttpMessage, HttpError>>,
+    headers: OnceLock<BTreeMap<String, String>>,
+}
+
+impl<'a> ParsedSession<'a> {
+    pub fn new(facts: &'a SessionFacts, limits: &HeadLimits) -> Self {
+        let transcript = facts.transcript.as_ref().map(|t| (&t.sent[..], &t.received[..]));
+        // Hidden request bytes read as zeros, so only a request whose first byte is
+        // authenticated has a request line worth parsing
+        let request_revealed = facts
+            .transcript
+            .as_ref()
+            .is_some_and(|t| t.sent_revealed.first().is_some_and(|range| range.start == 0));
+
+        let sent_revealed = facts.transcript.as_ref().map_or(&[][..], |t| &t.sent_revealed[..]);
+
+        let request = transcript.filter(|_| request_revealed).map(|(sent, _)| parse_request(sent, limits));
+        let response = transcript.map(|(_, received)| parse_response(received, limits));
+
+        Self { transcript, sent_revealed, request, response, headers: OnceLock::new() }
+    }
+
+    /// Whether the prover revealed the transcript at all
+    pub fn has_transcript(&self) -> bool {
+        self.transcript.is_some()
+    }
+
+    /// Fail if the revealed request or the response has ambiguous or malformed framing
+    pub fn check_framing(&self) -> Result<(), HttpError> {
+        for message in [&self.request, &self.response].into_iter().flatten() {
+            message.as_ref().map_err(Clone::clone)?;
+        }
+        Ok(())
+    }
+
+    /// Fail if the response body, or a request body revealed in full, has
+    /// anything but whitespace after its JSON document (see [`json_document`]).
+    /// Bodies with broken framing are left to [`ParsedSession::check_framing`]
+    pub fn check_trailing_data(&self) -> Result<(), HttpError> {
+        let Some((sent, received)) = self.transcript else {
+            return Ok(());
+        };
+        if let Some(request) = self.request().filter(|request| !request.body.is_empty()) {
+            // Hidden request bytes (credentials, usually) read as zeros, which aren't JSON
+            let body = &request.body;
+            if self.sent_revealed.iter().any(|range| range.start <= body.start && body.end <= range.end) {
+                json_document(sent, body.clone(), "Request")?;
+            }
+        }
+        if let Ok(response) = self.response() {
+            json_document(received, response.body.clone(), "Response")?;
+        }
+        Ok(())
+    }
+
+    pub fn sent(&self) -> Result<&'a [u8]> {
+        self.transcript.map(|(sent, _)| sent).context("No transcript available")
+    }
+
+    pub fn received(&self) -> Result<&'a [u8]> {
+        self.transcript.map(|(_, received)| received).context("No transcript available")
+    }
+
+    /// The request, when the prover revealed its start and it parsed
+    pub fn request(&self) -> Option<&HttpMessage> {
+        self.request.as_ref().and_then(|request| request.as_ref().ok())
+    }
+
+    /// The request target, e.g. `/credit/payroll_income/get?x=1`
+    pub fn request_target(&self) -> Result<&str> {
+        let request = self.request.as_ref().context("Request line was not revealed")?;
+        let request = request.as_ref().map_err(Clone::clone)?;
+        request.target().context("Request line has no target")
+    }
+
+    /// The response body, as framed by its head
+    pub fn body(&self) -> Result<&'a [u8]> {
+        let received = self.received()?;
+        let response = self.response().map_err(Clone::clone)?;
+        Ok(&received[response.body.clone()])
+    }
+
+    /// The response body's JSON document, without the whitespace after it
+    pub fn json_body(&self) -> Result<&'a [u8]> {
+        let received = self.received()?;
+        let response = self.response().map_err(Clone::clone)?;
+        Ok(&received[json_document(received, response.body.clone(), "Response")?])
+    }
+
+    /// The response status line, e.g. `HTTP/1.1 200 OK`
+    pub fn status_line(&self) -> Option<&str> {
+        self.response().ok().map(|response| response.start_line.as_str())
+    }
+
+    /// Response header fields with lowercased names, parsed on first use
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        self.headers.get_or_init(|| self.response().map(HttpMessage::headers).unwrap_or_default())
+    }
+
+    fn response(&self) -> Result<&HttpMessage, &HttpError> {
+        match &self.response {
+            Some(response) => response.as_ref(),
+            None => Err(&HttpError::MissingHeadEnd),
+        }
+    }
+}
@@ -1,50 +1,307 @@
-use anyhow::{Result, bail};
+use auditorzk_attestation::commitments::{self, TranscriptHash};
+use auditorzk_attestation::message;
+use auditorzk_attestation::profile::ProfileSelection;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 use tracing::{info, warn};
-use tlsn_core::VerifierOutput;
+use tlsn_core::transcript::Direction;
+
+use crate::balance::{
+    minor_unit_exponent, AccountFilter, BalanceFormula, BalanceParser, BalanceResult, BodyLimits,
+    LimitError, NumberLocale,
+};
+use crate::config::ServerConfig;
+use crate::control::BalanceOpening;
+use crate::error::{Error, Result};
+use crate::parsed::ParsedSession;
+use crate::profiles::{FieldPointers, ProfileSet, ResponseContext, SchemaProfile};
+use crate::session::{CommitmentFact, SessionFacts};
+use crate::templates::commitment_hash;
+
+/// Plaid API environment a deployment (and each verified host) belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaidEnvironment {
+    Sandbox,
+    Development,
+    Production,
+}
+
+impl PlaidEnvironment {
+    /// Environment implied by a Plaid hostname, `None` for non-Plaid hosts
+    pub fn from_host(host: &str) -> Option<Self> {
+        match host {
+            "sandbox.plaid.com" => Some(Self::Sandbox),
+            "development.plaid.com" => Some(Self::Development),
+            "production.plaid.com" => Some(Self::Production),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sandbox => "sandbox",
+            Self::Development => "development",
+            Self::Production => "production",
+        }
+    }
+
+    /// Single-byte encoding appended to the signed message
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            Self::Sandbox => message::ENV_SANDBOX,
+            Self::Development => message::ENV_DEVELOPMENT,
+            Self::Production => message::ENV_PRODUCTION,
+        }
+    }
+}
+
+impl fmt::Display for PlaidEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PlaidEnvironment {
+    type Err = PlaidError;
+
+    fn from_str(s: &str) -> Result<Self, PlaidError> {
+        match s.trim().to_lowercase().as_str() {
+            "sandbox" => Ok(Self::Sandbox),
+            "development" => Ok(Self::Development),
+            "production" => Ok(Self::Production),
+            other => Err(PlaidError::UnknownValue {
+                kind: "Plaid environment",
+                value: other.to_string(),
+                expected: "sandbox, development or production",
+            }),
+        }
+    }
+}
+
+/// TLS protocol versions, ordered oldest to newest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    V1_0,
+    #[serde(rename = "1.1")]
+    V1_1,
+    #[serde(rename = "1.2")]
+    V1_2,
+    #[serde(rename = "1.3")]
+    V1_3,
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V1_0 => "TLS 1.0",
+            Self::V1_1 => "TLS 1.1",
+            Self::V1_2 => "TLS 1.2",
+            Self::V1_3 => "TLS 1.3",
+        })
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = PlaidError;
+
+    fn from_str(s: &str) -> Result<Self, PlaidError> {
+        let version = s.trim().to_lowercase();
+        match version.trim_start_matches("tls").trim_start_matches(['v', ' ']) {
+            "1.0" => Ok(Self::V1_0),
+            "1.1" => Ok(Self::V1_1),
+            "1.2" => Ok(Self::V1_2),
+            "1.3" => Ok(Self::V1_3),
+            _ => Err(PlaidError::UnknownValue {
+                kind: "TLS version",
+                value: s.to_string(),
+                expected: "1.0, 1.1, 1.2 or 1.3",
+            }),
+        }
+    }
+}
+
+/// Failures validating a Plaid session or parsing its response
+#[derive(Debug, thiserror::Error)]
+pub enum PlaidError {
+    #[error("Unknown {kind} '{value}' (expected {expected})")]
+    UnknownValue {
+        kind: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    #[error("No server name provided")]
+    MissingServerName,
+    #[error("Server {host} is not allowed; it must be a Plaid API endpoint or localhost for testing")]
+    HostNotAllowed { host: String },
+    #[error("Server {host} belongs to the {actual} environment but this verifier is configured for {expected}")]
+    EnvironmentMismatch {
+        host: String,
+        expected: PlaidEnvironment,
+        actual: String,
+    },
+    #[error("Session used {negotiated}, below the required minimum {minimum}")]
+    TlsVersionTooOld {
+        negotiated: TlsVersion,
+        minimum: TlsVersion,
+    },
+    #[error("Prover made {found} transcript commitments, at least {required} required")]
+    TooFewCommitments { found: usize, required: usize },
+    #[error("Hash commitments are only over {found} data, balance claims require {allowed}")]
+    DisallowedCommitmentDirection { found: String, allowed: String },
+    #[error("Response is missing required header {name}")]
+    MissingHeader { name: String },
+    #[error("Header {name} value '{value}' does not match required pattern '{pattern}'")]
+    HeaderMismatch {
+        name: String,
+        value: String,
+        pattern: String,
+    },
+    #[error("Request asked for Plaid API version {actual}, this verifier expects {expected}")]
+    ApiVersionMismatch { expected: String, actual: String },
+    #[error("Transcript commitment uses unsupported hash algorithm id {0}")]
+    UnsupportedHashAlgorithm(u8),
+    #[error("Failed to parse JSON response")]
+    ResponseJson(#[source] serde_json::Error),
+    #[error("No accounts array found")]
+    NoAccounts,
+    #[error("Response doesn't match schema profile {profile}: {reason}")]
+    ProfileMismatch { profile: String, reason: String },
+    #[error("No schema profile applies to API version {api_version}")]
+    NoSchemaProfile { api_version: String },
+    #[error("Response has {count} summed account(s), at least {minimum} required")]
+    TooFewAccounts { count: usize, minimum: usize },
+    #[error("No balance found in accounts ({excluded} excluded by the account filter, {inactive} inactive)")]
+    NoBalance { excluded: usize, inactive: usize },
+    #[error("Balance is not a number: {value}")]
+    BalanceNotNumber { value: String },
+    #[error("Balance {value:?} is not a valid {locale} amount")]
+    BalanceNotLocale { value: String, locale: NumberLocale },
+    #[error("Balance {value} uses exponent notation; strict mode requires plain decimals")]
+    BalanceExponent { value: String },
+    #[error("Balance {value} {currency} is not a whole number of minor units ({allowed} decimals allowed)")]
+    FractionalMinorUnits {
+        value: String,
+        currency: String,
+        allowed: usize,
+    },
+    #[error("Multiple currencies present ({currencies}); configure per-currency mode (AUDITORZK_COMMIT_BY_CURRENCY)")]
+    MixedCurrencies { currencies: String },
+    #[error("A balance opening is required; send one in hello as balance_opening")]
+    BalanceOpeningRequired,
+    #[error("No received commitment opens to the {length} byte balance the prover declared")]
+    BalanceOpeningMismatch { length: usize },
+}
+
+impl PlaidError {
+    /// Stable snake_case code, see [`crate::error::Error::code`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownValue { .. } => "invalid_config_value",
+            Self::MissingServerName => "missing_server_name",
+            Self::HostNotAllowed { .. } => "host_not_allowed",
+            Self::EnvironmentMismatch { .. } => "environment_mismatch",
+            Self::TlsVersionTooOld { .. } => "tls_version_too_old",
+            Self::TooFewCommitments { .. } => "too_few_commitments",
+            Self::DisallowedCommitmentDirection { .. } => "disallowed_commitment_direction",
+            Self::MissingHeader { .. } => "missing_header",
+            Self::HeaderMismatch { .. } => "header_mismatch",
+            Self::ApiVersionMismatch { .. } => "api_version_mismatch",
+            Self::UnsupportedHashAlgorithm(_) => "unsupported_hash_algorithm",
+            Self::ResponseJson(_) => "invalid_response_json",
+            Self::NoAccounts => "no_accounts",
+            Self::ProfileMismatch { .. } => "schema_profile_mismatch",
+            Self::NoSchemaProfile { .. } => "no_schema_profile",
+            Self::TooFewAccounts { .. } => "too_few_accounts",
+            Self::NoBalance { .. } => "no_balance",
+            Self::BalanceNotNumber { .. } | Self::BalanceNotLocale { .. } | Self::BalanceExponent { .. } => {
+                "invalid_balance"
+            }
+            Self::FractionalMinorUnits { .. } => "fractional_minor_units",
+            Self::MixedCurrencies { .. } => "mixed_currencies",
+            Self::BalanceOpeningRequired => "balance_opening_required",
+            Self::BalanceOpeningMismatch { .. } => "balance_opening_mismatch",
+        }
+    }
+}
+
+/// The one form of a server name that is validated and signed: as the TLS
+/// session authenticated it, lowercased, without a trailing root dot.
+pub fn normalize_server_name(name: &str) -> String {
+    name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase()
+}
+
+/// Match a host against an allowlist entry (entries starting with `.` match subdomains)
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    if pattern.starts_with('.') {
+        host.ends_with(pattern)
+    } else {
+        host == pattern
+    }
+}
 
 /// Validate that the connection was to a Plaid server or localhost (for testing)
-pub fn validate_plaid_connection(output: &VerifierOutput) -> Result<()> {
+pub fn validate_plaid_connection(
+    facts: &SessionFacts,
+    parsed: &ParsedSession<'_>,
+    opening: Option<&BalanceOpening>,
+    config: &ServerConfig,
+) -> Result<()> {
     info!("🏦 Validating server connection...");
 
     // Check server identity
-    let server_name = output.server_name.as_ref()
-        .ok_or_else(|| anyhow::anyhow!("No server name provided"))?;
+    let name = facts.normalized_server_name().ok_or(PlaidError::MissingServerName)?;
+    let name_str = name.as_str();
 
-    // In alpha.12, ServerName has an as_str() method
-    let name_str = server_name.as_str();
-
-    // Accept Plaid domains or localhost for testing
-    let is_valid = name_str.ends_with(".plaid.com") ||
-        name_str == "production.plaid.com" ||
-        name_str == "sandbox.plaid.com" ||
-        name_str == "development.plaid.com" ||
-        name_str == "localhost" ||
-        name_str == "127.0.0.1";
+    // Accept only hosts on the configured allowlist
+    let is_valid = config.allowed_hosts
+        .iter()
+        .any(|pattern| host_matches(pattern, name_str));
 
     if !is_valid {
         warn!("❌ Server is not a valid Plaid or test domain: {}", name_str);
-        bail!("Server must be a Plaid API endpoint or localhost for testing");
+        return Err(PlaidError::HostNotAllowed { host: name_str.to_string() }.into());
     }
 
     info!("✅ Confirmed valid server: {}", name_str);
 
-    // // Validate we received commitments
-    // if output.transcript_commitments.is_empty() {
-    //     warn!("⚠️  No transcript commitments provided");
-    //     bail!("Prover must commit to transcript data");
-    // }
+    if let Some(expected) = config.environment {
+        check_environment(name_str, expected)?;
+    }
+
+    check_tls_version(facts.tls_version, config.min_tls_version)?;
+
+    check_commitment_directions(facts, &config.commitment_directions)?;
 
-    info!("✅ {} transcript commitments received",
-          output.transcript_commitments.len());
+    check_commitment_count(CommitmentCounts::of(facts), config.min_transcript_commitments)?;
+
+    // Don't sign over a balance commitment the prover can't open later
+    if config.check_balance_opening {
+        check_balance_opening(&transcript_hashes(facts, &[Direction::Received])?, opening)?;
+    }
 
     // Optionally validate transcript content (if revealed)
-    if let Some(transcript) = &output.transcript {
-        let recv = String::from_utf8_lossy(transcript.received_unsafe());
+    if parsed.has_transcript() {
+        if let Err(e) = parsed.check_framing() {
+            warn!("❌ Ambiguous HTTP framing in the transcript: {}", e);
+            return Err(e.into());
+        }
+        if let Err(e) = parsed.check_trailing_data() {
+            warn!("❌ Data after the JSON body in the transcript: {}", e);
+            return Err(e.into());
+        }
+
+        let body = parsed.body().unwrap_or_default();
 
         // Check for Plaid API response structure or generic JSON
-        if recv.contains("\"accounts\"") {
+        if body.windows(10).any(|window| window == b"\"accounts\"") {
             info!("✅ Detected balance API response structure");
-        } else if recv.contains("HTTP/1.1") || recv.contains("HTTP/1.0") {
+        } else if parsed.status_line().is_some_and(|line| line.starts_with("HTTP/1.")) {
             info!("✅ Valid HTTP response received");
         } else {
             warn!("⚠️  Response doesn't look like expected API response");
@@ -54,11 +311,1020 @@ pub fn validate_plaid_connection(output: &VerifierOutput) -> Result<()> {
     Ok(())
 }
 
-/// Analyze and log commitment details for debugging
-#[allow(dead_code)]
-pub fn analyze_commitments(output: &VerifierOutput) {
-    info!("📊 Commitment Analysis:");
-    info!("  Total commitments: {}", output.transcript_commitments.len());
-    // Note: In alpha.12, transcript_commitments is just a Vec<u8>
-    // For detailed analysis, we'd need to deserialize the commitment structure
+/// Ensure the verified host belongs to the deployment's Plaid environment.
+/// Test hosts (localhost) only pass in sandbox deployments.
+fn check_environment(host: &str, expected: PlaidEnvironment) -> Result<()> {
+    let actual = match PlaidEnvironment::from_host(host) {
+        Some(env) => env.to_string(),
+        None if expected == PlaidEnvironment::Sandbox && !host.ends_with(".plaid.com") => {
+            return Ok(());
+        }
+        None => "unknown".to_string(),
+    };
+
+    if actual != expected.as_str() {
+        warn!("❌ Environment mismatch: {} is {}, expected {}", host, actual, expected);
+        return Err(PlaidError::EnvironmentMismatch {
+            host: host.to_string(),
+            expected,
+            actual,
+        }
+        .into());
+    }
+
+    info!("✅ Server matches {} environment", expected);
+    Ok(())
+}
+
+/// Parse a transcript direction name (`sent` or `received`)
+pub fn parse_direction(s: &str) -> Result<Direction, PlaidError> {
+    match s.trim().to_lowercase().as_str() {
+        "sent" => Ok(Direction::Sent),
+        "received" => Ok(Direction::Received),
+        other => Err(PlaidError::UnknownValue {
+            kind: "commitment direction",
+            value: other.to_string(),
+            expected: "sent or received",
+        }),
+    }
+}
+
+/// Reject sessions whose hash commitments all cover a disallowed direction,
+/// e.g. request bytes passed off as a balance proof
+fn check_commitment_directions(facts: &SessionFacts, allowed: &[Direction]) -> Result<()> {
+    let mut hash_directions = Vec::new();
+    for commitment in &facts.commitments {
+        match commitment {
+            CommitmentFact::Hash(hash) => {
+                info!("   Hash commitment over {:?} data", hash.direction);
+                hash_directions.push(hash.direction);
+            }
+            CommitmentFact::Encoding => info!("   Encoding commitment"),
+        }
+    }
+
+    info!("🧭 Allowed commitment directions: {:?}", allowed);
+
+    if !hash_directions.is_empty() && !hash_directions.iter().any(|d| allowed.contains(d)) {
+        warn!("❌ No hash commitment in an allowed direction");
+        return Err(PlaidError::DisallowedCommitmentDirection {
+            found: format!("{:?}", hash_directions),
+            allowed: format!("{:?}", allowed),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Reject sessions negotiated below the configured minimum TLS version
+fn check_tls_version(negotiated: Option<TlsVersion>, minimum: TlsVersion) -> Result<()> {
+    let Some(negotiated) = negotiated else {
+        info!("ℹ️  TLS version not exposed by verifier output, skipping {} minimum check", minimum);
+        return Ok(());
+    };
+
+    if negotiated < minimum {
+        warn!("❌ Session used {} (minimum {})", negotiated, minimum);
+        return Err(PlaidError::TlsVersionTooOld { negotiated, minimum }.into());
+    }
+
+    info!("✅ Session used {}", negotiated);
+    Ok(())
+}
+
+/// Balance parser for Plaid `/accounts/balance/get` responses.
+///
+/// With `exclude_inactive`, closed or errored accounts and those that failed
+/// verification are neither summed nor inventoried, only counted.
+///
+/// Accounts from several linked Items, in an `items` array or a bare array of
+/// per-Item objects, are summed into one total. A top-level `accounts` array
+/// next to `items` counts as one more Item.
+pub struct PlaidParser {
+    /// Require balances to be whole numbers of the currency's smallest unit
+    pub strict_minor_units: bool,
+    /// How string-typed balances are read
+    pub number_locale: NumberLocale,
+    /// Leave closed or errored accounts out of the sum
+    pub exclude_inactive: bool,
+    /// Which balance fields are summed
+    pub formula: BalanceFormula,
+    /// Which accounts are summed
+    pub account_filter: AccountFilter,
+    /// Operator bounds on the body, enforced before and while streaming
+    pub limits: BodyLimits,
+    /// Response shapes to read the body as, see [`crate::profiles`]
+    pub profiles: Arc<ProfileSet>,
+    /// What selects among the profiles
+    pub context: ResponseContext,
+}
+
+impl Default for PlaidParser {
+    /// The server's defaults: current balances of depository accounts, with no
+    /// strict checks or body limits
+    fn default() -> Self {
+        Self {
+            strict_minor_units: false,
+            number_locale: NumberLocale::Strict,
+            exclude_inactive: false,
+            formula: BalanceFormula::Current,
+            account_filter: AccountFilter::new(vec!["depository".parse().expect("a valid account type rule")], Vec::new()),
+            limits: BodyLimits::default(),
+            profiles: ProfileSet::plaid(),
+            context: ResponseContext::default(),
+        }
+    }
+}
+
+impl BalanceParser for PlaidParser {
+    fn extract(&self, body: &[u8]) -> Result<BalanceResult> {
+        self.limits.check_body(body)?;
+
+        // Newest applicable profile first; only a response that doesn't have a
+        // profile's shape moves on to the next one
+        let api_version = self.context.api_version.as_deref();
+        let mut notes = Vec::new();
+        let mut mismatch = None;
+        for profile in self.profiles.applicable(api_version) {
+            let attempt = match profile.check_headers(self.context.headers.as_ref()) {
+                Ok(()) => self.extract_with(profile, body),
+                Err(e) => Err(e.into()),
+            };
+            match attempt {
+                Ok(mut balance) => {
+                    if notes.is_empty() {
+                        info!("🧩 Read the response with schema profile {}", profile.id);
+                    } else {
+                        warn!("⚠️  Fell back to schema profile {} ({})", profile.id, notes.join("; "));
+                    }
+                    balance.schema_profile = Some(ProfileSelection { id: profile.id.clone(), notes });
+                    return Ok(balance);
+                }
+                Err(Error::Plaid(e @ (PlaidError::NoAccounts | PlaidError::ProfileMismatch { .. }))) => {
+                    notes.push(format!("{}: {}", profile.id, e));
+                    mismatch = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let api_version = api_version.unwrap_or("unknown").to_string();
+        Err(mismatch.unwrap_or(PlaidError::NoSchemaProfile { api_version }).into())
+    }
+}
+
+impl PlaidParser {
+    /// Extract the balance from a body of `profile`'s shape
+    fn extract_with(&self, profile: &SchemaProfile, body: &[u8]) -> Result<BalanceResult> {
+        // Stream over the body: accounts are folded into the running total one at a
+        // time and every other field is skipped, so no JSON DOM is ever built and
+        // peak memory stays bounded by the largest single account object
+        let overflow = Cell::new(None);
+        let rejected = Cell::new(None);
+        let rules = AccountRules {
+            strict_minor_units: self.strict_minor_units,
+            locale: self.number_locale,
+            exclude_inactive: self.exclude_inactive,
+            formula: self.formula,
+            filter: &self.account_filter,
+            max_accounts: self.limits.max_accounts,
+            overflow: &overflow,
+            rejected: &rejected,
+            profile,
+        };
+
+        let mut deserializer = serde_json::Deserializer::from_slice(body);
+        let totals = (&mut deserializer).deserialize_any(ResponseVisitor { rules }).map_err(|e| {
+            // An account cap hit or a balance rejected while streaming keeps its own error code
+            match (overflow.take(), rejected.take()) {
+                (Some(limit), _) => Error::from(limit),
+                (None, Some(balance)) => balance.into(),
+                (None, None) => PlaidError::ResponseJson(e).into(),
+            }
+        })?;
+        deserializer.end().map_err(PlaidError::ResponseJson)?;
+
+        let totals = totals.ok_or(PlaidError::NoAccounts)?;
+
+        if let Some(sentinel) = profile.applies.sentinel.as_deref().filter(|_| totals.unmatched > 0) {
+            let reason = format!("{} of {} account(s) have no {}", totals.unmatched, totals.accounts, sentinel);
+            return Err(profile.mismatch(reason).into());
+        }
+
+        // A top-level accounts array next to an items array is only bounded per array while streaming
+        if let Some(max) = self.limits.max_accounts.filter(|&max| totals.accounts > max) {
+            return Err(LimitError::TooManyAccounts { count: totals.accounts, max }.into());
+        }
+
+        if totals.excluded > 0 {
+            info!("🚫 {} account(s) excluded by the account filter ({})", totals.excluded, self.account_filter.canonical());
+        }
+        if totals.inactive > 0 {
+            info!("🚫 {} closed or errored account(s) excluded", totals.inactive);
+        }
+
+        if totals.total_cents == 0 {
+            return Err(PlaidError::NoBalance { excluded: totals.excluded, inactive: totals.inactive }.into());
+        }
+
+        Ok(BalanceResult {
+            total_cents: totals.total_cents,
+            currency: totals.currency.unwrap_or_else(|| "USD".to_string()),
+            account_ids: totals.account_ids,
+            account_count: totals.accounts - totals.excluded - totals.inactive,
+            item_count: totals.items,
+            inactive_accounts: totals.inactive,
+            by_currency: totals.by_currency,
+            schema_profile: None,
+        })
+    }
+}
+
+/// Fields of a Plaid account needed for the balance, read through a profile's
+/// field pointers; everything else is ignored
+struct Account {
+    account_id: Option<String>,
+    account_type: Option<String>,
+    subtype: Option<String>,
+    /// Account state, e.g. `active`, `closed` or `error`, when the response reports it
+    status: Option<String>,
+    /// Micro-deposit / database verification outcome, e.g. `verification_failed`
+    verification_status: Option<String>,
+    balances: Balances,
+}
+
+impl Account {
+    /// Read an account object; absent and `null` fields are `None`
+    fn read(value: &serde_json::Value, fields: &FieldPointers) -> std::result::Result<Self, String> {
+        let text = |pointer: &str| match value.pointer(pointer) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(text)) => Ok(Some(text.clone())),
+            Some(other) => Err(format!("{} is not a string: {}", pointer, other)),
+        };
+        let amount = |pointer: &str| value.pointer(pointer).cloned().unwrap_or_default();
+
+        Ok(Self {
+            account_id: text(&fields.account_id)?,
+            account_type: text(&fields.account_type)?,
+            subtype: text(&fields.subtype)?,
+            status: text(&fields.status)?,
+            verification_status: text(&fields.verification_status)?,
+            balances: Balances {
+                current: amount(&fields.current),
+                available: amount(&fields.available),
+                holds: amount(&fields.holds),
+                iso_currency_code: text(&fields.currency)?,
+            },
+        })
+    }
+
+    /// Whether the account is closed, errored or failed verification, so its
+    /// balance may be stale. Accounts that report neither field are active
+    fn is_inactive(&self) -> bool {
+        let status_inactive = self
+            .status
+            .as_deref()
+            .is_some_and(|status| !status.eq_ignore_ascii_case("active") && !status.eq_ignore_ascii_case("open"));
+        let verification_failed = self
+            .verification_status
+            .as_deref()
+            .is_some_and(|status| matches!(status, "verification_expired" | "verification_failed"));
+        status_inactive || verification_failed
+    }
+}
+
+#[derive(Default)]
+struct Balances {
+    current: serde_json::Value,
+    available: serde_json::Value,
+    /// Pending holds against the available balance. Not a Plaid field; only
+    /// responses extended by the institution or an aggregator carry it
+    holds: serde_json::Value,
+    iso_currency_code: Option<String>,
+}
+
+/// Settings applied to every `accounts` array, whichever item it belongs to
+#[derive(Clone, Copy)]
+struct AccountRules<'a> {
+    strict_minor_units: bool,
+    locale: NumberLocale,
+    exclude_inactive: bool,
+    formula: BalanceFormula,
+    filter: &'a AccountFilter,
+    max_accounts: Option<usize>,
+    /// Set when the account cap aborts the stream, since serde errors carry only text
+    overflow: &'a Cell<Option<LimitError>>,
+    /// Set when strict mode rejects a balance, for the same reason
+    rejected: &'a Cell<Option<PlaidError>>,
+    /// Where the accounts and their fields are
+    profile: &'a SchemaProfile,
+}
+
+impl AccountRules<'_> {
+    /// Abort the stream with the account cap as the cause
+    fn too_many_accounts<E: de::Error>(&self, count: usize, max: usize) -> E {
+        let limit = LimitError::TooManyAccounts { count, max };
+        let error = E::custom(&limit);
+        self.overflow.set(Some(limit));
+        error
+    }
+
+    /// Abort the stream with a balance strict mode rejects as the cause
+    fn reject<E: de::Error>(&self, balance: PlaidError) -> E {
+        let error = E::custom(&balance);
+        self.rejected.set(Some(balance));
+        error
+    }
+}
+
+/// Running totals accumulated while streaming `accounts` arrays
+struct AccountTotals {
+    total_cents: i64,
+    currency: Option<String>,
+    /// Per-currency totals in each currency's minor units
+    by_currency: BTreeMap<String, i64>,
+    /// Account inventory, dropped as soon as an account without an id is seen
+    account_ids: Option<Vec<String>>,
+    /// Number of accounts in the response, summed or not
+    accounts: usize,
+    /// Number of accounts skipped by the account filter
+    excluded: usize,
+    /// Number of accounts skipped for a closed or errored status
+    inactive: usize,
+    /// Number of Plaid Items the accounts came from
+    items: usize,
+    /// Number of accounts without the profile's sentinel, which are skipped
+    unmatched: usize,
+}
+
+impl Default for AccountTotals {
+    fn default() -> Self {
+        Self {
+            total_cents: 0,
+            currency: None,
+            by_currency: BTreeMap::new(),
+            account_ids: Some(Vec::new()),
+            accounts: 0,
+            excluded: 0,
+            inactive: 0,
+            items: 1,
+            unmatched: 0,
+        }
+    }
+}
+
+impl AccountTotals {
+    /// Fold another item's totals into these
+    fn merge(&mut self, other: AccountTotals) -> std::result::Result<(), String> {
+        self.total_cents = self.total_cents.checked_add(other.total_cents).ok_or("Balance total overflows")?;
+        self.accounts += other.accounts;
+        self.excluded += other.excluded;
+        self.inactive += other.inactive;
+        self.items += other.items;
+        self.unmatched += other.unmatched;
+        if self.currency.is_none() {
+            self.currency = other.currency;
+        }
+        for (code, units) in other.by_currency {
+            let total = self.by_currency.entry(code).or_insert(0);
+            *total = total.checked_add(units).ok_or("Balance total overflows")?;
+        }
+
+        self.account_ids = match (self.account_ids.take(), other.account_ids) {
+            (Some(mut ids), Some(other_ids)) => {
+                for id in other_ids {
+                    if ids.contains(&id) {
+                        return Err(format!("Duplicate account_id {}", id));
+                    }
+                    ids.push(id);
+                }
+                Some(ids)
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+}
+
+/// Fold a sequence of per-item totals, or `None` if there were no items
+fn merge_items(
+    items: impl IntoIterator<Item = AccountTotals>,
+) -> std::result::Result<Option<AccountTotals>, String> {
+    let mut merged: Option<AccountTotals> = None;
+    for item in items {
+        match merged.as_mut() {
+            Some(totals) => totals.merge(item)?,
+            None => merged = Some(item),
+        }
+    }
+    Ok(merged)
+}
+
+/// Visits the top-level response: a single Item object with `accounts`, an
+/// object with an `items` array, or a bare array of per-Item objects
+struct ResponseVisitor<'a> {
+    rules: AccountRules<'a>,
+}
+
+impl<'de> Visitor<'de> for ResponseVisitor<'_> {
+    type Value = Option<AccountTotals>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a Plaid balance response object or an array of per-Item responses")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut totals = Vec::new();
+        let profile = self.rules.profile;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == profile.accounts {
+                totals.push(map.next_value_seed(AccountsSeed { rules: self.rules })?);
+            } else if key == profile.items {
+                if let Some(items) = map.next_value_seed(ItemsSeed { rules: self.rules })? {
+                    totals.push(items);
+                }
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        merge_items(totals).map_err(de::Error::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> std::result::Result<Self::Value, A::Error> {
+        ItemsSeed { rules: self.rules }.visit_seq(seq)
+    }
+}
+
+/// Streams an array of per-Item objects, each with its own `accounts`
+struct ItemsSeed<'a> {
+    rules: AccountRules<'a>,
+}
+
+impl<'de> DeserializeSeed<'de> for ItemsSeed<'_> {
+    type Value = Option<AccountTotals>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ItemsSeed<'_> {
+    type Value = Option<AccountTotals>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of Plaid Item responses")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut merged: Option<AccountTotals> = None;
+        let mut index = 0;
+        while let Some(item) = seq.next_element_seed(ItemSeed { rules: self.rules })? {
+            index += 1;
+            let item = item.ok_or_else(|| de::Error::custom(format!("Item {} has no accounts array", index)))?;
+            match merged.as_mut() {
+                Some(totals) => totals.merge(item).map_err(de::Error::custom)?,
+                None => merged = Some(item),
+            }
+
+            // The cap covers the whole response, so many small Items can't add up past it
+            let count = merged.as_ref().map_or(0, |totals| totals.accounts);
+            if let Some(max) = self.rules.max_accounts.filter(|&max| count > max) {
+                return Err(self.rules.too_many_accounts(count, max));
+            }
+        }
+        Ok(merged)
+    }
+}
+
+/// A single per-Item object; only its `accounts` are read
+struct ItemSeed<'a> {
+    rules: AccountRules<'a>,
+}
+
+impl<'de> DeserializeSeed<'de> for ItemSeed<'_> {
+    type Value = Option<AccountTotals>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ItemSeed<'_> {
+    type Value = Option<AccountTotals>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a Plaid Item response object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut totals = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.rules.profile.accounts {
+                totals = Some(map.next_value_seed(AccountsSeed { rules: self.rules })?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(totals)
+    }
+}
+
+/// Streams the `accounts` array, summing the balances the formula asks for
+struct AccountsSeed<'a> {
+    rules: AccountRules<'a>,
+}
+
+impl<'de> DeserializeSeed<'de> for AccountsSeed<'_> {
+    type Value = AccountTotals;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for AccountsSeed<'_> {
+    type Value = AccountTotals;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an accounts array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let rules = self.rules;
+        let mut totals = AccountTotals::default();
+        let mut index = 0;
+        while let Some(account) = seq.next_element::<serde_json::Value>()? {
+            index += 1;
+
+            // Checked per element so no balance past the cap is summed; the rest of the
+            // array is only skipped over to report its length
+            if let Some(max) = rules.max_accounts.filter(|&max| index > max) {
+                let mut count = index;
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    count += 1;
+                }
+                return Err(rules.too_many_accounts(count, max));
+            }
+
+            if !account.is_object() {
+                return Err(de::Error::custom(format!("Account {} is not an object", index)));
+            }
+            // An account of another shape is never read with this profile's pointers
+            if rules.profile.applies.sentinel.as_deref().is_some_and(|sentinel| account.pointer(sentinel).is_none()) {
+                totals.unmatched += 1;
+                continue;
+            }
+            let account = Account::read(&account, &rules.profile.fields)
+                .map_err(|e| de::Error::custom(format!("Account {}: {}", index, e)))?;
+            let inactive = account.is_inactive();
+            let balances = account.balances;
+
+            // Accounts outside the filter (by default anything but depository) are
+            // neither summed nor part of the inventory
+            if !rules.filter.admits(account.account_type.as_deref(), account.subtype.as_deref()) {
+                totals.excluded += 1;
+                continue;
+            }
+
+            // Closed or errored accounts may report stale balances; when the operator
+            // excludes them they are likewise neither summed nor inventoried
+            if rules.exclude_inactive && inactive {
+                totals.inactive += 1;
+                continue;
+            }
+
+            match (account.account_id, totals.account_ids.as_mut()) {
+                (Some(id), Some(ids)) => {
+                    if ids.contains(&id) {
+                        return Err(de::Error::custom(format!("Duplicate account_id {}", id)));
+                    }
+                    ids.push(id);
+                }
+                (None, _) => totals.account_ids = None,
+                (Some(_), None) => {}
+            }
+
+            // In strict mode every balance must be an exact amount of the currency's smallest unit
+            if rules.strict_minor_units {
+                check_minor_units(&balances, rules.formula, rules.locale).map_err(|balance| rules.reject(balance))?;
+            }
+
+            // Each account is also added to its own currency's total, in that currency's minor units
+            let code = balances.iso_currency_code.clone().unwrap_or_else(|| "USD".to_string());
+            let exponent = minor_unit_exponent(&code);
+            let figure = |exponent: usize| -> std::result::Result<Option<i64>, A::Error> {
+                let amount = |value: &serde_json::Value| -> std::result::Result<Option<i64>, A::Error> {
+                    rules.locale.amount_in(value, exponent).map_err(de::Error::custom)
+                };
+                match rules.formula {
+                    // "current" can be any JSON number (or a string, outside strict locale
+                    // mode); other types carry no balance
+                    BalanceFormula::Current => amount(&balances.current),
+                    // Never fall back to a less accurate figure when a field is missing
+                    BalanceFormula::AvailableMinusHolds => {
+                        let available = amount(&balances.available)?.ok_or_else(|| {
+                            de::Error::custom(format!("Account {} has no balances.available for {}", index, rules.formula))
+                        })?;
+                        let holds = amount(&balances.holds)?.ok_or_else(|| {
+                            de::Error::custom(format!("Account {} has no balances.holds for {}", index, rules.formula))
+                        })?;
+                        available
+                            .checked_sub(holds)
+                            .map(Some)
+                            .ok_or_else(|| de::Error::custom(format!("Balance total overflows at account {}", index)))
+                    }
+                }
+            };
+            let add = |total: i64, units: i64| -> std::result::Result<i64, A::Error> {
+                total
+                    .checked_add(units)
+                    .ok_or_else(|| de::Error::custom(format!("Balance total overflows at account {}", index)))
+            };
+            if let (Some(cents), Some(units)) = (figure(2)?, figure(exponent)?) {
+                totals.total_cents = add(totals.total_cents, cents)?;
+                let currency_total = totals.by_currency.entry(code).or_insert(0);
+                *currency_total = add(*currency_total, units)?;
+            }
+
+            if totals.currency.is_none() {
+                totals.currency = balances.iso_currency_code;
+            }
+        }
+        totals.accounts = index;
+        Ok(totals)
+    }
+}
+
+/// Reject balances with more decimal places than the account currency's smallest unit,
+/// in every field `formula` reads
+fn check_minor_units(balances: &Balances, formula: BalanceFormula, locale: NumberLocale) -> Result<(), PlaidError> {
+    let currency = balances.iso_currency_code.as_deref().unwrap_or("USD");
+    match formula {
+        BalanceFormula::Current => check_minor_unit_value(&balances.current, currency, locale),
+        BalanceFormula::AvailableMinusHolds => {
+            check_minor_unit_value(&balances.available, currency, locale)?;
+            check_minor_unit_value(&balances.holds, currency, locale)
+        }
+    }
+}
+
+fn check_minor_unit_value(value: &serde_json::Value, currency: &str, locale: NumberLocale) -> Result<(), PlaidError> {
+    // Inspect the number's decimal text rather than its f64 value, which can't tell 100.005 from 100.00
+    let text = match value {
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::Null => return Ok(()),
+        serde_json::Value::String(s) if locale != NumberLocale::Strict => locale
+            .normalize(s)
+            .ok_or_else(|| PlaidError::BalanceNotLocale { value: s.clone(), locale })?,
+        other => return Err(PlaidError::BalanceNotNumber { value: other.to_string() }),
+    };
+    if text.contains(['e', 'E']) {
+        return Err(PlaidError::BalanceExponent { value: text });
+    }
+
+    let decimals = text
+        .split_once('.')
+        .map(|(_, fraction)| fraction.trim_end_matches('0').len())
+        .unwrap_or(0);
+    let allowed = minor_unit_exponent(currency);
+
+    if decimals > allowed {
+        return Err(PlaidError::FractionalMinorUnits { value: text, currency: currency.to_string(), allowed });
+    }
+
+    Ok(())
+}
+
+/// Transcript commitments by kind: numbers of commitments, not of committed bytes.
+///
+/// Two hash commitments over hundreds of bytes count as two, and that count is
+/// what `AUDITORZK_MIN_TRANSCRIPT_COMMITMENTS` is enforced against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommitmentCounts {
+    pub hash: usize,
+    pub encoding: usize,
+}
+
+impl CommitmentCounts {
+    pub fn of(facts: &SessionFacts) -> Self {
+        let mut counts = Self::default();
+        for commitment in &facts.commitments {
+            match commitment {
+                CommitmentFact::Hash(_) => counts.hash += 1,
+                CommitmentFact::Encoding => counts.encoding += 1,
+            }
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.hash + self.encoding
+    }
+}
+
+impl fmt::Display for CommitmentCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} hash, {} encoding)", self.total(), self.hash, self.encoding)
+    }
+}
+
+/// The prover's hash commitments in allowed directions, in the order it made them.
+///
+/// Each one is listed (and signed) in the attestation with its ranges and
+/// algorithm, so a prover can commit to e.g. two institutions' balances separately
+/// and a consumer can check each against its own opening.
+pub fn transcript_hashes(facts: &SessionFacts, allowed: &[Direction]) -> Result<Vec<TranscriptHash>> {
+    let hashes = facts
+        .hashes()
+        .filter(|hash| allowed.contains(&hash.direction))
+        .map(|hash| {
+            Ok(TranscriptHash {
+                direction: direction_name(hash.direction).to_string(),
+                ranges: hash.ranges.iter().map(|range| [range.start as u64, range.end as u64]).collect(),
+                algorithm: hash_algorithm_name(hash.algorithm)?.to_string(),
+                hash: hash.hash.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, PlaidError>>()?;
+
+    for (i, hash) in hashes.iter().enumerate() {
+        info!("   Commitment #{}: {} {} over {:?}", i, hash.algorithm, hash.direction, hash.ranges);
+    }
+    Ok(hashes)
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Sent => "sent",
+        Direction::Received => "received",
+    }
+}
+
+/// Name of a tlsn hash algorithm id, as listed in the attestation
+fn hash_algorithm_name(id: u8) -> Result<&'static str, PlaidError> {
+    match id {
+        commitments::HASH_SHA256 => Ok("sha256"),
+        commitments::HASH_BLAKE3 => Ok("blake3"),
+        commitments::HASH_KECCAK256 => Ok("keccak256"),
+        other => Err(PlaidError::UnsupportedHashAlgorithm(other)),
+    }
+}
+
+/// Require a SHA-256 received-direction commitment, over exactly as many bytes as
+/// the balance, that opens to the prover's `(balance, blinder)`.
+pub fn check_balance_opening(commitments: &[TranscriptHash], opening: Option<&BalanceOpening>) -> Result<(), PlaidError> {
+    let opening = opening.ok_or(PlaidError::BalanceOpeningRequired)?;
+    let balance = opening.balance.as_bytes();
+    let expected = commitment_hash(balance, &opening.blinder);
+
+    let opened = commitments.iter().any(|commitment| {
+        commitment.direction == "received"
+            && commitment.algorithm == "sha256"
+            && commitment.ranges.iter().map(|[start, end]| end.saturating_sub(*start)).sum::<u64>() == balance.len() as u64
+            && commitment.hash[..] == expected[..]
+    });
+    if !opened {
+        warn!("❌ No received commitment opens to the declared balance ({} bytes)", balance.len());
+        return Err(PlaidError::BalanceOpeningMismatch { length: balance.len() });
+    }
+
+    info!("✅ Balance commitment opens to the declared balance");
+    Ok(())
+}
+
+/// Require a balance to be summed over at least `minimum` accounts.
+///
+/// Only accounts admitted by the account filter and not excluded as inactive
+/// count. A single-account response fails a minimum of two.
+pub fn check_min_accounts(count: usize, minimum: usize) -> Result<(), PlaidError> {
+    if count < minimum {
+        warn!("❌ Balance summed over {} account(s), {} required", count, minimum);
+        return Err(PlaidError::TooFewAccounts { count, minimum });
+    }
+    Ok(())
+}
+
+/// Refuse to sum balances in more than one currency into a single total.
+///
+/// Adding USD to EUR gives a meaningless figure, so unless `allow_mixed` (the
+/// legacy `AUDITORZK_ALLOW_MIXED_CURRENCY`) is set, a response with several
+/// currencies needs per-currency commitments instead.
+pub fn check_single_currency(by_currency: &BTreeMap<String, i64>, allow_mixed: bool) -> Result<(), PlaidError> {
+    if by_currency.len() <= 1 {
+        return Ok(());
+    }
+    let currencies = by_currency.keys().map(String::as_str).collect::<Vec<_>>().join(", ");
+    if !allow_mixed {
+        warn!("❌ Accounts in {} currencies ({}) and no per-currency mode", by_currency.len(), currencies);
+        return Err(PlaidError::MixedCurrencies { currencies });
+    }
+    warn!(
+        "⚠️  AUDITORZK_ALLOW_MIXED_CURRENCY: summing {} currencies ({}) into one meaningless total; set AUDITORZK_COMMIT_BY_CURRENCY instead",
+        by_currency.len(),
+        currencies
+    );
+    Ok(())
+}
+
+/// Require at least `minimum` transcript commitments
+fn check_commitment_count(counts: CommitmentCounts, minimum: usize) -> Result<()> {
+    if counts.total() < minimum {
+        warn!("❌ {} transcript commitments, {} required", counts, minimum);
+        return Err(PlaidError::TooFewCommitments { found: counts.total(), required: minimum }.into());
+    }
+    info!("✅ {} transcript commitments received", counts);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::SessionFixture;
+    use crate::http::HeadLimits;
+
+    fn parser(strict_minor_units: bool, formula: BalanceFormula) -> PlaidParser {
+        PlaidParser { strict_minor_units, formula, ..PlaidParser::default() }
+    }
+
+    #[test]
+    fn server_name_is_lowercased_without_root_dot() {
+        assert_eq!(normalize_server_name("Sandbox.Plaid.Com."), "sandbox.plaid.com");
+        assert_eq!(normalize_server_name("localhost"), "localhost");
+    }
+
+    #[test]
+    fn inactive_accounts_are_counted_not_summed() {
+        let parser = |exclude_inactive| PlaidParser { exclude_inactive, ..PlaidParser::default() };
+        // An active account, one reporting no status, a closed one and a failed verification
+        let body = include_str!("../fixtures/balance_closed_account.json").as_bytes();
+
+        // By default every account counts, including the closed account's stale 5000
+        let balance = parser(false).extract(body).unwrap();
+        assert_eq!((balance.canonical().as_str(), balance.account_count, balance.inactive_accounts), ("5320.00", 4, 0));
+
+        let balance = parser(true).extract(body).unwrap();
+        assert_eq!((balance.canonical().as_str(), balance.account_count, balance.inactive_accounts), ("320.00", 2, 2));
+        assert_eq!(
+            balance.account_ids.unwrap(),
+            ["BxBXxLj1m4HMXBm9WZZmCWVbPjX16EHwv99vp", "dVzbVMLjrxTnLjX4G66XUp5GLklm4oiZy88yK"],
+        );
+    }
+
+    #[test]
+    fn accounts_of_several_items_are_summed() {
+        let parser = PlaidParser::default();
+        let extract = |response: &serde_json::Value| parser.extract(&serde_json::to_vec(response).unwrap());
+        let response: serde_json::Value = serde_json::from_str(include_str!("../fixtures/balance_multi_item.json")).unwrap();
+
+        // 1000.00 + 250.25 from the first Item, 500.00 from the second; its brokerage account is filtered out
+        for shape in [&response, &response["items"]] {
+            let balance = extract(shape).unwrap();
+            assert_eq!((balance.canonical().as_str(), balance.item_count, balance.account_count), ("1750.25", 2, 3));
+            assert_eq!(balance.account_ids.unwrap()[2], "3gE5gnRzNyfXpBK5wEEKcymJ5albGVUqg77gr");
+        }
+
+        let mut mixed = response.clone();
+        mixed["accounts"] = serde_json::json!([{
+            "account_id": "nVRK5AmnpzFGv6LvpEoRivjk9p7N16F6wnZrX",
+            "balances": { "current": 10.00, "iso_currency_code": "USD" },
+            "type": "depository"
+        }]);
+        let balance = extract(&mixed).unwrap();
+        assert_eq!((balance.canonical().as_str(), balance.item_count, balance.account_count), ("1760.25", 3, 4));
+
+        // The inventory spans Items, so an account can't be counted twice
+        let mut duplicated = response.clone();
+        duplicated["items"][1]["accounts"][0]["account_id"] = response["items"][0]["accounts"][0]["account_id"].clone();
+        let Err(Error::Plaid(PlaidError::ResponseJson(e))) = extract(&duplicated) else { panic!("expected a duplicate") };
+        assert!(e.to_string().starts_with("Duplicate account_id BxBXxLj1m4HMXBm9WZZmCWVbPjX16EHwv99vp"));
+
+        // Every Item needs accounts, and a response of neither shape has none
+        let mut missing = response.clone();
+        missing["items"][1].as_object_mut().unwrap().remove("accounts");
+        let Err(Error::Plaid(PlaidError::ResponseJson(e))) = extract(&missing) else { panic!("expected a missing array") };
+        assert!(e.to_string().starts_with("Item 2 has no accounts array"));
+        for neither in [serde_json::json!({ "request_id": "1zlMf2pOb1VpJ3e" }), serde_json::json!({ "items": [] })] {
+            assert!(matches!(extract(&neither), Err(Error::Plaid(PlaidError::NoAccounts))));
+        }
+        assert!(matches!(extract(&serde_json::json!(42)), Err(Error::Plaid(PlaidError::ResponseJson(_)))));
+    }
+
+    #[test]
+    fn commitments_are_counted_not_bytes() {
+        let session = SessionFixture::new("sandbox.plaid.com")
+            .sent(&b"POST /accounts/balance/get HTTP/1.1\r\nhost: sandbox.plaid.com\r\ncontent-length: 0\r\n\r\n"[..])
+            .json_response(include_bytes!("../fixtures/balance_multi_currency.json"))
+            .hash_commitment(Direction::Received, vec![0..400], b"first blinder")
+            .hash_commitment(Direction::Received, vec![500..900, 1000..1200], b"second blinder");
+        let facts = session.clone().build();
+        let counts = CommitmentCounts::of(&facts);
+        assert_eq!(counts, CommitmentCounts { hash: 2, encoding: 0 });
+        assert_eq!(counts.to_string(), "2 (2 hash, 0 encoding)");
+        assert_eq!(CommitmentCounts::of(&session.encoding_commitment().build()).total(), 3);
+
+        let mut config = ServerConfig::from_env().unwrap();
+        let mut validate = |minimum| {
+            config.min_transcript_commitments = minimum;
+            let parsed = ParsedSession::new(&facts, &HeadLimits::from_config(&config));
+            validate_plaid_connection(&facts, &parsed, None, &config).map_err(|e| (e.code(), e.to_string()))
+        };
+        validate(2).unwrap();
+        assert_eq!(
+            validate(3),
+            Err(("too_few_commitments", "Prover made 2 transcript commitments, at least 3 required".to_string())),
+        );
+    }
+
+    #[test]
+    fn balance_opening_must_open_a_received_commitment() {
+        let blinder = hex::decode("3a2591c4e07bd85f16a9cb3e4d7208f1").unwrap();
+        let commitment = TranscriptHash {
+            direction: "received".to_string(),
+            ranges: vec![[412, 419]],
+            algorithm: "sha256".to_string(),
+            hash: commitment_hash(b"1234.56", &blinder).to_vec(),
+        };
+        let opening = |balance: &str, blinder: &[u8]| BalanceOpening { balance: balance.to_string(), blinder: blinder.to_vec() };
+
+        assert!(check_balance_opening(std::slice::from_ref(&commitment), Some(&opening("1234.56", &blinder))).is_ok());
+
+        // A different balance or blinder doesn't open it
+        for bad in [opening("1234.57", &blinder), opening("1234.56", &[0x5a; 16])] {
+            let err = check_balance_opening(std::slice::from_ref(&commitment), Some(&bad)).unwrap_err();
+            assert!(matches!(err, PlaidError::BalanceOpeningMismatch { length: 7 }));
+        }
+
+        // Nor does a commitment over a wider range with the same hash
+        let mut wider = commitment.clone();
+        wider.ranges = vec![[400, 419]];
+        assert!(check_balance_opening(&[wider], Some(&opening("1234.56", &blinder))).is_err());
+
+        assert!(matches!(check_balance_opening(&[commitment], None), Err(PlaidError::BalanceOpeningRequired)));
+    }
+
+    #[test]
+    fn single_account_fails_a_minimum_of_two() {
+        let parser = PlaidParser::default();
+        let balance = parser.extract(include_str!("../fixtures/balance_single_account.json").as_bytes()).unwrap();
+        assert_eq!(balance.account_count, 1);
+
+        check_min_accounts(balance.account_count, 1).unwrap();
+        let err = check_min_accounts(balance.account_count, 2).unwrap_err();
+        assert!(matches!(err, PlaidError::TooFewAccounts { count: 1, minimum: 2 }));
+        assert_eq!(err.code(), "too_few_accounts");
+    }
+
+    #[test]
+    fn mixed_currencies_need_per_currency_commitments() {
+        let parser = PlaidParser::default();
+        let mixed = parser.extract(include_str!("../fixtures/balance_mixed_currency.json").as_bytes()).unwrap();
+        assert_eq!(mixed.canonical(), "5000.00");
+
+        let err = check_single_currency(&mixed.by_currency, false).unwrap_err();
+        assert!(matches!(&err, PlaidError::MixedCurrencies { currencies } if currencies == "EUR, USD"));
+        assert_eq!(err.code(), "mixed_currencies");
+        check_single_currency(&mixed.by_currency, true).unwrap();
+
+        let single = parser.extract(include_str!("../fixtures/balance_single_account.json").as_bytes()).unwrap();
+        check_single_currency(&single.by_currency, false).unwrap();
+    }
+
+    #[test]
+    fn strict_minor_units_rejects_fractional_cents() {
+        let body = include_bytes!("../fixtures/balance_fractional_cents.json");
+
+        let err = parser(true, BalanceFormula::Current).extract(body).unwrap_err();
+        assert_eq!(err.code(), "fractional_minor_units");
+        let Error::Plaid(PlaidError::FractionalMinorUnits { value, currency, allowed }) = err else {
+            panic!("expected fractional minor units, got {:?}", err)
+        };
+        assert_eq!((value.as_str(), currency.as_str(), allowed), ("100.005", "USD", 2));
+
+        // Outside strict mode the amount is rounded to cents
+        assert_eq!(parser(false, BalanceFormula::Current).extract(body).unwrap().canonical(), "100.01");
+    }
+
+    #[test]
+    fn strict_minor_units_checks_the_fields_the_formula_reads() {
+        let mut response: serde_json::Value = serde_json::from_str(include_str!("../fixtures/balance_holds.json")).unwrap();
+        response["accounts"][0]["balances"]["available"] = serde_json::json!(1100.005);
+        let body = serde_json::to_vec(&response).unwrap();
+
+        // "current" is whole cents, so only the formula that reads "available" fails
+        assert_eq!(parser(true, BalanceFormula::Current).extract(&body).unwrap().canonical(), "21234.56");
+        let err = parser(true, BalanceFormula::AvailableMinusHolds).extract(&body).unwrap_err();
+        let Error::Plaid(PlaidError::FractionalMinorUnits { value, .. }) = err else {
+            panic!("expected fractional minor units, got {:?}", err)
+        };
+        assert_eq!(value, "1100.005");
+
+        // Holds are read by the same formula, and checked the same way
+        let mut response: serde_json::Value = serde_json::from_str(include_str!("../fixtures/balance_holds.json")).unwrap();
+        response["accounts"][1]["balances"]["holds"] = serde_json::json!(0.001);
+        let body = serde_json::to_vec(&response).unwrap();
+        let err = parser(true, BalanceFormula::AvailableMinusHolds).extract(&body).unwrap_err();
+        assert_eq!(err.code(), "fractional_minor_units");
+        assert_eq!(parser(true, BalanceFormula::Current).extract(&body).unwrap().canonical(), "21234.56");
+    }
 }
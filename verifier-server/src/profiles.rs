@@ -0,0 +1,294 @@
+//! Versioned schema profiles for provider responses.
+//!
+//! Providers reshape their responses over time: fields get added, blocks get
+//! renamed. A *schema profile* describes one shape of a provider's response as
+//! data: where the accounts are (the extraction paths), a JSON pointer per field
+//! read off each account, and the response headers that shape comes with. Its
+//! applicability rules say when it may be used, by the API version the request
+//! asked for (`api_version_from` inclusive, `api_version_until` exclusive, both
+//! `YYYY-MM-DD`) and by a *sentinel*, a pointer every account of that shape
+//! resolves.
+//!
+//! Profiles are listed newest first. The parser tries each one that applies to
+//! the request's version, in order, and uses the first whose shape the response
+//! has. A profile the response doesn't fit (a missing sentinel, accounts array
+//! or required header) is passed over with a note, which is recorded together
+//! with the profile used in the balance result and the attestation. A response
+//! that fits a profile but fails one of its checks is rejected, never retried
+//! under an older profile.
+//!
+//! The built-in profiles ([`ProfileSet::plaid`]) are embedded from
+//! `profiles/plaid.json`, so supporting a new shape only takes a new entry there.
+//! The tests read responses of two shapes with the two-profile set in
+//! `fixtures/schema_profiles.json`.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, OnceLock};
+
+use crate::headers::{requested_api_version, RequiredHeader};
+use crate::parsed::ParsedSession;
+use crate::plaid::PlaidError;
+
+/// Built-in Plaid profiles, newest first
+const PLAID_PROFILES: &str = include_str!("../profiles/plaid.json");
+
+/// Every schema profile of one provider, newest first
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileSet {
+    pub provider: String,
+    profiles: Vec<SchemaProfile>,
+}
+
+/// One shape of a provider's response
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaProfile {
+    /// Recorded with the claim, e.g. `plaid.balance.2020-09-14`
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub applies: Applicability,
+    /// Key of the accounts array, at the top level or in each Item
+    pub accounts: String,
+    /// Key of the array of per-Item objects
+    pub items: String,
+    pub fields: FieldPointers,
+    /// Response headers this shape comes with, in `AUDITORZK_REQUIRED_HEADERS` syntax
+    #[serde(default, deserialize_with = "required_headers")]
+    pub required_headers: Vec<RequiredHeader>,
+}
+
+/// When a profile may be used
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Applicability {
+    /// First API version the profile applies to
+    pub api_version_from: Option<String>,
+    /// First API version it no longer applies to
+    pub api_version_until: Option<String>,
+    /// Pointer every account of this shape resolves
+    pub sentinel: Option<String>,
+}
+
+/// JSON pointers into an account object, one per field the balance parser reads
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldPointers {
+    pub account_id: String,
+    #[serde(rename = "type")]
+    pub account_type: String,
+    pub subtype: String,
+    pub status: String,
+    pub verification_status: String,
+    pub current: String,
+    pub available: String,
+    pub holds: String,
+    pub currency: String,
+}
+
+/// What the parser knows of the exchange besides the response body
+#[derive(Debug, Clone, Default)]
+pub struct ResponseContext {
+    /// API version the request asked for; every profile applies when unknown
+    pub api_version: Option<String>,
+    /// Response headers; profiles' required headers aren't checked when unknown
+    pub headers: Option<BTreeMap<String, String>>,
+}
+
+impl ResponseContext {
+    pub fn of(parsed: &ParsedSession<'_>) -> Self {
+        Self {
+            api_version: requested_api_version(parsed.request()).map(str::to_string),
+            headers: Some(parsed.headers().clone()),
+        }
+    }
+}
+
+impl ProfileSet {
+    /// Parse and check a profile set
+    pub fn from_json(json: &str) -> Result<Self> {
+        let set: Self = serde_json::from_str(json).context("Malformed schema profiles")?;
+        if set.profiles.is_empty() {
+            bail!("No schema profiles for {}", set.provider);
+        }
+
+        let mut ids = BTreeSet::new();
+        for profile in &set.profiles {
+            if !ids.insert(profile.id.as_str()) {
+                bail!("Duplicate schema profile {}", profile.id);
+            }
+            profile.check().with_context(|| format!("Invalid schema profile {}", profile.id))?;
+        }
+        Ok(set)
+    }
+
+    /// The embedded Plaid profiles
+    pub fn plaid() -> Arc<Self> {
+        static PLAID: OnceLock<Arc<ProfileSet>> = OnceLock::new();
+        Arc::clone(PLAID.get_or_init(|| {
+            Arc::new(Self::from_json(PLAID_PROFILES).expect("embedded Plaid schema profiles are valid"))
+        }))
+    }
+
+    /// Every profile, newest first
+    pub fn profiles(&self) -> &[SchemaProfile] {
+        &self.profiles
+    }
+
+    /// Profiles applicable to a request for `api_version`, newest first
+    pub fn applicable<'a>(&'a self, api_version: Option<&'a str>) -> impl Iterator<Item = &'a SchemaProfile> {
+        self.profiles.iter().filter(move |profile| profile.applies.admits(api_version))
+    }
+}
+
+impl SchemaProfile {
+    /// The response's known headers include every one this shape requires
+    pub fn check_headers(&self, headers: Option<&BTreeMap<String, String>>) -> Result<(), PlaidError> {
+        let Some(headers) = headers else {
+            return Ok(());
+        };
+        for header in &self.required_headers {
+            header.check(headers).map_err(|e| self.mismatch(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// The response doesn't have this shape
+    pub fn mismatch(&self, reason: String) -> PlaidError {
+        PlaidError::ProfileMismatch { profile: self.id.clone(), reason }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.id.trim().is_empty() {
+            bail!("Empty profile id");
+        }
+        if self.accounts.is_empty() || self.items.is_empty() {
+            bail!("Empty accounts or items key");
+        }
+
+        let fields = &self.fields;
+        let pointers = [
+            &fields.account_id,
+            &fields.account_type,
+            &fields.subtype,
+            &fields.status,
+            &fields.verification_status,
+            &fields.current,
+            &fields.available,
+            &fields.holds,
+            &fields.currency,
+        ];
+        for pointer in pointers.into_iter().chain(&self.applies.sentinel) {
+            if !pointer.starts_with('/') {
+                bail!("Field pointer '{}' must start with /", pointer);
+            }
+        }
+
+        let dates = [&self.applies.api_version_from, &self.applies.api_version_until];
+        for date in dates.into_iter().flatten() {
+            if !is_date(date) {
+                bail!("API version '{}' is not a YYYY-MM-DD date", date);
+            }
+        }
+        if let (Some(from), Some(until)) = (&self.applies.api_version_from, &self.applies.api_version_until) {
+            if from >= until {
+                bail!("api_version_from {} is not before api_version_until {}", from, until);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Applicability {
+    /// Whether a request for `api_version` may use the profile. Versions compare
+    /// as `YYYY-MM-DD` text
+    pub fn admits(&self, api_version: Option<&str>) -> bool {
+        let Some(version) = api_version else {
+            return true;
+        };
+        self.api_version_from.as_deref().is_none_or(|from| version >= from)
+            && self.api_version_until.as_deref().is_none_or(|until| version < until)
+    }
+}
+
+fn is_date(text: &str) -> bool {
+    text.len() == 10
+        && text.bytes().enumerate().all(|(i, b)| if i == 4 || i == 7 { b == b'-' } else { b.is_ascii_digit() })
+}
+
+fn required_headers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<RequiredHeader>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|header| header.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auditorzk_attestation::profile::ProfileSelection;
+
+    use crate::balance::BalanceParser;
+    use crate::plaid::PlaidParser;
+
+    #[test]
+    fn newest_fitting_profile_is_used() {
+        let profiles = Arc::new(ProfileSet::from_json(include_str!("../fixtures/schema_profiles.json")).unwrap());
+        let parser = |api_version: Option<&str>| PlaidParser {
+            strict_minor_units: true,
+            exclude_inactive: true,
+            profiles: Arc::clone(&profiles),
+            context: ResponseContext { api_version: api_version.map(str::to_string), headers: None },
+            ..PlaidParser::default()
+        };
+        let reshaped = include_bytes!("../fixtures/balance_profile_2025.json");
+        let current = include_bytes!("../fixtures/balance_single_account.json");
+
+        // The newest profile fits the reshaped response, and reads its account state
+        let balance = parser(Some("2025-01-01")).extract(reshaped).unwrap();
+        let selection = balance.schema_profile.as_ref().unwrap();
+        assert_eq!(selection.id, "plaid.balance.2025-01-01");
+        assert!(selection.notes.is_empty());
+        assert_eq!((balance.canonical().as_str(), balance.inactive_accounts), ("1234.56", 1));
+
+        // Without a known version both profiles apply; the newest doesn't fit, so
+        // the older one is used and the fallback noted
+        let balance = parser(None).extract(current).unwrap();
+        let selection = balance.schema_profile.as_ref().unwrap();
+        assert_eq!(selection.id, "plaid.balance.2020-09-14");
+        assert_eq!(selection.notes.len(), 1);
+        assert!(selection.notes[0].starts_with("plaid.balance.2025-01-01: "), "{:?}", selection.notes);
+        assert_eq!(balance.canonical(), "1234.56");
+
+        // A request for an older version never tries the newer profile
+        let balance = parser(Some("2020-09-14")).extract(current).unwrap();
+        assert_eq!(balance.schema_profile.unwrap(), ProfileSelection {
+            id: "plaid.balance.2020-09-14".to_string(),
+            notes: vec![],
+        });
+
+        // A response that fits no profile for its version fails
+        let err = parser(Some("2025-06-01")).extract(current).unwrap_err();
+        assert_eq!(err.code(), "schema_profile_mismatch");
+
+        // Known response headers are part of a profile's shape: without its
+        // content-type the reshaped response falls back to the older profile, which
+        // finds no balance in it
+        let headers = |headers: &[(&str, &str)]| ResponseContext {
+            api_version: None,
+            headers: Some(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<BTreeMap<_, _>>()),
+        };
+        let err = PlaidParser { context: headers(&[]), ..parser(None) }.extract(reshaped).unwrap_err();
+        assert_eq!(err.code(), "no_balance");
+        let json = headers(&[("content-type", "application/json; charset=utf-8")]);
+        let balance = PlaidParser { context: json, ..parser(None) }.extract(reshaped).unwrap();
+        assert_eq!(balance.schema_profile.unwrap().id, "plaid.balance.2025-01-01");
+
+        // The embedded set loads
+        assert_eq!(ProfileSet::plaid().profiles()[0].id, "plaid.balance.2020-09-14");
+    }
+}
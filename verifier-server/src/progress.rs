@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::config::ServerConfig;
+use crate::control::{ProgressStage, VerifierMessage};
+
+/// Progress frames queued for the prover before new ones are dropped
+const PROGRESS_QUEUE_LEN: usize = 16;
+
+/// Sends best-effort `progress` frames to the prover for UX spinners.
+///
+/// Frames go through a small queue that the prover-to-ws writer only drains when
+/// no MPC bytes are waiting, and are dropped instead of waited on when the queue
+/// is full, so progress reporting never slows the MPC stream.
+#[derive(Clone)]
+pub struct Progress {
+    /// `None` when progress events are disabled
+    tx: Option<mpsc::Sender<VerifierMessage>>,
+}
+
+impl Progress {
+    /// A reporter and the queue's receiving end, for the writer
+    pub fn new(config: &ServerConfig) -> (Self, mpsc::Receiver<VerifierMessage>) {
+        let (tx, rx) = mpsc::channel(PROGRESS_QUEUE_LEN);
+        (Self { tx: config.progress_events.then_some(tx) }, rx)
+    }
+
+    pub fn stage(&self, stage: ProgressStage) {
+        self.send(VerifierMessage::Progress { stage, percent: None });
+    }
+
+    fn send(&self, message: VerifierMessage) {
+        if let Some(tx) = &self.tx {
+            if tx.try_send(message).is_err() {
+                debug!("Progress frame dropped, queue full");
+            }
+        }
+    }
+
+    /// Report `exchanging` percentages from the session's byte counter, at most once
+    /// per `interval` and only when the figure changed, until MPC completes
+    pub async fn track_mpc(self, bytes: Arc<AtomicU64>, mpc_complete: Arc<AtomicBool>, expected: u64, interval: Duration) {
+        if self.tx.is_none() {
+            return;
+        }
+
+        let mut ticker = tokio::time::interval(interval);
+        let mut last = None;
+        loop {
+            ticker.tick().await;
+            if mpc_complete.load(Ordering::Relaxed) {
+                return;
+            }
+            let forwarded = bytes.load(Ordering::Relaxed);
+            if forwarded == 0 {
+                continue;
+            }
+            let percent = mpc_percent(forwarded, expected);
+            if last != Some(percent) {
+                last = Some(percent);
+                self.send(VerifierMessage::Progress { stage: ProgressStage::Exchanging, percent: Some(percent) });
+            }
+        }
+    }
+}
+
+/// Completion estimate from forwarded bytes, held at 99% until MPC actually completes
+fn mpc_percent(forwarded: u64, expected: u64) -> u8 {
+    (forwarded.saturating_mul(100) / expected.max(1)).min(99) as u8
+}
@@ -0,0 +1,428 @@
+//! The prover's side of the control protocol, for provers written in Rust.
+//!
+//! A session is one WebSocket: JSON control frames travel as text frames and
+//! the MPC stream as binary frames (see [`crate::control`] and
+//! [`crate::upgrade`]). This module drives the prover's end over any
+//! `Sink + Stream` of WebSocket messages, with the frame types the verifier
+//! itself reads and writes, so the two can't drift apart:
+//!
+//! 1. [`ClientHandshake::start`] sends the `hello` and, when it declares
+//!    sizes, waits for the verifier's `limits` (pre-MPC).
+//! 2. It returns an [`MpcStream`] to hand to `tlsn-prover` (an
+//!    `AsyncRead + AsyncWrite`) and the [`ClientSession`] that carries that
+//!    stream over binary frames.
+//! 3. [`ClientSession::run`] forwards MPC bytes both ways and collects
+//!    `progress` frames. Once the prover is done with the stream (post-MPC) it
+//!    waits for the verifier's verdict and answers its close frame with one of
+//!    its own. A `1000` close, after the optional `blinder` and `summary`, is
+//!    the receipt that the attestation was signed ([`SessionReceipt`]). Any other close is
+//!    a [`ProtocolError::Closed`] with the code, reason and the control frame
+//!    that preceded it (`error`, `config_mismatch` or `retry_later`).
+//!
+//! With `tlsn-prover`, a session reads:
+//!
+//! ```ignore
+//! let (ws, response) = tokio_tungstenite::connect_async(request).await?;
+//! let hello = Hello { blinder: Some(blinder), expected_recv_bytes: Some(9000), ..Hello::default() };
+//! let (mpc, session) = ClientHandshake::new(hello, Framing::negotiated(&response)).start(ws).await?;
+//! let session = tokio::spawn(session.run());
+//!
+//! let prover = Prover::new(prover_config).setup(mpc).await?;
+//! // ... connect to the provider, send the request, prove and close as usual
+//!
+//! let receipt = session.await??;
+//! ```
+//!
+//! The tests run a whole session in memory against a stand-in verifier that
+//! answers with the verifier's own frame types, and [`ClientHandshake::start`]
+//! against the verifier's real session loop.
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::fmt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+
+use crate::control::{parse_verifier_message, VerifierClose};
+use crate::error::BoxError;
+use crate::upgrade::FrameError;
+use crate::verifier::DUPLEX_BUFFER_BYTES;
+
+pub use crate::admission::RefusalReason;
+pub use crate::control::{
+    AttestationFormat, BalanceOpening, Hello, ProgressStage, ProverMessage, SigningPolicy, TemplateDeclaration,
+    VerifierMessage,
+};
+pub use crate::disclosure::DisclosureReport;
+pub use crate::upgrade::{Framing, SUBPROTOCOL};
+
+/// The prover's end of the MPC stream, for `tlsn-prover`'s `Prover::setup`
+pub type MpcStream = Compat<DuplexStream>;
+
+/// Close code for a close frame without one (RFC 6455 `1005`)
+const NO_STATUS: u16 = 1005;
+
+/// The prover's side of a session before MPC starts
+#[derive(Debug, Clone)]
+pub struct ClientHandshake {
+    hello: Option<Hello>,
+    framing: Framing,
+}
+
+/// The verifier's MPC limits, from its `limits` frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimits {
+    pub max_sent_bytes: usize,
+    pub max_recv_bytes: usize,
+}
+
+/// The prover's side of a session once MPC may start: carries the
+/// [`MpcStream`] over binary frames until the verifier closes
+pub struct ClientSession<S> {
+    ws: S,
+    bridge: DuplexStream,
+    framing: Framing,
+    limits: Option<SessionLimits>,
+    stages: Vec<ProgressStage>,
+    on_progress: Option<ProgressCallback>,
+}
+
+/// Called with every `progress` frame's stage and percentage
+type ProgressCallback = Box<dyn FnMut(ProgressStage, Option<u8>) + Send>;
+
+/// What the prover learns of a session that ended with a signed attestation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionReceipt {
+    /// The verifier's limits, when the `hello` declared sizes
+    pub limits: Option<SessionLimits>,
+    /// Progress stages received, in order, each once. Progress frames are best
+    /// effort, so stages may be missing
+    pub stages: Vec<ProgressStage>,
+    /// The `summary` frame, when the `hello` asked for one
+    pub summary: Option<DisclosureReport>,
+    /// The blinder the verifier picked for the claim commitment, when the
+    /// `hello` carried none (or was skipped). Keep it to open the commitment
+    pub blinder: Option<Vec<u8>>,
+}
+
+/// Why the prover's side of a session failed
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("WebSocket to the verifier failed")]
+    Transport(#[source] BoxError),
+    #[error("Verifier connection ended without a close frame")]
+    Dropped,
+    #[error("Unreadable control frame from the verifier")]
+    ControlFrame(#[source] serde_json::Error),
+    #[error(transparent)]
+    Frame(#[from] FrameError),
+    #[error("Unexpected {0} frame before the verifier's limits")]
+    OutOfPhase(&'static str),
+    /// The verifier ended the session without signing
+    #[error("Verifier closed the session with {code}: {reason}")]
+    Closed {
+        /// Close code, see [`VerifierClose`]; `1005` for a close frame without one
+        code: u16,
+        reason: String,
+        /// The control frame sent just before the close, if any
+        frame: Option<VerifierMessage>,
+    },
+}
+
+impl ProtocolError {
+    fn closed(close: Option<CloseFrame<'_>>, frame: Option<VerifierMessage>) -> Self {
+        Self::Closed {
+            code: close.as_ref().map_or(NO_STATUS, |close| u16::from(close.code)),
+            reason: close.map(|close| close.reason.into_owned()).unwrap_or_default(),
+            frame,
+        }
+    }
+
+    /// Seconds to wait before reconnecting, when the verifier refused the session
+    /// for now
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Self::Closed { frame: Some(VerifierMessage::RetryLater { retry_after, .. }), .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+fn transport<E: Into<BoxError>>(error: E) -> ProtocolError {
+    ProtocolError::Transport(error.into())
+}
+
+impl ClientHandshake {
+    /// Open the session with `hello`
+    pub fn new(hello: Hello, framing: Framing) -> Self {
+        Self { hello: Some(hello), framing }
+    }
+
+    /// Go straight to MPC without a `hello`, taking the verifier's defaults
+    pub fn without_hello(framing: Framing) -> Self {
+        Self { hello: None, framing }
+    }
+
+    /// Send the `hello` and, if it declares sizes, wait for the verifier's limits.
+    ///
+    /// A rejected `hello` is only reported here when the prover waits for the
+    /// limits; otherwise [`ClientSession::run`] reports it.
+    pub async fn start<S, E>(self, mut ws: S) -> Result<(MpcStream, ClientSession<S>), ProtocolError>
+    where
+        S: Sink<Message, Error = E> + Stream<Item = Result<Message, E>> + Unpin,
+        E: Into<BoxError>,
+    {
+        let mut limits = None;
+        let mut stages = Vec::new();
+        if let Some(hello) = self.hello {
+            let declares_size = hello.declares_size();
+            ws.send(Message::Text(ProverMessage::Hello(hello).to_json())).await.map_err(transport)?;
+            if declares_size {
+                limits = Some(wait_for_limits(&mut ws, &mut stages).await?);
+            }
+        }
+
+        let (mpc, bridge) = tokio::io::duplex(DUPLEX_BUFFER_BYTES);
+        let session = ClientSession { ws, bridge, framing: self.framing, limits, stages, on_progress: None };
+        Ok((mpc.compat(), session))
+    }
+}
+
+/// Read up to the verifier's `limits`; the `error` or `retry_later` frame of a
+/// refused session is followed by the close frame
+async fn wait_for_limits<S, E>(ws: &mut S, stages: &mut Vec<ProgressStage>) -> Result<SessionLimits, ProtocolError>
+where
+    S: Stream<Item = Result<Message, E>> + Unpin,
+    E: Into<BoxError>,
+{
+    let mut last_frame = None;
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => match parse_verifier_message(&text).map_err(ProtocolError::ControlFrame)? {
+                VerifierMessage::Limits { max_sent_bytes, max_recv_bytes } => {
+                    return Ok(SessionLimits { max_sent_bytes, max_recv_bytes })
+                }
+                VerifierMessage::Progress { stage, .. } => record_stage(stages, stage),
+                frame => last_frame = Some(frame),
+            },
+            Some(Ok(Message::Binary(_))) => return Err(ProtocolError::OutOfPhase("binary")),
+            Some(Ok(Message::Close(close))) => return Err(ProtocolError::closed(close, last_frame)),
+            Some(Ok(_)) => {} // ping/pong
+            Some(Err(e)) => return Err(transport(e)),
+            None => return Err(ProtocolError::Dropped),
+        }
+    }
+}
+
+fn record_stage(stages: &mut Vec<ProgressStage>, stage: ProgressStage) {
+    if stages.last() != Some(&stage) {
+        stages.push(stage);
+    }
+}
+
+/// What the session loop woke up for
+enum Event<E> {
+    Mpc(std::io::Result<usize>),
+    Verifier(Option<Result<Message, E>>),
+}
+
+impl<S> ClientSession<S> {
+    /// The verifier's limits, when the `hello` declared sizes
+    pub fn limits(&self) -> Option<SessionLimits> {
+        self.limits
+    }
+
+    /// Call `f` with every progress frame as it arrives, e.g. to drive a spinner
+    pub fn on_progress(mut self, f: impl FnMut(ProgressStage, Option<u8>) + Send + 'static) -> Self {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+
+    /// Forward the MPC stream until the verifier closes, then acknowledge the close.
+    ///
+    /// Binary frames from the verifier are unwrapped into the [`MpcStream`] and
+    /// what the prover writes to it goes out wrapped per the negotiated framing.
+    /// Once the prover closes or drops its end, only control frames are read.
+    pub async fn run<E>(mut self) -> Result<SessionReceipt, ProtocolError>
+    where
+        S: Sink<Message, Error = E> + Stream<Item = Result<Message, E>> + Unpin,
+        E: Into<BoxError>,
+    {
+        let (mut mpc_read, mut mpc_write) = tokio::io::split(self.bridge);
+        let mut buf = vec![0u8; 8192];
+        let (mut mpc_open, mut prover_reading) = (true, true);
+        let (mut summary, mut blinder, mut last_frame) = (None, None, None);
+
+        loop {
+            let event = tokio::select! {
+                read = mpc_read.read(&mut buf), if mpc_open => Event::Mpc(read),
+                message = self.ws.next() => Event::Verifier(message),
+            };
+            match event {
+                Event::Mpc(Ok(0) | Err(_)) => mpc_open = false,
+                Event::Mpc(Ok(n)) => {
+                    self.ws.send(Message::Binary(self.framing.wrap(&buf[..n]))).await.map_err(transport)?
+                }
+                Event::Verifier(Some(Ok(Message::Binary(frame)))) => {
+                    let data = self.framing.payload(&frame)?;
+                    // A prover that gave up on the stream still hears the verdict
+                    if prover_reading && mpc_write.write_all(data).await.is_err() {
+                        prover_reading = false;
+                    }
+                }
+                Event::Verifier(Some(Ok(Message::Text(text)))) => {
+                    match parse_verifier_message(&text).map_err(ProtocolError::ControlFrame)? {
+                        VerifierMessage::Progress { stage, percent } => {
+                            record_stage(&mut self.stages, stage);
+                            if let Some(on_progress) = &mut self.on_progress {
+                                on_progress(stage, percent);
+                            }
+                        }
+                        VerifierMessage::Summary { disclosure } => summary = Some(disclosure),
+                        VerifierMessage::Blinder { blinder: picked } => blinder = Some(picked),
+                        frame => last_frame = Some(frame),
+                    }
+                }
+                Event::Verifier(Some(Ok(Message::Close(close)))) => {
+                    let _ = self.ws.close().await;
+                    let done = close.as_ref().is_some_and(|close| u16::from(close.code) == VerifierClose::Done.code());
+                    if !done {
+                        return Err(ProtocolError::closed(close, last_frame));
+                    }
+                    return Ok(SessionReceipt { limits: self.limits, stages: self.stages, summary, blinder });
+                }
+                Event::Verifier(Some(Ok(_))) => {} // ping/pong
+                Event::Verifier(Some(Err(e))) => return Err(transport(e)),
+                Event::Verifier(None) => return Err(ProtocolError::Dropped),
+            }
+        }
+    }
+}
+
+impl<S> fmt::Debug for ClientSession<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientSession")
+            .field("framing", &self.framing)
+            .field("limits", &self.limits)
+            .field("stages", &self.stages)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use std::sync::Arc;
+    use tokio_tungstenite::tungstenite::protocol::Role;
+    use tokio_tungstenite::WebSocketStream;
+
+    use crate::config::ServerConfig;
+    use crate::control::{parse_prover_message, ProverMessage};
+    use crate::memory::SessionRegistry;
+    use crate::proxy::ClientAddr;
+    use crate::tasks::TaskRegistry;
+    use crate::usage::UsageLedger;
+    use crate::verifier::handle_verification;
+
+    #[tokio::test]
+    async fn whole_session_against_a_stand_in_verifier() {
+        use ProgressStage::*;
+
+        let (client, server) = tokio::io::duplex(1 << 16);
+        let framing = Framing::LengthPrefixed;
+
+        let verifier = tokio::spawn(async move {
+            let mut ws = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+            let control = |message: VerifierMessage| Message::Text(message.to_json());
+            let Some(Ok(Message::Text(text))) = ws.next().await else { panic!("expected a hello") };
+            let ProverMessage::Hello(hello) = parse_prover_message(&text).unwrap() else { panic!("expected a hello") };
+            assert_eq!(hello.blinder.map(hex::encode).as_deref(), Some("3f8a1c9e47d2b6057ec1a4f0935d68b2"));
+            assert_eq!(hello.expected_recv_bytes, Some(9000));
+            assert!(hello.disclosure_report);
+
+            ws.send(control(VerifierMessage::Limits { max_sent_bytes: 4096, max_recv_bytes: 16384 })).await.unwrap();
+            ws.send(control(VerifierMessage::Progress { stage: ProgressStage::Connected, percent: None })).await.unwrap();
+            let Some(Ok(Message::Binary(frame))) = ws.next().await else { panic!("expected MPC bytes") };
+            assert_eq!(framing.payload(&frame).unwrap(), b"client hello");
+            ws.send(Message::Binary(framing.wrap(b"server hello"))).await.unwrap();
+
+            for stage in [ProgressStage::Verified, ProgressStage::Validated, ProgressStage::Signed] {
+                ws.send(control(VerifierMessage::Progress { stage, percent: None })).await.unwrap();
+            }
+            ws.send(control(VerifierMessage::Summary { disclosure: Default::default() })).await.unwrap();
+            ws.send(Message::Close(Some(VerifierClose::Done.frame("")))).await.unwrap();
+
+            // The prover acknowledges the close with its own
+            matches!(ws.next().await, Some(Ok(Message::Close(_))))
+        });
+
+        let ws = WebSocketStream::from_raw_socket(client, Role::Client, None).await;
+        let hello = Hello {
+            blinder: Some(hex::decode("3f8a1c9e47d2b6057ec1a4f0935d68b2").unwrap()),
+            expected_recv_bytes: Some(9000),
+            disclosure_report: true,
+            ..Hello::default()
+        };
+        let (mut mpc, session) = ClientHandshake::new(hello, framing).start(ws).await.unwrap();
+        assert_eq!(session.limits(), Some(SessionLimits { max_sent_bytes: 4096, max_recv_bytes: 16384 }));
+        let session = tokio::spawn(session.run());
+
+        // What tlsn-prover does with the stream
+        mpc.write_all(b"client hello").await.unwrap();
+        let mut reply = [0u8; 12];
+        mpc.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"server hello");
+        mpc.close().await.unwrap();
+
+        let receipt = session.await.unwrap().unwrap();
+        assert_eq!(receipt.stages, [Connected, Verified, Validated, Signed]);
+        assert_eq!(receipt.summary, Some(Default::default()));
+        assert_eq!(receipt.blinder, None);
+        assert!(verifier.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn hello_limits_are_enforced_before_mpc() {
+        let config = Arc::new(ServerConfig::from_env().unwrap());
+        let tasks = Arc::new(TaskRegistry::default());
+        let sessions = Arc::new(SessionRegistry::new(Arc::clone(&tasks), Arc::new(UsageLedger::new(None))));
+        let connect = || {
+            let (config, tasks, session) = (Arc::clone(&config), Arc::clone(&tasks), sessions.register());
+            async move {
+                let (client, server) = tokio::io::duplex(1 << 16);
+                let verifier = tokio::spawn(async move {
+                    let ws = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+                    let client = ClientAddr::direct("127.0.0.1:4711".parse().unwrap());
+                    handle_verification(ws, &client, Framing::LengthPrefixed, &config, None, &session, &tasks).await
+                });
+                (WebSocketStream::from_raw_socket(client, Role::Client, None).await, verifier)
+            }
+        };
+        let blinder = hex::decode("3f8a1c9e47d2b6057ec1a4f0935d68b2").unwrap();
+
+        // A declared response over the limit is rejected before MPC
+        let (ws, verifier) = connect().await;
+        let hello = Hello { blinder: Some(blinder), expected_recv_bytes: Some(1 << 20), ..Hello::default() };
+        let Err(ProtocolError::Closed { code, frame, .. }) = ClientHandshake::new(hello, Framing::LengthPrefixed).start(ws).await
+        else {
+            panic!("expected the hello to be rejected")
+        };
+        assert_eq!(code, 4000);
+        assert!(matches!(frame, Some(VerifierMessage::Error { message }) if message.contains("exceeds the limit")));
+        assert_eq!(verifier.await.unwrap().unwrap_err().code(), "hello_rejected");
+
+        // An accepted one gets the limits, with or without a blinder of its own
+        // (the verifier then picks one); bytes that aren't tlsn's then fail MPC
+        let (ws, verifier) = connect().await;
+        let hello = Hello { expected_recv_bytes: Some(9000), ..Hello::default() };
+        let (mut mpc, session) = ClientHandshake::new(hello, Framing::LengthPrefixed).start(ws).await.unwrap();
+        assert_eq!(session.limits(), Some(SessionLimits { max_sent_bytes: 4096, max_recv_bytes: 16384 }));
+        let session = tokio::spawn(session.run());
+        mpc.write_all(&[0xff; 64]).await.unwrap();
+        let Err(ProtocolError::Closed { code, .. }) = session.await.unwrap() else { panic!("expected MPC to fail") };
+        assert_eq!(code, 4002);
+        assert_eq!(verifier.await.unwrap().unwrap_err().code(), "mpc_failed");
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_tungstenite::tungstenite::http::HeaderMap;
+use tracing::debug;
+
+/// First 12 bytes of every PROXY protocol v2 header
+pub const PROXY_V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Largest PROXY v2 address block accepted (TLVs included)
+const MAX_PROXY_V2_LEN: usize = 1024;
+
+/// How long a trusted proxy has to send its PROXY header
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the effective client address of a connection comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClientIpSource {
+    /// The TCP peer; proxy headers are ignored
+    #[default]
+    Peer,
+    /// `Forwarded` / `X-Forwarded-For` on the upgrade request, from trusted proxies only
+    Forwarded,
+    /// A PROXY protocol v2 header before any HTTP, from trusted proxies only
+    ProxyProtocol,
+}
+
+impl ClientIpSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Peer => "peer",
+            Self::Forwarded => "forwarded",
+            Self::ProxyProtocol => "proxy_protocol",
+        }
+    }
+}
+
+impl fmt::Display for ClientIpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ClientIpSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "peer" => Ok(Self::Peer),
+            "forwarded" => Ok(Self::Forwarded),
+            "proxy_protocol" => Ok(Self::ProxyProtocol),
+            other => bail!("Unknown client IP source '{}' (expected peer, forwarded or proxy_protocol)", other),
+        }
+    }
+}
+
+/// An IP network in CIDR notation (`10.0.0.0/8`); a bare address is a /32 or /128
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => prefix_matches(&network.octets(), &ip.octets(), self.prefix),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => prefix_matches(&network.octets(), &ip.octets(), self.prefix),
+            _ => false,
+        }
+    }
+}
+
+/// Whether the first `prefix` bits of `a` and `b` agree
+fn prefix_matches(a: &[u8], b: &[u8], prefix: u8) -> bool {
+    let (bytes, bits) = ((prefix / 8) as usize, prefix % 8);
+    if a[..bytes] != b[..bytes] {
+        return false;
+    }
+    bits == 0 || (a[bytes] ^ b[bytes]) >> (8 - bits) == 0
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let network: IpAddr = address.parse().with_context(|| format!("Invalid proxy address '{}'", s))?;
+        let network = network.to_canonical();
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().ok().filter(|&p| p <= max).ok_or_else(|| {
+                anyhow!("Invalid prefix length in '{}' (0-{})", s, max)
+            })?,
+            None => max,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+/// Proxies whose forwarding headers and PROXY preambles are believed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(pub Vec<Cidr>);
+
+impl TrustedProxies {
+    pub fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for TrustedProxies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+        f.write_str(&list.join(", "))
+    }
+}
+
+/// The client a connection is attributed to in logs and records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr {
+    pub ip: IpAddr,
+    /// Source port, when the source of the address carries one
+    pub port: Option<u16>,
+    /// TCP peer the address was learned from, if not the client itself
+    pub proxy: Option<SocketAddr>,
+}
+
+impl ClientAddr {
+    /// The TCP peer is the client
+    pub fn direct(peer: SocketAddr) -> Self {
+        Self { ip: peer.ip().to_canonical(), port: Some(peer.port()), proxy: None }
+    }
+
+    fn via(source: SocketAddr, proxy: SocketAddr) -> Self {
+        Self { ip: source.ip().to_canonical(), port: Some(source.port()), proxy: Some(proxy) }
+    }
+}
+
+impl fmt::Display for ClientAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}", SocketAddr::new(self.ip, port))?,
+            None => write!(f, "{}", self.ip)?,
+        }
+        match self.proxy {
+            Some(proxy) => write!(f, " (via {})", proxy),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Client address from the upgrade request's forwarding headers.
+///
+/// Headers are only believed when the TCP peer is a trusted proxy. The hops are
+/// then walked right to left, skipping trusted proxies, and the first untrusted
+/// hop is the client: entries a client prepends itself are never reached. The
+/// RFC 7239 `Forwarded` header wins over `X-Forwarded-For` when both are present.
+/// If no usable hop is found the peer is the client.
+pub fn resolve_forwarded(headers: &HeaderMap, peer: SocketAddr, trusted: &TrustedProxies) -> ClientAddr {
+    let direct = ClientAddr::direct(peer);
+    let has_headers = headers.contains_key("forwarded") || headers.contains_key("x-forwarded-for");
+    if !has_headers {
+        return direct;
+    }
+    if !trusted.trusts(peer.ip()) {
+        debug!("Ignoring forwarding headers from untrusted peer {}", peer);
+        return direct;
+    }
+
+    let hops: Vec<Hop> = if headers.contains_key("forwarded") {
+        header_values(headers, "forwarded").iter().filter_map(|element| forwarded_for(element)).collect()
+    } else {
+        header_values(headers, "x-forwarded-for").iter().map(|hop| parse_hop(hop)).collect()
+    };
+
+    for hop in hops.into_iter().rev() {
+        match hop {
+            Some((ip, _)) if trusted.trusts(ip) => continue,
+            Some((ip, port)) => return ClientAddr { ip: ip.to_canonical(), port, proxy: Some(peer) },
+            // `unknown`, an obfuscated identifier or garbage: nothing further left is reliable
+            None => break,
+        }
+    }
+    direct
+}
+
+/// One forwarding hop: an address and maybe a port, `None` if it isn't an address
+type Hop = Option<(IpAddr, Option<u16>)>;
+
+/// Comma-separated entries of every instance of a header
+fn header_values(headers: &HeaderMap, name: &str) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|entry| entry.trim().to_string())
+        .collect()
+}
+
+/// The `for=` node of one `Forwarded` element, if it has one
+fn forwarded_for(element: &str) -> Option<Hop> {
+    element.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        key.trim().eq_ignore_ascii_case("for").then(|| parse_hop(value.trim().trim_matches('"')))
+    })
+}
+
+/// `1.2.3.4`, `1.2.3.4:5678`, `2001:db8::1` or `[2001:db8::1]:5678`
+fn parse_hop(hop: &str) -> Hop {
+    if let Ok(ip) = hop.parse::<IpAddr>() {
+        return Some((ip, None));
+    }
+    if let Ok(addr) = hop.parse::<SocketAddr>() {
+        return Some((addr.ip(), Some(addr.port())));
+    }
+    let bracketed = hop.strip_prefix('[')?.strip_suffix(']')?;
+    bracketed.parse().ok().map(|ip| (ip, None))
+}
+
+/// Parse a PROXY protocol v2 header at the start of `bytes`.
+///
+/// Returns the proxied source address (`None` for a `LOCAL` connection, e.g. a
+/// health check, or an address family other than TCP over IPv4/IPv6) and the
+/// header's length in bytes.
+pub fn parse_proxy_v2(bytes: &[u8]) -> Result<(Option<SocketAddr>, usize)> {
+    if bytes.len() < 16 || bytes[..12] != PROXY_V2_SIGNATURE {
+        bail!("Connection does not start with a PROXY protocol v2 header");
+    }
+    let (version, command, family) = (bytes[12] >> 4, bytes[12] & 0x0f, bytes[13]);
+    if version != 2 {
+        bail!("Unsupported PROXY protocol version {}", version);
+    }
+    let len = u16::from_be_bytes([bytes[14], bytes[15]]) as usize;
+    if len > MAX_PROXY_V2_LEN {
+        bail!("PROXY header address block is {} bytes (max {})", len, MAX_PROXY_V2_LEN);
+    }
+    let addresses = bytes.get(16..16 + len).context("Truncated PROXY protocol v2 header")?;
+
+    let source = match (command, family) {
+        (0x0, _) => None, // LOCAL: the proxy's own connection
+        (0x1, 0x11) if len >= 12 => {
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            Some(SocketAddr::new(ip.into(), u16::from_be_bytes([addresses[8], addresses[9]])))
+        }
+        (0x1, 0x21) if len >= 36 => {
+            let ip: [u8; 16] = addresses[..16].try_into()?;
+            Some(SocketAddr::new(Ipv6Addr::from(ip).into(), u16::from_be_bytes([addresses[32], addresses[33]])))
+        }
+        (0x1, 0x11 | 0x21) => bail!("PROXY header address block too short for its family"),
+        (0x1, _) => None, // UDP or UNIX sockets carry no usable client address
+        (command, _) => bail!("Unknown PROXY protocol command {}", command),
+    };
+    Ok((source, 16 + len))
+}
+
+/// Read the PROXY v2 header a trusted proxy sends before any HTTP, consuming
+/// exactly its bytes so the WebSocket handshake starts right after it
+pub async fn read_proxy_v2<S>(stream: &mut S, peer: SocketAddr, trusted: &TrustedProxies) -> Result<ClientAddr>
+where
+    S: AsyncRead + Unpin,
+{
+    if !trusted.trusts(peer.ip()) {
+        bail!("PROXY protocol required but {} is not a trusted proxy", peer);
+    }
+
+    let header = tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(stream))
+        .await
+        .map_err(|_| anyhow!("No PROXY protocol header from {} within {:?}", peer, PROXY_HEADER_TIMEOUT))??;
+    let (source, _) = parse_proxy_v2(&header)?;
+
+    Ok(match source {
+        Some(source) => ClientAddr::via(source, peer),
+        None => ClientAddr::direct(peer),
+    })
+}
+
+/// The fixed 16-byte part, then the address block it announces if it looks like a v2 header
+async fn read_proxy_header<S>(stream: &mut S) -> Result<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut header = vec![0u8; 16];
+    stream.read_exact(&mut header).await.context("Connection closed before the PROXY header")?;
+    let len = u16::from_be_bytes([header[14], header[15]]) as usize;
+    if header[..12] == PROXY_V2_SIGNATURE && len <= MAX_PROXY_V2_LEN {
+        header.resize(16 + len, 0);
+        stream.read_exact(&mut header[16..]).await.context("Truncated PROXY protocol v2 header")?;
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_for_is_trusted_only_from_proxies() {
+        let trusted = TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]);
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-forwarded-for", value.parse().unwrap());
+            headers
+        };
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        // Spoofed header from an untrusted peer: ignored
+        let client = resolve_forwarded(&headers("198.51.100.7"), "203.0.113.9:50000".parse().unwrap(), &trusted);
+        assert_eq!(client.ip, ip("203.0.113.9"));
+        assert_eq!(client.proxy, None);
+
+        // Behind two trusted hops, with a value the client prepended itself
+        let lb = "10.0.0.5:443".parse().unwrap();
+        let client = resolve_forwarded(&headers("6.6.6.6, 198.51.100.7, 10.1.2.3"), lb, &trusted);
+        assert_eq!(client.ip, ip("198.51.100.7"));
+        assert_eq!(client.proxy, Some(lb));
+
+        // RFC 7239 form, with a bracketed IPv6 address and port
+        let mut forwarded = HeaderMap::new();
+        forwarded.insert("forwarded", r#"for="[2001:db8::1]:4711";proto=https, for=10.1.2.3"#.parse().unwrap());
+        let client = resolve_forwarded(&forwarded, lb, &trusted);
+        assert_eq!(client.ip, ip("2001:db8::1"));
+        assert_eq!(client.port, Some(4711));
+    }
+
+    #[test]
+    fn proxy_v2_preamble_gives_the_source() {
+        let mut preamble = PROXY_V2_SIGNATURE.to_vec();
+        preamble.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c]); // v2 PROXY, TCP over IPv4, 12 bytes
+        preamble.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 1]); // source, destination
+        preamble.extend_from_slice(&56324u16.to_be_bytes());
+        preamble.extend_from_slice(&443u16.to_be_bytes());
+
+        let (source, len) = parse_proxy_v2(&preamble).unwrap();
+        assert_eq!(source, Some("192.0.2.1:56324".parse().unwrap()));
+        assert_eq!(len, 28);
+
+        // PROXY v1 text, or no preamble at all, is rejected
+        assert!(parse_proxy_v2(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n").is_err());
+        assert!(parse_proxy_v2(b"GET / HTTP/1.1\r\nHost: verifier\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Retention of per-session files in the attestation directory.
+//!
+//! Proof bundles (`<session id>.zip`) are written once per session, so a
+//! long-running server would otherwise fill the directory. With
+//! `AUDITORZK_ATTESTATION_MAX_FILES` and/or `AUDITORZK_ATTESTATION_TTL_SECS`
+//! set, a background task prunes every `AUDITORZK_ATTESTATION_PRUNE_INTERVAL_SECS`
+//! the files past the newest N or older than the TTL, by modification time.
+//!
+//! Only finished per-session files are considered. Files are written under a
+//! hidden `.partial` name and renamed once complete, so a file still being
+//! written is never pruned. Fixed-name files (the latest attestation) and
+//! subdirectories (captures, the delivery spool and records) are left alone.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use crate::config::ServerConfig;
+
+/// Extensions of the per-session files retention applies to
+const SESSION_FILE_EXTENSIONS: &[&str] = &["zip"];
+
+/// How many per-session files to keep, and for how long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep at most this many of the newest files
+    pub max_files: Option<usize>,
+    /// Prune files last modified longer ago than this
+    pub max_age: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    /// The configured policy, `None` when neither limit is set
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        let policy = Self {
+            max_files: config.attestation_max_files,
+            max_age: config.attestation_ttl_secs.map(Duration::from_secs),
+        };
+        (policy.max_files.is_some() || policy.max_age.is_some()).then_some(policy)
+    }
+
+    /// Delete the per-session files in `dir` the policy doesn't keep at `now`,
+    /// returning how many were deleted
+    pub fn prune(&self, dir: &Path, now: SystemTime) -> io::Result<usize> {
+        let mut files: Vec<(SystemTime, PathBuf)> = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let is_session_file = entry.file_name().to_str().is_some_and(is_session_file);
+            let metadata = entry.metadata()?;
+            if is_session_file && metadata.is_file() {
+                files.push((metadata.modified()?, entry.path()));
+            }
+        }
+        // Newest first; session ids break ties so runs agree on which to keep
+        files.sort_by(|a, b| b.cmp(a));
+
+        let mut pruned = 0;
+        for (index, (modified, path)) in files.iter().enumerate() {
+            let over_count = self.max_files.is_some_and(|max| index >= max);
+            let expired = self
+                .max_age
+                .is_some_and(|max_age| now.duration_since(*modified).is_ok_and(|age| age > max_age));
+            if !over_count && !expired {
+                continue;
+            }
+            match fs::remove_file(path) {
+                Ok(()) => pruned += 1,
+                // Already gone, e.g. removed by an operator
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Prune `dir` every `interval` until the process exits
+    pub async fn run(self, dir: PathBuf, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.prune(&dir, SystemTime::now()) {
+                Ok(0) => {}
+                Ok(pruned) => info!("🧹 Pruned {} per-session file(s) from {}", pruned, dir.display()),
+                Err(e) => warn!("⚠️  Failed to prune {}: {}", dir.display(), e),
+            }
+        }
+    }
+}
+
+/// `<32 hex session id>.<extension>`, never a hidden `.partial` file
+fn is_session_file(name: &str) -> bool {
+    let Some((stem, extension)) = name.split_once('.') else {
+        return false;
+    };
+    stem.len() == 32
+        && stem.bytes().all(|b| b.is_ascii_hexdigit())
+        && SESSION_FILE_EXTENSIONS.contains(&extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_keeps_the_newest_bundles_only() {
+        let dir = std::env::temp_dir().join(format!("auditorzk-retention-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // N + K finished bundles, the newest last
+        let (n, k) = (3, 4);
+        let now = SystemTime::now();
+        for i in 0..n + k {
+            let path = dir.join(format!("{:032x}.zip", i));
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(60 * (n + k - i) as u64)).unwrap();
+        }
+        // A bundle still being written, and files that aren't per-session
+        std::fs::write(dir.join(format!(".{:032x}.zip.partial", 99)), b"").unwrap();
+        std::fs::write(dir.join("auditor_zk_attestation.json"), b"{}").unwrap();
+        std::fs::create_dir_all(dir.join("spool")).unwrap();
+
+        let policy = RetentionPolicy { max_files: Some(n), max_age: None };
+        assert_eq!(policy.prune(&dir, now).unwrap(), k);
+        let mut left: Vec<String> =
+            std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                format!(".{:032x}.zip.partial", 99),
+                format!("{:032x}.zip", 4),
+                format!("{:032x}.zip", 5),
+                format!("{:032x}.zip", 6),
+                "auditor_zk_attestation.json".to_string(),
+                "spool".to_string(),
+            ]
+        );
+
+        // A TTL prunes by age, whatever the count: the bundles are 1, 2 and 3 minutes old
+        let policy = RetentionPolicy { max_files: None, max_age: Some(Duration::from_secs(150)) };
+        assert_eq!(policy.prune(&dir, now).unwrap(), 1);
+        assert!(!dir.join(format!("{:032x}.zip", 4)).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
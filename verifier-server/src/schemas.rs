@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use auditorzk_attestation::CURRENT_SCHEMA_VERSION;
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+use crate::attestation::{build_fingerprint, verifier_version, Attestation};
+use crate::control::{ProverMessage, VerifierMessage};
+
+/// JSON Schemas for every format partners consume, keyed by file stem.
+///
+/// Generated from the Rust types so they can't drift from what the server
+/// actually reads and writes. Each `$id` embeds the attestation schema version.
+pub fn all() -> Vec<(&'static str, RootSchema)> {
+    [
+        ("attestation", schema_for!(Attestation)),
+        ("prover-message", schema_for!(ProverMessage)),
+        ("verifier-message", schema_for!(VerifierMessage)),
+    ]
+    .into_iter()
+    .map(|(name, mut schema)| {
+        schema.schema.metadata().id =
+            Some(format!("urn:auditorzk:schema:{}:v{}", name, CURRENT_SCHEMA_VERSION));
+        (name, schema)
+    })
+    .collect()
+}
+
+/// Write every schema to `<dir>/<name>.schema.json`
+pub fn export(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create schema directory {}", dir.display()))?;
+
+    for (name, schema) in all() {
+        let path = dir.join(format!("{}.schema.json", name));
+        fs::write(&path, serde_json::to_string_pretty(&schema)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("📐 Wrote {}", path.display());
+    }
+
+    // Fingerprint → version for this build; releases append it to the published
+    // list consumers build denylists from
+    let path = dir.join("build-fingerprints.json");
+    let builds = serde_json::json!({ (build_fingerprint()): verifier_version() });
+    fs::write(&path, serde_json::to_string_pretty(&builds)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("🏷️  Wrote {}", path.display());
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! What the verifier learned from one notarized session, in crate-owned types.
+//!
+//! tlsn's `VerifierOutput` can only be produced by running the protocol, so
+//! nothing past [`SessionFacts::from_output`] reads it: validation, policy
+//! checks, claim extraction and attestation signing all take [`SessionFacts`].
+//! Tests assemble the same facts with `fixture::SessionFixture` (feature
+//! `test-fixtures`) instead of a live MPC session.
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use tlsn_core::transcript::{Direction, TranscriptCommitment};
+use tlsn_core::VerifierOutput;
+
+use crate::plaid::{normalize_server_name, TlsVersion};
+
+/// Facts about a notarized session the checks below the protocol rely on; they
+/// serialize to the dumps `analyze` reads back (see [`crate::analyze`])
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionFacts {
+    /// Server name the TLS session was authenticated to
+    pub server_name: Option<String>,
+    /// TLS version negotiated with the server, when the protocol exposes it
+    pub tls_version: Option<TlsVersion>,
+    /// The partial transcript, when the prover revealed one
+    pub transcript: Option<TranscriptFacts>,
+    /// Transcript commitments, in the order the prover made them
+    pub commitments: Vec<CommitmentFact>,
+}
+
+/// The partial transcript: full-length data with unrevealed bytes zeroed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptFacts {
+    pub sent: Vec<u8>,
+    pub received: Vec<u8>,
+    /// Authenticated ranges of `sent`, sorted and disjoint
+    pub sent_revealed: Vec<Range<usize>>,
+    /// Authenticated ranges of `received`, sorted and disjoint
+    pub received_revealed: Vec<Range<usize>>,
+}
+
+impl TranscriptFacts {
+    /// Data in one direction
+    pub fn data(&self, direction: Direction) -> &[u8] {
+        match direction {
+            Direction::Sent => &self.sent,
+            Direction::Received => &self.received,
+        }
+    }
+
+    /// Revealed ranges in one direction
+    pub fn revealed(&self, direction: Direction) -> &[Range<usize>] {
+        match direction {
+            Direction::Sent => &self.sent_revealed,
+            Direction::Received => &self.received_revealed,
+        }
+    }
+
+    /// Number of revealed bytes in one direction
+    pub fn revealed_len(&self, direction: Direction) -> usize {
+        self.revealed(direction).iter().map(ExactSizeIterator::len).sum()
+    }
+}
+
+/// One transcript commitment the prover made
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommitmentFact {
+    /// Plaintext hash commitment
+    Hash(HashFact),
+    /// Encoding commitment; its contents are opaque to the checks
+    Encoding,
+}
+
+/// A plaintext hash commitment over some transcript ranges
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashFact {
+    pub direction: Direction,
+    /// Committed ranges, sorted and disjoint
+    pub ranges: Vec<Range<usize>>,
+    /// tlsn hash algorithm id, e.g. [`auditorzk_attestation::commitments::HASH_SHA256`]
+    pub algorithm: u8,
+    pub hash: Vec<u8>,
+}
+
+impl HashFact {
+    /// Number of committed bytes
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(ExactSizeIterator::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SessionFacts {
+    /// The one adapter from tlsn's output
+    pub fn from_output(output: &VerifierOutput) -> Self {
+        let transcript = output.transcript.as_ref().map(|transcript| TranscriptFacts {
+            sent: transcript.sent_unsafe().to_vec(),
+            received: transcript.received_unsafe().to_vec(),
+            sent_revealed: transcript.sent_authed().iter_ranges().collect(),
+            received_revealed: transcript.received_authed().iter_ranges().collect(),
+        });
+
+        let commitments = output
+            .transcript_commitments
+            .iter()
+            .map(|commitment| match commitment {
+                TranscriptCommitment::Hash(hash) => CommitmentFact::Hash(HashFact {
+                    direction: hash.direction,
+                    ranges: hash.idx.iter_ranges().collect(),
+                    algorithm: hash.hash.alg.as_u8(),
+                    hash: hash.hash.value.as_ref().to_vec(),
+                }),
+                _ => CommitmentFact::Encoding,
+            })
+            .collect();
+
+        Self {
+            server_name: output.server_name.as_ref().map(|name| name.as_str().to_string()),
+            // `VerifierOutput` in tlsn-core v0.1.0-alpha.12 only carries the server
+            // name, partial transcript and commitments; the handshake details are not
+            // surfaced (the MPC-TLS client itself only implements TLS 1.2). Fill this in
+            // once tlsn exposes it and the minimum-version check starts enforcing
+            tls_version: None,
+            transcript,
+            commitments,
+        }
+    }
+
+    /// The server name as validated and signed, see [`normalize_server_name`]
+    pub fn normalized_server_name(&self) -> Option<String> {
+        self.server_name.as_deref().map(normalize_server_name)
+    }
+
+    /// Hash commitments, in the order the prover made them
+    pub fn hashes(&self) -> impl Iterator<Item = &HashFact> {
+        self.commitments.iter().filter_map(|commitment| match commitment {
+            CommitmentFact::Hash(hash) => Some(hash),
+            CommitmentFact::Encoding => None,
+        })
+    }
+}
@@ -0,0 +1,282 @@
+//! Graceful shutdown of the WebSocket listener.
+//!
+//! A drain (SIGTERM or `POST /drain`, see [`Drain`]) sorts every connection
+//! the listener has taken in three:
+//!
+//! - sessions whose WebSocket upgrade completed before the drain are served to
+//!   the end (*completed*);
+//! - sockets accepted but not yet upgraded, and those still queued in the
+//!   kernel backlog when the listener closes, are answered with a `503` and
+//!   closed instead of silently dropped (*refused before upgrade*);
+//! - sessions still running when `AUDITORZK_SHUTDOWN_GRACE_SECS` runs out are
+//!   aborted (*aborted at deadline*).
+//!
+//! [`ShutdownReport`] counts each, and is logged at exit.
+
+use anyhow::Result;
+use futures::future::join_all;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+use tokio_tungstenite::tungstenite::http::{self, StatusCode};
+use tracing::{debug, info, warn};
+
+use crate::admin::{read_head, Drain};
+
+/// Body of the `503` a connection refused before its upgrade gets
+const UNAVAILABLE_BODY: &str = "Verifier is shutting down";
+
+/// How long a refused client has to read the `503` before its socket closes
+const REFUSAL_LINGER: Duration = Duration::from_secs(1);
+
+/// What became of the connections a drain caught, counted for the log at exit
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    completed: AtomicU64,
+    refused_pre_upgrade: AtomicU64,
+    aborted_at_deadline: AtomicU64,
+}
+
+/// A [`ShutdownReport`] at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownCounts {
+    /// Sessions upgraded before the drain that ran to their end
+    pub completed: u64,
+    /// Connections answered with a `503` before their upgrade
+    pub refused_pre_upgrade: u64,
+    /// Sessions aborted when the grace period ran out
+    pub aborted_at_deadline: u64,
+}
+
+impl ShutdownReport {
+    /// An upgraded session ended during the drain
+    pub fn session_completed(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn refused(&self, connections: usize) {
+        self.refused_pre_upgrade.fetch_add(connections as u64, Ordering::Relaxed);
+    }
+
+    pub fn aborted(&self, sessions: usize) {
+        self.aborted_at_deadline.fetch_add(sessions as u64, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> ShutdownCounts {
+        ShutdownCounts {
+            completed: self.completed.load(Ordering::Relaxed),
+            refused_pre_upgrade: self.refused_pre_upgrade.load(Ordering::Relaxed),
+            aborted_at_deadline: self.aborted_at_deadline.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn log(&self) {
+        let counts = self.counts();
+        info!(
+            "🛑 Shutdown report: {} session(s) completed, {} connection(s) refused before upgrade, {} session(s) aborted at the deadline",
+            counts.completed, counts.refused_pre_upgrade, counts.aborted_at_deadline
+        );
+    }
+}
+
+/// Read the upgrade request head off an accepted socket.
+///
+/// If a drain starts first, or already has, the client gets a `503` and this
+/// returns `None`. Otherwise the returned stream replays the head, so the
+/// WebSocket handshake reads it as if nothing had.
+pub async fn read_upgrade_request(mut stream: TcpStream, drain: &Drain) -> Result<Option<Replay<TcpStream>>> {
+    let head = tokio::select! {
+        biased;
+        _ = drain.triggered() => None,
+        head = read_head(&mut stream) => Some(head?),
+    };
+
+    match head {
+        Some(head) => Ok(Some(Replay::new(head, stream))),
+        None => {
+            refuse(stream).await;
+            drain.report().refused(1);
+            Ok(None)
+        }
+    }
+}
+
+/// Close the listener, first taking whatever the kernel already queued on it
+/// and refusing each with a `503`. Returns how many were refused
+pub async fn close_listener(listener: TcpListener, drain: &Drain) -> usize {
+    // A std listener accepts with a plain non-blocking syscall, so the backlog
+    // is seen whether or not the runtime has polled the socket since
+    let listener = match listener.into_std() {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("⚠️  Failed to take the listener's backlog: {}", e);
+            return 0;
+        }
+    };
+
+    let mut queued = Vec::new();
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => queued.push(stream),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("⚠️  Failed to accept a queued connection: {}", e);
+                break;
+            }
+        }
+    }
+    drop(listener);
+
+    let streams: Vec<TcpStream> = queued
+        .into_iter()
+        .filter_map(|stream| stream.set_nonblocking(true).and_then(|()| TcpStream::from_std(stream)).ok())
+        .collect();
+    let refused = streams.len();
+    join_all(streams.into_iter().map(refuse)).await;
+    drain.report().refused(refused);
+    refused
+}
+
+/// Response the upgrade callback refuses with once a drain has started
+pub fn unavailable_response() -> ErrorResponse {
+    http::Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .body(Some(UNAVAILABLE_BODY.to_string()))
+        .expect("static status and body always build")
+}
+
+/// Answer a socket that was never upgraded with a `503` and close it
+async fn refuse(mut stream: TcpStream) {
+    let response = format!(
+        "HTTP/1.1 503 Service Unavailable\r\ncontent-type: text/plain\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        UNAVAILABLE_BODY.len(),
+        UNAVAILABLE_BODY
+    );
+    let refusal = async {
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        // Read what the client still sends, so closing doesn't reset the connection under the 503
+        let mut discard = [0u8; 1024];
+        while stream.read(&mut discard).await? > 0 {}
+        io::Result::Ok(())
+    };
+    if let Ok(Err(e)) = tokio::time::timeout(REFUSAL_LINGER, refusal).await {
+        debug!("503 to a connection refused during the drain failed: {}", e);
+    }
+}
+
+/// A stream whose first reads return bytes already read off it
+pub struct Replay<S> {
+    buffered: Vec<u8>,
+    position: usize,
+    inner: S,
+}
+
+impl<S> Replay<S> {
+    pub fn new(buffered: Vec<u8>, inner: S) -> Self {
+        Self { buffered, position: 0, inner }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Replay<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.position < this.buffered.len() {
+            let len = buf.remaining().min(this.buffered.len() - this.position);
+            buf.put_slice(&this.buffered[this.position..this.position + len]);
+            this.position += len;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Replay<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    use crate::tasks::TaskRegistry;
+
+    #[tokio::test]
+    async fn drain_refuses_pending_and_aborts_at_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let drain = Arc::new(Drain::default());
+        let tasks = Arc::new(TaskRegistry::default());
+
+        // A session as the server runs it: upgrade unless draining, then run until `finish`
+        let serve = |stream: TcpStream, session: u64, finish: oneshot::Receiver<()>| {
+            let drain = Arc::clone(&drain);
+            tasks.spawn("session", Some(session), None, async move {
+                let Some(stream) = read_upgrade_request(stream, &drain).await.unwrap() else {
+                    return;
+                };
+                let _ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let _ = finish.await;
+                drain.report().session_completed();
+            })
+        };
+
+        // Upgraded before the drain: one finishes within the grace period, one never does
+        let (finish, finished) = oneshot::channel();
+        let (_hang, hung) = oneshot::channel();
+        let mut upgraded = Vec::new();
+        for (session, finish) in [(1, finished), (2, hung)] {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let client = tokio::spawn(tokio_tungstenite::client_async(format!("ws://{}/", addr), stream));
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, session, finish);
+            upgraded.push(client.await.unwrap().unwrap().0);
+        }
+
+        // Accepted, but its upgrade request hasn't arrived when the drain starts
+        let mut pending = TcpStream::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (_never, never) = oneshot::channel();
+        let pending_session = serve(stream, 3, never);
+
+        // Still in the listener's backlog
+        let mut queued = TcpStream::connect(addr).await.unwrap();
+
+        drain.trigger();
+        pending_session.await.unwrap();
+        assert_eq!(close_listener(listener, &drain).await, 1);
+        for client in [&mut pending, &mut queued] {
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 503 "), "{}", response);
+        }
+        assert!(TcpStream::connect(addr).await.is_err());
+
+        finish.send(()).unwrap();
+        let aborted = tasks.shutdown(Duration::from_millis(500)).await;
+        assert_eq!(aborted, 1);
+        drain.report().aborted(aborted);
+        assert_eq!(
+            drain.report().counts(),
+            ShutdownCounts { completed: 1, refused_pre_upgrade: 2, aborted_at_deadline: 1 }
+        );
+        drop(upgraded);
+    }
+}
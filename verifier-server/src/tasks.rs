@@ -0,0 +1,276 @@
+use futures::FutureExt;
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{error, info, warn};
+
+/// How often the task watchdog looks for overdue tasks
+const OVERDUE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often shutdown re-checks for sessions still running
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+tokio::task_local! {
+    /// Label of the supervised task being polled, read by the panic hook
+    static CURRENT_TASK: TaskLabel;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TaskLabel {
+    name: &'static str,
+    session: Option<u64>,
+}
+
+/// Every task the server spawns, with the session it belongs to.
+///
+/// Tasks are spawned through [`TaskRegistry::spawn`] so a panic is logged with
+/// its task name and counted, and takes down the rest of its session instead of
+/// leaving the connection hung on a dead forwarder. The registry also drives
+/// graceful shutdown and the overdue-task watchdog.
+///
+/// A forwarder that panics mid-session fails the session instead of leaving the
+/// reader waiting on a prover that will never hear back.
+#[derive(Default)]
+pub struct TaskRegistry {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, TaskEntry>>,
+    panic_count: AtomicU64,
+}
+
+struct TaskEntry {
+    label: TaskLabel,
+    started: Instant,
+    /// Age past which the watchdog flags the task
+    expected_lifetime: Option<Duration>,
+    flagged: bool,
+    abort: Option<AbortHandle>,
+}
+
+/// A running task, as listed at shutdown
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub name: &'static str,
+    pub session: Option<u64>,
+    pub age: Duration,
+}
+
+/// Unregisters a task when it finishes, panics or is aborted
+struct Registration {
+    registry: Arc<TaskRegistry>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.registry.tasks.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl TaskRegistry {
+    /// Spawn a supervised task.
+    ///
+    /// A panic is still surfaced through the returned handle as a `JoinError`,
+    /// after every other task of `session` has been aborted.
+    pub fn spawn<F>(
+        self: &Arc<Self>,
+        name: &'static str,
+        session: Option<u64>,
+        expected_lifetime: Option<Duration>,
+        future: F,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let label = TaskLabel { name, session };
+        self.tasks.lock().unwrap().insert(
+            id,
+            TaskEntry { label, started: Instant::now(), expected_lifetime, flagged: false, abort: None },
+        );
+
+        let registration = Registration { registry: Arc::clone(self), id };
+        let handle = tokio::spawn(async move {
+            match CURRENT_TASK.scope(label, AssertUnwindSafe(future).catch_unwind()).await {
+                Ok(output) => output,
+                Err(panic) => {
+                    registration.registry.on_panic(label);
+                    std::panic::resume_unwind(panic)
+                }
+            }
+        });
+
+        // The task may already have finished and unregistered itself
+        if let Some(entry) = self.tasks.lock().unwrap().get_mut(&id) {
+            entry.abort = Some(handle.abort_handle());
+        }
+        handle
+    }
+
+    fn on_panic(&self, label: TaskLabel) {
+        let total = self.panic_count.fetch_add(1, Ordering::Relaxed) + 1;
+        error!("💥 Task {} panicked ({} panics total)", label.name, total);
+
+        if let Some(session) = label.session {
+            let aborted = self.abort_session(session);
+            warn!("❌ Session {} failed: {} panicked, aborted {} remaining task(s)", session, label.name, aborted);
+        }
+    }
+
+    /// Abort every task of a session, returning how many were running
+    pub fn abort_session(&self, session: u64) -> usize {
+        let tasks = self.tasks.lock().unwrap();
+        let mut aborted = 0;
+        for entry in tasks.values().filter(|entry| entry.label.session == Some(session)) {
+            if let Some(abort) = &entry.abort {
+                abort.abort();
+                aborted += 1;
+            }
+        }
+        aborted
+    }
+
+    /// Panics caught in supervised tasks since startup
+    pub fn panic_count(&self) -> u64 {
+        self.panic_count.load(Ordering::Relaxed)
+    }
+
+    /// Whether any task of the session has been spawned and is still registered
+    pub fn session_running(&self, session: u64) -> bool {
+        self.tasks
+            .lock()
+            .unwrap()
+            .values()
+            .any(|entry| entry.label.session == Some(session) && entry.abort.is_some())
+    }
+
+    /// Running tasks, oldest first
+    pub fn snapshot(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, entry)| TaskInfo {
+                id,
+                name: entry.label.name,
+                session: entry.label.session,
+                age: entry.started.elapsed(),
+            })
+            .collect();
+        tasks.sort_by_key(|task| task.id);
+        tasks
+    }
+
+    /// Warn once about each task alive past its expected lifetime
+    pub fn check_overdue(&self) {
+        let mut tasks = self.tasks.lock().unwrap();
+        for entry in tasks.values_mut().filter(|entry| !entry.flagged) {
+            let Some(expected) = entry.expected_lifetime else {
+                continue;
+            };
+            let age = entry.started.elapsed();
+            if age > expected {
+                entry.flagged = true;
+                warn!(
+                    "⏰ Task {} (session {:?}) alive for {}s, expected at most {}s",
+                    entry.label.name,
+                    entry.label.session,
+                    age.as_secs(),
+                    expected.as_secs()
+                );
+            }
+        }
+    }
+
+    /// Run `check_overdue` forever
+    pub async fn run_watchdog(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(OVERDUE_CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            self.check_overdue();
+        }
+    }
+
+    /// Wait up to `grace` for in-flight sessions to finish, then abort the rest,
+    /// returning how many sessions were aborted
+    pub async fn shutdown(&self, grace: Duration) -> usize {
+        let running = self.snapshot();
+        info!("🛑 Shutting down with {} task(s) running", running.len());
+        for task in &running {
+            info!("   #{} {} (session {:?}, {}s)", task.id, task.name, task.session, task.age.as_secs());
+        }
+
+        let deadline = Instant::now() + grace;
+        while self.snapshot().iter().any(|task| task.session.is_some()) && Instant::now() < deadline {
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+
+        let mut sessions: Vec<u64> = self.snapshot().iter().filter_map(|task| task.session).collect();
+        sessions.sort_unstable();
+        sessions.dedup();
+        for &session in &sessions {
+            warn!("🛑 Aborting session {} after {}s grace period", session, grace.as_secs());
+            self.abort_session(session);
+        }
+        info!("👋 Shutdown complete ({} panics during this run)", self.panic_count());
+        sessions.len()
+    }
+}
+
+/// Log panics through tracing with the supervised task's name and a backtrace
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let label = CURRENT_TASK.try_with(|label| *label).ok();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("<non-string panic payload>");
+        let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+
+        error!(
+            "💥 Panic in {} (session {:?}) at {}: {}\n{}",
+            label.map_or("unsupervised code", |label| label.name),
+            label.and_then(|label| label.session),
+            location,
+            message,
+            Backtrace::force_capture()
+        );
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn panic_aborts_the_rest_of_its_session() {
+        let tasks = Arc::new(TaskRegistry::default());
+        let reader = tasks.spawn("ws-to-prover", Some(7), None, std::future::pending::<()>());
+        let other = tasks.spawn("ws-to-prover", Some(8), None, std::future::pending::<()>());
+        let forwarder = tasks.spawn("prover-to-ws", Some(7), None, async {
+            tokio::task::yield_now().await;
+            panic!("injected panic");
+        });
+
+        // The panic still reaches whoever joins the forwarder...
+        assert!(forwarder.await.unwrap_err().is_panic());
+        // ...and the rest of its session was aborted rather than left hanging
+        assert!(reader.await.unwrap_err().is_cancelled());
+        assert!(!tasks.session_running(7));
+        assert_eq!(tasks.panic_count(), 1);
+
+        // Other sessions are untouched
+        assert!(tasks.session_running(8));
+        assert_eq!(tasks.snapshot().iter().map(|task| task.session).collect::<Vec<_>>(), [Some(8)]);
+        other.abort();
+    }
+}
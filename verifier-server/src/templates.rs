@@ -0,0 +1,234 @@
+//! Per-provider request templates, checked against the prover's committed request.
+//!
+//! When the prover keeps its request private, the verifier can't read the endpoint
+//! off the transcript. Instead the prover declares a template in its `hello`,
+//! together with the values the template leaves open, and makes a sent-direction
+//! hash commitment over exactly the request line and headers. The verifier renders
+//! the same bytes itself and checks the commitment, which proves the request's
+//! method, path and headers without revealing its body or credentials.
+//!
+//! Rendering is byte-exact, so provers must send the head exactly as [`RequestTemplate::render`]
+//! produces it:
+//!
+//! - the request line `METHOD SP path SP HTTP/1.1 CRLF`;
+//! - one `name: value CRLF` line per template header, in template order, with
+//!   lowercase names and a single space after the colon;
+//! - a final CRLF. The committed range is `[0, head length)`.
+//!
+//! The host is the notarized server name, never a prover-declared value. The
+//! only declared value is `content-length`.
+
+use anyhow::{bail, Result};
+use auditorzk_attestation::commitments::TranscriptHash;
+use auditorzk_attestation::request::RequestTemplateMatch;
+use sha2::{Digest, Sha256};
+use tlsn_core::transcript::Direction;
+use tracing::{info, warn};
+
+use crate::config::ServerConfig;
+use crate::control::TemplateDeclaration;
+use crate::income::PAYROLL_INCOME_PATH;
+use crate::plaid::transcript_hashes;
+use crate::session::SessionFacts;
+
+/// One header line of a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateHeader {
+    /// Sent exactly as given: lowercase name, value
+    Fixed(&'static str, &'static str),
+    /// `host`, the notarized server name
+    Host,
+    /// `content-length`, as the prover declares it
+    ContentLength,
+}
+
+/// The fixed shape of one provider request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTemplate {
+    /// Recorded in the attestation, e.g. `plaid.balance.v1`. Changing a template's
+    /// bytes needs a new id
+    pub id: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+    pub headers: &'static [TemplateHeader],
+}
+
+/// Header skeleton shared by Plaid's JSON POST endpoints
+const PLAID_JSON_HEADERS: &[TemplateHeader] = &[
+    TemplateHeader::Host,
+    TemplateHeader::Fixed("content-type", "application/json"),
+    TemplateHeader::ContentLength,
+];
+
+/// Built-in templates
+pub const TEMPLATES: &[RequestTemplate] = &[
+    RequestTemplate {
+        id: "plaid.balance.v1",
+        method: "POST",
+        path: "/accounts/balance/get",
+        headers: PLAID_JSON_HEADERS,
+    },
+    RequestTemplate {
+        id: "plaid.payroll_income.v1",
+        method: "POST",
+        path: PAYROLL_INCOME_PATH,
+        headers: PLAID_JSON_HEADERS,
+    },
+];
+
+/// Built-in template by id
+pub fn find(id: &str) -> Option<&'static RequestTemplate> {
+    TEMPLATES.iter().find(|template| template.id == id)
+}
+
+impl RequestTemplate {
+    /// The canonical request head for `host` and a declared `content_length`.
+    pub fn render(&self, host: &str, content_length: u64) -> Result<Vec<u8>> {
+        if host.is_empty() || !host.bytes().all(|b| b.is_ascii_graphic() && b != b'/') {
+            bail!("Server name '{}' can't be rendered into a Host header", host.escape_default());
+        }
+
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method, self.path);
+        for header in self.headers {
+            let line = match header {
+                TemplateHeader::Fixed(name, value) => format!("{}: {}\r\n", name, value),
+                TemplateHeader::Host => format!("host: {}\r\n", host),
+                TemplateHeader::ContentLength => format!("content-length: {}\r\n", content_length),
+            };
+            head.push_str(&line);
+        }
+        head.push_str("\r\n");
+        Ok(head.into_bytes())
+    }
+}
+
+/// The commitment a prover makes over `head` with `blinder`: tlsn's blinded
+/// plaintext hash, `SHA-256(head || blinder)`
+pub fn commitment_hash(head: &[u8], blinder: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(head);
+    hasher.update(blinder);
+    hasher.finalize().into()
+}
+
+/// Whether any of the prover's commitments is a SHA-256 sent-direction hash over
+/// exactly `[0, head length)` that opens to `head` with `blinder`.
+///
+/// Flipping any single byte of the declared values breaks the match.
+pub fn commitment_matches(commitments: &[TranscriptHash], head: &[u8], blinder: &[u8]) -> bool {
+    let expected = commitment_hash(head, blinder);
+    commitments.iter().any(|commitment| {
+        commitment.direction == "sent"
+            && commitment.algorithm == "sha256"
+            && commitment.ranges == [[0, head.len() as u64]]
+            && commitment.hash[..] == expected[..]
+    })
+}
+
+/// Check the prover's declared template against its commitments.
+///
+/// Without a declaration the session only passes when templates aren't required.
+/// A mismatch is recorded as such, or fails the session when templates are
+/// required.
+pub fn match_request_template(
+    facts: &SessionFacts,
+    declaration: Option<&TemplateDeclaration>,
+    config: &ServerConfig,
+) -> Result<Option<RequestTemplateMatch>> {
+    let Some(declaration) = declaration else {
+        if config.require_request_template {
+            bail!("A request template is required; declare one in hello as request_template");
+        }
+        return Ok(None);
+    };
+
+    let template = find(&declaration.id)
+        .filter(|template| config.request_templates.iter().any(|id| id == template.id))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Request template '{}' is not accepted (accepted: {})",
+                declaration.id,
+                config.request_templates.join(", ")
+            )
+        })?;
+
+    let host = facts.server_name.as_deref().unwrap_or_default();
+    let head = template.render(host, declaration.content_length)?;
+    let blinder = hex::decode(&declaration.blinder)
+        .map_err(|e| anyhow::anyhow!("Request template blinder is not hex: {}", e))?;
+
+    let sent_commitments = transcript_hashes(facts, &[Direction::Sent])?;
+    let matched = commitment_matches(&sent_commitments, &head, &blinder);
+    if matched {
+        info!("✅ Committed request matches template {}", template.id);
+    } else if config.require_request_template {
+        warn!("❌ No sent commitment opens to template {} ({} byte head)", template.id, head.len());
+        bail!("Committed request does not match template {}", template.id);
+    } else {
+        warn!("⚠️  No sent commitment opens to template {}; recording the mismatch", template.id);
+    }
+
+    Ok(Some(RequestTemplateMatch { id: template.id.to_string(), matched }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_template_renders_the_request_head() {
+        let head = find("plaid.balance.v1").unwrap().render("sandbox.plaid.com", 187).unwrap();
+        assert_eq!(
+            head,
+            b"POST /accounts/balance/get HTTP/1.1\r\n\
+              host: sandbox.plaid.com\r\n\
+              content-type: application/json\r\n\
+              content-length: 187\r\n\
+              \r\n"
+        );
+    }
+
+    #[test]
+    fn commitment_binds_every_byte_of_head_and_blinder() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/request_template_balance.json")).unwrap();
+        let commitment: TranscriptHash = serde_json::from_value(fixture["commitment"].clone()).unwrap();
+        let host = fixture["host"].as_str().unwrap();
+        let content_length = fixture["content_length"].as_u64().unwrap();
+        let blinder = hex::decode(fixture["blinder"].as_str().unwrap()).unwrap();
+        let template = find(fixture["template"].as_str().unwrap()).unwrap();
+
+        let head = template.render(host, content_length).unwrap();
+        assert_eq!(head, fixture["head"].as_str().unwrap().as_bytes());
+        assert!(commitment_matches(std::slice::from_ref(&commitment), &head, &blinder));
+
+        // Each byte of the declared content length
+        let digits = content_length.to_string();
+        for i in 0..digits.len() {
+            let mut flipped = digits.clone().into_bytes();
+            flipped[i] ^= 1;
+            let flipped: u64 = String::from_utf8(flipped).unwrap().parse().unwrap();
+            let head = template.render(host, flipped).unwrap();
+            assert!(!commitment_matches(std::slice::from_ref(&commitment), &head, &blinder), "digit {}", i);
+        }
+
+        // Each byte of the blinder
+        for i in 0..blinder.len() {
+            let mut flipped = blinder.clone();
+            flipped[i] ^= 1;
+            assert!(!commitment_matches(std::slice::from_ref(&commitment), &head, &flipped), "blinder byte {}", i);
+        }
+
+        // Each byte of the head, e.g. a different path or header value
+        for i in 0..head.len() {
+            let mut flipped = head.clone();
+            flipped[i] ^= 1;
+            assert!(!commitment_matches(std::slice::from_ref(&commitment), &flipped, &blinder), "head byte {}", i);
+        }
+
+        // A commitment over a longer range (head plus body) doesn't count
+        let mut longer = commitment.clone();
+        longer.ranges = vec![[0, head.len() as u64 + 187]];
+        assert!(!commitment_matches(&[longer], &head, &blinder));
+    }
+}
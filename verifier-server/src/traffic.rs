@@ -0,0 +1,319 @@
+//! Shape of the MPC traffic each session forwards.
+//!
+//! Both forwarders record every frame's size, and the gap since the previous
+//! frame in the same direction, into fixed-bucket histograms: a session's
+//! profile stays the same size however long it runs. Finished sessions are
+//! merged into [`TrafficMetrics`], served as Prometheus histograms on the admin
+//! listener's `/metrics`, and with `--traffic-profile <dir>` each session's full
+//! distribution is also written to `<dir>/<session id>.traffic.json`. The duplex
+//! size, read buffer and coalescing thresholds are tuned from these.
+
+use serde::Serialize;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Upper bounds of the frame size buckets, in bytes
+pub const FRAME_SIZE_BUCKETS: &[u64] = &[
+    64, 128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536, 131072, 262144, 524288, 1048576,
+];
+
+/// Upper bounds of the inter-frame gap buckets, in microseconds
+pub const FRAME_GAP_BUCKETS: &[u64] = &[
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000, 2_500_000,
+    5_000_000, 10_000_000,
+];
+
+/// Which way a frame went through the forwarders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// Prover to verifier
+    Inbound,
+    /// Verifier to prover
+    Outbound,
+}
+
+impl FrameDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Inbound => "inbound",
+            Self::Outbound => "outbound",
+        }
+    }
+}
+
+/// Observations counted into fixed buckets
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    /// Inclusive upper bound of each bucket
+    pub bounds: &'static [u64],
+    /// Observations per bucket; the last one counts those above every bound
+    pub counts: Vec<u64>,
+    pub sum: u64,
+    pub max: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [u64]) -> Self {
+        Self { bounds, counts: vec![0; bounds.len() + 1], sum: 0, max: 0 }
+    }
+
+    pub fn observe(&mut self, value: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.counts[bucket] += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile, capped at the largest
+    /// observation; `None` without observations
+    pub fn percentile(&self, q: f64) -> Option<u64> {
+        let rank = ((q * self.count() as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if count > 0 && seen >= rank {
+                return Some(self.bounds.get(bucket).map_or(self.max, |&bound| bound.min(self.max)));
+            }
+        }
+        None
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.sum = self.sum.saturating_add(other.sum);
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Frames forwarded in one direction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DirectionTraffic {
+    /// Frame sizes in bytes
+    pub sizes: Histogram,
+    /// Microseconds since the previous frame in this direction
+    pub gaps: Histogram,
+    #[serde(skip)]
+    last: Option<Instant>,
+}
+
+impl Default for DirectionTraffic {
+    fn default() -> Self {
+        Self { sizes: Histogram::new(FRAME_SIZE_BUCKETS), gaps: Histogram::new(FRAME_GAP_BUCKETS), last: None }
+    }
+}
+
+impl DirectionTraffic {
+    fn record(&mut self, len: usize, at: Instant) {
+        self.sizes.observe(len as u64);
+        if let Some(last) = self.last {
+            let gap = at.saturating_duration_since(last).as_micros();
+            self.gaps.observe(u64::try_from(gap).unwrap_or(u64::MAX));
+        }
+        self.last = Some(at);
+    }
+
+    fn merge(&mut self, other: &DirectionTraffic) {
+        self.sizes.merge(&other.sizes);
+        self.gaps.merge(&other.gaps);
+    }
+}
+
+/// One session's traffic in both directions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TrafficProfile {
+    pub inbound: DirectionTraffic,
+    pub outbound: DirectionTraffic,
+}
+
+impl TrafficProfile {
+    /// Record a frame of `len` bytes forwarded at `at`
+    pub fn record(&mut self, direction: FrameDirection, len: usize, at: Instant) {
+        self.direction_mut(direction).record(len, at);
+    }
+
+    pub fn direction(&self, direction: FrameDirection) -> &DirectionTraffic {
+        match direction {
+            FrameDirection::Inbound => &self.inbound,
+            FrameDirection::Outbound => &self.outbound,
+        }
+    }
+
+    fn direction_mut(&mut self, direction: FrameDirection) -> &mut DirectionTraffic {
+        match direction {
+            FrameDirection::Inbound => &mut self.inbound,
+            FrameDirection::Outbound => &mut self.outbound,
+        }
+    }
+}
+
+/// Frame counts and p50/p95 frame sizes, for the session summary
+impl fmt::Display for TrafficProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, direction) in [FrameDirection::Inbound, FrameDirection::Outbound].into_iter().enumerate() {
+            let sizes = &self.direction(direction).sizes;
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{} {} frames", direction.as_str(), sizes.count())?;
+            if let (Some(p50), Some(p95)) = (sizes.percentile(0.50), sizes.percentile(0.95)) {
+                write!(f, ", p50 {} B, p95 {} B", p50, p95)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records one session's traffic; both forwarders share the handle
+#[derive(Clone, Default)]
+pub struct SessionTraffic {
+    profile: Arc<Mutex<TrafficProfile>>,
+}
+
+impl SessionTraffic {
+    pub fn record(&self, direction: FrameDirection, len: usize) {
+        let mut profile = self.profile.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        profile.record(direction, len, Instant::now());
+    }
+
+    /// The profile recorded so far
+    pub fn snapshot(&self) -> TrafficProfile {
+        self.profile.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+/// Traffic of every finished session, for `/metrics`
+#[derive(Default)]
+pub struct TrafficMetrics {
+    profile: Mutex<TrafficProfile>,
+    sessions: AtomicU64,
+}
+
+impl TrafficMetrics {
+    /// Merge a finished session's profile
+    pub fn add(&self, profile: &TrafficProfile) {
+        let mut total = self.profile.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        total.inbound.merge(&profile.inbound);
+        total.outbound.merge(&profile.outbound);
+        self.sessions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition of the aggregate histograms
+    pub fn render(&self) -> String {
+        let total = self.profile.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        let mut out = String::new();
+
+        out.push_str("# HELP auditorzk_mpc_frame_bytes Size of MPC frames forwarded.\n");
+        out.push_str("# TYPE auditorzk_mpc_frame_bytes histogram\n");
+        for direction in [FrameDirection::Inbound, FrameDirection::Outbound] {
+            let sizes = &total.direction(direction).sizes;
+            render_histogram(&mut out, "auditorzk_mpc_frame_bytes", direction, sizes, |bytes| bytes.to_string());
+        }
+
+        out.push_str("# HELP auditorzk_mpc_frame_gap_seconds Time between consecutive MPC frames in one direction.\n");
+        out.push_str("# TYPE auditorzk_mpc_frame_gap_seconds histogram\n");
+        for direction in [FrameDirection::Inbound, FrameDirection::Outbound] {
+            let gaps = &total.direction(direction).gaps;
+            render_histogram(&mut out, "auditorzk_mpc_frame_gap_seconds", direction, gaps, micros_as_seconds);
+        }
+
+        out.push_str("# HELP auditorzk_mpc_sessions_total Sessions whose traffic is included above.\n");
+        out.push_str("# TYPE auditorzk_mpc_sessions_total counter\n");
+        let _ = writeln!(out, "auditorzk_mpc_sessions_total {}", self.sessions.load(Ordering::Relaxed));
+        out
+    }
+}
+
+/// Cumulative `_bucket` lines, then `_sum` and `_count`, with values formatted by `unit`
+fn render_histogram(
+    out: &mut String,
+    name: &str,
+    direction: FrameDirection,
+    histogram: &Histogram,
+    unit: fn(u64) -> String,
+) {
+    let direction = direction.as_str();
+    let mut cumulative = 0;
+    for (bucket, count) in histogram.counts.iter().enumerate() {
+        cumulative += count;
+        let le = histogram.bounds.get(bucket).map_or_else(|| "+Inf".to_string(), |&bound| unit(bound));
+        let _ = writeln!(out, "{}_bucket{{direction=\"{}\",le=\"{}\"}} {}", name, direction, le, cumulative);
+    }
+    let _ = writeln!(out, "{}_sum{{direction=\"{}\"}} {}", name, direction, unit(histogram.sum));
+    let _ = writeln!(out, "{}_count{{direction=\"{}\"}} {}", name, direction, cumulative);
+}
+
+fn micros_as_seconds(micros: u64) -> String {
+    (micros as f64 / 1e6).to_string()
+}
+
+/// Write a session's full distribution to `<dir>/<session id>.traffic.json`.
+/// A failure is logged; it never fails the session.
+pub fn write_profile(dir: &Path, session_id: &str, profile: &TrafficProfile) {
+    let path = dir.join(format!("{}.traffic.json", session_id));
+    let written = fs::create_dir_all(dir)
+        .and_then(|_| Ok(serde_json::to_vec_pretty(profile)?))
+        .and_then(|json| fs::write(&path, json));
+    match written {
+        Ok(()) => info!("📶 Traffic profile written to {}", path.display()),
+        Err(e) => warn!("⚠️  Cannot write traffic profile {}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn frame_sizes_and_gaps_are_bucketed() {
+        let start = Instant::now();
+        let mut profile = TrafficProfile::default();
+        // 18 small inbound frames 1ms apart, then two large ones after a 200ms pause
+        for i in 0..18 {
+            profile.record(FrameDirection::Inbound, 100, start + Duration::from_millis(i));
+        }
+        profile.record(FrameDirection::Inbound, 5000, start + Duration::from_millis(217));
+        profile.record(FrameDirection::Inbound, 6000, start + Duration::from_millis(218));
+        // One outbound read that filled the 8 KiB buffer
+        profile.record(FrameDirection::Outbound, 8192, start);
+
+        let sizes = &profile.inbound.sizes;
+        assert_eq!(sizes.count(), 20);
+        assert_eq!(sizes.counts[1], 18); // (64, 128]
+        assert_eq!(sizes.counts[7], 2); // (4096, 8192]
+        assert_eq!((sizes.sum, sizes.max), (18 * 100 + 11000, 6000));
+        assert_eq!(sizes.percentile(0.50), Some(128));
+        assert_eq!(sizes.percentile(0.95), Some(6000));
+
+        let gaps = &profile.inbound.gaps;
+        assert_eq!(gaps.count(), 19);
+        assert_eq!(gaps.counts[3], 18); // (500us, 1ms]
+        assert_eq!(gaps.counts[10], 1); // (100ms, 250ms]
+        assert_eq!(profile.outbound.gaps.count(), 0);
+        assert_eq!(
+            profile.to_string(),
+            "inbound 20 frames, p50 128 B, p95 6000 B; outbound 1 frames, p50 8192 B, p95 8192 B"
+        );
+
+        let metrics = TrafficMetrics::default();
+        metrics.add(&profile);
+        metrics.add(&profile);
+        let text = metrics.render();
+        assert!(text.contains("auditorzk_mpc_frame_bytes_bucket{direction=\"inbound\",le=\"128\"} 36\n"));
+        assert!(text.contains("auditorzk_mpc_frame_bytes_bucket{direction=\"inbound\",le=\"+Inf\"} 40\n"));
+        assert!(text.contains("auditorzk_mpc_frame_bytes_sum{direction=\"outbound\"} 16384\n"));
+        assert!(text.contains("auditorzk_mpc_frame_gap_seconds_bucket{direction=\"inbound\",le=\"0.001\"} 36\n"));
+        assert!(text.contains("auditorzk_mpc_sessions_total 2\n"));
+    }
+}
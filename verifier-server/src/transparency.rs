@@ -0,0 +1,225 @@
+//! Issuance transparency feed (`AUDITORZK_TRANSPARENCY_KEY_FILE`).
+//!
+//! The content hash of every signed attestation is appended to
+//! `transparency.log` in the attestation directory, one hex hash per line; an
+//! attestation's sequence number is its line number, from 0. Every
+//! `AUDITORZK_TRANSPARENCY_INTERVAL_SECS` the verifier signs a new tree head over
+//! the whole log with the organization key. Its own listener
+//! (`AUDITORZK_TRANSPARENCY_ADDR`) is meant to be public:
+//!
+//! - `GET /v1/transparency/sth` returns the latest signed tree head;
+//! - `GET /v1/transparency/proof/{seq}` returns an inclusion proof against it;
+//! - `GET /v1/transparency/consistency/{first}/{second}` returns a consistency
+//!   proof between two tree sizes up to it.
+//!
+//! Proofs are checked with `auditorzk_attestation::transparency`. Leaves logged
+//! after the latest tree head get proofs once the next head is signed.
+
+use anyhow::{Context, Result};
+use auditorzk_attestation::transparency::{consistency_proof, content_hash, inclusion_proof, SignedTreeHead};
+use auditorzk_attestation::Attestation;
+use k256::schnorr::SigningKey;
+use serde_json::json;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::admin::{parse_head, read_head, write_json, HEAD_TIMEOUT};
+use crate::config::ServerConfig;
+use crate::error::Error;
+use crate::tasks::TaskRegistry;
+
+/// File name of the log within the attestation directory
+pub const LOG_FILE: &str = "transparency.log";
+
+/// Append-only log of issued attestations' content hashes
+pub struct TransparencyLog {
+    /// Held while appending, so file order and sequence numbers agree
+    file: Mutex<File>,
+    leaves: RwLock<Vec<[u8; 32]>>,
+    head: RwLock<SignedTreeHead>,
+    org_key: SigningKey,
+}
+
+impl TransparencyLog {
+    /// Open (or create) the log at `path` and sign a first tree head over it
+    pub fn open(path: &Path, org_key: SigningKey, timestamp: u64) -> Result<Self> {
+        let leaves = match fs::read_to_string(path) {
+            Ok(text) => text
+                .lines()
+                .enumerate()
+                .map(|(seq, line)| {
+                    let hash = hex::decode(line.trim()).ok().and_then(|bytes| bytes.try_into().ok());
+                    hash.with_context(|| format!("Invalid leaf {} in {}", seq, path.display()))
+                })
+                .collect::<Result<Vec<[u8; 32]>>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let head = SignedTreeHead::sign(&leaves, timestamp, &org_key)?;
+        Ok(Self { file: Mutex::new(file), leaves: RwLock::new(leaves), head: RwLock::new(head), org_key })
+    }
+
+    /// The log configured by `AUDITORZK_TRANSPARENCY_KEY_FILE`, if any
+    pub fn from_config(config: &ServerConfig) -> Result<Option<Arc<Self>>> {
+        let Some(key_file) = &config.transparency_key_file else {
+            return Ok(None);
+        };
+        let key = fs::read_to_string(key_file).with_context(|| format!("Failed to read {}", key_file.display()))?;
+        let key = hex::decode(key.trim()).ok().and_then(|bytes| SigningKey::from_bytes(&bytes).ok());
+        let org_key =
+            key.with_context(|| format!("{} is not a hex-encoded secp256k1 secret key", key_file.display()))?;
+
+        let path = config.attestation_dir.join(LOG_FILE);
+        let log = Self::open(&path, org_key, unix_now())?;
+        info!("📜 Transparency log {} holds {} attestation(s)", path.display(), log.tree_head().tree_size);
+        Ok(Some(Arc::new(log)))
+    }
+
+    /// Log an issued attestation, returning its sequence number
+    pub fn append(&self, attestation: &Attestation) -> Result<u64, Error> {
+        let hash = content_hash(attestation)?;
+
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", hex::encode(hash))
+            .and_then(|_| file.sync_data())
+            .map_err(|source| Error::Save { artifact: "transparency log leaf", source })?;
+        let mut leaves = self.leaves.write().unwrap();
+        leaves.push(hash);
+        Ok(leaves.len() as u64 - 1)
+    }
+
+    /// Sign a tree head over every leaf logged so far
+    pub fn publish(&self, timestamp: u64) -> Result<SignedTreeHead, Error> {
+        let head = SignedTreeHead::sign(&self.leaves.read().unwrap(), timestamp, &self.org_key)?;
+        *self.head.write().unwrap() = head.clone();
+        Ok(head)
+    }
+
+    pub fn tree_head(&self) -> SignedTreeHead {
+        self.head.read().unwrap().clone()
+    }
+
+    /// Status code and JSON body for one request head
+    pub fn respond(&self, head: &[u8]) -> (u16, serde_json::Value) {
+        let Some((method, path, _)) = parse_head(head) else {
+            return (400, json!({ "error": "malformed request" }));
+        };
+        let Some(route) = path.strip_prefix("/v1/transparency/") else {
+            return (404, json!({ "error": "not found" }));
+        };
+        if method != "GET" {
+            return (405, json!({ "error": "method not allowed" }));
+        }
+
+        let sth = self.tree_head();
+        let leaves = self.leaves.read().unwrap();
+        let segments: Vec<&str> = route.split('/').collect();
+        let body = match segments.as_slice() {
+            ["sth"] => serde_json::to_value(&sth).ok(),
+            ["proof", seq] => seq
+                .parse()
+                .ok()
+                .and_then(|seq| inclusion_proof(&leaves, seq, sth.tree_size))
+                .and_then(|proof| serde_json::to_value(proof).ok()),
+            ["consistency", first, second] => match (first.parse(), second.parse()) {
+                (Ok(first), Ok(second)) if second <= sth.tree_size => consistency_proof(&leaves, first, second)
+                    .and_then(|proof| serde_json::to_value(proof).ok()),
+                _ => None,
+            },
+            _ => None,
+        };
+        match body {
+            Some(body) => (200, body),
+            None => (404, json!({ "error": "not found", "tree_size": sth.tree_size })),
+        }
+    }
+
+    /// Sign a fresh tree head every `interval` until the process exits
+    pub async fn run_publisher(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match self.publish(unix_now()) {
+                Ok(head) => {
+                    info!("📜 Signed transparency tree head: {} leaves, root {}", head.tree_size, head.root_hash)
+                }
+                Err(e) => warn!("⚠️  Failed to sign transparency tree head: {}", e),
+            }
+        }
+    }
+}
+
+/// Serve the transparency feed until the process exits
+pub async fn serve(listener: TcpListener, log: Arc<TransparencyLog>, tasks: Arc<TaskRegistry>) {
+    loop {
+        let (mut stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("⚠️  Failed to accept transparency connection: {}", e);
+                continue;
+            }
+        };
+
+        let log = Arc::clone(&log);
+        tasks.spawn("transparency", None, Some(HEAD_TIMEOUT * 2), async move {
+            let result = async {
+                let head = tokio::time::timeout(HEAD_TIMEOUT, read_head(&mut stream)).await??;
+                let (status, body) = log.respond(&head);
+                write_json(&mut stream, status, &body).await
+            };
+            if let Err(e) = result.await {
+                warn!("⚠️  Transparency request from {} failed: {}", peer_addr, e);
+            }
+        });
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auditorzk_attestation::transparency::{InclusionProof, verify_inclusion};
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn inclusion_proofs_verify_against_the_tree_head() {
+        let path = std::env::temp_dir().join(format!("auditorzk-transparency-{}.log", std::process::id()));
+        let org_key = SigningKey::from_bytes(&Sha256::digest(b"org")).unwrap();
+        let log = TransparencyLog::open(&path, org_key.clone(), 1_700_000_000).unwrap();
+
+        let attestation: Attestation = serde_json::from_str(include_str!("../../auditorzk-attestation/fixtures/attestation_v20.json")).unwrap();
+        assert_eq!(log.append(&attestation).unwrap(), 0);
+        assert_eq!(log.respond(b"GET /v1/transparency/proof/0 HTTP/1.1\r\n\r\n").0, 404);
+
+        log.publish(1_700_000_300).unwrap();
+        let (status, sth) = log.respond(b"GET /v1/transparency/sth HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 200);
+        let sth: SignedTreeHead = serde_json::from_value(sth).unwrap();
+        sth.verify(&org_key.verifying_key().to_bytes()).unwrap();
+
+        let (_, proof) = log.respond(b"GET /v1/transparency/proof/0 HTTP/1.1\r\n\r\n");
+        let proof: InclusionProof = serde_json::from_value(proof).unwrap();
+        verify_inclusion(&attestation, &proof, &sth).unwrap();
+
+        // The log survives a restart
+        drop(log);
+        let reopened = TransparencyLog::open(&path, org_key, 1_700_000_600).unwrap();
+        assert_eq!(reopened.tree_head().root_hash, sth.root_hash);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
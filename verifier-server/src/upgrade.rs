@@ -0,0 +1,169 @@
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{self, header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, StatusCode};
+use tracing::{debug, warn};
+
+/// WebSocket subprotocol for this verifier's framing: JSON control messages in
+/// text frames, the MPC stream in length-prefixed binary frames (see [`Framing`])
+pub const SUBPROTOCOL: &str = "auditorzk.v1";
+
+/// Bytes of the payload length prefixed to each binary frame under [`SUBPROTOCOL`]
+pub const FRAME_PREFIX_LEN: usize = 4;
+
+/// Subprotocols the client offered in `Sec-WebSocket-Protocol`, in its order
+fn offered_subprotocols(request: &Request) -> Vec<&str> {
+    request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|protocol| !protocol.is_empty())
+        .collect()
+}
+
+/// Upgrade callback negotiating [`SUBPROTOCOL`].
+///
+/// A client offering it gets it selected in the response. Otherwise, `strict`
+/// rejects the upgrade with 400 before any WebSocket traffic; without it the
+/// connection is accepted with no subprotocol, which clients that insisted on
+/// one of theirs then close themselves.
+pub fn negotiate_subprotocol(strict: bool) -> impl FnOnce(&Request, Response) -> Result<Response, ErrorResponse> {
+    move |request, mut response| {
+        let offered = offered_subprotocols(request);
+        if offered.contains(&SUBPROTOCOL) {
+            response.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(SUBPROTOCOL));
+            return Ok(response);
+        }
+
+        let offered = if offered.is_empty() { "none".to_string() } else { offered.join(", ") };
+        if strict {
+            warn!("❌ Rejecting upgrade: subprotocol {} required, client offered {}", SUBPROTOCOL, offered);
+            let rejection = http::Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Some(format!("Subprotocol {} required (offered: {})", SUBPROTOCOL, offered)))
+                .expect("static status and body always build");
+            return Err(rejection);
+        }
+
+        debug!("Accepting upgrade without a subprotocol (client offered {})", offered);
+        Ok(response)
+    }
+}
+
+/// How MPC bytes are carried in binary frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// No subprotocol: each binary frame is raw MPC bytes
+    Raw,
+    /// [`SUBPROTOCOL`]: each binary frame is the payload length (u32 big-endian)
+    /// followed by exactly that many MPC bytes, in both directions
+    LengthPrefixed,
+}
+
+/// A binary frame that doesn't follow [`Framing::LengthPrefixed`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FrameError {
+    #[error("Binary frame of {0} bytes is shorter than its length prefix")]
+    Truncated(usize),
+    #[error("Binary frame declares {declared} payload bytes but carries {actual}")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error("Binary frame has an empty payload")]
+    Empty,
+}
+
+impl Framing {
+    /// Framing selected by an upgrade response, the one [`negotiate_subprotocol`]
+    /// builds or the one a client receives
+    pub fn negotiated<B>(response: &http::Response<B>) -> Self {
+        let selected = response.headers().get(SEC_WEBSOCKET_PROTOCOL).and_then(|value| value.to_str().ok());
+        if selected == Some(SUBPROTOCOL) {
+            Self::LengthPrefixed
+        } else {
+            Self::Raw
+        }
+    }
+
+    /// MPC bytes carried by a binary frame from the prover.
+    pub fn payload<'a>(&self, frame: &'a [u8]) -> Result<&'a [u8], FrameError> {
+        if *self == Self::Raw {
+            return Ok(frame);
+        }
+        let (prefix, payload) = frame.split_first_chunk::<FRAME_PREFIX_LEN>().ok_or(FrameError::Truncated(frame.len()))?;
+        let declared = u32::from_be_bytes(*prefix) as usize;
+        if declared != payload.len() {
+            return Err(FrameError::LengthMismatch { declared, actual: payload.len() });
+        }
+        if payload.is_empty() {
+            return Err(FrameError::Empty);
+        }
+        Ok(payload)
+    }
+
+    /// Binary frame carrying `payload` to the prover
+    pub fn wrap(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Raw => payload.to_vec(),
+            Self::LengthPrefixed => {
+                let mut frame = Vec::with_capacity(FRAME_PREFIX_LEN + payload.len());
+                frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+                frame.extend_from_slice(payload);
+                frame
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subprotocol_selects_the_framing() {
+        let upgrade = |offered: &[&str], strict: bool| {
+            let mut request = Request::builder().uri("/");
+            for value in offered {
+                request = request.header(SEC_WEBSOCKET_PROTOCOL, *value);
+            }
+            negotiate_subprotocol(strict)(&request.body(()).unwrap(), Response::default())
+        };
+
+        // Offered alone, among others, or in a second header: selected, strict or not
+        for offered in [&["auditorzk.v1"][..], &["chat, auditorzk.v1"], &["chat", " auditorzk.v1 "]] {
+            for strict in [false, true] {
+                let response = upgrade(offered, strict).unwrap();
+                assert_eq!(response.headers()[SEC_WEBSOCKET_PROTOCOL], SUBPROTOCOL);
+                assert_eq!(Framing::negotiated(&response), Framing::LengthPrefixed);
+            }
+        }
+
+        // Not offered, or only a near miss: strict mode refuses the upgrade...
+        for offered in [&[][..], &["chat"], &["auditorzk.v2", "AUDITORZK.V1"]] {
+            let rejection = upgrade(offered, true).unwrap_err();
+            assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+            assert!(rejection.body().as_deref().unwrap().starts_with("Subprotocol auditorzk.v1 required"));
+
+            // ...and lenient mode accepts a bare connection with raw framing
+            let response = upgrade(offered, false).unwrap();
+            assert!(!response.headers().contains_key(SEC_WEBSOCKET_PROTOCOL));
+            assert_eq!(Framing::negotiated(&response), Framing::Raw);
+        }
+    }
+
+    #[test]
+    fn length_prefixed_frames_are_checked() {
+        let framing = Framing::LengthPrefixed;
+        assert_eq!(framing.payload(&[0, 0, 0, 3, 0xaa, 0xbb, 0xcc]), Ok(&[0xaa, 0xbb, 0xcc][..]));
+        assert_eq!(framing.payload(&framing.wrap(b"mpc")), Ok(&b"mpc"[..]));
+
+        // Malformed frames: raw MPC bytes, a short prefix, a wrong length, no payload
+        let raw = [0x16, 0x03, 0x03, 0x00, 0x2a];
+        assert_eq!(framing.payload(&raw), Err(FrameError::LengthMismatch { declared: 0x1603_0300, actual: 1 }));
+        assert_eq!(framing.payload(&[0, 0]), Err(FrameError::Truncated(2)));
+        assert_eq!(framing.payload(&[0, 0, 0, 2, 0xaa]), Err(FrameError::LengthMismatch { declared: 2, actual: 1 }));
+        assert_eq!(framing.payload(&[0, 0, 0, 0]), Err(FrameError::Empty));
+
+        // Without the subprotocol every frame is passed through
+        assert_eq!(Framing::Raw.payload(&[0, 0]), Ok(&[0, 0][..]));
+    }
+}
@@ -0,0 +1,261 @@
+//! Daily MPC usage accounting and budgets.
+//!
+//! Every finished session adds its wall-clock MPC time and the bytes it
+//! forwarded to the counters of the current UTC day. CPU time isn't counted:
+//! sessions share the runtime's worker threads, so it can't be attributed to one.
+//! The verifier has no tenants, so usage is accounted for the deployment as a
+//! whole, and kept in memory for the last [`RETAINED_DAYS`] days; a restart
+//! starts the counters from zero.
+//!
+//! With `AUDITORZK_DAILY_MPC_SECONDS` or `AUDITORZK_DAILY_BYTES_FORWARDED` set,
+//! a day that goes over budget logs a warning, and with
+//! `AUDITORZK_BUDGET_ENFORCEMENT=reject` new sessions are refused as
+//! `quota_exhausted` until the next UTC midnight. `GET /usage` on the admin
+//! listener lists the retained days, and `/metrics` exports today's counters.
+//!
+//! Sessions on either side of midnight land on their own day, and the budget
+//! resets with the day.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::config::ServerConfig;
+
+/// Days of usage kept for `GET /usage`
+pub const RETAINED_DAYS: usize = 31;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// What happens once a day's usage goes over budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetEnforcement {
+    /// Log a warning and keep accepting sessions
+    Warn,
+    /// Refuse new sessions until the next UTC day
+    Reject,
+}
+
+impl fmt::Display for BudgetEnforcement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warn => "warn",
+            Self::Reject => "reject",
+        })
+    }
+}
+
+impl FromStr for BudgetEnforcement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "warn" => Ok(Self::Warn),
+            "reject" => Ok(Self::Reject),
+            other => bail!("Unknown budget enforcement '{}' (expected warn or reject)", other),
+        }
+    }
+}
+
+/// Daily limits on MPC usage; an unset limit is unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UsageBudget {
+    pub mpc_seconds: Option<u64>,
+    pub bytes_forwarded: Option<u64>,
+    pub enforcement: BudgetEnforcement,
+}
+
+impl UsageBudget {
+    /// The first limit `usage` has reached, if any
+    pub fn exhausted_by(&self, usage: &DailyUsage) -> Option<&'static str> {
+        if self.mpc_seconds.is_some_and(|limit| usage.mpc_millis >= limit.saturating_mul(1000)) {
+            return Some("MPC seconds");
+        }
+        if self.bytes_forwarded.is_some_and(|limit| usage.bytes_forwarded >= limit) {
+            return Some("bytes forwarded");
+        }
+        None
+    }
+}
+
+/// Usage of one UTC day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DailyUsage {
+    /// Days since the Unix epoch
+    pub day: u64,
+    pub sessions: u64,
+    /// Wall-clock time spent in MPC
+    pub mpc_millis: u64,
+    /// Bytes forwarded between prover and verifier, in either direction
+    pub bytes_forwarded: u64,
+}
+
+/// Daily usage counters, shared by every session
+pub struct UsageLedger {
+    budget: Option<UsageBudget>,
+    /// Oldest first; the last entry is the most recent day with a session
+    days: Mutex<VecDeque<DailyUsage>>,
+}
+
+impl UsageLedger {
+    pub fn new(budget: Option<UsageBudget>) -> Self {
+        Self { budget, days: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let budget = (config.daily_mpc_seconds.is_some() || config.daily_bytes_forwarded.is_some()).then_some(
+            UsageBudget {
+                mpc_seconds: config.daily_mpc_seconds,
+                bytes_forwarded: config.daily_bytes_forwarded,
+                enforcement: config.budget_enforcement,
+            },
+        );
+        Self::new(budget)
+    }
+
+    /// Add a session that finished at `now`, warning when it takes the day over budget
+    pub fn record(&self, now: SystemTime, mpc: Duration, bytes_forwarded: u64) {
+        let day = day_of(now);
+        let mut days = self.days.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !days.back().is_some_and(|usage| usage.day >= day) {
+            days.push_back(DailyUsage { day, ..DailyUsage::default() });
+            while days.len() > RETAINED_DAYS {
+                days.pop_front();
+            }
+        }
+        // A clock step backwards still counts, on the latest day
+        let usage = days.back_mut().expect("a day was just ensured");
+        let exhausted_before = self.budget.and_then(|budget| budget.exhausted_by(usage));
+        usage.sessions += 1;
+        usage.mpc_millis = usage.mpc_millis.saturating_add(mpc.as_millis() as u64);
+        usage.bytes_forwarded = usage.bytes_forwarded.saturating_add(bytes_forwarded);
+
+        if let Some(budget) = self.budget.filter(|_| exhausted_before.is_none()) {
+            if let Some(limit) = budget.exhausted_by(usage) {
+                warn!(
+                    "💸 Daily budget for {} exhausted ({} sessions, {}s MPC, {} bytes forwarded){}",
+                    limit,
+                    usage.sessions,
+                    usage.mpc_millis / 1000,
+                    usage.bytes_forwarded,
+                    match budget.enforcement {
+                        BudgetEnforcement::Reject => ", refusing sessions until midnight UTC",
+                        BudgetEnforcement::Warn => "",
+                    }
+                );
+            }
+        }
+    }
+
+    /// Usage of the day containing `now`
+    pub fn today(&self, now: SystemTime) -> DailyUsage {
+        let day = day_of(now);
+        let days = self.days.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        days.back().filter(|usage| usage.day == day).copied().unwrap_or(DailyUsage { day, ..DailyUsage::default() })
+    }
+
+    /// Retained days with at least one session, oldest first
+    pub fn days(&self) -> Vec<DailyUsage> {
+        self.days.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter().copied().collect()
+    }
+
+    /// Whether a new session may start at `now`, or how long until the budget
+    /// resets when it is exhausted and enforced
+    pub fn check(&self, now: SystemTime) -> Result<(), Duration> {
+        let Some(budget) = self.budget.filter(|budget| budget.enforcement == BudgetEnforcement::Reject) else {
+            return Ok(());
+        };
+        if budget.exhausted_by(&self.today(now)).is_none() {
+            return Ok(());
+        }
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Err(Duration::from_secs((day_of(now) + 1) * SECS_PER_DAY).saturating_sub(since_epoch))
+    }
+
+    /// `GET /usage` body: the budget, today's usage and every retained day
+    pub fn to_json(&self, now: SystemTime) -> serde_json::Value {
+        let today = self.today(now);
+        serde_json::json!({
+            "budget": self.budget,
+            "exhausted": self.budget.and_then(|budget| budget.exhausted_by(&today)),
+            "today": today,
+            "days": self.days(),
+        })
+    }
+
+    /// Today's counters and the budget, in the Prometheus text format
+    pub fn render(&self, now: SystemTime) -> String {
+        let today = self.today(now);
+        let mut out = String::new();
+        let mut gauge = |name: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        gauge("auditorzk_usage_sessions", "Sessions finished today (UTC).", today.sessions.to_string());
+        gauge(
+            "auditorzk_usage_mpc_seconds",
+            "Wall-clock MPC time of sessions finished today (UTC).",
+            (today.mpc_millis as f64 / 1e3).to_string(),
+        );
+        gauge(
+            "auditorzk_usage_bytes_forwarded",
+            "Bytes forwarded by sessions finished today (UTC).",
+            today.bytes_forwarded.to_string(),
+        );
+        if let Some(budget) = self.budget {
+            if let Some(limit) = budget.mpc_seconds {
+                gauge("auditorzk_usage_budget_mpc_seconds", "Daily MPC time budget.", limit.to_string());
+            }
+            if let Some(limit) = budget.bytes_forwarded {
+                gauge("auditorzk_usage_budget_bytes_forwarded", "Daily forwarded bytes budget.", limit.to_string());
+            }
+            let exhausted = budget.exhausted_by(&today).is_some();
+            gauge("auditorzk_usage_budget_exhausted", "Whether today's budget is used up.", u8::from(exhausted).to_string());
+        }
+        out
+    }
+}
+
+/// UTC day of `time`, as days since the Unix epoch
+fn day_of(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / SECS_PER_DAY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_resets_at_midnight() {
+        let budget = UsageBudget { mpc_seconds: Some(60), bytes_forwarded: None, enforcement: BudgetEnforcement::Reject };
+        let ledger = UsageLedger::new(Some(budget));
+        let midnight = UNIX_EPOCH + Duration::from_secs(20_000 * 86_400);
+        let before = midnight - Duration::from_secs(600);
+
+        ledger.record(before, Duration::from_secs(45), 30 << 20);
+        assert!(ledger.check(before).is_ok());
+        ledger.record(before, Duration::from_secs(20), 30 << 20);
+
+        // Over budget: refused until midnight
+        assert_eq!(ledger.check(before + Duration::from_secs(60)), Err(Duration::from_secs(540)));
+
+        // A session straddling midnight counts on the day it finished
+        ledger.record(midnight + Duration::from_secs(5), Duration::from_secs(30), 10 << 20);
+        assert!(ledger.check(midnight + Duration::from_secs(5)).is_ok());
+
+        let days = ledger.days();
+        assert_eq!(days.len(), 2);
+        assert_eq!((days[0].sessions, days[0].mpc_millis, days[0].bytes_forwarded), (2, 65_000, 60 << 20));
+        assert_eq!((days[1].sessions, days[1].mpc_millis), (1, 30_000));
+        assert_eq!(days[1].day, days[0].day + 1);
+    }
+}
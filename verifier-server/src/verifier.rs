@@ -1,103 +1,571 @@
-use anyhow::{Result, Context};
-use futures::{StreamExt, SinkExt};
+use futures::{Stream, StreamExt, SinkExt};
 use tokio_tungstenite::WebSocketStream;
-use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::{self, Message};
+use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+use std::error::Error as StdError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tokio_util::compat::TokioAsyncReadCompatExt;
 use tracing::{info, debug, warn};
 
 use tlsn_common::config::ProtocolConfigValidator;
-use tlsn_core::{VerifierOutput, VerifyConfig};
+use tlsn_core::VerifyConfig;
 use tlsn_verifier::{Verifier, VerifierConfig};
 
+use crate::analyze::write_dump;
 use crate::attestation::sign_attestation;
-use crate::plaid::validate_plaid_connection;
+use crate::capture::{outcome_text, CaptureKind, CaptureRecord, SessionCapture};
+use crate::config::ServerConfig;
+use crate::control::{
+    check_blinder, check_clock_skew, check_declared_size, check_format, check_recipient, parse_prover_message, size_diverges,
+    validate_policy, Hello, ProgressStage, ProverDisconnect, ProverMessage, SessionPhase, VerifierClose,
+    VerifierMessage, SESSION_BLINDER_LEN,
+};
+use crate::disclosure::DisclosureReport;
+use crate::error::{report, BoxError, Check, Error, Result};
+use crate::http::HeadLimits;
+use crate::memory::SessionGuard;
+use crate::parsed::ParsedSession;
+use crate::plaid::{validate_plaid_connection, CommitmentCounts};
+use crate::progress::Progress;
+use crate::proxy::ClientAddr;
+use crate::session::SessionFacts;
+use crate::tasks::TaskRegistry;
+use crate::traffic::{self, FrameDirection, SessionTraffic};
+use crate::transparency::TransparencyLog;
+use crate::upgrade::Framing;
 
 /// Maximum data sizes for Plaid API calls
 const MAX_SENT_DATA: usize = 4096;      // 4KB for requests
 const MAX_RECV_DATA: usize = 16384;     // 16KB for responses
 
+/// Capacity of each direction of the in-process MPC pipe
+pub const DUPLEX_BUFFER_BYTES: usize = 1 << 20;
+
+/// What a session reserves from `AUDITORZK_MEMORY_BUDGET_MB`: both directions of
+/// the MPC pipe, plus the transcript and the copy parsing it makes
+pub const SESSION_BUFFER_BYTES: usize = 2 * DUPLEX_BUFFER_BYTES + 2 * (MAX_SENT_DATA + MAX_RECV_DATA);
+
+/// tlsn protocol configuration fields the verifier bounds; anything else is
+/// reported as `other` so the field name stays a small, fixed set
+const CONFIG_FIELDS: &[&str] = &["max_sent_data", "max_recv_data", "max_sent_records", "max_recv_records_online"];
+
+/// How long to wait for each forwarder to wind down once the MPC stream is closed
+const CLOSE_WAIT: Duration = Duration::from_secs(2);
+
 pub async fn handle_verification<S>(
     ws_stream: WebSocketStream<S>,
-    peer_addr: std::net::SocketAddr,
+    client: &ClientAddr,
+    framing: Framing,
+    config: &ServerConfig,
+    transparency: Option<&TransparencyLog>,
+    session: &SessionGuard,
+    tasks: &Arc<TaskRegistry>,
 ) -> Result<()>
 where
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
 {
-    info!("🔍 Starting verification for {}", peer_addr);
+    let session_id = new_session_id();
+    info!("🔍 Starting verification for {} (session {})", client, session_id);
 
     // Create bidirectional channel for MPC protocol
-    let (prover_stream, verifier_stream) = tokio::io::duplex(1 << 20); // 1MB buffer
+    let (prover_stream, verifier_stream) = tokio::io::duplex(DUPLEX_BUFFER_BYTES);
 
     // Split WebSocket into read/write halves
     let (mut ws_write, mut ws_read) = ws_stream.split();
 
+    // Optional control handshake before the MPC stream starts
+    let (mut hello, first_frame) = match read_handshake(&mut ws_read, config).await {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            let reason = report(&e);
+            warn!("❌ Handshake failed for {} ({}): {}", client, e.code(), reason);
+            let error = VerifierMessage::Error { message: reason.clone() };
+            let _ = ws_write.send(Message::Text(error.to_json())).await;
+            let close = e.close().frame(&reason);
+            let _ = ws_write.send(Message::Close(Some(close))).await;
+            return Err(e.in_session(&session_id));
+        }
+    };
+
+    // A prover without a blinder of its own gets a random one for the session,
+    // returned in a `blinder` frame once the attestation is signed
+    let issued_blinder = hello.blinder.is_none().then(new_blinder);
+    hello.blinder = hello.blinder.or_else(|| issued_blinder.clone());
+
+    // A prover that declared its sizes waits for the limits before starting MPC
+    if hello.declares_size() {
+        let limits = VerifierMessage::Limits { max_sent_bytes: MAX_SENT_DATA, max_recv_bytes: MAX_RECV_DATA };
+        ws_write.send(Message::Text(limits.to_json())).await?;
+    }
+
+    // Progress frames are queued here and interleaved by the prover-to-ws writer
+    let (progress, mut progress_rx) = Progress::new(config);
+    progress.stage(ProgressStage::Connected);
+
     // Split prover stream for forwarding
     let (mut prover_read, mut prover_write) = tokio::io::split(prover_stream);
 
+    // Both forwarders record what they pass on, when capture is enabled
+    let capture = SessionCapture::open(config, &session_id);
+
+    // They also record every frame's size and timing, to tune buffers from
+    let traffic = SessionTraffic::default();
+
     // Forward: WebSocket → Prover stream (writes)
-    let ws_to_prover = tokio::spawn(async move {
+    // Only binary frames reach the MPC stream, unwrapped per the negotiated framing;
+    // malformed frames, and text frames out of session phase, end the session
+    let inbound_bytes = Arc::clone(&session.bytes_forwarded);
+    let mpc_complete = Arc::new(AtomicBool::new(false));
+    let forwarder_mpc_complete = Arc::clone(&mpc_complete);
+    let forwarder_progress = progress.clone();
+    let inbound_capture = capture.clone();
+    let inbound_traffic = traffic.clone();
+    let mut ws_to_prover = tasks.spawn("ws-to-prover", Some(session.id()), None, async move {
         use tokio::io::AsyncWriteExt;
+        let mut phase = SessionPhase::Mpc;
+        let mut mpc_started = false;
+        if let Some(frame) = first_frame {
+            let data = framing.payload(&frame)?;
+            mpc_started = true;
+            forwarder_progress.stage(ProgressStage::MpcStarted);
+            inbound_bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+            inbound_capture.record(CaptureKind::Inbound, data);
+            inbound_traffic.record(FrameDirection::Inbound, data.len());
+            if let Err(e) = prover_write.write_all(data).await {
+                warn!("Error forwarding to prover stream: {}", e);
+                return Ok(ProverDisconnect::Open);
+            }
+        }
         while let Some(msg) = ws_read.next().await {
+            if forwarder_mpc_complete.load(Ordering::Relaxed) {
+                phase = phase.on_mpc_complete();
+            }
             match msg {
-                Ok(Message::Binary(data)) => {
-                    if let Err(e) = prover_write.write_all(&data).await {
+                Ok(Message::Binary(frame)) => {
+                    phase = phase.on_mpc_frame();
+                    if phase == SessionPhase::PostMpc {
+                        continue;
+                    }
+                    let data = framing.payload(&frame)?;
+                    if !mpc_started {
+                        mpc_started = true;
+                        forwarder_progress.stage(ProgressStage::MpcStarted);
+                    }
+                    inbound_bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    inbound_capture.record(CaptureKind::Inbound, data);
+                    inbound_traffic.record(FrameDirection::Inbound, data.len());
+                    if let Err(e) = prover_write.write_all(data).await {
                         warn!("Error forwarding to prover stream: {}", e);
-                        break;
+                        return Ok(ProverDisconnect::Open);
                     }
                 }
-                Ok(Message::Close(_)) => {
-                    debug!("WebSocket closed by prover");
-                    break;
+                Ok(Message::Text(text)) => {
+                    let message =
+                        parse_prover_message(&text).map_err(|source| Error::ControlMessage { phase, source })?;
+                    phase = phase.on_message(&message)?;
+                }
+                Ok(Message::Close(frame)) => {
+                    let disconnect = ProverDisconnect::from_frame(frame.as_ref());
+                    debug!("WebSocket {} by prover", disconnect);
+                    return Ok(disconnect);
                 }
                 Err(e) => {
                     warn!("WebSocket error: {}", e);
-                    break;
+                    return Ok(ProverDisconnect::Dropped(e.to_string()));
                 }
                 _ => {}
             }
         }
+        Ok::<_, Error>(ProverDisconnect::Dropped("stream ended without a close frame".to_string()))
     });
 
     // Forward: Prover stream (reads) → WebSocket
-    let prover_to_ws = tokio::spawn(async move {
+    // MPC bytes always win; queued progress frames only go out while the stream is idle
+    let bytes_forwarded = Arc::clone(&session.bytes_forwarded);
+    let outbound_capture = capture.clone();
+    let outbound_traffic = traffic.clone();
+    let prover_to_ws = tasks.spawn("prover-to-ws", Some(session.id()), None, async move {
         use tokio::io::AsyncReadExt;
         let mut buf = vec![0u8; 8192];
         loop {
-            match prover_read.read(&mut buf).await {
-                Ok(0) => break, // EOF
-                Ok(n) => {
-                    if let Err(e) = ws_write.send(Message::Binary(buf[..n].to_vec())).await {
-                        warn!("Error sending to WebSocket: {}", e);
+            let frame = tokio::select! {
+                biased;
+                read = prover_read.read(&mut buf) => match read {
+                    Ok(0) => break, // EOF
+                    Ok(n) => {
+                        bytes_forwarded.fetch_add(n as u64, Ordering::Relaxed);
+                        outbound_capture.record(CaptureKind::Outbound, &buf[..n]);
+                        outbound_traffic.record(FrameDirection::Outbound, n);
+                        Message::Binary(framing.wrap(&buf[..n]))
+                    }
+                    Err(e) => {
+                        warn!("Error reading from prover stream: {}", e);
                         break;
                     }
+                },
+                Some(message) = progress_rx.recv() => Message::Text(message.to_json()),
+            };
+            if let Err(e) = ws_write.send(frame).await {
+                warn!("Error sending to WebSocket: {}", e);
+                break;
+            }
+        }
+        (ws_write, progress_rx)
+    });
+
+    // Estimate how far MPC has got from the bytes forwarded so far
+    let progress_ticker = tasks.spawn(
+        "progress",
+        Some(session.id()),
+        None,
+        progress.clone().track_mpc(
+            Arc::clone(&session.bytes_forwarded),
+            Arc::clone(&mpc_complete),
+            config.progress_expected_bytes,
+            Duration::from_millis(config.progress_interval_ms),
+        ),
+    );
+
+    // Run verifier with verifier side of duplex stream, then validate and sign
+    let mpc_started = Instant::now();
+    // The memory watchdog may shed the session mid-MPC; the prover is then told why
+    let mpc = tokio::select! {
+        mpc = run_verifier(verifier_stream.compat()) => mpc,
+        () = session.shed() => Err(Error::Shed),
+    };
+    let mpc_time = mpc_started.elapsed();
+    capture.finish(&mpc);
+    let mpc_ok = mpc.is_ok();
+    let mut size_flag = None;
+    let mut result = match mpc {
+        Ok(facts) => {
+            mpc_complete.store(true, Ordering::Relaxed);
+            if config.dump_output {
+                match write_dump(&config.capture_dir, &session_id, &facts) {
+                    Ok(path) => info!("🗂️  Verifier output dumped to {}", path.display()),
+                    Err(e) => warn!("⚠️  Cannot dump the output of session {}: {:#}", session_id, e),
                 }
-                Err(e) => {
-                    warn!("Error reading from prover stream: {}", e);
-                    break;
-                }
             }
+            size_flag = size_divergence(&hello, &facts);
+            progress.stage(ProgressStage::Verified);
+            validate_and_sign(&facts, &hello, &session_id, &progress, config, transparency).await
+        }
+        Err(e) => {
+            // e.g. an under-declared response that then ran past the limits
+            if hello.declares_size() {
+                size_flag = Some("declared sizes unconfirmed, MPC failed".to_string());
+            }
+            Err(e)
+        }
+    };
+    if let Some(flag) = &size_flag {
+        warn!("⚠️  Session {} size divergence: {}", session_id, flag);
+    }
+
+    // An out-of-phase control message or a malformed frame drops the prover
+    // stream, which is what failed MPC; report the protocol violation rather
+    // than the I/O error. MPC can notice the dropped stream before the reader
+    // has returned, so wait for the reader's verdict before choosing the error
+    let mut disconnect = None;
+    let reader_done = !mpc_ok && match tokio::time::timeout(CLOSE_WAIT, &mut ws_to_prover).await {
+        Ok(Ok(Err(violation))) => {
+            warn!("❌ Session {} terminated ({}): {}", session_id, violation.code(), report(&violation));
+            result = Err(violation);
+            true
+        }
+        Ok(Ok(Ok(prover_end))) => {
+            disconnect = Some(prover_end);
+            true
+        }
+        Ok(Err(_)) => true, // Panicked; the registry already aborted the session
+        Err(_) => false,    // Still reading; the prover gets its close below
+    };
+
+    let close = match &result {
+        Ok(_) => VerifierClose::Done,
+        Err(e) => e.close(),
+    };
+
+    // Close our side with the code for the outcome. The MPC stream is gone, so the
+    // writer drains and hands the sink back, along with any progress still queued
+    if let Ok(Ok((mut ws_write, mut progress_rx))) = tokio::time::timeout(CLOSE_WAIT, prover_to_ws).await {
+        while let Ok(message) = progress_rx.try_recv() {
+            let _ = ws_write.send(Message::Text(message.to_json())).await;
+        }
+        if let Some(blinder) = issued_blinder.filter(|_| result.is_ok()) {
+            let _ = ws_write.send(Message::Text(VerifierMessage::Blinder { blinder }.to_json())).await;
         }
+        if let Some(disclosure) = result.as_ref().ok().filter(|_| hello.disclosure_report) {
+            let summary = VerifierMessage::Summary { disclosure: disclosure.clone() };
+            let _ = ws_write.send(Message::Text(summary.to_json())).await;
+        }
+        if let Some(frame) = result.as_ref().err().and_then(Error::control_frame) {
+            let _ = ws_write.send(Message::Text(frame.to_json())).await;
+        }
+        let reason = result.as_ref().err().map(|e| report(e)).unwrap_or_default();
+        let _ = ws_write.send(Message::Close(Some(close.frame(&reason)))).await;
+    }
+
+    // Then give the prover a moment to answer with its own close
+    if !reader_done {
+        match tokio::time::timeout(CLOSE_WAIT, &mut ws_to_prover).await {
+            Ok(Ok(Ok(prover_end))) => disconnect = Some(prover_end),
+            Ok(Ok(Err(e))) => debug!("Control message error after MPC: {}", report(&e)),
+            Ok(Err(_)) => {}
+            Err(_) => ws_to_prover.abort(),
+        }
+    }
+
+    // Only stops by itself once MPC completes
+    progress_ticker.abort();
+
+    let profile = traffic.snapshot();
+    info!("📶 Session {} traffic: {}", session_id, profile);
+    session.traffic().add(&profile);
+    session.usage().record(SystemTime::now(), mpc_time, session.bytes_forwarded.load(Ordering::Relaxed));
+    if let Some(dir) = &config.traffic_profile_dir {
+        traffic::write_profile(dir, &session_id, &profile);
+    }
+
+    let disconnect = disconnect.unwrap_or(ProverDisconnect::Open);
+    let flags = size_flag.map(|flag| format!(" [size_divergence: {}]", flag)).unwrap_or_default();
+    match disconnect.outcome(mpc_ok) {
+        Some(outcome) => info!("📴 Session {} outcome: {} (prover {}){}", session_id, outcome, disconnect, flags),
+        None => {
+            let code = result.as_ref().err().map_or("ok", Error::code);
+            info!("📴 Session {} closed by the verifier ({} {}){}", session_id, close.code(), code, flags)
+        }
+    }
+
+    result.map(|_| ()).map_err(|e| e.in_session(&session_id))
+}
+
+/// Classify an MPC failure where it leaves tlsn, so nothing downstream reads error text.
+///
+/// tlsn only reports its protocol config validator rejecting the prover's limits as
+/// text, `"<field> <value> is greater than the configured limit <limit>"`, somewhere
+/// in the source chain; that becomes [`Error::ConfigMismatch`], anything else [`Error::Mpc`].
+fn mpc_error(source: BoxError) -> Error {
+    const MARKER: &str = " is greater than the configured limit ";
+    let mismatch = std::iter::successors(Some(&*source as &(dyn StdError + 'static)), |&cause| cause.source())
+        .find_map(|cause| {
+            let text = cause.to_string();
+            let (claim, limit) = text.split_once(MARKER)?;
+            let verifier_limit: u64 = limit.split_whitespace().next()?.parse().ok()?;
+            let mut words = claim.split_whitespace().rev();
+            let prover_value = words.next().and_then(|value| value.parse().ok());
+            let field = words.next()?;
+            let field = CONFIG_FIELDS.iter().find(|&&known| known == field).copied().unwrap_or("other");
+            Some((field, prover_value, verifier_limit))
+        });
+
+    match mismatch {
+        Some((field, prover_value, verifier_limit)) => Error::ConfigMismatch { field, prover_value, verifier_limit, source },
+        None => Error::Mpc(source),
+    }
+}
+
+/// Declared sizes far off the notarized transcript, for abuse review
+fn size_divergence(hello: &Hello, facts: &SessionFacts) -> Option<String> {
+    let transcript = facts.transcript.as_ref()?;
+    let flags: Vec<String> = [
+        ("sent", hello.expected_sent_bytes, transcript.sent.len()),
+        ("received", hello.expected_recv_bytes, transcript.received.len()),
+    ]
+    .into_iter()
+    .filter_map(|(direction, declared, actual)| {
+        let declared = declared?;
+        size_diverges(declared, actual as u64).then(|| format!("{} declared {}, actual {}", direction, declared, actual))
+    })
+    .collect();
+    (!flags.is_empty()).then(|| flags.join("; "))
+}
+
+/// What replaying a capture through `run_verifier` produced
+#[derive(Debug)]
+pub struct ReplayReport {
+    /// Outcome recorded with the capture, if the session got that far
+    pub recorded_outcome: Option<String>,
+    /// Outcome of the replay, in the same form
+    pub replayed_outcome: String,
+    /// Offset of the first outbound byte that differs from the recording, if any
+    pub outbound_divergence: Option<u64>,
+    /// Outbound bytes the replay produced
+    pub outbound_bytes: u64,
+}
+
+impl ReplayReport {
+    pub fn reproduced(&self) -> bool {
+        self.recorded_outcome.as_deref() == Some(self.replayed_outcome.as_str())
+    }
+}
+
+/// Feed a capture's inbound bytes into `run_verifier` and compare what it sends back.
+///
+/// The verifier draws fresh randomness for every session, so the recorded prover
+/// bytes only stay meaningful up to the first message that depends on it: failures
+/// in protocol setup (config validation, framing, a truncated stream) reproduce
+/// exactly, later ones show up as an outbound divergence that locates them.
+pub async fn replay(records: &[CaptureRecord]) -> Result<ReplayReport> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (prover_stream, verifier_stream) = tokio::io::duplex(DUPLEX_BUFFER_BYTES);
+    let (mut prover_read, mut prover_write) = tokio::io::split(prover_stream);
+
+    let inbound: Vec<Vec<u8>> = records
+        .iter()
+        .filter(|record| record.kind == CaptureKind::Inbound)
+        .map(|record| record.data.clone())
+        .collect();
+    let recorded_outbound: Vec<u8> = records
+        .iter()
+        .filter(|record| record.kind == CaptureKind::Outbound)
+        .flat_map(|record| record.data.iter().copied())
+        .collect();
+    let recorded_outcome = records
+        .iter()
+        .rev()
+        .find(|record| record.kind == CaptureKind::Outcome)
+        .map(|record| String::from_utf8_lossy(&record.data).into_owned());
+
+    // Closing the write half after the last record ends the stream like the
+    // prover going away did
+    let feeder = tokio::spawn(async move {
+        for data in inbound {
+            if prover_write.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        let _ = prover_write.shutdown().await;
+    });
+
+    let comparer = tokio::spawn(async move {
+        let (mut offset, mut divergence) = (0u64, None);
+        let mut buf = vec![0u8; 8192];
+        while let Ok(n) = prover_read.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+            if divergence.is_none() {
+                divergence = buf[..n]
+                    .iter()
+                    .enumerate()
+                    .find(|&(i, byte)| recorded_outbound.get(offset as usize + i) != Some(byte))
+                    .map(|(i, _)| offset + i as u64);
+            }
+            offset += n as u64;
+        }
+        (offset, divergence)
     });
 
-    // Run verifier with verifier side of duplex stream
-    let output = run_verifier(verifier_stream.compat()).await?;
+    let replayed_outcome = outcome_text(&run_verifier(verifier_stream.compat()).await);
+
+    feeder.abort();
+    let (outbound_bytes, outbound_divergence) = comparer.await?;
+    Ok(ReplayReport { recorded_outcome, replayed_outcome, outbound_divergence, outbound_bytes })
+}
+
+/// Validate Plaid-specific requirements and sign the attestation, returning what
+/// the verifier saw of the transcript
+async fn validate_and_sign(
+    facts: &SessionFacts,
+    hello: &Hello,
+    session_id: &str,
+    progress: &Progress,
+    config: &ServerConfig,
+    transparency: Option<&TransparencyLog>,
+) -> Result<DisclosureReport> {
+    // Split the transcript once; every check below reads it through this
+    let parsed = ParsedSession::new(facts, &HeadLimits::from_config(config));
+
+    validate_plaid_connection(facts, &parsed, hello.balance_opening.as_ref(), config)?;
+    progress.stage(ProgressStage::Validated);
+
+    let disclosure = DisclosureReport::of(facts);
+    let (sent, received) = (disclosure.sent.counts, disclosure.received.counts);
+    info!(
+        "🙈 Verifier saw: sent {} revealed, {} committed, {} hidden; received {} revealed, {} committed, {} hidden",
+        sent.revealed, sent.committed, sent.hidden, received.revealed, received.committed, received.hidden
+    );
+
+    let attestation = sign_attestation(facts, &parsed, hello, &disclosure, session_id, config, transparency).await?;
+    progress.stage(ProgressStage::Signed);
+
+    debug!("Attestation size: {} bytes", attestation.len());
+    Ok(disclosure)
+}
+
+/// Read the optional `hello` control message that may precede the MPC stream.
+///
+/// Returns the prover's hello (defaults if it went straight to MPC) and the first
+/// binary frame if one was consumed while looking for it.
+async fn read_handshake<R>(ws_read: &mut R, config: &ServerConfig) -> Result<(Hello, Option<Vec<u8>>)>
+where
+    R: Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+{
+    let phase = SessionPhase::PreMpc;
+    let (hello, first_frame) = loop {
+        match ws_read.next().await {
+            Some(Ok(Message::Text(text))) => {
+                let message = parse_prover_message(&text).map_err(|source| Error::ControlMessage { phase, source })?;
+                phase.on_message(&message)?;
+                match message {
+                    ProverMessage::Hello(hello) => break (hello, None),
+                    ProverMessage::Ping => continue,
+                }
+            }
+            Some(Ok(Message::Binary(data))) => break (Hello::default(), Some(data)),
+            Some(Ok(Message::Close(frame))) => {
+                return Err(Error::LeftBeforeMpc(ProverDisconnect::from_frame(frame.as_ref())))
+            }
+            None => return Err(Error::LeftBeforeMpc(ProverDisconnect::Dropped("stream ended".to_string()))),
+            Some(Ok(_)) => continue, // ping/pong
+            Some(Err(e)) => return Err(Error::LeftBeforeMpc(ProverDisconnect::Dropped(e.to_string()))),
+        }
+    };
+
+    let rejected = Error::check(Check::Hello);
+    validate_policy(&hello.policy, config).map_err(rejected)?;
+    info!("👋 Signing policy: {}", hello.policy.mode());
 
-    // Validate Plaid-specific requirements
-    validate_plaid_connection(&output)?;
+    if let Some(blinder) = &hello.blinder {
+        check_blinder(blinder).map_err(rejected)?;
+    }
+    if let Some(opening) = &hello.balance_opening {
+        check_blinder(&opening.blinder).map_err(rejected)?;
+    }
+    check_recipient(hello.recipient.as_deref(), config).map_err(rejected)?;
+    check_format(hello.format, config).map_err(rejected)?;
+    check_declared_size(&hello, MAX_SENT_DATA, MAX_RECV_DATA).map_err(rejected)?;
 
-    // Sign attestation
-    let attestation = sign_attestation(output).await?;
+    if let Some(prover_timestamp) = hello.timestamp {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        check_clock_skew(prover_timestamp, now, config).map_err(rejected)?;
+    }
 
-    info!("✅ Attestation signed");
-    info!("   Attestation size: {} bytes", attestation.len());
+    Ok((hello, first_frame))
+}
 
-    // Wait for forwarding tasks to complete
-    let _ = tokio::join!(ws_to_prover, prover_to_ws);
+/// Random blinder for a session whose prover sent none
+fn new_blinder() -> Vec<u8> {
+    let mut blinder = vec![0u8; SESSION_BLINDER_LEN];
+    OsRng.fill_bytes(&mut blinder);
+    blinder
+}
 
-    Ok(())
+/// Random identifier used to name per-session artifacts
+fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
 }
 
-async fn run_verifier<T>(socket: T) -> Result<VerifierOutput>
+/// Run the MPC-TLS protocol to completion, returning what it established
+async fn run_verifier<T>(socket: T) -> Result<SessionFacts>
 where
     T: futures::AsyncRead + futures::AsyncWrite + Send + Sync + Unpin + 'static,
 {
@@ -108,7 +576,7 @@ where
         .max_sent_data(MAX_SENT_DATA)
         .max_recv_data(MAX_RECV_DATA)
         .build()
-        .context("Failed to build protocol config validator")?;
+        .map_err(|e| Error::Setup(e.into()))?;
 
     info!("📋 Protocol limits: {}KB sent, {}KB recv",
           MAX_SENT_DATA / 1024, MAX_RECV_DATA / 1024);
@@ -117,7 +585,7 @@ where
     let verifier_config = VerifierConfig::builder()
         .protocol_config_validator(config_validator)
         .build()
-        .context("Failed to build verifier config")?;
+        .map_err(|e| Error::Setup(e.into()))?;
 
     // Step 3: Create verifier instance
     let verifier = Verifier::new(verifier_config);
@@ -128,23 +596,25 @@ where
     let output = verifier
         .verify(socket, &VerifyConfig::default())
         .await
-        .context("Verification failed")?;
+        .map_err(|e| mpc_error(e.into()))?;
 
     info!("✅ MPC-TLS verification complete");
 
-    // Step 5: Log what was verified
-    if let Some(server_name) = &output.server_name {
+    // Step 5: Nothing past this point reads tlsn's output directly
+    let facts = SessionFacts::from_output(&output);
+
+    // Step 6: Log what was verified
+    if let Some(server_name) = &facts.server_name {
         info!("🌐 Verified server: {:?}", server_name);
     }
 
-    if let Some(transcript) = &output.transcript {
+    if let Some(transcript) = &facts.transcript {
         info!("📊 Transcript: {} bytes sent, {} bytes received",
-              transcript.sent_unsafe().len(),
-              transcript.received_unsafe().len());
+              transcript.sent.len(),
+              transcript.received.len());
     }
 
-    info!("🔐 Commitments: {} bytes of transcript commitments received",
-          output.transcript_commitments.len());
+    info!("🔐 Transcript commitments: {}", CommitmentCounts::of(&facts));
 
-    Ok(output)
+    Ok(facts)
 }
@@ -0,0 +1,55 @@
+//! Startup checks that need `AUDITORZK_*` variables set. The environment is
+//! shared by the whole process, so these run in a test binary of their own, one
+//! at a time, away from the unit tests that read the default configuration.
+
+use auditor_zk_verifier::config::ServerConfig;
+use std::sync::{Mutex, MutexGuard};
+
+static ENV: Mutex<()> = Mutex::new(());
+
+/// A variable set for as long as the guard lives
+struct EnvVar {
+    name: &'static str,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl EnvVar {
+    fn set(name: &'static str, value: &str) -> Self {
+        let lock = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::env::set_var(name, value);
+        Self { name, _lock: lock }
+    }
+}
+
+impl Drop for EnvVar {
+    fn drop(&mut self) {
+        std::env::remove_var(self.name);
+    }
+}
+
+#[test]
+fn unknown_signing_policy_fails_startup() {
+    let _var = EnvVar::set("AUDITORZK_ALLOWED_POLICIES", "commitment,threshold");
+    assert_eq!(
+        ServerConfig::from_env().unwrap_err().to_string(),
+        "Unknown signing policy 'threshold' in AUDITORZK_ALLOWED_POLICIES \
+         (known: commitment, spendable_commitment, income_commitment)",
+    );
+}
+
+#[cfg(not(feature = "test-endpoints"))]
+#[test]
+fn loopback_hosts_need_test_endpoints() {
+    let _var = EnvVar::set("AUDITORZK_ALLOWED_HOSTS", "sandbox.plaid.com,localhost");
+    let err = ServerConfig::from_env().unwrap_err();
+    assert!(err.to_string().contains("`test-endpoints` feature"));
+}
+
+#[cfg(feature = "test-endpoints")]
+#[test]
+fn test_builds_keep_the_test_affordances() {
+    let _var = EnvVar::set("AUDITORZK_LOG_SENSITIVE", "1");
+    let config = ServerConfig::from_env().unwrap();
+    assert!(config.sensitive_logging());
+    assert!(config.allowed_hosts.iter().any(|host| host == "localhost"));
+}